@@ -2,29 +2,56 @@ pragma solidity ^0.5.0;
 pragma experimental ABIEncoderV2;
 
 import "./ReportHandle.sol";
+import "./utils/BytesUtils.sol";
 import "./utils/Secp256k1.sol";
 
 // Consider: Avoid inheritting
 contract Anonify is ReportHandle {
     event StoreCiphertext(bytes ciphertext);
-    event StoreHandshake(bytes handshake);
+    event StoreHandshake(bytes handshake, bytes enclaveSig);
+    event JoinGroup(uint32 rosterIdx, address enclaveAddress, bytes handshake, bytes enclaveSig);
+
+    // A SCALE-encoded handshake starts with the prior epoch (4 bytes), the prior transcript hash (32 bytes)
+    // and the sender's roster index (4 bytes), which are followed by its kind.
+    // Integers are in little endian. The kind `Remove` is 0x01 followed by the removed roster index.
+    uint constant internal HANDSHAKE_PRIOR_TRANSCRIPT_HASH_OFFSET = 4;
+    uint constant internal HANDSHAKE_ROSTER_IDX_OFFSET = 36;
+    uint constant internal HANDSHAKE_KIND_OFFSET = 40;
+    uint8 constant internal HANDSHAKE_KIND_UPDATE = 0;
+    uint8 constant internal HANDSHAKE_KIND_REMOVE = 1;
+
+    // The epoch and the transcript hash of the group, which every stored handshake advances
+    // in the same way as enclaves do. So a handshake enclaves would reject as stale is reverted here,
+    // and the contract and enclaves agree on which removals have taken effect.
+    uint32 public epoch;
+    bytes32 public transcriptHash;
 
     constructor(
         bytes memory _report,
         bytes memory _reportSig,
-        bytes memory _handshake
+        bytes memory _handshake,
+        bytes memory _enclaveSig
     ) ReportHandle(_report, _reportSig) public {
-        handshake(_handshake);
+        require(recoverHandshakeSigner(_handshake, _enclaveSig) == roster[0], "Invalid enclave signature.");
+        checkJoinHandshake(_handshake, 0);
+        chainHandshake(_handshake);
+        emit JoinGroup(0, roster[0], _handshake, _enclaveSig);
      }
 
-    // a new TEE participant joins the group.
+    // a new TEE participant joins the group with the next roster index.
     function joinGroup(
         bytes memory _report,
         bytes memory _reportSig,
-        bytes memory _handshake
+        bytes memory _handshake,
+        bytes memory _enclaveSig,
+        uint32 _rosterIdx
     ) public {
+        require(_rosterIdx == roster.length, "The roster index is not the next one.");
         handleReport(_report, _reportSig);
-        handshake(_handshake);
+        require(recoverHandshakeSigner(_handshake, _enclaveSig) == roster[_rosterIdx], "Invalid enclave signature.");
+        checkJoinHandshake(_handshake, _rosterIdx);
+        chainHandshake(_handshake);
+        emit JoinGroup(_rosterIdx, roster[_rosterIdx], _handshake, _enclaveSig);
     }
 
     // Store ciphertexts which is generated by trusted environment.
@@ -39,7 +66,74 @@ contract Anonify is ReportHandle {
         emit StoreCiphertext(_newCiphertext);
     }
 
-    function handshake(bytes memory _handshake) public {
-        emit StoreHandshake(_handshake);
+    // Store handshakes which are signed by the registered enclave at the sender's roster index.
+    function handshake(bytes memory _handshake, bytes memory _enclaveSig) public {
+        address inpEnclaveAddr = recoverHandshakeSigner(_handshake, _enclaveSig);
+        require(inpEnclaveAddr != address(0) && enclaveAddress[inpEnclaveAddr] == inpEnclaveAddr, "Invalid enclave signature.");
+        require(_handshake.length > HANDSHAKE_KIND_OFFSET, "Invalid handshake.");
+        uint32 senderIdx = readUint32(_handshake, HANDSHAKE_ROSTER_IDX_OFFSET);
+        require(senderIdx < roster.length && roster[senderIdx] == inpEnclaveAddr, "The handshake isn't signed by the sender.");
+        chainHandshake(_handshake);
+        deregisterRemovedMember(_handshake, senderIdx);
+
+        emit StoreHandshake(_handshake, _enclaveSig);
+    }
+
+    // A join handshake is an update handshake from the joining roster index.
+    function checkJoinHandshake(bytes memory _handshake, uint32 _rosterIdx) internal pure {
+        require(_handshake.length > HANDSHAKE_KIND_OFFSET, "Invalid handshake.");
+        require(
+            readUint32(_handshake, HANDSHAKE_ROSTER_IDX_OFFSET) == _rosterIdx &&
+            uint8(_handshake[HANDSHAKE_KIND_OFFSET]) == HANDSHAKE_KIND_UPDATE,
+            "The join handshake isn't an add handshake with the roster index."
+        );
+    }
+
+    // Check the prior epoch and the prior transcript hash of the handshake as enclaves do,
+    // and then chain it to the transcript hash, which is the sha256 hash of the prior one followed by the handshake.
+    function chainHandshake(bytes memory _handshake) internal {
+        require(_handshake.length > HANDSHAKE_KIND_OFFSET, "Invalid handshake.");
+        require(readUint32(_handshake, 0) == epoch, "The handshake's prior epoch isn't the current epoch.");
+        require(
+            BytesUtils.toBytes32(_handshake, HANDSHAKE_PRIOR_TRANSCRIPT_HASH_OFFSET) == transcriptHash,
+            "The handshake's prior transcript hash isn't the current one."
+        );
+
+        epoch += 1;
+        transcriptHash = sha256(abi.encodePacked(transcriptHash, _handshake));
+    }
+
+    // If the handshake removes a member, deregister its enclave address,
+    // so that the removed enclave can neither store instructions nor send handshakes, nor register again.
+    // This must follow `chainHandshake`, so that only a removal enclaves also process takes effect.
+    function deregisterRemovedMember(bytes memory _handshake, uint32 _senderIdx) internal {
+        if (uint8(_handshake[HANDSHAKE_KIND_OFFSET]) != HANDSHAKE_KIND_REMOVE) {
+            return;
+        }
+
+        require(_handshake.length >= HANDSHAKE_KIND_OFFSET + 5, "Invalid handshake.");
+        uint32 removedIdx = readUint32(_handshake, HANDSHAKE_KIND_OFFSET + 1);
+        require(removedIdx < roster.length, "The removed member hasn't joined the group.");
+        require(removedIdx != _senderIdx, "A member cannot remove itself.");
+        address removedAddr = roster[removedIdx];
+        require(!removedEnclave[removedAddr], "The member has already been removed.");
+
+        enclaveAddress[removedAddr] = address(0);
+        removedEnclave[removedAddr] = true;
+    }
+
+    // Read a uint32 which SCALE encodes in little endian.
+    function readUint32(bytes memory _bytes, uint _start) internal pure returns (uint32) {
+        uint32 res = 0;
+        for (uint i = 0; i < 4; i++) {
+            res |= uint32(uint8(_bytes[_start + i])) << (8 * i);
+        }
+        return res;
+    }
+
+    // The signed message is derived from the handshake itself,
+    // so that a signature cannot be replayed with another handshake.
+    function recoverHandshakeSigner(bytes memory _handshake, bytes memory _enclaveSig) internal pure returns (address) {
+        return Secp256k1.recover(sha256(_handshake), _enclaveSig);
     }
 }
@@ -14,6 +14,11 @@ contract ReportHandle {
     bytes32 private _mrEnclave;
     // The address-formated identity public key.
     mapping(address => address) public enclaveAddress;
+    // Enclave addresses in the order they joined the group, indexed by roster index.
+    address[] public roster;
+    // Enclave addresses removed from the group, which cannot be registered again
+    // since enclaves refuse a join handshake from an address they have had in the roster.
+    mapping(address => bool) internal removedEnclave;
     // Nonce data which is included in the `reportdata` field and used to prevent from replay attacks.
     mapping(bytes32 => bytes32) private _reportNonce;
 
@@ -31,6 +36,7 @@ contract ReportHandle {
         _mrEnclave = inpMrEnclave;
         _reportNonce[reportNonce] = reportNonce;
         enclaveAddress[inpEnclaveAddr] = inpEnclaveAddr;
+        roster.push(inpEnclaveAddr);
     }
 
     // Check mrenclave value and report signature and then set new enclave address.
@@ -40,6 +46,7 @@ contract ReportHandle {
 
         _reportNonce[reportNonce] = reportNonce;
         enclaveAddress[inpEnclaveAddr] = inpEnclaveAddr;
+        roster.push(inpEnclaveAddr);
     }
 
     function extractFromReport(bytes memory _report, bytes memory _reportSig) internal view returns (bytes32, address, bytes32) {
@@ -50,6 +57,7 @@ contract ReportHandle {
         address inpEnclaveAddr = BytesUtils.toAddress(extractElement(quote, 368, 20), 0);
         bytes32 reportNonce = BytesUtils.toBytes32(extractElement(quote, 388, 32), 0);
         require(enclaveAddress[inpEnclaveAddr] == address(0), "The enclave public key has already been registered.");
+        require(!removedEnclave[inpEnclaveAddr], "The enclave has been removed from the group.");
         require(_reportNonce[reportNonce] == 0, "The report nonce has already been used.");
 
         return (mrEnclave, inpEnclaveAddr, reportNonce);
@@ -16,8 +16,14 @@ pub enum HostError {
     AddressNotSet,
     #[error("Event watcher have not been set.")]
     EventWatcherNotSet,
+    #[error("RPC call timed out after {0:?}.")]
+    Timeout(std::time::Duration),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("Web3 error: {0}")]
     Web3Error(#[from] web3::Error),
+    #[error("Ledger error: {0}")]
+    LedgerError(#[from] reqwest::Error),
+    #[error("Runtime error: {0}")]
+    RuntimeError(#[from] anonify_runtime::RuntimeError),
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+//! Compiled contracts, which are loaded at runtime so that one binary can deploy different versions of them.
+use std::{fs, path::Path};
+use ethabi::{Contract as ContractABI, Token};
+use anyhow::anyhow;
+use crate::error::Result;
+
+/// The ABI and the creation bytecode of a contract, as output by `solc --abi --bin`.
+#[derive(Debug, Clone)]
+pub struct ContractArtifact {
+    abi: ContractABI,
+    bytecode: Vec<u8>,
+}
+
+impl ContractArtifact {
+    pub fn new(abi: ContractABI, bytecode: Vec<u8>) -> Self {
+        ContractArtifact { abi, bytecode }
+    }
+
+    /// Load the `.abi` and `.bin` files of a contract.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(abi_path: P, bin_path: Q) -> Result<Self> {
+        let abi = fs::read(abi_path)?;
+        let bin = fs::read_to_string(bin_path)?;
+
+        Self::from_slices(&abi, &bin)
+    }
+
+    /// Parse the JSON ABI and the hex-encoded bytecode of a contract.
+    pub fn from_slices(abi: &[u8], bin: &str) -> Result<Self> {
+        let abi = ContractABI::load(abi)
+            .map_err(|e| anyhow!("Failed to load contract abi.: {:?}", e))?;
+        let bytecode = hex::decode(bin.trim().trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Failed to decode contract bytecode: {:?}", e))?;
+
+        Ok(ContractArtifact { abi, bytecode })
+    }
+
+    /// The Anonify contract embedded at compile time, which requires running solc before building.
+    #[cfg(feature = "embedded-contract")]
+    pub fn embedded() -> Result<Self> {
+        Self::from_slices(
+            include_bytes!("../../../../build/Anonify.abi"),
+            include_str!("../../../../build/Anonify.bin"),
+        )
+    }
+
+    pub fn abi(&self) -> &ContractABI {
+        &self.abi
+    }
+
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+
+    /// The data of a transaction deploying the contract with constructor arguments `params`.
+    pub fn deploy_data(&self, params: &[Token]) -> Result<Vec<u8>> {
+        match self.abi.constructor() {
+            Some(constructor) => constructor
+                .encode_input(self.bytecode.clone(), params)
+                .map_err(|e| anyhow!("{:?}", e).into()),
+            None if params.is_empty() => Ok(self.bytecode.clone()),
+            None => Err(anyhow!("The contract has no constructor.").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &[u8] = br#"[{"type":"constructor","inputs":[{"name":"handshake","type":"bytes"}],"payable":false,"stateMutability":"nonpayable"}]"#;
+
+    #[test]
+    fn test_deploy_data() {
+        let artifact = ContractArtifact::from_slices(ABI, "0x6080\n").unwrap();
+        assert_eq!(artifact.bytecode(), &[0x60, 0x80]);
+
+        let data = artifact.deploy_data(&[Token::Bytes(vec![1, 2])]).unwrap();
+        // The bytecode followed by the ABI-encoded offset, length and content of the bytes.
+        assert_eq!(&data[..2], &[0x60, 0x80]);
+        assert_eq!(data.len(), 2 + 32 * 3);
+        assert_eq!(data[2 + 31], 0x20);
+        assert_eq!(data[2 + 63], 2);
+        assert_eq!(&data[2 + 64..2 + 66], &[1, 2]);
+
+        assert!(artifact.deploy_data(&[]).is_err());
+    }
+
+    #[test]
+    fn test_without_constructor() {
+        let artifact = ContractArtifact::from_slices(b"[]", "6080").unwrap();
+        assert_eq!(artifact.deploy_data(&[]).unwrap(), vec![0x60, 0x80]);
+        assert!(artifact.deploy_data(&[Token::Bytes(vec![])]).is_err());
+    }
+}
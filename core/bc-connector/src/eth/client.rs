@@ -1,5 +1,4 @@
 use std::{
-    path::Path,
     sync::Arc,
     boxed::Box,
 };
@@ -9,13 +8,21 @@ use anonify_common::AccessRight;
 use anonify_runtime::{traits::State, UpdatedState};
 use anonify_app_preluder::Ciphertext;
 use web3::types::Address as EthAddress;
+use futures::{executor::block_on, lock::Mutex};
+use async_trait::async_trait;
+use anyhow::anyhow;
 use crate::{
-    error::Result,
+    error::{Result, HostError},
     eventdb::{BlockNumDB, InnerEnclaveLog},
+    sync::{EventSync, Rollback, SyncConfig},
     traits::*,
     utils::*,
 };
-use super::primitives::{Web3Http, Web3Contract};
+use super::{
+    artifact::ContractArtifact,
+    gas::GasConfig,
+    primitives::{Web3Http, Web3Contract, Web3SubscribedContract, LogStream},
+};
 
 /// Components needed to deploy a contract
 #[derive(Debug)]
@@ -23,9 +30,13 @@ pub struct EthDeployer {
     enclave_id: sgx_enclave_id_t,
     web3_conn: Web3Http,
     address: Option<EthAddress>, // contract address
+    abi: Option<ethabi::Contract>, // abi of the deployed contract
+    gas_config: GasConfig,
 }
 
 impl Deployer for EthDeployer {
+    type Artifact = ContractArtifact;
+
     fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
         let web3_conn = Web3Http::new(node_url)?;
 
@@ -33,18 +44,22 @@ impl Deployer for EthDeployer {
             enclave_id,
             web3_conn,
             address: None,
+            abi: None,
+            gas_config: GasConfig::default(),
         })
     }
 
     fn get_account(&self, index: usize) -> Result<SignerAddress> {
         Ok(SignerAddress::EthAddress(
-            self.web3_conn.get_account(index)?
+            block_on(self.web3_conn.get_account(index))?
         ))
     }
 
     fn deploy<F>(
         &mut self,
         deploy_user: &SignerAddress,
+        artifact: &ContractArtifact,
+        gas_config: GasConfig,
         reg_fn: F,
     ) -> Result<String>
     where
@@ -52,27 +67,29 @@ impl Deployer for EthDeployer {
     {
         let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
 
-        let contract_addr = match deploy_user {
-            SignerAddress::EthAddress(address) => {
-                self.web3_conn.deploy(
-                    &address,
-                    &join_group_tx.report,
-                    &join_group_tx.report_sig,
-                    &join_group_tx.handshake,
-                )?
-            }
-        };
+        let contract_addr = block_on(self.web3_conn.deploy(
+            deploy_user,
+            artifact,
+            &gas_config,
+            &join_group_tx.report,
+            &join_group_tx.report_sig,
+            &join_group_tx.handshake,
+            &join_group_tx.enclave_sig,
+        ))?;
         self.address = Some(contract_addr);
+        self.abi = Some(artifact.abi().clone());
+        self.gas_config = gas_config;
 
         Ok(hex::encode(contract_addr.as_bytes()))
     }
 
-    // TODO: generalize, remove abi.
-    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<ContractKind> {
-        let addr = self.address.expect("The contract hasn't be deployed yet.").to_string();
-        let contract_info = ContractInfo::new(abi_path, &addr);
+    fn get_contract(self) -> Result<ContractKind> {
+        let (address, abi) = match (self.address, self.abi) {
+            (Some(address), Some(abi)) => (address, abi),
+            _ => return Err(HostError::AddressNotSet),
+        };
         Ok(ContractKind::Web3Contract(
-            Web3Contract::new(self.web3_conn, contract_info)?
+            Web3Contract::from_abi(self.web3_conn, abi, address, self.gas_config)
         ))
     }
 
@@ -103,14 +120,18 @@ pub struct EthSender {
     contract: Web3Contract,
 }
 
-impl Sender for EthSender {
-    fn new<P: AsRef<Path>>(
+#[async_trait]
+impl AsyncSender for EthSender {
+    type Artifact = ContractArtifact;
+
+    fn new(
         enclave_id: sgx_enclave_id_t,
         node_url: &str,
-        contract_info: ContractInfo<'_, P>,
+        contract_info: ContractInfo<'_, ContractArtifact>,
+        gas_config: GasConfig,
     ) -> Result<Self> {
         let web3_http = Web3Http::new(node_url)?;
-        let contract = Web3Contract::new(web3_http, contract_info)?;
+        let contract = Web3Contract::new(web3_http, contract_info, gas_config)?;
 
         Ok(EthSender { enclave_id, contract })
     }
@@ -118,103 +139,99 @@ impl Sender for EthSender {
     fn from_contract(
         enclave_id: sgx_enclave_id_t,
         contract: ContractKind,
-    ) -> Self {
+    ) -> Result<Self> {
         match contract {
             ContractKind::Web3Contract(contract) => {
-                EthSender {
+                Ok(EthSender {
                     enclave_id,
                     contract,
-                }
+                })
             }
+            _ => Err(anyhow!("EthSender can only be built from a web3 contract.").into()),
         }
     }
 
-    fn get_account(&self, index: usize) -> Result<SignerAddress> {
+    async fn get_account(&self, index: usize) -> Result<SignerAddress> {
         Ok(SignerAddress::EthAddress(
-            self.contract.get_account(index)?
+            self.contract.get_account(index).await?
         ))
     }
 
-    fn join_group<F>(
+    async fn join_group<F>(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         reg_fn: F,
     ) -> Result<String>
     where
-        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx>,
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send,
     {
         let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
-        let receipt = match signer {
-            SignerAddress::EthAddress(addr) => {
-                self.contract.join_group(
-                    addr,
-                    &join_group_tx.report,
-                    &join_group_tx.report_sig,
-                    &join_group_tx.handshake,
-                    gas
-                )?
-            }
-        };
+        let receipt = self.contract.join_group(
+            &signer,
+            &join_group_tx.report,
+            &join_group_tx.report_sig,
+            &join_group_tx.handshake,
+            &join_group_tx.enclave_sig,
+            join_group_tx.roster_idx,
+            gas
+        ).await?;
 
         Ok(hex::encode(receipt.as_bytes()))
     }
 
-    fn send_instruction<ST, F>(
+    async fn send_instruction<ST, F>(
         &self,
         access_right: AccessRight,
         signer: SignerAddress,
         state_info: StateInfo<'_, ST>,
-        gas: u64,
+        gas: Option<u64>,
         enc_ins_fn: F,
     ) -> Result<String>
     where
-        ST: State,
-        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx>,
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send,
     {
         // ecall of encrypt instruction
         let mut instruction_tx: BoxedInstructionTx = enc_ins_fn(self.enclave_id, access_right, state_info)?.into();
         let ciphertext = instruction_tx.get_ciphertext();
 
-        let receipt = match signer {
-            SignerAddress::EthAddress(addr) => {
-                self.contract.send_instruction(
-                    addr,
-                    instruction_tx.state_id,
-                    ciphertext,
-                    &instruction_tx.enclave_sig,
-                    &instruction_tx.msg,
-                    gas,
-                )?
-            }
-        };
+        let receipt = self.contract.send_instruction(
+            &signer,
+            instruction_tx.state_id,
+            ciphertext,
+            &instruction_tx.enclave_sig,
+            &instruction_tx.msg,
+            gas,
+        ).await?;
 
         Ok(hex::encode(receipt.as_bytes()))
     }
 
-    fn handshake<F>(
+    async fn handshake<F>(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         handshake_fn: F,
     ) -> Result<String>
     where
-        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx>
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send
     {
         let handshake_tx: BoxedHandshakeTx = handshake_fn(self.enclave_id)?.into();
-        let receipt = match signer {
-            SignerAddress::EthAddress(addr) => {
-                self.contract.handshake(
-                    addr,
-                    &handshake_tx.handshake,
-                    gas
-                )?
-            }
-        };
+        let receipt = self.contract.handshake(
+            &signer,
+            &handshake_tx.handshake,
+            &handshake_tx.enclave_sig,
+            gas
+        ).await?;
 
         Ok(hex::encode(receipt.as_bytes()))
     }
 
+    async fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt> {
+        self.contract.get_receipt(parse_tx_hash(tx_hash)?, confirmations).await
+    }
+
     fn get_contract(self) -> ContractKind {
         ContractKind::Web3Contract(self.contract)
     }
@@ -224,38 +241,40 @@ impl Sender for EthSender {
 pub struct EventWatcher<DB: BlockNumDB> {
     contract: Web3Contract,
     event_db: Arc<DB>,
+    sync_config: SyncConfig,
 }
 
-impl<DB: BlockNumDB> Watcher for EventWatcher<DB> {
+#[async_trait]
+impl<DB: BlockNumDB> AsyncWatcher for EventWatcher<DB> {
     type WatcherDB = DB;
+    type Artifact = ContractArtifact;
 
-    fn new<P: AsRef<Path>>(
+    fn new(
         node_url: &str,
-        contract_info: ContractInfo<'_, P>,
+        contract_info: ContractInfo<'_, ContractArtifact>,
         event_db: Arc<DB>,
+        sync_config: SyncConfig,
     ) -> Result<Self> {
         let web3_http = Web3Http::new(node_url)?;
-        let contract = Web3Contract::new(web3_http, contract_info)?;
+        let contract = Web3Contract::new(web3_http, contract_info, GasConfig::default())?;
 
-        Ok(EventWatcher { contract, event_db })
+        Ok(EventWatcher { contract, event_db, sync_config })
     }
 
-    fn block_on_event<F, S>(
+    async fn fetch_events<F, R, S>(
         &self,
         eid: sgx_enclave_id_t,
         insert_fn: F,
+        rollback: &R,
     ) -> Result<Option<Vec<UpdatedState<S>>>>
     where
-        F: FnOnce(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>>,
-        S: State,
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send,
     {
-        let enclave_updated_state = self.contract
-            .get_event(self.event_db.clone(), self.contract.address())?
-            .into_enclave_log()?
-            .insert_enclave(eid, insert_fn)?
-            .set_to_db(self.contract.address());
-
-        Ok(enclave_updated_state.updated_states())
+        EventSync::new(&self.contract, &*self.event_db, self.contract.address(), self.sync_config)
+            .sync(eid, insert_fn, rollback)
+            .await
     }
 
     fn get_contract(self) -> ContractKind {
@@ -263,11 +282,72 @@ impl<DB: BlockNumDB> Watcher for EventWatcher<DB> {
     }
 }
 
+/// Components needed to watch events pushed over a WebSocket subscription.
+/// Logs emitted while the subscription is disconnected are backfilled by `eth_getLogs`.
+pub struct SubscriptionWatcher<DB: BlockNumDB> {
+    contract: Web3SubscribedContract,
+    event_db: Arc<DB>,
+    sync_config: SyncConfig,
+    subscription: Mutex<Option<LogStream>>,
+}
+
+#[async_trait]
+impl<DB: BlockNumDB> AsyncWatcher for SubscriptionWatcher<DB> {
+    type WatcherDB = DB;
+    type Artifact = ContractArtifact;
+
+    fn new(
+        node_url: &str,
+        contract_info: ContractInfo<'_, ContractArtifact>,
+        event_db: Arc<DB>,
+        sync_config: SyncConfig,
+    ) -> Result<Self> {
+        let web3_http = Web3Http::new(node_url)?;
+        let contract = Web3SubscribedContract::new(
+            Web3Contract::new(web3_http, contract_info, GasConfig::default())?
+        )?;
+
+        Ok(SubscriptionWatcher {
+            contract,
+            event_db,
+            sync_config,
+            subscription: Mutex::new(None),
+        })
+    }
+
+    /// Wait until logs are pushed and insert them into the enclave.
+    /// See `sync::EventSync::sync_on_push` for how long it waits.
+    async fn fetch_events<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send,
+    {
+        // The lock is awaited so that a concurrent call waits without blocking the thread.
+        let mut subscription = self.subscription.lock().await;
+        EventSync::new(&self.contract, &*self.event_db, self.contract.address(), self.sync_config)
+            .sync_on_push(&mut subscription, eid, insert_fn, rollback)
+            .await
+    }
+
+    fn get_contract(self) -> ContractKind {
+        ContractKind::Web3Contract(self.contract.into_contract())
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct BoxedJoinGroupTx {
     pub report: Box<[u8]>,
     pub report_sig: Box<[u8]>,
     pub handshake: Box<[u8]>,
+    pub enclave_sig: Box<[u8]>,
+    pub roster_idx: u32,
+    pub enclave_address: [u8; 20],
 }
 
 impl From<RawJoinGroupTx> for BoxedJoinGroupTx {
@@ -280,10 +360,15 @@ impl From<RawJoinGroupTx> for BoxedJoinGroupTx {
         let report_sig = unsafe { Box::from_raw(box_report_sig) };
         let box_handshake = raw_reg_tx.handshake as *mut Box<[u8]>;
         let handshake = unsafe { Box::from_raw(box_handshake) };
+        let box_enclave_sig = raw_reg_tx.enclave_sig as *mut Box<[u8]>;
+        let enclave_sig = unsafe { Box::from_raw(box_enclave_sig) };
 
         res_tx.report = *report;
         res_tx.report_sig = *report_sig;
         res_tx.handshake = *handshake;
+        res_tx.enclave_sig = *enclave_sig;
+        res_tx.roster_idx = raw_reg_tx.roster_idx;
+        res_tx.enclave_address = raw_reg_tx.enclave_address;
 
         res_tx
     }
@@ -326,6 +411,7 @@ impl From<RawInstructionTx> for BoxedInstructionTx {
 #[derive(Debug, Clone, Default)]
 pub(crate) struct BoxedHandshakeTx {
     pub handshake: Box<[u8]>,
+    pub enclave_sig: Box<[u8]>,
 }
 
 impl From<RawHandshakeTx> for BoxedHandshakeTx {
@@ -333,7 +419,10 @@ impl From<RawHandshakeTx> for BoxedHandshakeTx {
         let mut res_tx = BoxedHandshakeTx::default();
         let box_handshake = raw_handshake_tx.handshake as *mut Box<[u8]>;
         let handshake = unsafe { Box::from_raw(box_handshake) };
+        let box_enclave_sig = raw_handshake_tx.enclave_sig as *mut Box<[u8]>;
+        let enclave_sig = unsafe { Box::from_raw(box_enclave_sig) };
         res_tx.handshake = *handshake;
+        res_tx.enclave_sig = *enclave_sig;
 
         res_tx
     }
@@ -0,0 +1,105 @@
+//! Gas limits and fees of transactions.
+use std::time::Duration;
+use web3::types::U256;
+
+/// Estimated gas is multiplied by this percentage, as the state may change before transactions are mined.
+pub const DEFAULT_GAS_MULTIPLIER_PERCENT: u64 = 120;
+/// Transactions pending for longer than this are resubmitted.
+pub const DEFAULT_RESUBMIT_AFTER: Duration = Duration::from_secs(60);
+/// Nodes replace a pending transaction only if the new fee is at least 10% higher.
+pub const DEFAULT_FEE_BUMP_PERCENT: u64 = 125;
+pub const DEFAULT_MAX_RESUBMISSIONS: u32 = 3;
+/// Transactions are tracked for much longer than all resubmissions take.
+pub const DEFAULT_TRACK_FOR: Duration = Duration::from_secs(60 * 60);
+
+/// How fees of transactions are decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeePolicy {
+    /// Pay `gasPrice` returned by `eth_gasPrice`.
+    Legacy,
+    /// Pay EIP-1559 fees. `maxFeePerGas` is twice the latest base fee plus `max_priority_fee`,
+    /// so that transactions stay valid while base fees rise for a few blocks.
+    Eip1559 { max_priority_fee: U256 },
+}
+
+/// Configurations of gas limits, fees and resubmission of transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasConfig {
+    pub multiplier_percent: u64,
+    pub fee_policy: FeePolicy,
+    pub resubmit_after: Duration,
+    pub fee_bump_percent: u64,
+    pub max_resubmissions: u32,
+    /// How long a sent transaction is tracked to resubmit it and to find the receipts of its replacements.
+    /// Receipts of older transactions are looked up by their own hashes only.
+    pub track_for: Duration,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        GasConfig {
+            multiplier_percent: DEFAULT_GAS_MULTIPLIER_PERCENT,
+            fee_policy: FeePolicy::Legacy,
+            resubmit_after: DEFAULT_RESUBMIT_AFTER,
+            fee_bump_percent: DEFAULT_FEE_BUMP_PERCENT,
+            max_resubmissions: DEFAULT_MAX_RESUBMISSIONS,
+            track_for: DEFAULT_TRACK_FOR,
+        }
+    }
+}
+
+impl GasConfig {
+    /// Gas limit of a transaction whose gas is estimated as `estimated`.
+    pub fn gas_limit(&self, estimated: U256) -> U256 {
+        mul_percent_ceil(estimated, self.multiplier_percent)
+    }
+}
+
+/// The fee of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fee {
+    Legacy { gas_price: U256 },
+    Eip1559 { max_fee: U256, max_priority_fee: U256 },
+}
+
+impl Fee {
+    /// The fee to replace a pending transaction paying this fee.
+    pub fn bump(&self, percent: u64) -> Self {
+        match *self {
+            Fee::Legacy { gas_price } => Fee::Legacy {
+                gas_price: mul_percent_ceil(gas_price, percent),
+            },
+            Fee::Eip1559 { max_fee, max_priority_fee } => Fee::Eip1559 {
+                max_fee: mul_percent_ceil(max_fee, percent),
+                max_priority_fee: mul_percent_ceil(max_priority_fee, percent),
+            },
+        }
+    }
+}
+
+/// Rounded up, so that a bumped fee is always higher than the original one.
+fn mul_percent_ceil(value: U256, percent: u64) -> U256 {
+    (value * U256::from(percent) + U256::from(99)) / U256::from(100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_limit() {
+        let config = GasConfig::default();
+        assert_eq!(config.gas_limit(U256::from(100_000)), U256::from(120_000));
+        assert_eq!(config.gas_limit(U256::from(1)), U256::from(2));
+    }
+
+    #[test]
+    fn test_bump_fee() {
+        let fee = Fee::Legacy { gas_price: U256::from(20) };
+        assert_eq!(fee.bump(125), Fee::Legacy { gas_price: U256::from(25) });
+
+        // Replacements need at least 10% higher fees even for tiny ones.
+        let fee = Fee::Eip1559 { max_fee: U256::from(3), max_priority_fee: U256::from(1) };
+        assert_eq!(fee.bump(110), Fee::Eip1559 { max_fee: U256::from(4), max_priority_fee: U256::from(2) });
+    }
+}
@@ -1,4 +1,10 @@
+pub mod artifact;
 pub mod client;
+pub mod gas;
 pub mod primitives;
+pub mod signer;
 
-pub use self::client::{EthDeployer, EthSender, EventWatcher};
+pub use self::artifact::ContractArtifact;
+pub use self::client::{EthDeployer, EthSender, EventWatcher, SubscriptionWatcher};
+pub use self::signer::EthSigner;
+pub use self::gas::{GasConfig, FeePolicy};
@@ -1,35 +1,56 @@
-use log::debug;
+use log::{debug, warn};
 use std::{
-    path::Path,
-    sync::Arc,
+    pin::Pin,
+    sync::{Mutex, MutexGuard},
+    collections::HashMap,
+    time::{Duration, Instant},
 };
 use web3::{
     Web3,
-    transports::{EventLoopHandle, Http},
-    contract::{Contract, Options},
-    types::{Address, H256, U256, Filter, FilterBuilder, Log, BlockNumber},
-    futures::Future,
+    Transport,
+    transports::{EventLoopHandle, Http, WebSocket},
+    contract::tokens::Tokenize,
+    types::{
+        Address, H256, U256, Bytes, Filter, FilterBuilder, Log, BlockNumber, BlockId,
+        TransactionId, TransactionReceipt, CallRequest,
+    },
 };
+use futures_timer::Delay;
+use serde_json::json;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    stream::{Stream, StreamExt},
+};
+use async_trait::async_trait;
 use ethabi::{
     Topic,
     TopicFilter,
     Event,
     EventParam,
     ParamType,
+    Token,
     decode,
     Hash,
 };
-use anonify_common::IntoVec;
-use anonify_app_preluder::Ciphertext;
+use anonify_common::{IntoVec, EnclaveEvent};
+use anonify_app_preluder::{Ciphertext, CIPHERTEXT_SIZE};
 use anyhow::anyhow;
 use crate::{
     error::Result,
-    eventdb::{BlockNumDB, InnerEnclaveLog, EnclaveLog},
-    utils::ContractInfo,
+    eventdb::InnerEnclaveLog,
+    sync::{ChainReader, LogSubscriber},
+    utils::{ContractInfo, SignerAddress, TxReceipt, TxStatus, with_timeout},
+};
+use super::{
+    artifact::ContractArtifact,
+    signer::{EthSigner, RawTransaction},
+    gas::{GasConfig, FeePolicy, Fee},
 };
 
-pub const CONFIRMATIONS: usize = 0;
-pub const DEPLOY_GAS: u64 = 5_000_000;
+/// The selector of `Error(string)`, which is returned by `revert` and `require` with a reason.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Interval to poll the receipt of a deploy transaction.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Basic web3 connection components via HTTP.
 #[derive(Debug)]
@@ -51,40 +72,212 @@ impl Web3Http {
         })
     }
 
-    pub fn get_account(&self, index: usize) -> Result<Address> {
-        let account = self.web3.eth().accounts().wait()?[index];
-        Ok(account)
+    pub async fn get_account(&self, index: usize) -> Result<Address> {
+        let accounts = with_timeout(self.web3.eth().accounts().compat()).await?;
+        accounts.get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("Account index {} is out of {} accounts", index, accounts.len()).into())
+    }
+
+    pub async fn get_logs(&self, filter: Filter) -> Result<Vec<Log>> {
+        with_timeout(self.web3.eth().logs(filter).compat()).await
+    }
+
+    pub async fn get_block_number(&self) -> Result<u64> {
+        let block_num = with_timeout(self.web3.eth().block_number().compat()).await?;
+        Ok(block_num.as_u64())
     }
 
-    pub fn get_logs(&self, filter: Filter) -> Result<Vec<Log>> {
-        let logs = self.web3.eth().logs(filter).wait()?;
-        Ok(logs)
+    pub async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        let block = with_timeout(
+            self.web3.eth()
+                .block(BlockId::Number(BlockNumber::Number(block_num)))
+                .compat()
+        ).await?;
+        Ok(block.and_then(|b| b.hash))
     }
 
-    pub fn deploy(
+    pub async fn get_chain_id(&self) -> Result<u64> {
+        // web3 doesn't have `eth_chainId` API, so the RPC is called directly.
+        let chain_id = with_timeout(
+            self.web3.transport().execute("eth_chainId", vec![]).compat()
+        ).await?;
+        let chain_id: U256 = serde_json::from_value(chain_id)
+            .map_err(|e| anyhow!("Invalid chain id: {}", e))?;
+
+        Ok(chain_id.as_u64())
+    }
+
+    /// Decide the fee of a new transaction by `fee_policy`.
+    pub async fn get_fee(&self, fee_policy: FeePolicy) -> Result<Fee> {
+        match fee_policy {
+            FeePolicy::Legacy => {
+                let gas_price = with_timeout(self.web3.eth().gas_price().compat()).await?;
+                Ok(Fee::Legacy { gas_price })
+            }
+            FeePolicy::Eip1559 { max_priority_fee } => {
+                // web3 doesn't know base fees of blocks, so the RPC is called directly.
+                let block = with_timeout(
+                    self.web3.transport()
+                        .execute("eth_getBlockByNumber", vec![json!("latest"), json!(false)])
+                        .compat()
+                ).await?;
+                let base_fee: U256 = block.get("baseFeePerGas")
+                    .and_then(|base_fee| serde_json::from_value(base_fee.clone()).ok())
+                    .ok_or_else(|| anyhow!("The node doesn't support EIP-1559 fees."))?;
+
+                Ok(Fee::Eip1559 {
+                    max_fee: base_fee * U256::from(2) + max_priority_fee,
+                    max_priority_fee,
+                })
+            }
+        }
+    }
+
+    /// Estimate gas of a transaction by `eth_estimateGas`. `to` is `None` to deploy a contract.
+    pub async fn estimate_gas(&self, from: Address, to: Option<Address>, data: &[u8]) -> Result<U256> {
+        // web3 requires `to` of calls, so the RPC is called directly.
+        let mut req = json!({ "from": from, "data": Bytes(data.to_vec()) });
+        if let Some(to) = to {
+            req["to"] = json!(to);
+        }
+        let gas = with_timeout(
+            self.web3.transport().execute("eth_estimateGas", vec![req]).compat()
+        ).await?;
+
+        serde_json::from_value(gas).map_err(|e| anyhow!("Invalid estimated gas: {}", e).into())
+    }
+
+    pub async fn deploy(
         &self,
-        deployer: &Address,
+        deployer: &SignerAddress,
+        artifact: &ContractArtifact,
+        gas_config: &GasConfig,
         report: &[u8],
         report_sig: &[u8],
         handshake: &[u8],
+        enclave_sig: &[u8],
     ) -> Result<Address> {
-        let abi = include_bytes!("../../../../build/Anonify.abi");
-        let bin = include_str!("../../../../build/Anonify.bin");
-
-        let contract = Contract::deploy(self.web3.eth(), abi)
-            .map_err(|e| anyhow!("{:?}", e))?
-            .confirmations(CONFIRMATIONS)
-            .options(Options::with(|opt| opt.gas = Some(DEPLOY_GAS.into())))
-            .execute(
-                bin,
-                (report.to_vec(), report_sig.to_vec(), handshake.to_vec()), // Parameters are got from ecall, so these have to be allocated.
-                *deployer,
-            )
-            .map_err(|e| anyhow!("{:?}", e))?
-            .wait()
-            .map_err(|e| anyhow!("{:?}", e))?;
+        let data = artifact.deploy_data(&[
+            // Parameters are got from ecall, so these have to be allocated.
+            Token::Bytes(report.to_vec()),
+            Token::Bytes(report_sig.to_vec()),
+            Token::Bytes(handshake.to_vec()),
+            Token::Bytes(enclave_sig.to_vec()),
+        ])?;
+
+        let gas = self.estimate_gas(deployer.eth_address()?, None, &data).await?;
+        let mut tx = TxRequest {
+            to: None,
+            data,
+            gas: gas_config.gas_limit(gas),
+            fee: self.get_fee(gas_config.fee_policy).await?,
+            nonce: None,
+        };
+        let tx_hash = self.send_transaction(deployer, &mut tx).await?;
+        let receipt = self.wait_for_receipt(tx_hash).await?;
+
+        receipt.contract_address
+            .ok_or_else(|| anyhow!("No contract was deployed by {:?}", tx_hash).into())
+    }
+
+    /// Send a transaction signed by the node or locally depending on `signer`.
+    /// If it is signed locally, `tx.nonce` is set to the nonce used.
+    pub async fn send_transaction(&self, signer: &SignerAddress, tx: &mut TxRequest) -> Result<H256> {
+        match signer {
+            SignerAddress::EthAddress(from) => {
+                let mut req = json!({ "from": from, "gas": tx.gas, "data": Bytes(tx.data.clone()) });
+                if let Some(to) = tx.to {
+                    req["to"] = json!(to);
+                }
+                if let Some(nonce) = tx.nonce {
+                    req["nonce"] = json!(nonce);
+                }
+                match tx.fee {
+                    Fee::Legacy { gas_price } => req["gasPrice"] = json!(gas_price),
+                    Fee::Eip1559 { max_fee, max_priority_fee } => {
+                        req["maxFeePerGas"] = json!(max_fee);
+                        req["maxPriorityFeePerGas"] = json!(max_priority_fee);
+                    }
+                }
+
+                // web3 doesn't support EIP-1559 fees, so the RPC is called directly.
+                let tx_hash = with_timeout(
+                    self.web3.transport().execute("eth_sendTransaction", vec![req]).compat()
+                ).await?;
+                serde_json::from_value(tx_hash).map_err(|e| anyhow!("Invalid transaction hash: {}", e).into())
+            }
+            SignerAddress::EthSigner(signer) => {
+                let takes_nonce = tx.nonce.is_none();
+                let raw_tx = self.sign_transaction(signer, tx).await?;
+                with_timeout(self.web3.eth().send_raw_transaction(raw_tx).compat())
+                    .await
+                    .map_err(|e| {
+                        // The nonce may not have been used, so it is fetched from the node again.
+                        if takes_nonce {
+                            signer.reset_nonce();
+                        }
+                        e
+                    })
+            }
+            SignerAddress::LedgerAccount(name) => {
+                Err(anyhow!("{} is not an Ethereum account", name).into())
+            }
+        }
+    }
+
+    /// The nonce is fetched from the node for the first transaction of the signer,
+    /// and counted up locally for the following ones, which may still be pending.
+    async fn sign_transaction(&self, signer: &EthSigner, tx: &mut TxRequest) -> Result<Bytes> {
+        let chain_id = match signer.chain_id() {
+            Some(chain_id) => chain_id,
+            None => self.get_chain_id().await?,
+        };
+        let takes_nonce = tx.nonce.is_none();
+        // A replacement reuses the nonce of the pending transaction.
+        let nonce = match tx.nonce.or_else(|| signer.take_nonce()) {
+            Some(nonce) => nonce,
+            None => {
+                let count = with_timeout(
+                    self.web3.eth()
+                        .transaction_count(signer.address(), Some(BlockNumber::Pending))
+                        .compat()
+                ).await?;
+                signer.take_fetched_nonce(count)
+            }
+        };
+        tx.nonce = Some(nonce);
+
+        let raw_tx = RawTransaction {
+            nonce,
+            fee: tx.fee,
+            gas: tx.gas,
+            to: tx.to,
+            value: U256::zero(),
+            data: tx.data.clone(),
+        };
+        let raw_tx = signer.sign(&raw_tx, chain_id).map_err(|e| {
+            if takes_nonce {
+                signer.reset_nonce();
+            }
+            e
+        })?;
 
-        Ok(contract.address())
+        Ok(Bytes(raw_tx))
+    }
+
+    /// Poll the receipt of `tx_hash` until it is mined.
+    async fn wait_for_receipt(&self, tx_hash: H256) -> Result<TransactionReceipt> {
+        let poll = async {
+            loop {
+                if let Some(receipt) = self.web3.eth().transaction_receipt(tx_hash).compat().await? {
+                    return Ok::<_, web3::Error>(receipt);
+                }
+                Delay::new(RECEIPT_POLL_INTERVAL).await;
+            }
+        };
+
+        with_timeout(poll).await
     }
 
     pub fn get_eth_url(&self) -> &str {
@@ -92,97 +285,339 @@ impl Web3Http {
     }
 }
 
+/// Numbers of the blocks which pushed logs are included in.
+pub type LogStream = Pin<Box<dyn Stream<Item = Result<u64>> + Send>>;
+
+/// Web3 connection components via WebSocket, used to subscribe to logs.
+#[derive(Debug)]
+pub struct Web3Ws {
+    web3: Web3<WebSocket>,
+    eloop: EventLoopHandle,
+}
+
+impl Web3Ws {
+    /// Connect to the WebSocket endpoint served on the same host and port as `eth_url`,
+    /// as ganache does. WebSocket urls are used as they are.
+    pub fn new(eth_url: &str) -> Result<Self> {
+        let (eloop, transport) = WebSocket::new(&Self::ws_url(eth_url))?;
+        let web3 = Web3::new(transport);
+
+        Ok(Web3Ws {
+            web3,
+            eloop,
+        })
+    }
+
+    pub async fn subscribe_logs(&self, filter: Filter) -> Result<LogStream> {
+        let logs = with_timeout(self.web3.eth_subscribe().subscribe_logs(filter).compat()).await?;
+        let block_nums = logs.compat().map(|log| match log {
+            Ok(log) => Ok(log.block_number.unwrap_or_default().as_u64()),
+            Err(e) => Err(e.into()),
+        });
+
+        Ok(Box::pin(block_nums))
+    }
+
+    fn ws_url(eth_url: &str) -> String {
+        if eth_url.starts_with("https://") {
+            format!("wss://{}", &eth_url["https://".len()..])
+        } else if eth_url.starts_with("http://") {
+            format!("ws://{}", &eth_url["http://".len()..])
+        } else {
+            eth_url.to_string()
+        }
+    }
+}
+
+/// Parameters of a transaction to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRequest {
+    /// `None` to deploy a contract
+    pub to: Option<Address>,
+    pub data: Vec<u8>,
+    pub gas: U256,
+    pub fee: Fee,
+    /// The nonce of a pending transaction to replace, or `None` to use a new one.
+    pub nonce: Option<U256>,
+}
+
+/// A transaction sent by `Web3Contract`, which is resubmitted with a bumped fee while it is stuck.
+#[derive(Debug, Clone)]
+struct SentTx {
+    signer: SignerAddress,
+    tx: TxRequest,
+    /// Hashes of the original transaction and its replacements in the order they were sent
+    hashes: Vec<H256>,
+    /// When the original transaction was sent
+    first_sent_at: Instant,
+    /// When the latest replacement was sent
+    sent_at: Instant,
+    resubmissions: u32,
+}
+
+impl SentTx {
+    fn is_stuck(&self, gas_config: &GasConfig) -> bool {
+        self.resubmissions < gas_config.max_resubmissions
+            && self.sent_at.elapsed() >= gas_config.resubmit_after
+    }
+
+    fn is_expired(&self, gas_config: &GasConfig) -> bool {
+        self.first_sent_at.elapsed() >= gas_config.track_for
+    }
+}
+
 /// Web3 connection components of a contract.
 #[derive(Debug)]
 pub struct Web3Contract {
-    contract: Contract<Http>,
+    abi: ethabi::Contract,
     address: Address, // contract address
     web3_conn: Web3Http,
+    gas_config: GasConfig,
+    /// Transactions which have not been confirmed, keyed by the hashes returned to callers.
+    /// They are forgotten once they are confirmed, dropped or tracked for `GasConfig::track_for`.
+    sent_txs: Mutex<HashMap<H256, SentTx>>,
 }
 
 impl Web3Contract {
-    pub fn new<P: AsRef<Path>>(
+    pub fn new(
         web3_conn: Web3Http,
-        contract_info: ContractInfo<'_, P>
+        contract_info: ContractInfo<'_, ContractArtifact>,
+        gas_config: GasConfig,
     ) -> Result<Self> {
-        let abi = contract_info.contract_abi()?;
+        let abi = contract_info.artifact().abi().clone();
         let address = contract_info.address()?;
-        let contract = Contract::new(web3_conn.web3.eth(), address, abi);
 
-        Ok(Web3Contract {
-            contract,
+        Ok(Self::from_abi(web3_conn, abi, address, gas_config))
+    }
+
+    pub fn from_abi(
+        web3_conn: Web3Http,
+        abi: ethabi::Contract,
+        address: Address,
+        gas_config: GasConfig,
+    ) -> Self {
+        Web3Contract {
+            abi,
             address,
             web3_conn,
-        })
+            gas_config,
+            sent_txs: Mutex::new(HashMap::new()),
+        }
     }
 
-    pub fn join_group<G: Into<U256>>(
+    pub async fn join_group(
         &self,
-        from: Address,
+        signer: &SignerAddress,
         report: &[u8],
         report_sig: &[u8],
         handshake: &[u8],
-        gas: G,
+        enclave_sig: &[u8],
+        roster_idx: u32,
+        gas: Option<u64>,
     ) -> Result<H256> {
-        let call = self.contract.call(
-            "join_group",
-            (report.to_vec(), report_sig.to_vec(), handshake.to_vec()),
-            from,
-            Options::with(|opt| opt.gas = Some(gas.into())),
-        );
-
-        // https://github.com/tomusdrw/rust-web3/blob/c69bf938a0d3cfb5b64fca5974829408460e6685/src/confirm.rs#L253
-        let res = call.wait().unwrap(); //TODO: error handling
-        Ok(res)
+        self.call(
+            "joinGroup",
+            (report.to_vec(), report_sig.to_vec(), handshake.to_vec(), enclave_sig.to_vec(), U256::from(roster_idx)),
+            signer,
+            gas,
+        ).await
     }
 
-    pub fn send_instruction(
+    pub async fn send_instruction(
         &self,
-        from: Address,
+        signer: &SignerAddress,
         _state_id: u64,
         ciphertext: Ciphertext,
         enclave_sig: &[u8],
         msg: &[u8],
-        gas: u64,
+        gas: Option<u64>,
     ) -> Result<H256> {
-        let call = self.contract.call(
+        self.call(
             "storeInstruction",
             (ciphertext.into_vec(), enclave_sig.to_vec(), H256::from_slice(msg)),
-            from,
-            Options::with(|opt| opt.gas = Some(gas.into())),
-        );
-
-        // https://github.com/tomusdrw/rust-web3/blob/c69bf938a0d3cfb5b64fca5974829408460e6685/src/confirm.rs#L253
-        let res = call.wait().unwrap(); //TODO: error handling
-        Ok(res)
+            signer,
+            gas,
+        ).await
     }
 
-    pub fn handshake<G: Into<U256>>(
+    pub async fn handshake(
         &self,
-        from: Address,
+        signer: &SignerAddress,
         handshake: &[u8],
-        gas: G,
+        enclave_sig: &[u8],
+        gas: Option<u64>,
     ) -> Result<H256> {
-        let call = self.contract.call(
+        self.call(
             "handshake",
-            handshake.to_vec(),
-            from,
-            Options::with(|opt| opt.gas = Some(gas.into())),
-        );
+            (handshake.to_vec(), enclave_sig.to_vec()),
+            signer,
+            gas,
+        ).await
+    }
 
-        // https://github.com/tomusdrw/rust-web3/blob/c69bf938a0d3cfb5b64fca5974829408460e6685/src/confirm.rs#L253
-        let res = call.wait().unwrap(); //TODO: error handling
-        Ok(res)
+    /// Send a transaction calling `func`. Its gas is estimated if `gas` is `None`.
+    async fn call<P: Tokenize>(
+        &self,
+        func: &str,
+        params: P,
+        signer: &SignerAddress,
+        gas: Option<u64>,
+    ) -> Result<H256> {
+        let data = self.abi.function(func)
+            .and_then(|f| f.encode_input(&params.into_tokens()))
+            .map_err(|e| anyhow!("{:?}", e))?;
+        let gas = match gas {
+            Some(gas) => gas.into(),
+            None => {
+                let estimated = self.web3_conn.estimate_gas(signer.eth_address()?, Some(self.address), &data).await?;
+                self.gas_config.gas_limit(estimated)
+            }
+        };
+
+        let mut tx = TxRequest {
+            to: Some(self.address),
+            data,
+            gas,
+            fee: self.web3_conn.get_fee(self.gas_config.fee_policy).await?,
+            nonce: None,
+        };
+        let tx_hash = self.web3_conn.send_transaction(signer, &mut tx).await?;
+        let now = Instant::now();
+        self.tracked_txs().insert(tx_hash, SentTx {
+            signer: signer.clone(),
+            tx,
+            hashes: vec![tx_hash],
+            first_sent_at: now,
+            sent_at: now,
+            resubmissions: 0,
+        });
+
+        Ok(tx_hash)
     }
 
-    pub fn get_event<D: BlockNumDB>(
+    /// Get the status of `tx_hash`, which is pending until `confirmations` blocks are built on top of it.
+    /// If it has been pending for `GasConfig::resubmit_after`, it is resubmitted with a bumped fee,
+    /// and the status of the replacement is returned from then on.
+    pub async fn get_receipt(&self, tx_hash: H256, confirmations: u64) -> Result<TxReceipt> {
+        let eth = self.web3_conn.web3.eth();
+        let hashes = self.tracked_txs()
+            .get(&tx_hash)
+            .map(|sent| sent.hashes.clone())
+            .unwrap_or_else(|| vec![tx_hash]);
+
+        // Any of the replaced transactions may have been mined instead of the latest one.
+        for hash in hashes.iter().rev() {
+            if let Some(receipt) = with_timeout(eth.transaction_receipt(*hash).compat()).await? {
+                return self.mined_receipt(tx_hash, receipt, confirmations).await;
+            }
+        }
+
+        let latest_hash = hashes[hashes.len() - 1];
+        if with_timeout(eth.transaction(TransactionId::Hash(latest_hash)).compat()).await?.is_none() {
+            self.tracked_txs().remove(&tx_hash);
+            return Ok(TxReceipt::new(TxStatus::Dropped, None));
+        }
+        if let Err(e) = self.resubmit_if_stuck(tx_hash).await {
+            // The transaction may have been mined in the meantime, so it is tracked as is.
+            warn!("Failed to resubmit transaction {:?}: {}", tx_hash, e);
+        }
+
+        Ok(TxReceipt::new(TxStatus::Pending, None))
+    }
+
+    async fn mined_receipt(
         &self,
-        block_num_db: Arc<D>,
-        key: Address,
-    ) -> Result<Web3Logs<D>> {
+        tx_hash: H256,
+        receipt: TransactionReceipt,
+        confirmations: u64,
+    ) -> Result<TxReceipt> {
+        let gas_used = receipt.gas_used.map(|gas| gas.as_u64());
+        let block_num = match receipt.block_number {
+            Some(block_num) => block_num.as_u64(),
+            None => return Ok(TxReceipt::new(TxStatus::Pending, gas_used)),
+        };
+        if self.web3_conn.get_block_number().await? < block_num + confirmations {
+            return Ok(TxReceipt::new(TxStatus::Pending, gas_used));
+        }
+
+        let status = match receipt.status {
+            Some(status) if status.is_zero() => {
+                TxStatus::Reverted(self.revert_reason(receipt.transaction_hash, block_num).await)
+            }
+            _ => TxStatus::Mined(block_num),
+        };
+        self.tracked_txs().remove(&tx_hash);
+
+        Ok(TxReceipt::new(status, gas_used))
+    }
+
+    /// Replace a stuck transaction by one with the same nonce and a bumped fee.
+    async fn resubmit_if_stuck(&self, tx_hash: H256) -> Result<()> {
+        let sent = match self.tracked_txs().get(&tx_hash) {
+            Some(sent) if sent.is_stuck(&self.gas_config) => sent.clone(),
+            _ => return Ok(()),
+        };
+
+        let mut tx = sent.tx;
+        tx.fee = tx.fee.bump(self.gas_config.fee_bump_percent);
+        if tx.nonce.is_none() {
+            // Transactions signed by nodes are replaced with the nonces assigned by the nodes.
+            let latest_hash = sent.hashes[sent.hashes.len() - 1];
+            let pending = with_timeout(
+                self.web3_conn.web3.eth().transaction(TransactionId::Hash(latest_hash)).compat()
+            ).await?;
+            tx.nonce = match pending {
+                Some(pending) => Some(pending.nonce),
+                None => return Ok(()),
+            };
+        }
+
+        let new_hash = self.web3_conn.send_transaction(&sent.signer, &mut tx).await?;
+        debug!("Resubmitted transaction {:?} as {:?} with fee {:?}", tx_hash, new_hash, tx.fee);
+
+        if let Some(sent) = self.tracked_txs().get_mut(&tx_hash) {
+            sent.tx = tx;
+            sent.hashes.push(new_hash);
+            sent.sent_at = Instant::now();
+            sent.resubmissions += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Lock the tracked transactions, forgetting the expired ones so that they don't pile up
+    /// when their receipts are never polled.
+    fn tracked_txs(&self) -> MutexGuard<'_, HashMap<H256, SentTx>> {
+        let mut sent_txs = self.sent_txs.lock().unwrap();
+        sent_txs.retain(|_, sent| !sent.is_expired(&self.gas_config));
+        sent_txs
+    }
+
+    /// Replay a reverted transaction by `eth_call` on the state before its block to get the reason,
+    /// because receipts don't include it.
+    async fn revert_reason(&self, tx_hash: H256, block_num: u64) -> Option<String> {
+        let eth = self.web3_conn.web3.eth();
+        let tx = with_timeout(eth.transaction(TransactionId::Hash(tx_hash)).compat()).await.ok()??;
+        let req = CallRequest {
+            from: Some(tx.from),
+            to: self.address,
+            gas: Some(tx.gas),
+            gas_price: Some(tx.gas_price),
+            value: Some(tx.value),
+            data: Some(tx.input),
+        };
+
+        match with_timeout(eth.call(req, Some(BlockNumber::Number(block_num.saturating_sub(1)))).compat()).await {
+            // Some nodes return the revert data as the result.
+            Ok(output) => decode_revert_reason(&output.0),
+            // The others, e.g. ganache, put the reason in the error message.
+            Err(e) => Some(e.to_string()),
+        }
+    }
+
+    /// Fetch event logs emitted in the blocks from `from` to `to` inclusive.
+    pub async fn get_event(&self, from: u64, to: u64) -> Result<Web3Logs> {
         let events = EthEvent::create_event();
-        // Read latest block number from in-memory event db.
-        let latest_fetched_num = block_num_db.get_latest_block_num(key);
         let mut logs_acc = vec![];
 
         for event in &events.0 {
@@ -196,23 +631,42 @@ impl Web3Contract {
                     topic2: Topic::Any,
                     topic3: Topic::Any,
                 })
-                .from_block(BlockNumber::Number(latest_fetched_num))
-                .to_block(BlockNumber::Latest)
+                .from_block(BlockNumber::Number(from))
+                .to_block(BlockNumber::Number(to))
                 .build();
 
-            let logs = self.web3_conn.get_logs(filter)?;
+            let logs = self.web3_conn.get_logs(filter).await?;
             logs_acc.extend_from_slice(&logs);
         }
 
         Ok(Web3Logs {
             logs: logs_acc,
-            db: block_num_db,
             events,
         })
     }
 
-    pub fn get_account(&self, index: usize) -> Result<Address> {
-        self.web3_conn.get_account(index)
+    /// Subscribe to `StoreCiphertext`, `StoreHandshake` and `JoinGroup` logs of the contract over `ws_conn`.
+    pub async fn subscribe_event(&self, ws_conn: &Web3Ws) -> Result<LogStream> {
+        let events = EthEvent::create_event();
+        let filter = FilterBuilder::default()
+            .address(vec![self.address])
+            .topic_filter(TopicFilter {
+                topic0: Topic::OneOf(vec![
+                    events.ciphertext_signature(),
+                    events.handshake_signature(),
+                    events.join_group_signature(),
+                ]),
+                topic1: Topic::Any,
+                topic2: Topic::Any,
+                topic3: Topic::Any,
+            })
+            .build();
+
+        ws_conn.subscribe_logs(filter).await
+    }
+
+    pub async fn get_account(&self, index: usize) -> Result<Address> {
+        self.web3_conn.get_account(index).await
     }
 
     pub fn address(&self) -> Address {
@@ -220,31 +674,100 @@ impl Web3Contract {
     }
 }
 
+#[async_trait]
+impl ChainReader for Web3Contract {
+    async fn latest_block_num(&self) -> Result<u64> {
+        self.web3_conn.get_block_number().await
+    }
+
+    async fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        self.web3_conn.get_block_hash(block_num).await
+    }
+
+    async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>> {
+        self.get_event(from, to).await?.into_enclave_log()
+    }
+}
+
+/// A contract whose logs are fetched over HTTP and pushed over WebSocket.
+#[derive(Debug)]
+pub struct Web3SubscribedContract {
+    contract: Web3Contract,
+    ws_conn: Web3Ws,
+}
+
+impl Web3SubscribedContract {
+    pub fn new(contract: Web3Contract) -> Result<Self> {
+        let ws_conn = Web3Ws::new(contract.web3_conn.get_eth_url())?;
+        Ok(Web3SubscribedContract { contract, ws_conn })
+    }
+
+    pub fn address(&self) -> Address {
+        self.contract.address()
+    }
+
+    pub fn into_contract(self) -> Web3Contract {
+        self.contract
+    }
+}
+
+#[async_trait]
+impl ChainReader for Web3SubscribedContract {
+    async fn latest_block_num(&self) -> Result<u64> {
+        self.contract.latest_block_num().await
+    }
+
+    async fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        self.contract.block_hash(block_num).await
+    }
+
+    async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>> {
+        self.contract.enclave_log(from, to).await
+    }
+}
+
+#[async_trait]
+impl LogSubscriber for Web3SubscribedContract {
+    type Subscription = LogStream;
+
+    async fn subscribe(&self) -> Result<Self::Subscription> {
+        self.contract.subscribe_event(&self.ws_conn).await
+    }
+}
+
+/// Decode the reason from the output of a call reverted by `revert` or `require`.
+fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < ERROR_SELECTOR.len() || output[..ERROR_SELECTOR.len()] != ERROR_SELECTOR {
+        return None;
+    }
+
+    match decode(&[ParamType::String], &output[ERROR_SELECTOR.len()..]).ok()?.pop()? {
+        Token::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
 /// Event fetched logs from smart contracts.
 #[derive(Debug)]
-pub struct Web3Logs<D: BlockNumDB>{
+pub struct Web3Logs {
     logs: Vec<Log>,
-    db: Arc<D>,
     events: EthEvent,
 }
 
-impl<D: BlockNumDB> Web3Logs<D> {
-    pub fn into_enclave_log(self) -> Result<EnclaveLog<D>> {
-        let mut ciphertexts: Vec<Ciphertext> = vec![];
-        let mut handshakes: Vec<Vec<u8>> = vec![];
-
-        // If log data is not fetched currently, return empty EnclaveLog.
-        // This is occurred when it fetched data of dupulicated block number.
+impl Web3Logs {
+    pub fn into_enclave_log(mut self) -> Result<Option<InnerEnclaveLog>> {
+        // If log data is not fetched currently, return None.
         if self.logs.len() == 0 {
-            return Ok(EnclaveLog{
-                inner: None,
-                db: self.db
-            });
+            return Ok(None);
         }
 
+        // Logs are fetched per event type, so they are sorted in the order they were emitted.
+        self.logs.sort_by_key(|log| (log.block_number, log.log_index));
+
         let contract_addr = self.logs[0].address;
         let mut latest_blc_num = 0;
-        let ciphertext_size = Self::decode_data(&self.logs[0]).len();
+        let mut latest_blc_hash = None;
+        let mut events = Vec::with_capacity(self.logs.len());
 
         for (i, log) in self.logs.iter().enumerate() {
             debug!("log: {:?}, \nindex: {:?}", log, i);
@@ -252,40 +775,37 @@ impl<D: BlockNumDB> Web3Logs<D> {
                 return Err(anyhow!("Each log should have same contract address.: index: {}", i).into());
             }
 
-            let mut data = Self::decode_data(&log);
-
-            // Processing conditions by ciphertext or handshake event
+            // Processing conditions by ciphertext, handshake or join group event
             if log.topics[0] == self.events.ciphertext_signature() {
-                if ciphertext_size != data.len() && data.len() != 0  {
-                    return Err(anyhow!("Each log should have same size of data.: index: {}", i).into());
+                let data = Self::decode_data(&log);
+                if data.len() != CIPHERTEXT_SIZE {
+                    return Err(anyhow!("Each ciphertext should have {} bytes.: index: {}", CIPHERTEXT_SIZE, i).into());
                 }
-                let res = Ciphertext::from_bytes(&mut data[..]);
-
-                ciphertexts.push(res);
+                events.push(EnclaveEvent::Ciphertext(data));
             } else if log.topics[0] == self.events.handshake_signature() {
-                handshakes.push(data);
+                events.push(Self::decode_handshake(&log)?);
+            } else if log.topics[0] == self.events.join_group_signature() {
+                events.push(Self::decode_join_group(&log)?);
             } else {
                 return Err(anyhow!("Invalid topics").into());
             }
 
-            // Update latest block number
+            // Update latest block number and its hash
             if let Some(blc_num) = log.block_number {
                 let blc_num = blc_num.as_u64();
                 if latest_blc_num < blc_num {
-                    latest_blc_num = blc_num
+                    latest_blc_num = blc_num;
+                    latest_blc_hash = log.block_hash;
                 }
             }
         }
 
-        Ok(EnclaveLog {
-            inner : Some(InnerEnclaveLog {
-                contract_addr: contract_addr.to_fixed_bytes(),
-                latest_blc_num: latest_blc_num,
-                ciphertexts,
-                handshakes,
-            }),
-            db: self.db,
-        })
+        Ok(Some(InnerEnclaveLog {
+            contract_addr: contract_addr.to_fixed_bytes(),
+            latest_blc_num: latest_blc_num,
+            latest_blc_hash,
+            events,
+        }))
     }
 
     fn decode_data(log: &Log) -> Vec<u8> {
@@ -299,6 +819,42 @@ impl<D: BlockNumDB> Web3Logs<D> {
 
         res
     }
+
+    fn decode_handshake(log: &Log) -> Result<EnclaveEvent> {
+        let mut tokens = decode(&[ParamType::Bytes, ParamType::Bytes], &log.data.0)
+            .map_err(|e| anyhow!("Failed to decode StoreHandshake log: {}", e))?
+            .into_iter();
+        let handshake = tokens.next().and_then(|t| t.to_bytes())
+            .ok_or_else(|| anyhow!("Failed to decode handshake."))?;
+        let enclave_sig = tokens.next().and_then(|t| t.to_bytes())
+            .ok_or_else(|| anyhow!("Failed to decode enclave signature."))?;
+
+        Ok(EnclaveEvent::Handshake { handshake, enclave_sig })
+    }
+
+    fn decode_join_group(log: &Log) -> Result<EnclaveEvent> {
+        let mut tokens = decode(
+            &[ParamType::Uint(32), ParamType::Address, ParamType::Bytes, ParamType::Bytes],
+            &log.data.0,
+        )
+            .map_err(|e| anyhow!("Failed to decode JoinGroup log: {}", e))?
+            .into_iter();
+        let roster_idx = tokens.next().and_then(|t| t.to_uint())
+            .ok_or_else(|| anyhow!("Failed to decode roster index."))?;
+        let enclave_address = tokens.next().and_then(|t| t.to_address())
+            .ok_or_else(|| anyhow!("Failed to decode enclave address."))?;
+        let handshake = tokens.next().and_then(|t| t.to_bytes())
+            .ok_or_else(|| anyhow!("Failed to decode handshake."))?;
+        let enclave_sig = tokens.next().and_then(|t| t.to_bytes())
+            .ok_or_else(|| anyhow!("Failed to decode enclave signature."))?;
+
+        Ok(EnclaveEvent::JoinGroup {
+            roster_idx: roster_idx.as_u32(),
+            enclave_address: enclave_address.0,
+            handshake,
+            enclave_sig,
+        })
+    }
 }
 
 /// A type of events from ethererum network.
@@ -327,6 +883,37 @@ impl EthEvent {
                         kind: ParamType::Bytes,
                         indexed: false,
                     },
+                    EventParam {
+                        name: "enclaveSig".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
+                ],
+                anonymous: false,
+            },
+            Event {
+                name: "JoinGroup".to_owned(),
+                inputs: vec![
+                    EventParam {
+                        name: "rosterIdx".to_owned(),
+                        kind: ParamType::Uint(32),
+                        indexed: false,
+                    },
+                    EventParam {
+                        name: "enclaveAddress".to_owned(),
+                        kind: ParamType::Address,
+                        indexed: false,
+                    },
+                    EventParam {
+                        name: "handshake".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
+                    EventParam {
+                        name: "enclaveSig".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
                 ],
                 anonymous: false,
             }
@@ -342,4 +929,8 @@ impl EthEvent {
     pub fn handshake_signature(&self) -> Hash {
         self.0[1].signature()
     }
+
+    pub fn join_group_signature(&self) -> Hash {
+        self.0[2].signature()
+    }
 }
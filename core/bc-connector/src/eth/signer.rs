@@ -0,0 +1,263 @@
+//! Local signing of transactions, so that nodes don't need to manage unlocked accounts.
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use secp256k1::{Secp256k1, SecretKey, PublicKey, Message};
+use rlp::{RlpStream, EMPTY_LIST_RLP};
+use tiny_keccak::keccak256;
+use web3::types::{Address, U256};
+use anyhow::anyhow;
+use crate::error::Result;
+use super::gas::Fee;
+
+/// The type of EIP-1559 transactions defined in EIP-2718.
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// A secp256k1 key which signs transactions locally.
+/// Clones share the nonce of the next transaction, so they can be passed to both deployer and sender.
+#[derive(Clone)]
+pub struct EthSigner {
+    secret: SecretKey,
+    address: Address,
+    chain_id: Option<u64>,
+    next_nonce: Arc<Mutex<Option<U256>>>,
+}
+
+impl EthSigner {
+    pub fn from_secret(secret: &[u8]) -> Result<Self> {
+        let secret = SecretKey::from_slice(secret)
+            .map_err(|e| anyhow!("Invalid secp256k1 secret key: {}", e))?;
+        let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret);
+        let hash = keccak256(&public.serialize_uncompressed()[1..]);
+
+        Ok(EthSigner {
+            secret,
+            address: Address::from_slice(&hash[12..]),
+            chain_id: None,
+            next_nonce: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Sign for `chain_id` instead of the one returned by `eth_chainId`.
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn chain_id(&self) -> Option<u64> {
+        self.chain_id
+    }
+
+    /// Take the nonce of the next transaction if it has been fetched from the node.
+    pub(crate) fn take_nonce(&self) -> Option<U256> {
+        let mut next_nonce = self.next_nonce.lock().unwrap();
+        let nonce = (*next_nonce)?;
+        *next_nonce = Some(nonce + 1);
+        Some(nonce)
+    }
+
+    /// Take `fetched`, the pending transaction count on the node, unless another transaction has
+    /// taken a nonce in the meantime.
+    pub(crate) fn take_fetched_nonce(&self, fetched: U256) -> U256 {
+        let mut next_nonce = self.next_nonce.lock().unwrap();
+        let nonce = next_nonce.unwrap_or(fetched);
+        *next_nonce = Some(nonce + 1);
+        nonce
+    }
+
+    /// Forget the cached nonce so that it is fetched again, e.g. after a transaction was rejected.
+    pub(crate) fn reset_nonce(&self) {
+        *self.next_nonce.lock().unwrap() = None;
+    }
+
+    /// Sign `tx` for `chain_id` and encode it for `eth_sendRawTransaction`.
+    pub fn sign(&self, tx: &RawTransaction, chain_id: u64) -> Result<Vec<u8>> {
+        let hash = tx.signing_hash(chain_id);
+        let msg = Message::from_slice(&hash)
+            .map_err(|e| anyhow!("Invalid message to sign: {}", e))?;
+        let (rec_id, sig) = Secp256k1::signing_only()
+            .sign_recoverable(&msg, &self.secret)
+            .serialize_compact();
+
+        let r = U256::from_big_endian(&sig[..32]);
+        let s = U256::from_big_endian(&sig[32..]);
+
+        Ok(tx.encode(chain_id, rec_id.to_i32() as u64, r, s))
+    }
+}
+
+impl fmt::Debug for EthSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EthSigner")
+            .field("address", &self.address)
+            .field("chain_id", &self.chain_id)
+            .finish()
+    }
+}
+
+/// A transaction to be signed locally. `to` is `None` to deploy a contract.
+/// It is a legacy transaction following EIP-155 or an EIP-1559 transaction depending on `fee`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawTransaction {
+    pub nonce: U256,
+    pub fee: Fee,
+    pub gas: U256,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+impl RawTransaction {
+    /// The hash of `(nonce, gas_price, gas, to, value, data, chain_id, 0, 0)` defined in EIP-155,
+    /// or of `0x02 || (chain_id, nonce, max_priority_fee, max_fee, gas, to, value, data, [])` defined in EIP-1559.
+    pub fn signing_hash(&self, chain_id: u64) -> [u8; 32] {
+        match self.fee {
+            Fee::Legacy { gas_price } => {
+                let mut stream = RlpStream::new_list(9);
+                self.append_legacy_fields(&mut stream, gas_price);
+                stream.append(&chain_id);
+                stream.append(&0u8);
+                stream.append(&0u8);
+
+                keccak256(&stream.out())
+            }
+            Fee::Eip1559 { max_fee, max_priority_fee } => {
+                let mut stream = RlpStream::new_list(9);
+                self.append_eip1559_fields(&mut stream, chain_id, max_fee, max_priority_fee);
+
+                let mut payload = vec![EIP1559_TX_TYPE];
+                payload.extend_from_slice(&stream.out());
+                keccak256(&payload)
+            }
+        }
+    }
+
+    fn encode(&self, chain_id: u64, rec_id: u64, r: U256, s: U256) -> Vec<u8> {
+        match self.fee {
+            Fee::Legacy { gas_price } => {
+                let mut stream = RlpStream::new_list(9);
+                self.append_legacy_fields(&mut stream, gas_price);
+                stream.append(&(rec_id + 35 + chain_id * 2));
+                stream.append(&r);
+                stream.append(&s);
+
+                stream.out()
+            }
+            Fee::Eip1559 { max_fee, max_priority_fee } => {
+                let mut stream = RlpStream::new_list(12);
+                self.append_eip1559_fields(&mut stream, chain_id, max_fee, max_priority_fee);
+                stream.append(&rec_id);
+                stream.append(&r);
+                stream.append(&s);
+
+                let mut raw_tx = vec![EIP1559_TX_TYPE];
+                raw_tx.extend_from_slice(&stream.out());
+                raw_tx
+            }
+        }
+    }
+
+    fn append_legacy_fields(&self, stream: &mut RlpStream, gas_price: U256) {
+        stream.append(&self.nonce);
+        stream.append(&gas_price);
+        stream.append(&self.gas);
+        self.append_call(stream);
+    }
+
+    fn append_eip1559_fields(&self, stream: &mut RlpStream, chain_id: u64, max_fee: U256, max_priority_fee: U256) {
+        stream.append(&chain_id);
+        stream.append(&self.nonce);
+        stream.append(&max_priority_fee);
+        stream.append(&max_fee);
+        stream.append(&self.gas);
+        self.append_call(stream);
+        // No access list
+        stream.append_raw(&EMPTY_LIST_RLP, 1);
+    }
+
+    fn append_call(&self, stream: &mut RlpStream) {
+        match self.to {
+            Some(ref to) => stream.append(to),
+            None => stream.append_empty_data(),
+        };
+        stream.append(&self.value);
+        stream.append(&self.data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example transaction in EIP-155.
+    fn eip155_example() -> (EthSigner, RawTransaction) {
+        let signer = EthSigner::from_secret(
+            &hex::decode("4646464646464646464646464646464646464646464646464646464646464646").unwrap()
+        ).unwrap();
+        let tx = RawTransaction {
+            nonce: U256::from(9),
+            fee: Fee::Legacy { gas_price: U256::from(20_000_000_000u64) },
+            gas: U256::from(21_000),
+            to: Some(Address::from_slice(&hex::decode("3535353535353535353535353535353535353535").unwrap())),
+            value: U256::from(1_000_000_000_000_000_000u64),
+            data: vec![],
+        };
+
+        (signer, tx)
+    }
+
+    #[test]
+    fn test_eip155_signing_hash() {
+        let (_, tx) = eip155_example();
+        assert_eq!(
+            hex::encode(tx.signing_hash(1)),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+    }
+
+    #[test]
+    fn test_eip155_signed_transaction() {
+        let (signer, tx) = eip155_example();
+        assert_eq!(
+            hex::encode(signer.sign(&tx, 1).unwrap()),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+    }
+
+    #[test]
+    fn test_eip1559_transaction_type() {
+        let (signer, mut tx) = eip155_example();
+        tx.fee = Fee::Eip1559 {
+            max_fee: U256::from(40_000_000_000u64),
+            max_priority_fee: U256::from(1_000_000_000u64),
+        };
+
+        let raw_tx = signer.sign(&tx, 1).unwrap();
+        assert_eq!(raw_tx[0], EIP1559_TX_TYPE);
+        // The remaining bytes are a list of 12 fields, whose y parity is 0 or 1.
+        let fields = rlp::Rlp::new(&raw_tx[1..]);
+        assert_eq!(fields.item_count().unwrap(), 12);
+        assert!(fields.val_at::<u8>(9).unwrap() <= 1);
+        assert_ne!(tx.signing_hash(1), eip155_example().1.signing_hash(1));
+    }
+
+    #[test]
+    fn test_nonce_is_fetched_once_and_reset_on_failure() {
+        let (signer, _) = eip155_example();
+        let cloned = signer.clone();
+
+        assert_eq!(signer.take_nonce(), None);
+        assert_eq!(signer.take_fetched_nonce(U256::from(5)), U256::from(5));
+        // A count fetched concurrently doesn't rewind the cached nonce.
+        assert_eq!(cloned.take_fetched_nonce(U256::from(5)), U256::from(6));
+        assert_eq!(cloned.take_nonce(), Some(U256::from(7)));
+
+        signer.reset_nonce();
+        assert_eq!(cloned.take_nonce(), None);
+    }
+}
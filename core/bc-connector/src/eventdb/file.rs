@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write, BufWriter},
+    path::Path,
+    sync::Mutex,
+};
+use anonify_common::kvs::{KVS, MemoryDB, DBTx, DBOp};
+use web3::types::{Address, H256};
+use byteorder::{LittleEndian, ByteOrder};
+use log::warn;
+use super::{BlockNumDB, EventDBTx, block_num_from_value, block_hash_from_value};
+use crate::error::Result;
+
+const INSERT_TAG: u8 = 0;
+const DELETE_TAG: u8 = 1;
+const LEN_SIZE: usize = 4;
+
+/// A `BlockNumDB` persisted to an append-only log file,
+/// so that a restarted server resumes syncing events where it left off.
+///
+/// Each operation is appended as `tag || key length || key || value length || value`
+/// with little-endian u32 lengths, and all operations are replayed in order on opening.
+/// A partially written record at the end of the file, left by a crash, is discarded.
+#[derive(Debug)]
+pub struct FileEventDB {
+    cache: MemoryDB,
+    log: Mutex<File>,
+}
+
+impl FileEventDB {
+    /// Open the database at the given path, creating it if it does not exist.
+    /// The log is compacted to the latest values on opening.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let entries = match fs::read(path) {
+            Ok(buf) => replay(&buf),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut tx = DBTx::new();
+        for (key, value) in &entries {
+            tx.put(key, value);
+        }
+
+        // Replace the old log atomically so that a crash while compacting never loses records.
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&encode_ops(tx.ops()))?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, path)?;
+
+        let cache = MemoryDB::new();
+        cache.inner_write(tx);
+        let log = OpenOptions::new().append(true).open(path)?;
+
+        Ok(FileEventDB {
+            cache,
+            log: Mutex::new(log),
+        })
+    }
+}
+
+impl BlockNumDB for FileEventDB {
+    fn set_next_block_num(&self, tx: EventDBTx) -> Result<()> {
+        let mut log = self.log.lock().unwrap();
+        // Persist operations before updating the cache, so that a failed write is never observed.
+        log.write_all(&encode_ops(tx.0.ops()))?;
+        log.sync_data()?;
+        self.cache.inner_write(tx.0);
+
+        Ok(())
+    }
+
+    fn get_latest_block_num(&self, key: Address) -> u64 {
+        block_num_from_value(self.cache.inner_get(key.as_bytes()))
+    }
+
+    fn get_latest_block_hash(&self, key: Address) -> Option<H256> {
+        block_hash_from_value(self.cache.inner_get(key.as_bytes()))
+    }
+}
+
+fn encode_ops(ops: &[DBOp]) -> Vec<u8> {
+    let mut buf = vec![];
+    for op in ops {
+        let (tag, value) = match op {
+            DBOp::Insert { value, .. } => (INSERT_TAG, value.clone().into_vec()),
+            DBOp::Delete { .. } => (DELETE_TAG, vec![]),
+        };
+        buf.push(tag);
+        write_bytes(&mut buf, op.key());
+        write_bytes(&mut buf, &value);
+    }
+
+    buf
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    let mut len = [0u8; LEN_SIZE];
+    LittleEndian::write_u32(&mut len, bytes.len() as u32);
+    buf.extend_from_slice(&len);
+    buf.extend_from_slice(bytes);
+}
+
+/// Replay all records in the log and return the latest value of each key.
+fn replay(mut buf: &[u8]) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let mut entries = BTreeMap::new();
+    while !buf.is_empty() {
+        let (tag, key, value, rest) = match read_record(buf) {
+            Some(record) => record,
+            None => {
+                warn!("Discarded a truncated record at the end of the event db log.");
+                break;
+            }
+        };
+
+        match tag {
+            INSERT_TAG => { entries.insert(key.to_vec(), value.to_vec()); },
+            DELETE_TAG => { entries.remove(key); },
+            _ => {
+                warn!("Discarded records after an unknown tag {} in the event db log.", tag);
+                break;
+            }
+        }
+        buf = rest;
+    }
+
+    entries
+}
+
+fn read_record(buf: &[u8]) -> Option<(u8, &[u8], &[u8], &[u8])> {
+    let (&tag, rest) = buf.split_first()?;
+    let (key, rest) = read_bytes(rest)?;
+    let (value, rest) = read_bytes(rest)?;
+
+    Some((tag, key, value, rest))
+}
+
+fn read_bytes(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    if buf.len() < LEN_SIZE {
+        return None;
+    }
+    let len = LittleEndian::read_u32(&buf[..LEN_SIZE]) as usize;
+    let rest = &buf[LEN_SIZE..];
+    if rest.len() < len {
+        return None;
+    }
+
+    Some(rest.split_at(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::PathBuf, process};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("anonify-eventdb-{}-{}", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn put(db: &FileEventDB, address: Address, block_num: u64, block_hash: Option<H256>) {
+        let mut tx = EventDBTx::new();
+        tx.put(address, block_num, block_hash);
+        db.set_next_block_num(tx).unwrap();
+    }
+
+    #[test]
+    fn test_resume_after_reopen() {
+        let path = temp_path("resume");
+        let addr1 = Address::repeat_byte(1);
+        let addr2 = Address::repeat_byte(2);
+        let hash = H256::repeat_byte(3);
+
+        let db = FileEventDB::open(&path).unwrap();
+        assert_eq!(db.get_latest_block_num(addr1), 0);
+        assert_eq!(db.get_latest_block_hash(addr1), None);
+
+        put(&db, addr1, 10, None);
+        put(&db, addr1, 12, Some(hash));
+        put(&db, addr2, 5, None);
+        drop(db);
+
+        let db = FileEventDB::open(&path).unwrap();
+        assert_eq!(db.get_latest_block_num(addr1), 12);
+        assert_eq!(db.get_latest_block_hash(addr1), Some(hash));
+        assert_eq!(db.get_latest_block_num(addr2), 5);
+        assert_eq!(db.get_latest_block_hash(addr2), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_discard_truncated_record() {
+        let path = temp_path("truncated");
+        let addr = Address::repeat_byte(1);
+        let hash = H256::repeat_byte(2);
+
+        let db = FileEventDB::open(&path).unwrap();
+        put(&db, addr, 7, Some(hash));
+        drop(db);
+
+        // Simulate a crash in the middle of appending the next record.
+        let mut tx = EventDBTx::new();
+        tx.put(addr, 8, None);
+        let record = encode_ops(tx.0.ops());
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(&record[..record.len() - 1]).unwrap();
+        drop(log);
+
+        let db = FileEventDB::open(&path).unwrap();
+        assert_eq!(db.get_latest_block_num(addr), 7);
+        assert_eq!(db.get_latest_block_hash(addr), Some(hash));
+
+        // The truncated record is dropped by compaction, so new records are appended cleanly.
+        put(&db, addr, 9, None);
+        drop(db);
+        let db = FileEventDB::open(&path).unwrap();
+        assert_eq!(db.get_latest_block_num(addr), 9);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
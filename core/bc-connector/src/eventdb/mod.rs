@@ -0,0 +1,94 @@
+use anonify_common::{
+    EnclaveEvent,
+    kvs::{KVS, MemoryDB, DBTx, DBValue}
+};
+use web3::types::{Address, H256};
+use byteorder::{LittleEndian, ByteOrder};
+use crate::error::Result;
+
+mod file;
+
+pub use self::file::FileEventDB;
+
+const BLOCK_NUM_SIZE: usize = 8;
+const BLOCK_HASH_SIZE: usize = 32;
+
+/// Stores the next block number to fetch events from per contract address,
+/// along with the hash of the latest processed block.
+pub trait BlockNumDB: Send + Sync {
+    fn set_next_block_num(&self, tx: EventDBTx) -> Result<()>;
+
+    fn get_latest_block_num(&self, key: Address) -> u64;
+
+    /// Hash of the latest processed block, used to detect that the block has been reorganized away.
+    /// Returns `None` if no block has been processed or its hash is unknown.
+    fn get_latest_block_hash(&self, key: Address) -> Option<H256>;
+}
+
+#[derive(Debug)]
+pub struct EventDB(MemoryDB);
+
+impl EventDB {
+    pub fn new() -> Self {
+        EventDB(MemoryDB::new())
+    }
+}
+
+impl BlockNumDB for EventDB {
+    fn set_next_block_num(&self, tx: EventDBTx) -> Result<()> {
+        self.0.inner_write(tx.0);
+        Ok(())
+    }
+
+    fn get_latest_block_num(&self, key: Address) -> u64 {
+        block_num_from_value(self.0.inner_get(key.as_bytes()))
+    }
+
+    fn get_latest_block_hash(&self, key: Address) -> Option<H256> {
+        block_hash_from_value(self.0.inner_get(key.as_bytes()))
+    }
+}
+
+/// A value is a little-endian next block number followed by the latest processed block hash if it is known.
+fn block_num_from_value(value: Option<DBValue>) -> u64 {
+    match value {
+        Some(val) => LittleEndian::read_u64(&val.into_vec()[..BLOCK_NUM_SIZE]),
+        None => 0,
+    }
+}
+
+fn block_hash_from_value(value: Option<DBValue>) -> Option<H256> {
+    let val = value?.into_vec();
+    if val.len() != BLOCK_NUM_SIZE + BLOCK_HASH_SIZE {
+        return None;
+    }
+
+    Some(H256::from_slice(&val[BLOCK_NUM_SIZE..]))
+}
+
+#[derive(Default, Clone, PartialEq)]
+pub struct EventDBTx(DBTx);
+
+impl EventDBTx {
+    pub fn new() -> Self {
+        EventDBTx(DBTx::new())
+    }
+
+    pub fn put(&mut self, address: Address, block_num: u64, block_hash: Option<H256>) {
+        let mut wtr = vec![0u8; BLOCK_NUM_SIZE];
+        LittleEndian::write_u64(&mut wtr, block_num);
+        if let Some(hash) = block_hash {
+            wtr.extend_from_slice(hash.as_bytes());
+        }
+        self.0.put(address.as_bytes(), &wtr);
+    }
+}
+
+/// A log which is sent to enclave. Each log containes events of a given contract address up to a given block number.
+#[derive(Debug, Clone)]
+pub struct InnerEnclaveLog {
+    pub contract_addr: [u8; 20],
+    pub latest_blc_num: u64,
+    pub latest_blc_hash: Option<H256>,
+    pub events: Vec<EnclaveEvent>, // All fetched events in chain order
+}
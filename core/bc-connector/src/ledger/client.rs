@@ -0,0 +1,257 @@
+use std::{
+    sync::Arc,
+};
+use sgx_types::sgx_enclave_id_t;
+use anonify_types::{RawJoinGroupTx, RawInstructionTx, RawHandshakeTx};
+use anonify_common::{AccessRight, EnclaveEvent, IntoVec};
+use anonify_runtime::{traits::State, UpdatedState};
+use web3::types::Address;
+use async_trait::async_trait;
+use anyhow::anyhow;
+use crate::{
+    error::{Result, HostError},
+    eth::{
+        client::{BoxedJoinGroupTx, BoxedInstructionTx, BoxedHandshakeTx},
+        GasConfig,
+    },
+    eventdb::{BlockNumDB, InnerEnclaveLog},
+    sync::{ChainReader, EventSync, Rollback, SyncConfig},
+    traits::*,
+    utils::*,
+};
+use super::primitives::{LedgerHttp, LedgerContract};
+
+/// Accounts are named after their indices, as any account can append to a ledger.
+fn ledger_account(index: usize) -> SignerAddress {
+    SignerAddress::LedgerAccount(format!("account{}", index))
+}
+
+/// Components needed to deploy a contract on a ledger
+#[derive(Debug)]
+pub struct LedgerDeployer {
+    enclave_id: sgx_enclave_id_t,
+    ledger_conn: LedgerHttp,
+    address: Option<Address>, // contract address
+}
+
+impl Deployer for LedgerDeployer {
+    type Artifact = ();
+
+    fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
+        Ok(LedgerDeployer {
+            enclave_id,
+            ledger_conn: LedgerHttp::new(node_url)?,
+            address: None,
+        })
+    }
+
+    fn get_account(&self, index: usize) -> Result<SignerAddress> {
+        Ok(ledger_account(index))
+    }
+
+    /// Ledgers have no gas, so `gas_config` is ignored.
+    fn deploy<F>(
+        &mut self,
+        deploy_user: &SignerAddress,
+        _artifact: &(),
+        _gas_config: GasConfig,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx>,
+    {
+        let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
+        let (contract_addr, _) = self.ledger_conn.deploy(
+            deploy_user.account_name(),
+            &join_group_tx.report,
+            &join_group_tx.report_sig,
+            &join_group_tx.handshake,
+            &join_group_tx.enclave_sig,
+        )?;
+        self.address = Some(contract_addr);
+
+        Ok(hex::encode(contract_addr.as_bytes()))
+    }
+
+    fn get_contract(self) -> Result<ContractKind> {
+        let address = self.address.ok_or(HostError::AddressNotSet)?;
+        Ok(ContractKind::LedgerContract(LedgerContract::from_deployed(self.ledger_conn, address)))
+    }
+
+    fn get_enclave_id(&self) -> sgx_enclave_id_t {
+        self.enclave_id
+    }
+
+    fn get_node_url(&self) -> &str {
+        self.ledger_conn.get_ledger_url()
+    }
+
+    fn register_notification<F>(
+        &self,
+        access_right: AccessRight,
+        reg_notify_fn: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(sgx_enclave_id_t, AccessRight) -> Result<()>,
+    {
+        reg_notify_fn(self.enclave_id, access_right)
+    }
+}
+
+/// Components needed to append events to a ledger
+#[derive(Debug)]
+pub struct LedgerSender {
+    enclave_id: sgx_enclave_id_t,
+    contract: LedgerContract,
+}
+
+#[async_trait]
+impl AsyncSender for LedgerSender {
+    type Artifact = ();
+
+    fn new(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, ()>,
+        _gas_config: GasConfig,
+    ) -> Result<Self> {
+        let contract = LedgerContract::new(LedgerHttp::new(node_url)?, contract_info)?;
+        Ok(LedgerSender { enclave_id, contract })
+    }
+
+    fn from_contract(
+        enclave_id: sgx_enclave_id_t,
+        contract: ContractKind,
+    ) -> Result<Self> {
+        match contract {
+            ContractKind::LedgerContract(contract) => Ok(LedgerSender { enclave_id, contract }),
+            _ => Err(anyhow!("LedgerSender can only be built from a ledger contract.").into()),
+        }
+    }
+
+    async fn get_account(&self, index: usize) -> Result<SignerAddress> {
+        Ok(ledger_account(index))
+    }
+
+    async fn join_group<F>(
+        &self,
+        signer: SignerAddress,
+        _gas: Option<u64>,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send,
+    {
+        let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
+        let receipt = self.contract.join_group(
+            signer.account_name(),
+            join_group_tx.roster_idx,
+            &join_group_tx.report,
+            &join_group_tx.report_sig,
+            &join_group_tx.handshake,
+            &join_group_tx.enclave_sig,
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    async fn send_instruction<ST, F>(
+        &self,
+        access_right: AccessRight,
+        signer: SignerAddress,
+        state_info: StateInfo<'_, ST>,
+        _gas: Option<u64>,
+        enc_ins_fn: F,
+    ) -> Result<String>
+    where
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send,
+    {
+        let mut instruction_tx: BoxedInstructionTx = enc_ins_fn(self.enclave_id, access_right, state_info)?.into();
+        let receipt = self.contract.send(
+            signer.account_name(),
+            EnclaveEvent::Ciphertext(instruction_tx.get_ciphertext().into_vec()),
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    async fn handshake<F>(
+        &self,
+        signer: SignerAddress,
+        _gas: Option<u64>,
+        handshake_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send
+    {
+        let handshake_tx: BoxedHandshakeTx = handshake_fn(self.enclave_id)?.into();
+        let receipt = self.contract.send(
+            signer.account_name(),
+            EnclaveEvent::Handshake {
+                handshake: handshake_tx.handshake.to_vec(),
+                enclave_sig: handshake_tx.enclave_sig.to_vec(),
+            },
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    /// Transactions are appended as soon as they are sent and never reverted.
+    async fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt> {
+        let status = match self.contract.tx_block_num(parse_tx_hash(tx_hash)?)? {
+            None => TxStatus::Dropped,
+            Some(block_num) if self.contract.latest_block_num().await? < block_num + confirmations => TxStatus::Pending,
+            Some(block_num) => TxStatus::Mined(block_num),
+        };
+
+        Ok(TxReceipt::new(status, None))
+    }
+
+    fn get_contract(self) -> ContractKind {
+        ContractKind::LedgerContract(self.contract)
+    }
+}
+
+/// Components needed to watch events appended to a ledger
+pub struct LedgerWatcher<DB: BlockNumDB> {
+    contract: LedgerContract,
+    event_db: Arc<DB>,
+    sync_config: SyncConfig,
+}
+
+#[async_trait]
+impl<DB: BlockNumDB> AsyncWatcher for LedgerWatcher<DB> {
+    type WatcherDB = DB;
+    type Artifact = ();
+
+    fn new(
+        node_url: &str,
+        contract_info: ContractInfo<'_, ()>,
+        event_db: Arc<DB>,
+        sync_config: SyncConfig,
+    ) -> Result<Self> {
+        let contract = LedgerContract::new(LedgerHttp::new(node_url)?, contract_info)?;
+        Ok(LedgerWatcher { contract, event_db, sync_config })
+    }
+
+    async fn fetch_events<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send,
+    {
+        EventSync::new(&self.contract, &*self.event_db, self.contract.address(), self.sync_config)
+            .sync(eid, insert_fn, rollback)
+            .await
+    }
+
+    fn get_contract(self) -> ContractKind {
+        ContractKind::LedgerContract(self.contract)
+    }
+}
@@ -0,0 +1,10 @@
+//! A backend of an append-only ledger server, which runs Anonify without an Ethereum node.
+//!
+//! Each transaction is appended in its own block, and transaction hashes are the block hashes.
+//! Ledgers have no gas or contract code, so `gas` and `GasConfig` given to senders and deployers are ignored,
+//! and the artifact of the contract is `()`. Contract addresses are still 20 bytes,
+//! as they are stored in the event db and passed to the enclave.
+pub mod client;
+pub mod primitives;
+
+pub use self::client::{LedgerDeployer, LedgerSender, LedgerWatcher};
@@ -0,0 +1,245 @@
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{Serialize, de::DeserializeOwned};
+use web3::types::{Address, Bytes, H256};
+use async_trait::async_trait;
+use anyhow::anyhow;
+use anonify_common::EnclaveEvent;
+use anonify_ledger_api as api;
+use crate::{
+    error::{Result, HostError},
+    eventdb::InnerEnclaveLog,
+    sync::ChainReader,
+    utils::{ContractInfo, RPC_TIMEOUT},
+};
+
+/// HTTP connection to a ledger server.
+#[derive(Debug)]
+pub struct LedgerHttp {
+    client: Client,
+    ledger_url: String,
+}
+
+impl LedgerHttp {
+    pub fn new(ledger_url: &str) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(RPC_TIMEOUT)
+            .build()?;
+
+        Ok(LedgerHttp {
+            client,
+            ledger_url: ledger_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Deploy a contract, and return its address and the hash of the deploy transaction.
+    pub fn deploy(
+        &self,
+        sender: String,
+        report: &[u8],
+        report_sig: &[u8],
+        handshake: &[u8],
+        enclave_sig: &[u8],
+    ) -> Result<(Address, H256)> {
+        let req = api::deploy::post::Request {
+            sender,
+            report: Bytes(report.to_vec()),
+            report_sig: Bytes(report_sig.to_vec()),
+            handshake: Bytes(handshake.to_vec()),
+            enclave_sig: Bytes(enclave_sig.to_vec()),
+        };
+        let res: api::deploy::post::Response = self.post("/api/v1/contracts", &req)?;
+
+        Ok((res.contract, res.tx_hash))
+    }
+
+    /// Whether the contract is deployed.
+    pub fn is_deployed(&self, contract: Address) -> Result<bool> {
+        let res: Option<api::contract::get::Response> = self.get_opt(&contract_path(contract))?;
+        Ok(res.is_some())
+    }
+
+    /// Join the group of the contract with the attestation report of the enclave.
+    pub fn join_group(&self, contract: Address, req: &api::join::post::Request) -> Result<H256> {
+        let res: api::join::post::Response = self.post(&format!("{}/members", contract_path(contract)), req)?;
+
+        Ok(res.tx_hash)
+    }
+
+    pub fn send(&self, contract: Address, sender: String, event: api::Event) -> Result<H256> {
+        let req = api::events::post::Request { sender, event };
+        let res: api::events::post::Response = self.post(&format!("{}/events", contract_path(contract)), &req)?;
+
+        Ok(res.tx_hash)
+    }
+
+    pub fn events(&self, contract: Address, from: u64, to: u64) -> Result<api::events::get::Response> {
+        let path = format!("{}/events", contract_path(contract));
+        self.send_request(self.client.get(&self.url(&path)).query(&api::events::get::Request { from, to }))?
+            .ok_or_else(|| anyhow!("Contract {:?} is not deployed", contract).into())
+    }
+
+    pub fn latest_block_num(&self) -> Result<u64> {
+        let res: Option<api::block::get::Response> = self.get_opt("/api/v1/blocks/latest")?;
+        res.map(|block| block.number)
+            .ok_or_else(|| anyhow!("The ledger has no blocks").into())
+    }
+
+    pub fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        let res: Option<api::block::get::Response> = self.get_opt(&format!("/api/v1/blocks/{}", block_num))?;
+        Ok(res.map(|block| block.hash))
+    }
+
+    /// The number of the block which includes the transaction, or `None` if it is unknown to the ledger.
+    pub fn tx_block_num(&self, tx_hash: H256) -> Result<Option<u64>> {
+        let res: Option<api::tx::get::Response> = self.get_opt(&format!("/api/v1/txs/{}", hex::encode(tx_hash.as_bytes())))?;
+        Ok(res.map(|tx| tx.block_number))
+    }
+
+    pub fn get_ledger_url(&self) -> &str {
+        &self.ledger_url
+    }
+
+    fn post<Req: Serialize, Res: DeserializeOwned>(&self, path: &str, req: &Req) -> Result<Res> {
+        self.send_request(self.client.post(&self.url(path)).json(req))?
+            .ok_or_else(|| anyhow!("{} is not found on the ledger", path).into())
+    }
+
+    /// `None` if the resource is not found.
+    fn get_opt<Res: DeserializeOwned>(&self, path: &str) -> Result<Option<Res>> {
+        self.send_request(self.client.get(&self.url(path)))
+    }
+
+    fn send_request<Res: DeserializeOwned>(&self, req: RequestBuilder) -> Result<Option<Res>> {
+        let mut res = req.send().map_err(into_host_error)?;
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(res.json().map_err(into_host_error)?)),
+            status => {
+                let body = res.text().unwrap_or_default();
+                Err(anyhow!("The ledger returned {}: {}", status, body).into())
+            }
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.ledger_url, path)
+    }
+}
+
+fn contract_path(contract: Address) -> String {
+    format!("/api/v1/contracts/{}", hex::encode(contract.as_bytes()))
+}
+
+fn into_host_error(e: reqwest::Error) -> HostError {
+    if e.is_timeout() {
+        HostError::Timeout(RPC_TIMEOUT)
+    } else {
+        e.into()
+    }
+}
+
+/// A contract deployed on a ledger.
+#[derive(Debug)]
+pub struct LedgerContract {
+    address: Address,
+    ledger_conn: LedgerHttp,
+}
+
+impl LedgerContract {
+    pub fn new(ledger_conn: LedgerHttp, contract_info: ContractInfo<'_, ()>) -> Result<Self> {
+        let address = contract_info.address()?;
+        if !ledger_conn.is_deployed(address)? {
+            return Err(anyhow!("Contract {:?} is not deployed", address).into());
+        }
+
+        Ok(LedgerContract { address, ledger_conn })
+    }
+
+    pub(crate) fn from_deployed(ledger_conn: LedgerHttp, address: Address) -> Self {
+        LedgerContract { address, ledger_conn }
+    }
+
+    pub fn join_group(
+        &self,
+        sender: String,
+        roster_idx: u32,
+        report: &[u8],
+        report_sig: &[u8],
+        handshake: &[u8],
+        enclave_sig: &[u8],
+    ) -> Result<H256> {
+        let req = api::join::post::Request {
+            sender,
+            roster_idx,
+            report: Bytes(report.to_vec()),
+            report_sig: Bytes(report_sig.to_vec()),
+            handshake: Bytes(handshake.to_vec()),
+            enclave_sig: Bytes(enclave_sig.to_vec()),
+        };
+        self.ledger_conn.join_group(self.address, &req)
+    }
+
+    /// Enclaves join the group only by `join_group` with their attestation reports.
+    pub fn send(&self, sender: String, event: EnclaveEvent) -> Result<H256> {
+        let event = match event {
+            EnclaveEvent::Ciphertext(bytes) => api::Event::Ciphertext(Bytes(bytes)),
+            EnclaveEvent::Handshake { handshake, enclave_sig } => api::Event::Handshake {
+                handshake: Bytes(handshake),
+                enclave_sig: Bytes(enclave_sig),
+            },
+            EnclaveEvent::JoinGroup { .. } => {
+                return Err(anyhow!("A JoinGroup event must be sent with an attestation report").into());
+            }
+        };
+        self.ledger_conn.send(self.address, sender, event)
+    }
+
+    pub fn tx_block_num(&self, tx_hash: H256) -> Result<Option<u64>> {
+        self.ledger_conn.tx_block_num(tx_hash)
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+}
+
+#[async_trait]
+impl ChainReader for LedgerContract {
+    async fn latest_block_num(&self) -> Result<u64> {
+        self.ledger_conn.latest_block_num()
+    }
+
+    async fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        self.ledger_conn.block_hash(block_num)
+    }
+
+    async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>> {
+        let res = self.ledger_conn.events(self.address, from, to)?;
+        if res.events.is_empty() {
+            return Ok(None);
+        }
+
+        let events = res.events.into_iter()
+            .map(|event| match event {
+                api::Event::Ciphertext(bytes) => EnclaveEvent::Ciphertext(bytes.0),
+                api::Event::Handshake { handshake, enclave_sig } => EnclaveEvent::Handshake {
+                    handshake: handshake.0,
+                    enclave_sig: enclave_sig.0,
+                },
+                api::Event::JoinGroup { roster_idx, enclave_address, handshake, enclave_sig } => EnclaveEvent::JoinGroup {
+                    roster_idx,
+                    enclave_address: enclave_address.to_fixed_bytes(),
+                    handshake: handshake.0,
+                    enclave_sig: enclave_sig.0,
+                },
+            })
+            .collect();
+
+        Ok(Some(InnerEnclaveLog {
+            contract_addr: self.address.to_fixed_bytes(),
+            latest_blc_num: to,
+            latest_blc_hash: res.to_hash,
+            events,
+        }))
+    }
+}
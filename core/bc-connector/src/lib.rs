@@ -1,8 +1,12 @@
 
 pub mod eth;
+pub mod ledger;
 pub mod traits;
 pub mod eventdb;
+pub mod sync;
 pub mod utils;
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod error;
 
-pub use eventdb::{BlockNumDB, EventDB};
+pub use eventdb::{BlockNumDB, EventDB, FileEventDB};
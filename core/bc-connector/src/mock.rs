@@ -0,0 +1,507 @@
+//! An in-process blockchain backend for tests.
+//!
+//! All deployers, senders and watchers connected to the same node url share a `MockChain`,
+//! which is an in-memory ordered log of `StoreCiphertext`, `StoreHandshake` and `JoinGroup` events,
+//! so that several dispatchers can run group scenarios without an Ethereum node.
+//! Each transaction is mined in its own block, and attestation reports are not verified.
+
+use std::{
+    sync::{Arc, Mutex},
+    collections::HashMap,
+};
+use sgx_types::sgx_enclave_id_t;
+use anonify_types::{RawJoinGroupTx, RawInstructionTx, RawHandshakeTx};
+use anonify_common::{AccessRight, EnclaveEvent, IntoVec};
+use anonify_runtime::{traits::State, UpdatedState};
+use web3::types::{Address, H256};
+use lazy_static::lazy_static;
+use async_trait::async_trait;
+use anyhow::anyhow;
+use crate::{
+    error::{Result, HostError},
+    eth::{
+        client::{BoxedJoinGroupTx, BoxedInstructionTx, BoxedHandshakeTx},
+        GasConfig,
+    },
+    eventdb::{BlockNumDB, InnerEnclaveLog},
+    sync::{ChainReader, EventSync, Rollback, SyncConfig},
+    traits::*,
+    utils::*,
+};
+
+lazy_static! {
+    static ref CHAINS: Mutex<HashMap<String, Arc<MockChain>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug)]
+struct MockBlock {
+    hash: H256,
+    logs: Vec<(Address, EnclaveEvent)>,
+}
+
+#[derive(Debug)]
+struct MockChainInner {
+    blocks: Vec<MockBlock>,
+    contracts: Vec<Address>,
+}
+
+impl MockChainInner {
+    fn mine(&mut self, logs: Vec<(Address, EnclaveEvent)>) -> H256 {
+        let hash = H256::from_low_u64_be(self.blocks.len() as u64 + 1);
+        self.blocks.push(MockBlock { hash, logs });
+        hash
+    }
+
+    /// The number of enclaves which have joined the group of the contract.
+    fn roster_len(&self, address: Address) -> u32 {
+        self.blocks.iter()
+            .flat_map(|block| block.logs.iter())
+            .filter(|(addr, event)| match event {
+                EnclaveEvent::JoinGroup { .. } => *addr == address,
+                _ => false,
+            })
+            .count() as u32
+    }
+}
+
+/// An in-memory chain holding events of deployed contracts in the order they are sent.
+#[derive(Debug)]
+pub struct MockChain(Mutex<MockChainInner>);
+
+impl MockChain {
+    /// Connect to the chain of the given node url, which is created on the first connection.
+    pub fn connect(node_url: &str) -> Arc<Self> {
+        CHAINS.lock().unwrap()
+            .entry(node_url.to_string())
+            .or_insert_with(|| Arc::new(MockChain::new()))
+            .clone()
+    }
+
+    /// Create a chain with only a genesis block.
+    fn new() -> Self {
+        let mut inner = MockChainInner {
+            blocks: vec![],
+            contracts: vec![],
+        };
+        inner.mine(vec![]);
+
+        MockChain(Mutex::new(inner))
+    }
+
+    /// Mine an empty block, which adds a confirmation to the previous blocks.
+    pub fn mine(&self) -> H256 {
+        self.0.lock().unwrap().mine(vec![])
+    }
+
+    pub fn latest_block_num(&self) -> u64 {
+        self.0.lock().unwrap().blocks.len() as u64 - 1
+    }
+
+    /// Deploy a contract whose first event is the deployer's joining with roster index 0.
+    /// Unlike the anonify contract, signatures of handshakes are verified only by enclaves.
+    fn deploy(&self, enclave_address: [u8; 20], handshake: Vec<u8>, enclave_sig: Vec<u8>) -> Address {
+        let mut inner = self.0.lock().unwrap();
+        let address = Address::from_low_u64_be(inner.contracts.len() as u64 + 1);
+        inner.contracts.push(address);
+        inner.mine(vec![(address, EnclaveEvent::JoinGroup { roster_idx: 0, enclave_address, handshake, enclave_sig })]);
+
+        address
+    }
+
+    /// Emit an event of the contract in a new block, and return the block hash as the receipt.
+    /// Like the anonify contract, a join is rejected unless it takes the next roster index.
+    fn send(&self, address: Address, event: EnclaveEvent) -> Result<H256> {
+        let mut inner = self.0.lock().unwrap();
+        if !inner.contracts.contains(&address) {
+            return Err(anyhow!("Contract {:?} is not deployed", address).into());
+        }
+        if let EnclaveEvent::JoinGroup { roster_idx, .. } = event {
+            let next_roster_idx = inner.roster_len(address);
+            if roster_idx != next_roster_idx {
+                return Err(anyhow!("Roster index {} is not the next one, {}", roster_idx, next_roster_idx).into());
+            }
+        }
+
+        Ok(inner.mine(vec![(address, event)]))
+    }
+
+    fn is_deployed(&self, address: Address) -> bool {
+        self.0.lock().unwrap().contracts.contains(&address)
+    }
+
+    /// The number of the block which includes the transaction. Block hashes are used as transaction hashes.
+    fn tx_block_num(&self, tx_hash: H256) -> Option<u64> {
+        self.0.lock().unwrap().blocks.iter()
+            .position(|b| b.hash == tx_hash)
+            .map(|block_num| block_num as u64)
+    }
+
+    fn block_hash(&self, block_num: u64) -> Option<H256> {
+        self.0.lock().unwrap().blocks.get(block_num as usize).map(|b| b.hash)
+    }
+
+    fn enclave_log(&self, address: Address, from: u64, to: u64) -> Option<InnerEnclaveLog> {
+        let inner = self.0.lock().unwrap();
+        let events: Vec<EnclaveEvent> = inner.blocks.iter()
+            .take(to as usize + 1)
+            .skip(from as usize)
+            .flat_map(|block| block.logs.iter())
+            .filter(|(addr, _)| *addr == address)
+            .map(|(_, event)| event.clone())
+            .collect();
+
+        if events.is_empty() {
+            return None;
+        }
+
+        Some(InnerEnclaveLog {
+            contract_addr: address.to_fixed_bytes(),
+            latest_blc_num: to,
+            latest_blc_hash: inner.blocks.get(to as usize).map(|b| b.hash),
+            events,
+        })
+    }
+}
+
+/// A contract deployed on a `MockChain`.
+#[derive(Debug, Clone)]
+pub struct MockContract {
+    chain: Arc<MockChain>,
+    address: Address,
+}
+
+impl MockContract {
+    fn new(node_url: &str, contract_info: ContractInfo<'_, ()>) -> Result<Self> {
+        let chain = MockChain::connect(node_url);
+        let address = contract_info.address()?;
+        if !chain.is_deployed(address) {
+            return Err(anyhow!("Contract {:?} is not deployed", address).into());
+        }
+
+        Ok(MockContract { chain, address })
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+}
+
+#[async_trait]
+impl ChainReader for MockContract {
+    async fn latest_block_num(&self) -> Result<u64> {
+        Ok(self.chain.latest_block_num())
+    }
+
+    async fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        Ok(self.chain.block_hash(block_num))
+    }
+
+    async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>> {
+        Ok(self.chain.enclave_log(self.address, from, to))
+    }
+}
+
+/// Accounts are derived from their indices, as any account can send transactions to a `MockChain`.
+fn mock_account(index: usize) -> SignerAddress {
+    SignerAddress::EthAddress(Address::from_low_u64_be(index as u64))
+}
+
+/// Components needed to deploy a contract on a `MockChain`
+#[derive(Debug)]
+pub struct MockDeployer {
+    enclave_id: sgx_enclave_id_t,
+    node_url: String,
+    chain: Arc<MockChain>,
+    address: Option<Address>, // contract address
+}
+
+impl Deployer for MockDeployer {
+    type Artifact = ();
+
+    fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
+        Ok(MockDeployer {
+            enclave_id,
+            node_url: node_url.to_string(),
+            chain: MockChain::connect(node_url),
+            address: None,
+        })
+    }
+
+    fn get_account(&self, index: usize) -> Result<SignerAddress> {
+        Ok(mock_account(index))
+    }
+
+    fn deploy<F>(
+        &mut self,
+        _deploy_user: &SignerAddress,
+        _artifact: &(),
+        _gas_config: GasConfig,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx>,
+    {
+        let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
+        let contract_addr = self.chain.deploy(
+            join_group_tx.enclave_address,
+            join_group_tx.handshake.to_vec(),
+            join_group_tx.enclave_sig.to_vec(),
+        );
+        self.address = Some(contract_addr);
+
+        Ok(hex::encode(contract_addr.as_bytes()))
+    }
+
+    fn get_contract(self) -> Result<ContractKind> {
+        let address = self.address.ok_or(HostError::AddressNotSet)?;
+        Ok(ContractKind::MockContract(MockContract {
+            chain: self.chain,
+            address,
+        }))
+    }
+
+    fn get_enclave_id(&self) -> sgx_enclave_id_t {
+        self.enclave_id
+    }
+
+    fn get_node_url(&self) -> &str {
+        &self.node_url
+    }
+
+    fn register_notification<F>(
+        &self,
+        access_right: AccessRight,
+        reg_notify_fn: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(sgx_enclave_id_t, AccessRight) -> Result<()>,
+    {
+        reg_notify_fn(self.enclave_id, access_right)
+    }
+}
+
+/// Components needed to send a transaction to a `MockChain`
+#[derive(Debug)]
+pub struct MockSender {
+    enclave_id: sgx_enclave_id_t,
+    contract: MockContract,
+}
+
+#[async_trait]
+impl AsyncSender for MockSender {
+    type Artifact = ();
+
+    fn new(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, ()>,
+        _gas_config: GasConfig,
+    ) -> Result<Self> {
+        let contract = MockContract::new(node_url, contract_info)?;
+        Ok(MockSender { enclave_id, contract })
+    }
+
+    fn from_contract(
+        enclave_id: sgx_enclave_id_t,
+        contract: ContractKind,
+    ) -> Result<Self> {
+        match contract {
+            ContractKind::MockContract(contract) => Ok(MockSender { enclave_id, contract }),
+            _ => Err(anyhow!("MockSender can only be built from a mock contract.").into()),
+        }
+    }
+
+    async fn get_account(&self, index: usize) -> Result<SignerAddress> {
+        Ok(mock_account(index))
+    }
+
+    async fn join_group<F>(
+        &self,
+        _signer: SignerAddress,
+        _gas: Option<u64>,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send,
+    {
+        let join_group_tx: BoxedJoinGroupTx = reg_fn(self.enclave_id)?.into();
+        let receipt = self.contract.chain.send(
+            self.contract.address,
+            EnclaveEvent::JoinGroup {
+                roster_idx: join_group_tx.roster_idx,
+                enclave_address: join_group_tx.enclave_address,
+                handshake: join_group_tx.handshake.to_vec(),
+                enclave_sig: join_group_tx.enclave_sig.to_vec(),
+            },
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    async fn send_instruction<ST, F>(
+        &self,
+        access_right: AccessRight,
+        _signer: SignerAddress,
+        state_info: StateInfo<'_, ST>,
+        _gas: Option<u64>,
+        enc_ins_fn: F,
+    ) -> Result<String>
+    where
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send,
+    {
+        let mut instruction_tx: BoxedInstructionTx = enc_ins_fn(self.enclave_id, access_right, state_info)?.into();
+        let receipt = self.contract.chain.send(
+            self.contract.address,
+            EnclaveEvent::Ciphertext(instruction_tx.get_ciphertext().into_vec()),
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    async fn handshake<F>(
+        &self,
+        _signer: SignerAddress,
+        _gas: Option<u64>,
+        handshake_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send
+    {
+        let handshake_tx: BoxedHandshakeTx = handshake_fn(self.enclave_id)?.into();
+        let receipt = self.contract.chain.send(
+            self.contract.address,
+            EnclaveEvent::Handshake {
+                handshake: handshake_tx.handshake.to_vec(),
+                enclave_sig: handshake_tx.enclave_sig.to_vec(),
+            },
+        )?;
+
+        Ok(hex::encode(receipt.as_bytes()))
+    }
+
+    /// Transactions are mined as soon as they are sent and never reverted.
+    async fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt> {
+        let chain = &self.contract.chain;
+        let status = match chain.tx_block_num(parse_tx_hash(tx_hash)?) {
+            None => TxStatus::Dropped,
+            Some(block_num) if chain.latest_block_num() < block_num + confirmations => TxStatus::Pending,
+            Some(block_num) => TxStatus::Mined(block_num),
+        };
+
+        Ok(TxReceipt::new(status, None))
+    }
+
+    fn get_contract(self) -> ContractKind {
+        ContractKind::MockContract(self.contract)
+    }
+}
+
+/// Components needed to watch events on a `MockChain`
+pub struct MockWatcher<DB: BlockNumDB> {
+    contract: MockContract,
+    event_db: Arc<DB>,
+    sync_config: SyncConfig,
+}
+
+#[async_trait]
+impl<DB: BlockNumDB> AsyncWatcher for MockWatcher<DB> {
+    type WatcherDB = DB;
+    type Artifact = ();
+
+    fn new(
+        node_url: &str,
+        contract_info: ContractInfo<'_, ()>,
+        event_db: Arc<DB>,
+        sync_config: SyncConfig,
+    ) -> Result<Self> {
+        let contract = MockContract::new(node_url, contract_info)?;
+        Ok(MockWatcher { contract, event_db, sync_config })
+    }
+
+    async fn fetch_events<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send,
+    {
+        EventSync::new(&self.contract, &*self.event_db, self.contract.address, self.sync_config)
+            .sync(eid, insert_fn, rollback)
+            .await
+    }
+
+    fn get_contract(self) -> ContractKind {
+        ContractKind::MockContract(self.contract)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_shared_chain_per_node_url() {
+        let chain = MockChain::connect("mock://test_shared_chain_per_node_url");
+        let address = chain.deploy([0u8; 20], b"h0".to_vec(), vec![]);
+        let other_address = chain.deploy([0u8; 20], b"other".to_vec(), vec![]);
+
+        // Another connection to the same url sees the deployed contract.
+        let same_chain = MockChain::connect("mock://test_shared_chain_per_node_url");
+        same_chain.send(address, handshake(b"h1")).unwrap();
+        assert!(same_chain.send(Address::repeat_byte(0xff), handshake(b"")).is_err());
+        assert!(!MockChain::connect("mock://another_node_url").is_deployed(address));
+
+        let contract = MockContract { chain, address };
+        let latest = block_on(contract.latest_block_num()).unwrap();
+        assert_eq!(latest, 3);
+
+        // Only the events of the contract are fetched, in the order they were sent.
+        let log = block_on(contract.enclave_log(0, latest)).unwrap().unwrap();
+        assert_eq!(log.events, vec![join_group(0, b"h0"), handshake(b"h1")]);
+        assert_eq!(log.latest_blc_num, latest);
+        assert_eq!(log.latest_blc_hash, block_on(contract.block_hash(latest)).unwrap());
+        assert!(block_on(contract.enclave_log(2, 2)).unwrap().is_none());
+
+        let other = MockContract { chain: same_chain, address: other_address };
+        assert_eq!(block_on(other.enclave_log(0, latest)).unwrap().unwrap().events, vec![join_group(0, b"other")]);
+    }
+
+    #[test]
+    fn test_join_in_roster_order() {
+        let chain = MockChain::connect("mock://test_join_in_roster_order");
+        let address = chain.deploy([0u8; 20], b"h0".to_vec(), vec![]);
+        let other_address = chain.deploy([0u8; 20], b"other".to_vec(), vec![]);
+
+        // Roster indices are counted per contract.
+        chain.send(address, join_group(1, b"h1")).unwrap();
+        chain.send(other_address, join_group(1, b"h1")).unwrap();
+        // A join with an index which is already assigned or skips the next one is rejected.
+        assert!(chain.send(address, join_group(1, b"h2")).is_err());
+        assert!(chain.send(address, join_group(3, b"h2")).is_err());
+        chain.send(address, join_group(2, b"h2")).unwrap();
+
+        let contract = MockContract { chain, address };
+        let latest = block_on(contract.latest_block_num()).unwrap();
+        let log = block_on(contract.enclave_log(0, latest)).unwrap().unwrap();
+        assert_eq!(log.events, vec![join_group(0, b"h0"), join_group(1, b"h1"), join_group(2, b"h2")]);
+    }
+
+    fn handshake(handshake: &[u8]) -> EnclaveEvent {
+        EnclaveEvent::Handshake { handshake: handshake.to_vec(), enclave_sig: vec![] }
+    }
+
+    fn join_group(roster_idx: u32, handshake: &[u8]) -> EnclaveEvent {
+        EnclaveEvent::JoinGroup {
+            roster_idx,
+            enclave_address: [0u8; 20],
+            handshake: handshake.to_vec(),
+            enclave_sig: vec![],
+        }
+    }
+}
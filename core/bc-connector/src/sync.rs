@@ -0,0 +1,714 @@
+//! Syncing enclave states with event logs on a chain which may be reorganized.
+//!
+//! Only blocks with enough confirmations are fetched, page by page, and after each page the next block number
+//! and the hash of the latest processed block are recorded in the event db, so syncing resumes partway after failures.
+//! If the recorded block is no longer on the canonical chain, the enclave is rolled back to a snapshot
+//! at a block before the fork point and the events after the snapshot are replayed.
+//!
+//! Chains which push logs over a subscription are synced as soon as logs are pushed,
+//! and the logs emitted while the subscription is disconnected are backfilled.
+
+use std::time::Duration;
+use sgx_types::sgx_enclave_id_t;
+use anonify_runtime::{UpdatedState, traits::State};
+use web3::types::{Address, H256};
+use futures::{
+    future::{self, Either, FutureExt},
+    stream::{Stream, StreamExt},
+};
+use futures_timer::Delay;
+use async_trait::async_trait;
+use anyhow::anyhow;
+use log::{debug, warn};
+use crate::{
+    error::Result,
+    eventdb::{BlockNumDB, EventDBTx, InnerEnclaveLog},
+};
+
+/// The default number of blocks built on top of a block before its logs are inserted into the enclave.
+pub const DEFAULT_CONFIRMATIONS: u64 = 0;
+/// The default maximum number of blocks whose logs are fetched by one request.
+pub const DEFAULT_PAGE_SIZE: u64 = 1_000;
+
+/// Parameters of syncing the enclave with event logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncConfig {
+    /// The number of blocks built on top of a block before its logs are inserted into the enclave.
+    pub confirmations: u64,
+    /// The maximum number of blocks whose logs are fetched by one request.
+    /// It is halved for the rest of a sync whenever the node rejects a request.
+    pub page_size: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            confirmations: DEFAULT_CONFIRMATIONS,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+/// How long to wait for logs pushed by a subscription before syncing anyway.
+/// Blocks without logs of the contract are not pushed, so this bounds the delay of
+/// logs waiting for confirmations. It is kept short since callers fetching events wait for it.
+pub const SUBSCRIPTION_WAIT: Duration = Duration::from_secs(3);
+
+/// Read-only access to a chain needed to sync event logs.
+#[async_trait]
+pub trait ChainReader: Sync {
+    async fn latest_block_num(&self) -> Result<u64>;
+
+    /// Hash of the block with the given number on the canonical chain, if it exists.
+    async fn block_hash(&self, block_num: u64) -> Result<Option<H256>>;
+
+    /// Fetch logs of the contract emitted in the blocks from `from` to `to` inclusive.
+    /// Returns `None` if there are no logs in the range.
+    async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>>;
+}
+
+/// A chain which pushes logs of the contract as soon as they are included in blocks.
+#[async_trait]
+pub trait LogSubscriber: Sync {
+    /// A stream of numbers of the blocks which pushed logs are included in.
+    /// It ends or yields an error when the subscription is disconnected.
+    type Subscription: Stream<Item = Result<u64>> + Unpin + Send;
+
+    async fn subscribe(&self) -> Result<Self::Subscription>;
+}
+
+/// Snapshots of the enclave taken after inserting logs, used to roll it back on chain reorganizations.
+pub trait Rollback: Sync {
+    /// Blocks `(number, hash)` at which the snapshots were taken, newest first.
+    fn snapshot_blocks(&self) -> Result<Vec<(u64, [u8; 32])>>;
+
+    /// Restore the enclave from the snapshot taken at `block_num`, discarding newer snapshots.
+    /// Returns the block the restored snapshot was taken at.
+    fn rollback(&self, eid: sgx_enclave_id_t, block_num: u64) -> Result<(u64, [u8; 32])>;
+
+    /// Whether no logs have changed the enclave yet, so that logs can be replayed from the genesis without a snapshot.
+    fn is_initial(&self, eid: sgx_enclave_id_t) -> Result<bool>;
+}
+
+/// Syncs the enclave with event logs of a contract identified by `key` in the event db.
+pub struct EventSync<'a, C: ChainReader, DB: BlockNumDB> {
+    chain: &'a C,
+    event_db: &'a DB,
+    key: Address,
+    config: SyncConfig,
+}
+
+impl<'a, C: ChainReader, DB: BlockNumDB> EventSync<'a, C, DB> {
+    pub fn new(chain: &'a C, event_db: &'a DB, key: Address, config: SyncConfig) -> Self {
+        EventSync {
+            chain,
+            event_db,
+            key,
+            config,
+        }
+    }
+
+    /// Insert logs of newly confirmed blocks into the enclave by `insert_fn`, once per page of blocks.
+    /// The progress is recorded after each page, so an error leaves the pages inserted before it processed.
+    /// If some of them have updated states, syncing stops at the error and returns those states instead.
+    ///
+    /// If the latest processed block has been reorganized away, the enclave is first rolled back
+    /// by `rollback` to the newest snapshot taken at a block still on the canonical chain.
+    pub async fn sync<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        mut insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>>,
+        R: Rollback,
+        S: State,
+    {
+        let mut next_blc_num = self.event_db.get_latest_block_num(self.key);
+        if let Some(hash) = self.event_db.get_latest_block_hash(self.key) {
+            // A hash is recorded only along with the next block number of the processed block.
+            let processed_num = next_blc_num.saturating_sub(1);
+            if !self.is_canonical(processed_num, hash).await? {
+                warn!("Block {} ({:?}) has been reorganized away. Rolling back the enclave.", processed_num, hash);
+                next_blc_num = self.rollback(eid, rollback).await?;
+            }
+        }
+
+        let latest_blc_num = self.chain.latest_block_num().await?;
+        if latest_blc_num < next_blc_num + self.config.confirmations {
+            return Ok(None);
+        }
+
+        let to = latest_blc_num - self.config.confirmations;
+        let mut updated_states = None;
+        if let Err(e) = self.sync_pages(eid, next_blc_num, to, &mut insert_fn, &mut updated_states).await {
+            // The states updated by the committed pages would be lost with the error,
+            // so they are returned and the failed page is retried on the next sync.
+            if updated_states.is_none() {
+                return Err(e);
+            }
+            warn!("Stopped syncing at a failed page: {}", e);
+        }
+
+        Ok(updated_states)
+    }
+
+    /// Insert logs of blocks from `from` to `to` page by page, gathering the updated states into `updated_states`.
+    async fn sync_pages<F, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        mut from: u64,
+        to: u64,
+        insert_fn: &mut F,
+        updated_states: &mut Option<Vec<UpdatedState<S>>>,
+    ) -> Result<()>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>>,
+        S: State,
+    {
+        let mut page_size = self.config.page_size.max(1);
+
+        while from <= to {
+            let page_to = to.min(from.saturating_add(page_size - 1));
+            let page_hash = self.chain.block_hash(page_to).await?
+                .ok_or_else(|| anyhow!("Block {} is not found", page_to))?;
+            let enclave_log = match self.chain.enclave_log(from, page_to).await {
+                Ok(log) => log,
+                Err(e) if page_size > 1 => {
+                    page_size /= 2;
+                    warn!(
+                        "Failed to fetch logs of blocks {} to {}: {}. Retrying with {} blocks per request.",
+                        from, page_to, e, page_size
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // The logs may have been fetched from another fork if the chain was reorganized meanwhile.
+            if !self.is_canonical(page_to, page_hash).await? {
+                warn!("Block {} has been reorganized while fetching logs. Retrying later.", page_to);
+                break;
+            }
+
+            if let Some(mut log) = enclave_log {
+                log.latest_blc_num = page_to;
+                log.latest_blc_hash = Some(page_hash);
+                if let Some(states) = insert_fn(eid, &log)? {
+                    updated_states.get_or_insert_with(Vec::new).extend(states);
+                }
+            }
+            self.set_next_block(page_to + 1, Some(page_hash))?;
+            from = page_to + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Roll the enclave back and return the next block number to replay logs from.
+    async fn rollback<R: Rollback>(&self, eid: sgx_enclave_id_t, rollback: &R) -> Result<u64> {
+        let snapshots = rollback.snapshot_blocks()?;
+        if snapshots.is_empty() {
+            // Replaying logs into an enclave which has already inserted some would apply them twice.
+            if !rollback.is_initial(eid)? {
+                return Err(anyhow!("No snapshots are found. The enclave cannot be rolled back.").into());
+            }
+            self.set_next_block(0, None)?;
+            return Ok(0);
+        }
+
+        for (block_num, hash) in snapshots {
+            if self.is_canonical(block_num, hash.into()).await? {
+                let (block_num, hash) = rollback.rollback(eid, block_num)?;
+                self.set_next_block(block_num + 1, Some(hash.into()))?;
+                return Ok(block_num + 1);
+            }
+        }
+
+        Err(anyhow!("All snapshots have been reorganized away. The enclave cannot be rolled back.").into())
+    }
+
+    async fn is_canonical(&self, block_num: u64, hash: H256) -> Result<bool> {
+        Ok(self.chain.block_hash(block_num).await? == Some(hash))
+    }
+
+    fn set_next_block(&self, block_num: u64, block_hash: Option<H256>) -> Result<()> {
+        let mut dbtx = EventDBTx::new();
+        dbtx.put(self.key, block_num, block_hash);
+        self.event_db.set_next_block_num(dbtx)
+    }
+}
+
+impl<'a, C: ChainReader + LogSubscriber, DB: BlockNumDB> EventSync<'a, C, DB> {
+    /// Wait for logs pushed by `subscription`, and insert logs of newly confirmed blocks by `sync`.
+    ///
+    /// If `subscription` is `None` or has been disconnected, the chain is subscribed to again and
+    /// the logs emitted meanwhile are backfilled without waiting.
+    /// Subscribing before the backfill ensures that no logs are missed in between.
+    pub async fn sync_on_push<F, R, S>(
+        &self,
+        subscription: &mut Option<C::Subscription>,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>>,
+        R: Rollback,
+        S: State,
+    {
+        if let Some(stream) = subscription.as_mut() {
+            if !Self::wait_for_push(stream).await {
+                *subscription = None;
+            }
+        }
+        if subscription.is_none() {
+            *subscription = Some(self.chain.subscribe().await?);
+        }
+
+        self.sync(eid, insert_fn, rollback).await
+    }
+
+    /// Wait for pushed logs up to `SUBSCRIPTION_WAIT`, consuming all the ones pushed so far.
+    /// Returns `false` if the subscription has been disconnected.
+    async fn wait_for_push(stream: &mut C::Subscription) -> bool {
+        let mut pushed = match future::select(stream.next(), Delay::new(SUBSCRIPTION_WAIT)).await {
+            Either::Left((pushed, _)) => pushed,
+            Either::Right(_) => return true,
+        };
+
+        loop {
+            match pushed {
+                Some(Ok(block_num)) => debug!("Logs in block {} are pushed.", block_num),
+                Some(Err(e)) => {
+                    warn!("Log subscription is disconnected: {}. Backfilling logs.", e);
+                    return false;
+                }
+                None => {
+                    warn!("Log subscription is closed. Backfilling logs.");
+                    return false;
+                }
+            }
+
+            match stream.next().now_or_never() {
+                Some(next) => pushed = next,
+                None => return true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        executor::block_on,
+    };
+    use anonify_runtime::{U64, MemId};
+    use anonify_common::{EnclaveEvent, UserAddress};
+    use crate::eventdb::EventDB;
+
+    /// A chain whose blocks carry handshakes as payloads, and whose tail can be replaced by a fork.
+    /// Blocks with payloads are pushed to the subscribers.
+    struct FakeChain {
+        blocks: Mutex<Vec<(H256, Vec<Vec<u8>>)>>,
+        hash_seed: Mutex<u64>,
+        subscribers: Mutex<Vec<UnboundedSender<Result<u64>>>>,
+        /// The maximum number of blocks whose logs can be fetched at once, like limits of providers.
+        max_range: Mutex<Option<u64>>,
+    }
+
+    impl FakeChain {
+        /// Create a chain with only a genesis block.
+        fn new() -> Self {
+            let chain = FakeChain {
+                blocks: Mutex::new(vec![]),
+                hash_seed: Mutex::new(0),
+                subscribers: Mutex::new(vec![]),
+                max_range: Mutex::new(None),
+            };
+            chain.mine(vec![]);
+            chain
+        }
+
+        fn mine(&self, payloads: Vec<Vec<u8>>) {
+            let mut seed = self.hash_seed.lock().unwrap();
+            *seed += 1;
+            let mut blocks = self.blocks.lock().unwrap();
+            if !payloads.is_empty() {
+                for subscriber in self.subscribers.lock().unwrap().iter() {
+                    subscriber.unbounded_send(Ok(blocks.len() as u64)).unwrap();
+                }
+            }
+            blocks.push((H256::from_low_u64_be(*seed), payloads));
+        }
+
+        /// Close all subscriptions as if the connection to the node was lost.
+        fn disconnect(&self) {
+            self.subscribers.lock().unwrap().clear();
+        }
+
+        /// Replace the blocks from `fork_num` with new blocks.
+        fn reorg(&self, fork_num: u64, blocks: Vec<Vec<Vec<u8>>>) {
+            self.blocks.lock().unwrap().truncate(fork_num as usize);
+            for payloads in blocks {
+                self.mine(payloads);
+            }
+        }
+
+        /// All payloads on the canonical chain up to the given block.
+        fn payloads(&self, to: u64) -> Vec<Vec<u8>> {
+            self.blocks.lock().unwrap()[..=to as usize]
+                .iter()
+                .flat_map(|(_, payloads)| payloads.clone())
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl ChainReader for FakeChain {
+        async fn latest_block_num(&self) -> Result<u64> {
+            Ok(self.blocks.lock().unwrap().len() as u64 - 1)
+        }
+
+        async fn block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+            Ok(self.blocks.lock().unwrap().get(block_num as usize).map(|(hash, _)| *hash))
+        }
+
+        async fn enclave_log(&self, from: u64, to: u64) -> Result<Option<InnerEnclaveLog>> {
+            if let Some(max_range) = *self.max_range.lock().unwrap() {
+                if to - from + 1 > max_range {
+                    return Err(anyhow!("Block range is too wide").into());
+                }
+            }
+            let blocks = self.blocks.lock().unwrap();
+            let events: Vec<EnclaveEvent> = blocks[from as usize..=to as usize]
+                .iter()
+                .flat_map(|(_, payloads)| payloads.iter().cloned()
+                    .map(|handshake| EnclaveEvent::Handshake { handshake, enclave_sig: vec![] }))
+                .collect();
+            if events.is_empty() {
+                return Ok(None);
+            }
+
+            Ok(Some(InnerEnclaveLog {
+                contract_addr: [0u8; 20],
+                latest_blc_num: to,
+                latest_blc_hash: Some(blocks[to as usize].0),
+                events,
+            }))
+        }
+    }
+
+    #[async_trait]
+    impl LogSubscriber for FakeChain {
+        type Subscription = UnboundedReceiver<Result<u64>>;
+
+        async fn subscribe(&self) -> Result<Self::Subscription> {
+            let (sender, receiver) = unbounded();
+            self.subscribers.lock().unwrap().push(sender);
+            Ok(receiver)
+        }
+    }
+
+    /// An enclave which applies payloads in order and takes a snapshot after each insertion.
+    #[derive(Default)]
+    struct FakeEnclave {
+        applied: Mutex<Vec<Vec<u8>>>,
+        snapshots: Mutex<Vec<(u64, H256, Vec<Vec<u8>>)>>,
+        /// A payload whose insertion fails.
+        broken: Mutex<Option<Vec<u8>>>,
+    }
+
+    impl FakeEnclave {
+        fn insert(&self, log: &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<U64>>>> {
+            let payloads: Vec<Vec<u8>> = log.events.iter().map(|event| match event {
+                EnclaveEvent::Handshake { handshake, .. } => handshake.clone(),
+                EnclaveEvent::Ciphertext(_) | EnclaveEvent::JoinGroup { .. } => unreachable!("FakeChain only has handshakes"),
+            }).collect();
+            if payloads.iter().any(|payload| Some(payload) == self.broken.lock().unwrap().as_ref()) {
+                return Err(anyhow!("Failed to insert logs").into());
+            }
+            let mut applied = self.applied.lock().unwrap();
+            applied.extend(payloads);
+            let hash = log.latest_blc_hash.unwrap();
+            self.snapshots.lock().unwrap().push((log.latest_blc_num, hash, applied.clone()));
+            Ok(None)
+        }
+
+        fn sync(&self, event_sync: &EventSync<FakeChain, EventDB>) -> Result<()> {
+            block_on(event_sync.sync(0, |_, log| self.insert(log), self))?;
+            Ok(())
+        }
+
+        fn sync_on_push(
+            &self,
+            event_sync: &EventSync<FakeChain, EventDB>,
+            subscription: &mut Option<UnboundedReceiver<Result<u64>>>,
+        ) -> Result<()> {
+            block_on(event_sync.sync_on_push(subscription, 0, |_, log| self.insert(log), self))?;
+            Ok(())
+        }
+    }
+
+    impl Rollback for FakeEnclave {
+        fn snapshot_blocks(&self) -> Result<Vec<(u64, [u8; 32])>> {
+            Ok(self.snapshots.lock().unwrap().iter().rev()
+                .map(|(block_num, hash, _)| (*block_num, hash.to_fixed_bytes()))
+                .collect())
+        }
+
+        fn rollback(&self, _eid: sgx_enclave_id_t, block_num: u64) -> Result<(u64, [u8; 32])> {
+            let mut snapshots = self.snapshots.lock().unwrap();
+            snapshots.retain(|(num, _, _)| *num <= block_num);
+            let (num, hash, applied) = snapshots.last().cloned()
+                .ok_or_else(|| anyhow!("No snapshot at block {}", block_num))?;
+            *self.applied.lock().unwrap() = applied;
+            Ok((num, hash.to_fixed_bytes()))
+        }
+
+        fn is_initial(&self, _eid: sgx_enclave_id_t) -> Result<bool> {
+            Ok(self.applied.lock().unwrap().is_empty())
+        }
+    }
+
+    fn key() -> Address {
+        Address::repeat_byte(1)
+    }
+
+    #[test]
+    fn test_wait_for_confirmations() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig { confirmations: 2, ..Default::default() });
+
+        chain.mine(vec![b"a".to_vec()]);
+        chain.mine(vec![b"b".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        // Block 1 has only one confirmation.
+        assert!(enclave.applied.lock().unwrap().is_empty());
+        assert_eq!(event_db.get_latest_block_num(key()), 1);
+
+        chain.mine(vec![b"c".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(1));
+        assert_eq!(event_db.get_latest_block_num(key()), 2);
+        assert_eq!(event_db.get_latest_block_hash(key()), block_on(chain.block_hash(1)).unwrap());
+
+        chain.mine(vec![]);
+        chain.mine(vec![]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(3));
+        assert_eq!(event_db.get_latest_block_num(key()), 4);
+    }
+
+    #[test]
+    fn test_rollback_and_replay_on_reorg() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+
+        chain.mine(vec![b"a".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        chain.mine(vec![b"b".to_vec()]);
+        chain.mine(vec![]);
+        enclave.sync(&event_sync).unwrap();
+        chain.mine(vec![b"c".to_vec(), b"d".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(4));
+
+        // Blocks from 2 are replaced, so the enclave must forget "b", "c" and "d".
+        chain.reorg(2, vec![vec![b"e".to_vec()], vec![], vec![b"f".to_vec()]]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), vec![b"a".to_vec(), b"e".to_vec(), b"f".to_vec()]);
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(4));
+        assert_eq!(event_db.get_latest_block_num(key()), 5);
+        assert_eq!(event_db.get_latest_block_hash(key()), block_on(chain.block_hash(4)).unwrap());
+        // The snapshots taken on the old fork have been discarded.
+        assert_eq!(enclave.snapshots.lock().unwrap().iter().map(|s| s.0).collect::<Vec<_>>(), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_reorg_of_blocks_without_logs() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+
+        chain.mine(vec![]);
+        chain.mine(vec![]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(event_db.get_latest_block_num(key()), 3);
+
+        // Nothing has been inserted, so the logs of the new fork are synced from the genesis.
+        chain.reorg(1, vec![vec![b"a".to_vec()], vec![], vec![b"b".to_vec()]]);
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(3));
+        assert_eq!(event_db.get_latest_block_num(key()), 4);
+    }
+
+    #[test]
+    fn test_reorg_deeper_than_snapshots() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+
+        chain.mine(vec![b"a".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        chain.reorg(1, vec![vec![b"b".to_vec()]]);
+
+        assert!(enclave.sync(&event_sync).is_err());
+        // The event db is left untouched so that the reorg is detected again.
+        assert_eq!(event_db.get_latest_block_num(key()), 2);
+    }
+
+    #[test]
+    fn test_reorg_without_snapshots_after_inserting_logs() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+
+        chain.mine(vec![b"a".to_vec()]);
+        enclave.sync(&event_sync).unwrap();
+        enclave.snapshots.lock().unwrap().clear();
+        chain.reorg(1, vec![vec![b"a".to_vec()], vec![b"b".to_vec()]]);
+
+        // The logs are not replayed into the enclave which has already applied "a".
+        assert!(enclave.sync(&event_sync).is_err());
+        assert_eq!(*enclave.applied.lock().unwrap(), vec![b"a".to_vec()]);
+        assert_eq!(event_db.get_latest_block_num(key()), 2);
+    }
+
+    #[test]
+    fn test_sync_on_push_and_backfill_after_disconnect() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+        let mut subscription = None;
+
+        // The first call subscribes and backfills the logs emitted before it.
+        chain.mine(vec![b"a".to_vec()]);
+        enclave.sync_on_push(&event_sync, &mut subscription).unwrap();
+        assert!(subscription.is_some());
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(1));
+
+        chain.mine(vec![b"b".to_vec()]);
+        chain.mine(vec![b"c".to_vec()]);
+        enclave.sync_on_push(&event_sync, &mut subscription).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(3));
+
+        // Logs emitted while disconnected are not pushed, but backfilled after subscribing again.
+        chain.disconnect();
+        chain.mine(vec![b"d".to_vec()]);
+        enclave.sync_on_push(&event_sync, &mut subscription).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(4));
+        assert_eq!(chain.subscribers.lock().unwrap().len(), 1);
+
+        chain.mine(vec![b"e".to_vec()]);
+        enclave.sync_on_push(&event_sync, &mut subscription).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(5));
+        assert_eq!(event_db.get_latest_block_num(key()), 6);
+    }
+
+    #[test]
+    fn test_halve_page_size_when_rejected() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let config = SyncConfig { page_size: 16, ..Default::default() };
+        let event_sync = EventSync::new(&chain, &event_db, key(), config);
+
+        for payload in b"abcdefgh" {
+            chain.mine(vec![vec![*payload]]);
+        }
+        *chain.max_range.lock().unwrap() = Some(3);
+
+        // Pages are halved to two blocks, each of which is inserted separately.
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(8));
+        assert_eq!(enclave.snapshots.lock().unwrap().len(), 5);
+        assert_eq!(event_db.get_latest_block_num(key()), 9);
+    }
+
+    #[test]
+    fn test_resume_from_last_committed_page() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let config = SyncConfig { page_size: 2, ..Default::default() };
+        let event_sync = EventSync::new(&chain, &event_db, key(), config);
+
+        for payload in b"abcdef" {
+            chain.mine(vec![vec![*payload]]);
+        }
+        *enclave.broken.lock().unwrap() = Some(b"d".to_vec());
+
+        // The pages before the failed one are committed.
+        assert!(enclave.sync(&event_sync).is_err());
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(3));
+        assert_eq!(event_db.get_latest_block_num(key()), 4);
+        assert_eq!(event_db.get_latest_block_hash(key()), block_on(chain.block_hash(3)).unwrap());
+
+        *enclave.broken.lock().unwrap() = None;
+        enclave.sync(&event_sync).unwrap();
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(6));
+        assert_eq!(event_db.get_latest_block_num(key()), 7);
+    }
+
+    #[test]
+    fn test_return_committed_states_on_failure() {
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let config = SyncConfig { page_size: 2, ..Default::default() };
+        let event_sync = EventSync::new(&chain, &event_db, key(), config);
+        // Each inserted page updates a state with its latest block number.
+        let insert = |log: &InnerEnclaveLog| -> Result<Option<Vec<UpdatedState<U64>>>> {
+            enclave.insert(log)?;
+            let address = UserAddress::from_array([0u8; 20]);
+            let state = UpdatedState::new(address, MemId::from_raw(0), U64::from_raw(log.latest_blc_num));
+            Ok(Some(vec![state]))
+        };
+
+        for payload in b"abcdef" {
+            chain.mine(vec![vec![*payload]]);
+        }
+        *enclave.broken.lock().unwrap() = Some(b"d".to_vec());
+
+        // The states updated by the pages before the failed one are returned.
+        let states = block_on(event_sync.sync(0, |_, log| insert(log), &enclave)).unwrap().unwrap();
+        let blocks: Vec<u64> = states.iter().map(|s| s.state.as_raw()).collect();
+        assert_eq!(blocks, vec![1, 3]);
+        assert_eq!(event_db.get_latest_block_num(key()), 4);
+
+        // The failed page is retried, and an error is returned if no page is committed.
+        assert!(block_on(event_sync.sync(0, |_, log| insert(log), &enclave)).is_err());
+        *enclave.broken.lock().unwrap() = None;
+        let states = block_on(event_sync.sync(0, |_, log| insert(log), &enclave)).unwrap().unwrap();
+        let blocks: Vec<u64> = states.iter().map(|s| s.state.as_raw()).collect();
+        assert_eq!(blocks, vec![5, 6]);
+        assert_eq!(*enclave.applied.lock().unwrap(), chain.payloads(6));
+    }
+
+    #[test]
+    fn test_sync_futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let chain = FakeChain::new();
+        let event_db = EventDB::new();
+        let enclave = FakeEnclave::default();
+        let event_sync = EventSync::new(&chain, &event_db, key(), SyncConfig::default());
+        let mut subscription = None;
+
+        // Futures of syncing can be spawned on multithreaded executors.
+        assert_send(&event_sync.sync(0, |_, log| enclave.insert(log), &enclave));
+        assert_send(&event_sync.sync_on_push(&mut subscription, 0, |_, log| enclave.insert(log), &enclave));
+    }
+}
@@ -1,35 +1,42 @@
 #![allow(dead_code)]
 
-use std::{
-    path::Path,
-    sync::Arc,
-};
+use std::sync::Arc;
 use sgx_types::sgx_enclave_id_t;
 use anonify_types::{RawJoinGroupTx, RawInstructionTx, RawHandshakeTx};
 use anonify_common::AccessRight;
 use anonify_runtime::{traits::State, UpdatedState};
+use futures::executor::block_on;
+use async_trait::async_trait;
 use crate::{
     error::Result,
     eventdb::{BlockNumDB, InnerEnclaveLog},
+    sync::{Rollback, SyncConfig},
+    eth::GasConfig,
     utils::*,
 };
 
 /// A trait for deploying contracts
 pub trait Deployer: Sized {
+    /// The compiled contract to deploy, e.g. its ABI and bytecode on Ethereum.
+    type Artifact;
+
     fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self>;
 
     fn get_account(&self, index: usize) -> Result<SignerAddress>;
 
-    /// Deploying contract with attestation.
+    /// Deploying `artifact` with attestation.
+    /// The contract returned by `get_contract` sends transactions with `gas_config`.
     fn deploy<F>(
         &mut self,
         deploy_user: &SignerAddress,
+        artifact: &Self::Artifact,
+        gas_config: GasConfig,
         reg_fn: F,
     ) -> Result<String>
     where
         F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx>;
 
-    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<ContractKind>;
+    fn get_contract(self) -> Result<ContractKind>;
 
     fn get_enclave_id(&self) -> sgx_enclave_id_t;
 
@@ -44,18 +51,24 @@ pub trait Deployer: Sized {
         F: FnOnce(sgx_enclave_id_t, AccessRight) -> Result<()>;
 }
 
-/// A trait for sending transactions to blockchain nodes
+/// A trait for sending transactions to blockchain nodes.
+/// Gas of each transaction is estimated by the node if `gas` is `None`.
 pub trait Sender: Sized {
-    fn new<P: AsRef<Path>>(
+    /// The compiled contract to send transactions to. See `Deployer::Artifact`.
+    type Artifact;
+
+    fn new(
         enclave_id: sgx_enclave_id_t,
         node_url: &str,
-        contract_info: ContractInfo<'_, P>,
+        contract_info: ContractInfo<'_, Self::Artifact>,
+        gas_config: GasConfig,
     ) -> Result<Self>;
 
+    /// Fails if `contract` is deployed on another kind of blockchain.
     fn from_contract(
         enclave_id: sgx_enclave_id_t,
         contract: ContractKind,
-    ) -> Self;
+    ) -> Result<Self>;
 
     fn get_account(&self, index: usize) -> Result<SignerAddress>;
 
@@ -65,55 +78,258 @@ pub trait Sender: Sized {
         access_right: AccessRight,
         signer: SignerAddress,
         state_info: StateInfo<'_, ST>,
-        gas: u64,
+        gas: Option<u64>,
         st_fn: F,
     ) -> Result<String>
     where
-        ST: State,
-        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx>;
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send;
 
     /// Attestation with deployed contract.
     fn join_group<F>(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         reg_fn: F,
     ) -> Result<String>
     where
-        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx>;
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send;
 
     fn handshake<F>(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         handshake_fn: F,
     ) -> Result<String>
     where
-        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx>;
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send;
+
+    /// Get the current status of a sent transaction. It is pending until `confirmations` blocks are
+    /// built on top of the block which includes it.
+    fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt>;
 
     fn get_contract(self) -> ContractKind;
 }
 
+/// A non-blocking counterpart of `Sender`, whose futures are `Send` so that they can run on any executor.
+/// Every implementor is also a `Sender` which blocks the current thread until the calls complete.
+#[async_trait]
+pub trait AsyncSender: Sized + Send + Sync {
+    type Artifact;
+
+    fn new(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, Self::Artifact>,
+        gas_config: GasConfig,
+    ) -> Result<Self>;
+
+    fn from_contract(
+        enclave_id: sgx_enclave_id_t,
+        contract: ContractKind,
+    ) -> Result<Self>;
+
+    async fn get_account(&self, index: usize) -> Result<SignerAddress>;
+
+    /// Send an encrypted instruction of state transition to blockchain nodes.
+    async fn send_instruction<ST, F>(
+        &self,
+        access_right: AccessRight,
+        signer: SignerAddress,
+        state_info: StateInfo<'_, ST>,
+        gas: Option<u64>,
+        st_fn: F,
+    ) -> Result<String>
+    where
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send;
+
+    /// Attestation with deployed contract.
+    async fn join_group<F>(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send;
+
+    async fn handshake<F>(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        handshake_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send;
+
+    /// Get the current status of a sent transaction. See `Sender::get_receipt`.
+    async fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt>;
+
+    fn get_contract(self) -> ContractKind;
+}
+
+impl<T: AsyncSender> Sender for T {
+    type Artifact = <T as AsyncSender>::Artifact;
+
+    fn new(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, Self::Artifact>,
+        gas_config: GasConfig,
+    ) -> Result<Self> {
+        <T as AsyncSender>::new(enclave_id, node_url, contract_info, gas_config)
+    }
+
+    fn from_contract(
+        enclave_id: sgx_enclave_id_t,
+        contract: ContractKind,
+    ) -> Result<Self> {
+        <T as AsyncSender>::from_contract(enclave_id, contract)
+    }
+
+    fn get_account(&self, index: usize) -> Result<SignerAddress> {
+        block_on(AsyncSender::get_account(self, index))
+    }
+
+    fn send_instruction<ST, F>(
+        &self,
+        access_right: AccessRight,
+        signer: SignerAddress,
+        state_info: StateInfo<'_, ST>,
+        gas: Option<u64>,
+        st_fn: F,
+    ) -> Result<String>
+    where
+        ST: State + Send,
+        F: FnOnce(sgx_enclave_id_t, AccessRight, StateInfo<'_, ST>) -> Result<RawInstructionTx> + Send,
+    {
+        block_on(AsyncSender::send_instruction(self, access_right, signer, state_info, gas, st_fn))
+    }
+
+    fn join_group<F>(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        reg_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawJoinGroupTx> + Send,
+    {
+        block_on(AsyncSender::join_group(self, signer, gas, reg_fn))
+    }
+
+    fn handshake<F>(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        handshake_fn: F,
+    ) -> Result<String>
+    where
+        F: FnOnce(sgx_enclave_id_t) -> Result<RawHandshakeTx> + Send,
+    {
+        block_on(AsyncSender::handshake(self, signer, gas, handshake_fn))
+    }
+
+    fn get_receipt(&self, tx_hash: &str, confirmations: u64) -> Result<TxReceipt> {
+        block_on(AsyncSender::get_receipt(self, tx_hash, confirmations))
+    }
+
+    fn get_contract(self) -> ContractKind {
+        AsyncSender::get_contract(self)
+    }
+}
+
 /// A trait of fetching event from blockchian nodes
 pub trait Watcher: Sized {
     type WatcherDB: BlockNumDB;
+    /// The compiled contract to fetch events from. See `Deployer::Artifact`.
+    type Artifact;
 
-    fn new<P: AsRef<Path>>(
+    fn new(
         node_url: &str,
-        contract_info: ContractInfo<'_, P>,
+        contract_info: ContractInfo<'_, Self::Artifact>,
         event_db: Arc<Self::WatcherDB>,
+        sync_config: SyncConfig,
     ) -> Result<Self>;
 
     /// Blocking event fetch from blockchain nodes.
-    fn block_on_event<F, S>(
+    /// Only blocks with `sync_config.confirmations` blocks on top of them are fetched, and if fetched blocks
+    /// have been reorganized away, the enclave is rolled back by `rollback` and the logs are replayed.
+    fn block_on_event<F, R, S>(
         &self,
         eid: sgx_enclave_id_t,
         insert_fn: F,
+        rollback: &R,
     ) -> Result<Option<Vec<UpdatedState<S>>>>
     where
-        F: FnOnce(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>>,
-        S: State
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send
     ;
 
     fn get_contract(self) -> ContractKind;
 }
+
+/// A non-blocking counterpart of `Watcher`, whose futures are `Send` so that they can run on any executor.
+/// Every implementor is also a `Watcher` which blocks the current thread until events are fetched.
+#[async_trait]
+pub trait AsyncWatcher: Sized + Send + Sync {
+    type WatcherDB: BlockNumDB;
+    type Artifact;
+
+    fn new(
+        node_url: &str,
+        contract_info: ContractInfo<'_, Self::Artifact>,
+        event_db: Arc<Self::WatcherDB>,
+        sync_config: SyncConfig,
+    ) -> Result<Self>;
+
+    /// Fetch events from blockchain nodes and insert them into the enclave.
+    /// See `Watcher::block_on_event` for the arguments.
+    async fn fetch_events<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send
+    ;
+
+    fn get_contract(self) -> ContractKind;
+}
+
+impl<T: AsyncWatcher> Watcher for T {
+    type WatcherDB = <T as AsyncWatcher>::WatcherDB;
+    type Artifact = <T as AsyncWatcher>::Artifact;
+
+    fn new(
+        node_url: &str,
+        contract_info: ContractInfo<'_, Self::Artifact>,
+        event_db: Arc<Self::WatcherDB>,
+        sync_config: SyncConfig,
+    ) -> Result<Self> {
+        <T as AsyncWatcher>::new(node_url, contract_info, event_db, sync_config)
+    }
+
+    fn block_on_event<F, R, S>(
+        &self,
+        eid: sgx_enclave_id_t,
+        insert_fn: F,
+        rollback: &R,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>
+    where
+        F: FnMut(sgx_enclave_id_t, &InnerEnclaveLog) -> Result<Option<Vec<UpdatedState<S>>>> + Send,
+        R: Rollback,
+        S: State + Send,
+    {
+        block_on(self.fetch_events(eid, insert_fn, rollback))
+    }
+
+    fn get_contract(self) -> ContractKind {
+        AsyncWatcher::get_contract(self)
+    }
+}
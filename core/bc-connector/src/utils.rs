@@ -1,50 +1,77 @@
 use std::{
-    path::Path,
-    io::BufReader,
-    fs::File,
     str::FromStr,
+    time::Duration,
 };
-use web3::types::Address;
-use ethabi::Contract as ContractABI;
+use futures::{
+    future::{self, Either, Future},
+    pin_mut,
+};
+use futures_timer::Delay;
+use web3::types::{Address, H256};
 use anonify_runtime::traits::State;
 use anonify_app_preluder::call_name_to_id;
 use anyhow::anyhow;
 use crate::{
-    error::Result,
+    error::{Result, HostError},
     eth::primitives::Web3Contract,
 };
 
+/// How long to wait for a response from a blockchain node.
+pub const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Await a call to a blockchain node, failing with `HostError::Timeout` if it takes longer than `RPC_TIMEOUT`.
+pub async fn with_timeout<F, T, E>(call: F) -> Result<T>
+where
+    F: Future<Output = std::result::Result<T, E>>,
+    E: Into<HostError>,
+{
+    pin_mut!(call);
+    match future::select(call, Delay::new(RPC_TIMEOUT)).await {
+        Either::Left((res, _)) => res.map_err(Into::into),
+        Either::Right(_) => Err(HostError::Timeout(RPC_TIMEOUT)),
+    }
+}
+
 /// Needed information to handle smart contracts.
-#[derive(Debug, Clone, Copy)]
-pub struct ContractInfo<'a, P: AsRef<Path>> {
-    abi_path: P,
+/// `artifact` is the compiled contract of the blockchain's kind, e.g. its ABI on Ethereum.
+#[derive(Debug)]
+pub struct ContractInfo<'a, A> {
+    artifact: &'a A,
     addr: &'a str,
 }
 
-impl<'a, P: AsRef<Path>> ContractInfo<'a, P> {
-    pub fn new(abi_path: P, addr: &'a str) -> Self {
+impl<'a, A> Clone for ContractInfo<'a, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, A> Copy for ContractInfo<'a, A> {}
+
+impl<'a, A> ContractInfo<'a, A> {
+    pub fn new(artifact: &'a A, addr: &'a str) -> Self {
         ContractInfo {
-            abi_path,
+            artifact,
             addr,
         }
     }
 
-    pub fn contract_abi(&self) -> Result<ContractABI> {
-        let f = File::open(&self.abi_path)?;
-        let reader = BufReader::new(f);
-
-        ContractABI::load(reader)
-            .map_err(|e| anyhow!("Failed to load contract abi.: {:?}", e))
-            .map_err(Into::into)
+    pub fn artifact(&self) -> &'a A {
+        self.artifact
     }
 
     pub fn address(&self) -> Result<Address> {
-        Address::from_str(self.addr)
-            .map_err(|e| anyhow!("{:?}", e))
-            .map_err(Into::into)
+        contract_address(self.addr)
     }
 }
 
+/// Parse the hex-encoded address of a contract.
+pub fn contract_address(addr: &str) -> Result<Address> {
+    Address::from_str(addr)
+        .map_err(|e| anyhow!("{:?}", e))
+        .map_err(Into::into)
+}
+
 pub struct StateInfo<'a, ST: State> {
     state: ST,
     state_id: u64,
@@ -64,8 +91,8 @@ impl<'a, ST: State> StateInfo<'a, ST> {
         self.state.as_bytes()
     }
 
-    pub fn call_name_to_id(&self) -> u32 {
-        call_name_to_id(&self.call_name)
+    pub fn call_name_to_id(&self) -> Result<u32> {
+        call_name_to_id(&self.call_name).map_err(Into::into)
     }
 
     pub fn state_id(&self) -> u64 {
@@ -76,10 +103,78 @@ impl<'a, ST: State> StateInfo<'a, ST> {
 /// A type of transaction signing address
 #[derive(Debug, Clone)]
 pub enum SignerAddress {
-    EthAddress(web3::types::Address)
+    /// An account managed and unlocked by the node, used with `eth_sendTransaction`.
+    EthAddress(web3::types::Address),
+    /// A local key, used with `eth_sendRawTransaction`.
+    EthSigner(crate::eth::EthSigner),
+    /// An account of a ledger server, which only records its name.
+    LedgerAccount(String),
+}
+
+impl SignerAddress {
+    pub fn eth_address(&self) -> Result<Address> {
+        match self {
+            SignerAddress::EthAddress(address) => Ok(*address),
+            SignerAddress::EthSigner(signer) => Ok(signer.address()),
+            SignerAddress::LedgerAccount(name) => Err(anyhow!("{} is not an Ethereum account", name).into()),
+        }
+    }
+
+    /// The name recorded as the sender on ledgers. Ethereum accounts are named by their hex-encoded addresses.
+    pub fn account_name(&self) -> String {
+        match self {
+            SignerAddress::EthAddress(address) => hex::encode(address.as_bytes()),
+            SignerAddress::EthSigner(signer) => hex::encode(signer.address().as_bytes()),
+            SignerAddress::LedgerAccount(name) => name.clone(),
+        }
+    }
+}
+
+/// Status of a sent transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Not mined yet, or mined in a block without enough confirmations.
+    Pending,
+    /// Mined successfully in the block of the number.
+    Mined(u64),
+    /// Mined but reverted, with the reason if the node returns it.
+    Reverted(Option<String>),
+    /// Unknown to the node, e.g. evicted from the transaction pool.
+    Dropped,
+}
+
+/// The result of a sent transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxReceipt {
+    pub status: TxStatus,
+    pub gas_used: Option<u64>,
+}
+
+impl TxReceipt {
+    pub fn new(status: TxStatus, gas_used: Option<u64>) -> Self {
+        TxReceipt { status, gas_used }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == TxStatus::Pending
+    }
+}
+
+/// Parse a transaction hash returned by senders, with or without `0x` prefix.
+pub fn parse_tx_hash(tx_hash: &str) -> Result<H256> {
+    let bytes = hex::decode(tx_hash.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid transaction hash {}: {}", tx_hash, e))?;
+    if bytes.len() != 32 {
+        return Err(anyhow!("Transaction hash should have 32 bytes: {}", tx_hash).into());
+    }
+
+    Ok(H256::from_slice(&bytes))
 }
 
 /// A type of contract
 pub enum ContractKind {
-    Web3Contract(Web3Contract)
+    Web3Contract(Web3Contract),
+    LedgerContract(crate::ledger::primitives::LedgerContract),
+    #[cfg(feature = "mock")]
+    MockContract(crate::mock::MockContract),
 }
@@ -308,8 +308,14 @@ impl AccessRight {
     ) -> Result<Self, SignatureError> {
         let sig = Signature::from_bytes(&raw_sig)?;
         let pubkey = PublicKey::from_bytes(&raw_pubkey)?;
+        // Raw access rights come from users, so an invalid signature is an error rather than an assertion failure.
+        pubkey.verify(&raw_challenge, &sig)?;
 
-        Ok(AccessRight::new(sig, pubkey, raw_challenge))
+        Ok(AccessRight {
+            sig,
+            pubkey,
+            challenge: raw_challenge,
+        })
     }
 }
 
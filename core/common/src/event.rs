@@ -0,0 +1,25 @@
+use crate::localstd::vec::Vec;
+use codec::{Encode, Decode};
+
+/// An event emitted by the anonify contract.
+/// Events are passed to the enclave in chain order, by block number and log index,
+/// since a handshake changes the keys which decrypt the following ciphertexts.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub enum EnclaveEvent {
+    /// A ciphertext of an instruction stored by `StoreCiphertext`.
+    Ciphertext(Vec<u8>),
+    /// An encoded handshake stored by `StoreHandshake`.
+    /// `enclave_sig` is a recoverable signature over the sha256 hash of the handshake by the sender enclave.
+    Handshake {
+        handshake: Vec<u8>,
+        enclave_sig: Vec<u8>,
+    },
+    /// An encoded add handshake of an enclave joining the group, stored by `JoinGroup`.
+    /// Roster indices are assigned in the join order from 0, starting with the deployer.
+    JoinGroup {
+        roster_idx: u32,
+        enclave_address: [u8; 20],
+        handshake: Vec<u8>,
+        enclave_sig: Vec<u8>,
+    },
+}
@@ -115,6 +115,11 @@ impl DBTx {
         });
     }
 
+    /// Operations in the order they were added.
+    pub fn ops(&self) -> &[DBOp] {
+        &self.ops
+    }
+
     pub fn delete(&mut self, key: &[u8]) {
         let mut ekey = ElasticArray32::new();
         ekey.append_slice(key);
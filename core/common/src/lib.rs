@@ -19,7 +19,9 @@ use sgx_anyhow as local_anyhow;
 extern crate lazy_static;
 
 mod crypto;
+mod event;
 pub mod kvs;
 
 pub use crate::crypto::*;
+pub use crate::event::*;
 pub use crate::kvs::*;
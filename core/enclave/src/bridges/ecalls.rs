@@ -1,60 +1,95 @@
-use std::slice;
+use std::{slice, vec::Vec};
 use sgx_types::*;
 use anonify_types::*;
-use anonify_common::{UserAddress, AccessRight};
+use anonify_common::{UserAddress, AccessRight, EnclaveEvent};
 use anonify_app_preluder::{CIPHERTEXT_SIZE, Ciphertext, CallKind};
-use anonify_runtime::{StateGetter, State, MemId};
-use anonify_treekem::handshake::HandshakeParams;
-use ed25519_dalek::{PublicKey, Signature};
-use codec::Decode;
+use anonify_runtime::{StateGetter, State, StateType, MemId, UpdatedState};
+use ed25519_dalek::{PublicKey, Signature, SignatureError};
+use codec::{Encode, Decode};
+use log::warn;
 use crate::{
-    context::ENCLAVE_CONTEXT,
+    context::{ENCLAVE_CONTEXT, EnclaveContext},
     transaction::{JoinGroupTx, EnclaveTx, HandshakeTx, InstructionTx},
     kvs::EnclaveDB,
     config::{IAS_URL, TEST_SUB_KEY},
     instructions::Instructions,
-    notify::updated_state_into_raw,
+    notify::updated_states_into_raw,
+    sealing::SgxSealer,
 };
 use super::ocalls::save_to_host_memory;
 
-/// Insert a ciphertext in event logs from blockchain nodes into enclave's memory database.
+/// Insert events from blockchain nodes into enclave's memory database in the order they were emitted,
+/// since a handshake changes the keys which decrypt the following ciphertexts.
 #[no_mangle]
-pub unsafe extern "C" fn ecall_insert_ciphertext(
-    ciphertext: *mut u8,
-    ciphertext_len: usize,
-    raw_updated_state: &mut RawUpdatedState,
+pub unsafe extern "C" fn ecall_insert_events(
+    events: *mut u8,
+    events_len: usize,
+    raw_updated_states: &mut RawUpdatedStates,
 ) -> sgx_status_t {
-    let ciphertext = slice::from_raw_parts_mut(ciphertext, ciphertext_len);
-    let ciphertext = Ciphertext::from_bytes(ciphertext);
-    let group_key = &mut *ENCLAVE_CONTEXT.group_key.write().unwrap();
-
-    if let Some(updated_state) = ENCLAVE_CONTEXT
-        .update_state(&ciphertext, group_key)
-        .expect("Failed to write cihpertexts.") {
-             *raw_updated_state = updated_state_into_raw(updated_state)
-                .expect("Failed to convert into raw updated state");
+    let events = slice::from_raw_parts_mut(events, events_len);
+    let events = match Vec::<EnclaveEvent>::decode(&mut &events[..]) {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("Failed to decode events: {:?}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
         }
+    };
+    let updated_states = insert_events(&*ENCLAVE_CONTEXT, events);
 
-    let roster_idx = ciphertext.roster_idx() as usize;
-    // ratchet app keychain per a log.
-    group_key.ratchet(roster_idx).unwrap();
+    // The events have been inserted, so the host must not insert them again even if notifications are lost.
+    *raw_updated_states = match updated_states_into_raw(updated_states) {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("Failed to convert into raw updated states: {}", e);
+            RawUpdatedStates::default()
+        }
+    };
 
     sgx_status_t::SGX_SUCCESS
 }
 
-/// Insert handshake received from blockchain nodes into enclave.
-#[no_mangle]
-pub unsafe extern "C" fn ecall_insert_handshake(
-    handshake: *mut u8,
-    handshake_len: usize,
-) -> sgx_status_t {
-    let handshake_bytes = slice::from_raw_parts_mut(handshake, handshake_len);
-    let handshake = HandshakeParams::decode(&mut &handshake_bytes[..]).unwrap();
-    let group_key = &mut *ENCLAVE_CONTEXT.group_key.write().unwrap();
+/// Every node inserts the same events in the same order, so an event which can't be applied fails on all of them,
+/// e.g. a ciphertext sent in the previous epoch and mined after a concurrent handshake, or a failed state transition.
+/// Such an event is logged and skipped, since a failure would make the host retry the events forever
+/// while the ones before it have already changed the states.
+fn insert_events(
+    ctx: &EnclaveContext<StateType>,
+    events: Vec<EnclaveEvent>,
+) -> Vec<UpdatedState<StateType>> {
+    let group_key = &mut *ctx.group_key.write().unwrap();
+    let mut updated_states = vec![];
 
-    group_key.process_handshake(&handshake).unwrap();
+    for event in events {
+        match event {
+            EnclaveEvent::Ciphertext(mut ciphertext) => {
+                let ciphertext = Ciphertext::from_bytes(&mut ciphertext[..]);
+                match ctx.update_state(&ciphertext, group_key) {
+                    Ok(states) => updated_states.extend(states),
+                    Err(e) => warn!("Skipped a ciphertext from roster index {}: {}", ciphertext.roster_idx(), e),
+                }
+            }
+            // Handshakes can be sent by anyone on some backends, so a rejected one, e.g. with an invalid signature,
+            // is skipped without changing the group key rather than aborting the enclave.
+            EnclaveEvent::Handshake { handshake, enclave_sig } => {
+                if let Err(e) = ctx.process_handshake(&handshake, &enclave_sig, group_key) {
+                    warn!("Skipped a rejected handshake: {}", e);
+                }
+            }
+            EnclaveEvent::JoinGroup { roster_idx, enclave_address, handshake, enclave_sig } => {
+                if let Err(e) = ctx.process_join_handshake(
+                    roster_idx,
+                    enclave_address,
+                    &handshake,
+                    &enclave_sig,
+                    group_key,
+                ) {
+                    warn!("Skipped a rejected join handshake for roster index {}: {}", roster_idx, e);
+                }
+            }
+        }
+    }
 
-    sgx_status_t::SGX_SUCCESS
+    updated_states
 }
 
 /// Get current state of the user represented the given public key from enclave memory database.
@@ -66,15 +101,133 @@ pub unsafe extern "C" fn ecall_get_state(
     mem_id: u32,
     state: &mut EnclaveState,
 ) -> sgx_status_t {
-    let sig = Signature::from_bytes(&sig[..])
-        .expect("Failed to read signatures.");
-    let pubkey = PublicKey::from_bytes(&pubkey[..])
-        .expect("Failed to read public key.");
-    let key = UserAddress::from_sig(&challenge[..], &sig, &pubkey)
-        .expect("Failed to generate user address.");
+    let key = match user_address_from_raw(sig, pubkey, challenge) {
+        Ok(key) => key,
+        Err(e) => {
+            warn!("Failed to generate user address: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
 
     let user_state = &ENCLAVE_CONTEXT.get_by_id(key, MemId::from_raw(mem_id));
-    state.0 = save_to_host_memory(user_state.as_bytes()).unwrap() as *const u8;
+    state.0 = match save_to_host_memory(user_state.as_bytes()) {
+        Ok(ptr) => ptr as *const u8,
+        Err(e) => {
+            warn!("Failed to save the state to host memory: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Run a read-only view function and return the encoded result to the user
+/// represented by the given access right.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_query(
+    raw_sig: &RawSig,
+    raw_pubkey: &RawPubkey,
+    raw_challenge: &RawChallenge,
+    params: *mut u8,
+    params_len: usize,
+    view_id: u32,
+    result: &mut EnclaveState,
+) -> sgx_status_t {
+    let params = slice::from_raw_parts_mut(params, params_len);
+
+    // Unauthorized or invalid queries must not abort the enclave.
+    let ar = match AccessRight::from_raw(*raw_pubkey, *raw_sig, *raw_challenge) {
+        Ok(ar) => ar,
+        Err(e) => {
+            warn!("Failed to generate access right: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
+    let res = match ENCLAVE_CONTEXT.query(view_id, params, &ar) {
+        Ok(res) => res,
+        Err(e) => {
+            warn!("Failed to query state: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
+    result.0 = save_to_host_memory(&res[..]).unwrap() as *const u8;
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Get the digest of all states in enclave memory database for consistency checks across nodes.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_get_state_root(
+    state_root: &mut [u8; 32],
+) -> sgx_status_t {
+    *state_root = ENCLAVE_CONTEXT.state_root().as_array();
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Get the encoded addresses of the enclaves which have joined the group, indexed by their roster indices.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_get_roster(
+    roster: &mut EnclaveState,
+) -> sgx_status_t {
+    let encoded_roster = ENCLAVE_CONTEXT.group_key.read().unwrap().roster().encode();
+    roster.0 = save_to_host_memory(&encoded_roster[..]).unwrap() as *const u8;
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Seal enclave's states, group key and identity key with the latest block whose logs have been inserted,
+/// and pass it to the host to persist it.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_seal_snapshot(
+    block_num: u64,
+    block_hash: &[u8; 32],
+    sealed: &mut EnclaveState,
+) -> sgx_status_t {
+    let snapshot = match ENCLAVE_CONTEXT.seal_snapshot(&SgxSealer, block_num, *block_hash) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            warn!("Failed to seal snapshot: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+    sealed.0 = save_to_host_memory(&snapshot[..]).unwrap() as *const u8;
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Restore enclave's states, group key and identity key from a sealed snapshot on startup or on a reorganization,
+/// and return the block number and hash the snapshot was taken at.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_restore_snapshot(
+    sealed: *mut u8,
+    sealed_len: usize,
+    block_num: &mut u64,
+    block_hash: &mut [u8; 32],
+) -> sgx_status_t {
+    let sealed = slice::from_raw_parts(sealed, sealed_len);
+
+    // A broken snapshot must not abort the enclave, so that the host can start from scratch.
+    let (restored_num, restored_hash) = match ENCLAVE_CONTEXT.restore_snapshot(&SgxSealer, sealed) {
+        Ok(restored) => restored,
+        Err(e) => {
+            warn!("Failed to restore snapshot: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
+    *block_num = restored_num;
+    *block_hash = restored_hash;
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Return MRENCLAVE of this enclave, with which the host keys sealed snapshots
+/// so that an enclave never restores a snapshot taken by another one.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_get_mr_enclave(
+    mr_enclave: &mut [u8; 32],
+) -> sgx_status_t {
+    *mr_enclave = sgx_tse::rsgx_self_report().body.mr_enclave.m;
 
     sgx_status_t::SGX_SUCCESS
 }
@@ -83,15 +236,21 @@ pub unsafe extern "C" fn ecall_get_state(
 pub unsafe extern "C" fn ecall_join_group(
     raw_join_group_tx: &mut RawJoinGroupTx,
 ) -> sgx_status_t {
-    let join_group_tx = JoinGroupTx::construct(
-            IAS_URL,
-            TEST_SUB_KEY,
-            &*ENCLAVE_CONTEXT,
-        )
-        .expect("Failed to construct JoinGroup transaction.");
+    let join_group_tx = match JoinGroupTx::construct(IAS_URL, TEST_SUB_KEY, &*ENCLAVE_CONTEXT) {
+        Ok(join_group_tx) => join_group_tx,
+        Err(e) => {
+            warn!("Failed to construct JoinGroup transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
 
-    *raw_join_group_tx = join_group_tx.into_raw()
-        .expect("Failed to convert into raw JoinGroup transaction.");
+    *raw_join_group_tx = match join_group_tx.into_raw() {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("Failed to convert into raw JoinGroup transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
 
     sgx_status_t::SGX_SUCCESS
 }
@@ -109,21 +268,31 @@ pub unsafe extern "C" fn ecall_instruction(
     raw_instruction_tx: &mut RawInstructionTx,
 ) -> sgx_status_t {
     let params = slice::from_raw_parts_mut(state, state_len);
-    let ar = AccessRight::from_raw(*raw_pubkey, *raw_sig, *raw_challenge)
-        .expect("Failed to generate access right.");
-
-    let instruction_tx = InstructionTx::construct(
-            call_id,
-            params,
-            state_id,
-            &ar,
-            &*ENCLAVE_CONTEXT,
-        )
-        .expect("Failed to construct state tx.");
+
+    // Invalid instructions, e.g. with an unknown call id or undecodable params, must not abort the enclave.
+    let ar = match AccessRight::from_raw(*raw_pubkey, *raw_sig, *raw_challenge) {
+        Ok(ar) => ar,
+        Err(e) => {
+            warn!("Failed to generate access right: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
+    let instruction_tx = match InstructionTx::construct(call_id, params, state_id, &ar, &*ENCLAVE_CONTEXT) {
+        Ok(instruction_tx) => instruction_tx,
+        Err(e) => {
+            warn!("Failed to construct state tx: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
 
     ENCLAVE_CONTEXT.set_notification(ar.user_address());
-    *raw_instruction_tx = instruction_tx.into_raw()
-        .expect("Failed to convert into raw state transaction.");
+    *raw_instruction_tx = match instruction_tx.into_raw() {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("Failed to convert into raw state transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
 
     sgx_status_t::SGX_SUCCESS
 }
@@ -132,11 +301,47 @@ pub unsafe extern "C" fn ecall_instruction(
 pub unsafe extern "C" fn ecall_handshake(
     raw_handshake_tx: &mut RawHandshakeTx,
 ) -> sgx_status_t {
-    let handshake_tx = HandshakeTx::construct(&*ENCLAVE_CONTEXT)
-        .expect("Failed to construct handshake transaction.");
+    let handshake_tx = match HandshakeTx::construct(&*ENCLAVE_CONTEXT) {
+        Ok(handshake_tx) => handshake_tx,
+        Err(e) => {
+            warn!("Failed to construct handshake transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+
+    *raw_handshake_tx = match handshake_tx.into_raw() {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("Failed to convert into raw handshake transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Handshake to remove the member at `roster_idx` from the group.
+/// The handshake is sent and processed in the same way as `ecall_handshake`'s.
+#[no_mangle]
+pub unsafe extern "C" fn ecall_remove_member(
+    roster_idx: u32,
+    raw_handshake_tx: &mut RawHandshakeTx,
+) -> sgx_status_t {
+    let handshake_tx = match HandshakeTx::construct_remove(&*ENCLAVE_CONTEXT, roster_idx as usize) {
+        Ok(handshake_tx) => handshake_tx,
+        Err(e) => {
+            warn!("Failed to construct a handshake removing member {}: {}", roster_idx, e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
 
-    *raw_handshake_tx = handshake_tx.into_raw()
-        .expect("Failed to convert into raw handshake transaction.");
+    *raw_handshake_tx = match handshake_tx.into_raw() {
+        Ok(raw) => raw,
+        Err(e) => {
+            warn!("Failed to convert into raw handshake transaction: {}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
 
     sgx_status_t::SGX_SUCCESS
 }
@@ -147,18 +352,160 @@ pub unsafe extern "C" fn ecall_register_notification(
     pubkey: &RawPubkey,
     challenge: &RawChallenge,
 ) -> sgx_status_t {
-    let sig = Signature::from_bytes(&sig[..])
-        .expect("Failed to read signatures.");
-    let pubkey = PublicKey::from_bytes(&pubkey[..])
-        .expect("Failed to read public key.");
-    let user_address = UserAddress::from_sig(&challenge[..], &sig, &pubkey)
-        .expect("Failed to generate user address.");
+    let user_address = match user_address_from_raw(sig, pubkey, challenge) {
+        Ok(user_address) => user_address,
+        Err(e) => {
+            warn!("Failed to generate user address: {}", e);
+            return sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+        }
+    };
 
     ENCLAVE_CONTEXT.set_notification(user_address);
 
     sgx_status_t::SGX_SUCCESS
 }
 
+fn user_address_from_raw(
+    sig: &RawSig,
+    pubkey: &RawPubkey,
+    challenge: &RawChallenge,
+) -> Result<UserAddress, SignatureError> {
+    let sig = Signature::from_bytes(&sig[..])?;
+    let pubkey = PublicKey::from_bytes(&pubkey[..])?;
+
+    UserAddress::from_sig(&challenge[..], &sig, &pubkey)
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use anonify_common::{Sha256, Hash256};
+    use anonify_treekem::{handshake::{PathSecretKVS, PathSecretRequest}, init_path_secret_kvs};
+    use crate::{
+        crypto::{EnclaveIdentityKey, RECOVERABLE_SIGNATURE_SIZE},
+        group_key::GroupKey,
+        config::TEST_SPID,
+    };
+
+    pub fn test_skip_handshake_with_bad_signature() {
+        let mut kvs = PathSecretKVS::new();
+        init_path_secret_kvs(&mut kvs, 3, 3);
+        let handshake = GroupKey::new(PathSecretRequest::Local(kvs)).unwrap()
+            .create_join_handshake().unwrap()
+            .encode();
+        let joining_address = EnclaveIdentityKey::new().unwrap().address();
+        let other_sig = EnclaveIdentityKey::new().unwrap()
+            .sign_recoverable(Sha256::hash(&handshake).as_bytes()).unwrap();
+        let roster_len = ENCLAVE_CONTEXT.group_key.read().unwrap().roster().len();
+
+        let mut events = vec![
+            // Signed by an enclave other than the joining one.
+            EnclaveEvent::JoinGroup {
+                roster_idx: roster_len as u32,
+                enclave_address: joining_address,
+                handshake: handshake.clone(),
+                enclave_sig: other_sig.to_vec(),
+            },
+            // Not a valid signature at all.
+            EnclaveEvent::Handshake {
+                handshake,
+                enclave_sig: vec![0u8; RECOVERABLE_SIGNATURE_SIZE],
+            },
+        ].encode();
+        let mut raw_updated_states = RawUpdatedStates::default();
+        let status = unsafe {
+            ecall_insert_events(events.as_mut_ptr(), events.len(), &mut raw_updated_states)
+        };
+
+        // The rejected handshakes are skipped and the group key is still available.
+        assert_eq!(status, sgx_status_t::SGX_SUCCESS);
+        assert!(raw_updated_states.is_empty());
+        assert_eq!(ENCLAVE_CONTEXT.group_key.read().unwrap().roster().len(), roster_len);
+    }
+
+    #[cfg(feature = "erc20")]
+    pub fn test_skip_stale_epoch_ciphertext() {
+        use anonify_app_preluder::{construct, transfer};
+        use anonify_runtime::U256;
+
+        let ctx = EnclaveContext::new(TEST_SPID).unwrap();
+        let join_handshake = ctx.group_key.read().unwrap().create_join_handshake().unwrap().encode();
+        let join = EnclaveEvent::JoinGroup {
+            roster_idx: 0,
+            enclave_address: ctx.enclave_address(),
+            enclave_sig: ctx.sign_handshake(&join_handshake).unwrap().to_vec(),
+            handshake: join_handshake,
+        };
+        insert_events(&ctx, vec![join]);
+
+        let access_right = AccessRight::new_from_rng().unwrap();
+        let sender = access_right.user_address();
+        let recipient = UserAddress::from_array([1; 20]);
+        let encrypt = |call_id: u32, mut params: Vec<u8>, group_key: &mut GroupKey| {
+            let ciphertext = Instructions::new(call_id, &mut params, &access_right).unwrap()
+                .encrypt(group_key).unwrap();
+            // Ratchet past the generation, as every node does when the ciphertext is inserted.
+            group_key.decrypt(&ciphertext).unwrap();
+            EnclaveEvent::Ciphertext(ciphertext.encode())
+        };
+
+        // Sent in the current epoch, but mined after a concurrent handshake.
+        let mut group_key = ctx.group_key.read().unwrap().clone();
+        let stale = encrypt(0, construct { total_supply: U256::from(999u64) }.encode(), &mut group_key);
+
+        let handshake = group_key.create_handshake().unwrap().encode();
+        let enclave_sig = ctx.sign_handshake(&handshake).unwrap().to_vec();
+        ctx.process_handshake(&handshake, &enclave_sig, &mut group_key).unwrap();
+        let constructed = encrypt(0, construct { total_supply: U256::from(100u64) }.encode(), &mut group_key);
+        let failed = encrypt(1, transfer { amount: U256::from(1000u64), recipient }.encode(), &mut group_key);
+        let transferred = encrypt(1, transfer { amount: U256::from(30u64), recipient }.encode(), &mut group_key);
+
+        insert_events(&ctx, vec![
+            EnclaveEvent::Handshake { handshake, enclave_sig },
+            constructed,
+            stale,
+            failed,
+            transferred,
+        ]);
+
+        // The stale ciphertext and the failed transfer are skipped, and the following ones are still applied.
+        assert_eq!(ctx.get::<U256>(sender, "Balance").unwrap(), U256::from(70u64));
+        assert_eq!(ctx.get::<U256>(recipient, "Balance").unwrap(), U256::from(30u64));
+    }
+
+    pub fn test_reject_invalid_instruction() {
+        let (pubkey, sig, challenge) = AccessRight::new_from_rng().unwrap().into_raw();
+        let mut params = vec![];
+        let mut raw_instruction_tx = RawInstructionTx::default();
+
+        // No function has this call id.
+        let status = unsafe {
+            ecall_instruction(
+                &sig, &pubkey, &challenge,
+                params.as_mut_ptr(), 0, 0, u32::max_value(),
+                &mut raw_instruction_tx,
+            )
+        };
+        assert_eq!(status, sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+
+        // The signature doesn't match the challenge.
+        let mut other_challenge = challenge;
+        other_challenge[0] ^= 1;
+        let status = unsafe {
+            ecall_instruction(
+                &sig, &pubkey, &other_challenge,
+                params.as_mut_ptr(), 0, 0, 0,
+                &mut raw_instruction_tx,
+            )
+        };
+        assert_eq!(status, sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+
+        let mut state = EnclaveState(std::ptr::null());
+        let status = unsafe { ecall_get_state(&sig, &pubkey, &other_challenge, 0, &mut state) };
+        assert_eq!(status, sgx_status_t::SGX_ERROR_INVALID_PARAMETER);
+    }
+}
+
 pub mod enclave_tests {
     use test_utils::{test_case, run_inventory_tests};
     use std::vec::Vec;
@@ -169,9 +516,84 @@ pub mod enclave_tests {
         anonify_treekem::tests::app_msg_correctness();
     }
 
+    #[test_case]
+    fn test_join_in_roster_order() {
+        anonify_treekem::tests::join_in_roster_order();
+    }
+
+    #[test_case]
+    fn test_reject_handshake_on_forked_transcript() {
+        anonify_treekem::tests::reject_handshake_on_forked_transcript();
+    }
+
+    #[test_case]
+    fn test_out_of_order_app_msgs() {
+        anonify_treekem::tests::out_of_order_app_msgs();
+    }
+
+    #[test_case]
+    fn test_forward_secrecy_against_removed_member() {
+        anonify_treekem::tests::forward_secrecy_against_removed_member();
+    }
+
+    #[test_case]
+    fn test_reject_handshake_from_removed_member() {
+        anonify_treekem::tests::reject_handshake_from_removed_member();
+    }
+
     #[test_case]
     fn test_ecies_correctness() { anonify_treekem::tests::ecies_correctness(); }
 
+    #[test_case]
+    fn test_write_tx_atomically() { crate::kvs::tests::test_write_tx_atomically(); }
+
+    #[test_case]
+    fn test_rollback_failed_tx() { crate::kvs::tests::test_rollback_failed_tx(); }
+
+    #[test_case]
+    fn test_snapshot_correctness() { anonify_treekem::tests::snapshot_correctness(); }
+
+    #[test_case]
+    fn test_reject_handshake_signed_by_other_enclave() {
+        crate::group_key::tests::test_reject_handshake_signed_by_other_enclave();
+    }
+
+    #[test_case]
+    fn test_skip_handshake_with_bad_signature() {
+        crate::bridges::ecalls::tests::test_skip_handshake_with_bad_signature();
+    }
+
+    #[cfg(feature = "erc20")]
+    #[test_case]
+    fn test_skip_stale_epoch_ciphertext() {
+        crate::bridges::ecalls::tests::test_skip_stale_epoch_ciphertext();
+    }
+
+    #[test_case]
+    fn test_reject_invalid_instruction() {
+        crate::bridges::ecalls::tests::test_reject_invalid_instruction();
+    }
+
+    #[test_case]
+    fn test_recover_address() { crate::crypto::tests::test_recover_address(); }
+
+    #[test_case]
+    fn test_aes_sealer_roundtrip() { crate::sealing::tests::test_aes_sealer_roundtrip(); }
+
+    #[test_case]
+    fn test_aes_sealer_detects_tampering() { crate::sealing::tests::test_aes_sealer_detects_tampering(); }
+
+    #[test_case]
+    fn test_restore_sealed_snapshot() { crate::snapshot::tests::test_restore_sealed_snapshot(); }
+
+    #[test_case]
+    fn test_keep_enclave_address_after_restore() {
+        crate::snapshot::tests::test_keep_enclave_address_after_restore();
+    }
+
+    #[test_case]
+    fn test_sealed_path_secret_is_bound_to_access_key() { crate::key_vault::tests::test_sealed_path_secret_is_bound_to_access_key(); }
+
     #[no_mangle]
     pub fn ecall_run_tests() { run_inventory_tests!(|_s: &str| true); }
 }
@@ -1,5 +1,3 @@
-pub const MY_ROSTER_IDX: usize = 0;
-pub const MAX_ROSTER_IDX: usize = 2;
 pub const TEST_SPID: &str = "2C149BFC94A61D306A96211AED155BE9";
 pub const UNTIL_ROSTER_IDX: usize = 10;
 pub const UNTIL_EPOCH: usize = 30;
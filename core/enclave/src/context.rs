@@ -4,23 +4,26 @@ use std::{
 };
 use sgx_types::*;
 use std::prelude::v1::*;
-use anonify_common::{kvs::{MemoryDB, DBValue}, UserAddress};
-use anonify_app_preluder::{mem_name_to_id, Ciphertext};
+use anonify_common::{kvs::{MemoryDB, DBValue}, UserAddress, AccessRight, Sha256, Hash256};
+use anonify_app_preluder::{mem_name_to_id, Ciphertext, ViewKind, Runtime};
 use anonify_runtime::{State, StateGetter, StateType, MemId, UpdatedState};
 use anonify_treekem::{
-    handshake::{PathSecretRequest, PathSecretKVS},
+    handshake::{PathSecretRequest, PathSecretKVS, KeyVault, HandshakeParams},
     init_path_secret_kvs,
 };
-use codec::Encode;
+use codec::{Encode, Decode};
 use crate::{
     notify::Notifier,
-    crypto::EnclaveIdentityKey,
+    crypto::{EnclaveIdentityKey, VaultSigningKey, RECOVERABLE_SIGNATURE_SIZE, recover_address},
     group_key::GroupKey,
-    config::{TEST_SPID, MY_ROSTER_IDX, MAX_ROSTER_IDX, UNTIL_ROSTER_IDX, UNTIL_EPOCH},
+    config::{TEST_SPID, UNTIL_ROSTER_IDX, UNTIL_EPOCH},
     ocalls::{sgx_init_quote, get_quote},
     error::Result,
     kvs::{EnclaveDB, EnclaveDBTx},
     instructions::Instructions,
+    sealing::{Sealer, SgxSealer},
+    snapshot::Snapshot,
+    key_vault::RemoteKeyVault,
 };
 
 lazy_static! {
@@ -30,7 +33,7 @@ lazy_static! {
 
 impl StateGetter for EnclaveContext<StateType> {
     fn get<S: State>(&self, key: impl Into<UserAddress>, name: &str) -> anyhow::Result<S> {
-        let mem_id = mem_name_to_id(name);
+        let mem_id = mem_name_to_id(name)?;
         let mut buf = self.db
             .get(key.into(), mem_id)
             .into_bytes();
@@ -50,7 +53,7 @@ impl StateGetter for EnclaveContext<StateType> {
 #[derive(Clone)]
 pub struct EnclaveContext<S: State> {
     spid: sgx_spid_t,
-    identity_key: EnclaveIdentityKey,
+    identity_key: Arc<SgxRwLock<EnclaveIdentityKey>>,
     db: EnclaveDB<S>,
     notifier: Notifier,
     pub group_key: Arc<SgxRwLock<GroupKey>>,
@@ -64,24 +67,26 @@ impl EnclaveContext<StateType> {
         id.copy_from_slice(&spid_vec);
         let spid: sgx_spid_t = sgx_spid_t { id };
 
-        let identity_key = EnclaveIdentityKey::new()?;
+        let identity_key = Arc::new(SgxRwLock::new(EnclaveIdentityKey::new()?));
         let db = EnclaveDB::new();
 
-        // temporary path secrets are generated in local.
-        let mut kvs = PathSecretKVS::new();
-        init_path_secret_kvs(&mut kvs, UNTIL_ROSTER_IDX, UNTIL_EPOCH);
-        let req = PathSecretRequest::Local(kvs);
-
-        let my_roster_idx: usize = env::var("MY_ROSTER_IDX")
-            .expect("MY_ROSTER_IDX is not set")
-            .parse()
-            .expect("Failed to parse MY_ROSTER_IDX to usize");
-        let max_roster_idx: usize = env::var("MAX_ROSTER_IDX")
-            .expect("MAX_ROSTER_IDX is not set")
-            .parse()
-            .expect("Failed to parse MAX_ROSTER_IDX to usize");
-
-        let group_key = Arc::new(SgxRwLock::new(GroupKey::new(my_roster_idx, max_roster_idx, req)?));
+        // Path secrets are backed up to a key vault if KEY_VAULT_ADDR is set,
+        // otherwise they are pre-generated in local up to UNTIL_ROSTER_IDX and UNTIL_EPOCH.
+        let req = match env::var("KEY_VAULT_ADDR") {
+            Ok(addr) => {
+                let key_vault: Arc<dyn KeyVault> =
+                    Arc::new(RemoteKeyVault::new(addr, VaultSigningKey::derive()?, SgxSealer));
+                PathSecretRequest::Remote(key_vault)
+            },
+            Err(_) => {
+                let mut kvs = PathSecretKVS::new();
+                init_path_secret_kvs(&mut kvs, UNTIL_ROSTER_IDX, UNTIL_EPOCH);
+                PathSecretRequest::Local(kvs)
+            },
+        };
+
+        // The roster index is assigned when the enclave's own JoinGroup event is inserted.
+        let group_key = Arc::new(SgxRwLock::new(GroupKey::new(req)?));
         let notifier = Notifier::new();
 
         Ok(EnclaveContext{
@@ -115,40 +120,141 @@ impl EnclaveContext<StateType> {
         Ok(target_info)
     }
 
+    /// The address of the enclave identity key, which identifies the enclave in the roster.
+    pub fn enclave_address(&self) -> [u8; 20] {
+        self.identity_key.read().unwrap().address()
+    }
+
     /// Generate a signature using enclave's identity key.
     /// This signature is used to verify enclave's program dependencies and
     /// should be verified in the public available place such as smart contract on blockchain.
     pub fn sign(&self, msg: &[u8]) -> Result<secp256k1::Signature> {
-        self.identity_key.sign(msg)
+        self.identity_key.read().unwrap().sign(msg)
+    }
+
+    /// Sign an encoded handshake using enclave's identity key, so that members and the contract
+    /// can verify it is sent by the enclave registered in the roster.
+    pub fn sign_handshake(&self, handshake: &[u8]) -> Result<[u8; RECOVERABLE_SIGNATURE_SIZE]> {
+        self.identity_key.read().unwrap().sign_recoverable(Sha256::hash(handshake).as_bytes())
+    }
+
+    /// Verify the signature over an encoded handshake of a member and process it.
+    pub fn process_handshake(
+        &self,
+        handshake: &[u8],
+        enclave_sig: &[u8],
+        group_key: &mut GroupKey,
+    ) -> Result<()> {
+        let signer = recover_address(Sha256::hash(handshake).as_bytes(), enclave_sig)?;
+        let handshake = HandshakeParams::decode(&mut &handshake[..])?;
+        group_key.process_handshake(&handshake, signer)?;
+
+        Ok(())
+    }
+
+    /// Verify the signature over an encoded join handshake of the enclave at `enclave_address`
+    /// and process it, which assigns `roster_idx` to this enclave if the address is its own.
+    pub fn process_join_handshake(
+        &self,
+        roster_idx: u32,
+        enclave_address: [u8; 20],
+        handshake: &[u8],
+        enclave_sig: &[u8],
+        group_key: &mut GroupKey,
+    ) -> Result<()> {
+        let signer = recover_address(Sha256::hash(handshake).as_bytes(), enclave_sig)?;
+        let handshake = HandshakeParams::decode(&mut &handshake[..])?;
+        group_key.process_join_handshake(
+            roster_idx,
+            enclave_address,
+            &handshake,
+            signer,
+            self.enclave_address(),
+        )?;
+
+        Ok(())
     }
 
     /// Only if the TEE belongs to the group, you can receive ciphertext and decrypt it,
     /// otherwise do nothing.
-    /// Returns a updated state of registerd address in notification.
-    // TODO: Enables to return multiple updated states.
+    /// Returns all updated states of registerd addresses in notification.
     pub fn update_state(
         &self,
         ciphertext: &Ciphertext,
         group_key: &mut GroupKey,
-    ) -> Result<Option<UpdatedState<StateType>>> {
+    ) -> Result<Vec<UpdatedState<StateType>>> {
         if let Some(instructions) = Instructions::decrypt(ciphertext, group_key)? {
-            let mut state_iter = instructions
-                .state_transition::<StateType>(self)?
-                .into_iter();
+            let updated_states = instructions.state_transition::<StateType>(self)?;
 
-            state_iter.clone().for_each(|s| self.db.insert_by_updated_state(s));
-            let res = state_iter.find(|s| self.is_notified(&s.address));
+            // All updates from one instruction are applied atomically.
+            let tx = EnclaveDBTx::from_updated_states(updated_states.clone());
+            self.db.write(tx)?;
+            let res = updated_states
+                .into_iter()
+                .filter(|s| self.is_notified(&s.address))
+                .collect();
 
             return Ok(res)
         }
 
-        Ok(None)
+        Ok(vec![])
+    }
+
+    /// Digest of all states in the enclave database.
+    /// Nodes which processed the same instructions have the same state root.
+    pub fn state_root(&self) -> Sha256 {
+        self.db.state_root()
+    }
+
+    /// Seal all states, the group key and the identity key along with the latest block
+    /// whose logs have been inserted, to be saved by the host.
+    pub fn seal_snapshot<T: Sealer>(&self, sealer: &T, block_num: u64, block_hash: [u8; 32]) -> Result<Vec<u8>> {
+        let group_key = self.group_key.read().unwrap();
+        let snapshot = Snapshot {
+            block_num,
+            block_hash,
+            states: self.db.entries(),
+            group_key: group_key.snapshot(),
+            identity_key: self.identity_key.read().unwrap().secret(),
+        };
+
+        snapshot.seal(sealer)
+    }
+
+    /// Restore all states, the group key and the identity key from a sealed snapshot,
+    /// and return the block number and hash the snapshot was taken at.
+    /// The enclave keeps the address it has joined the group with, so that the contract and the members
+    /// still accept its signatures.
+    pub fn restore_snapshot<T: Sealer>(&self, sealer: &T, sealed: &[u8]) -> Result<(u64, [u8; 32])> {
+        let snapshot = Snapshot::<StateType>::unseal(sealer, sealed)?;
+        let identity_key = EnclaveIdentityKey::from_secret(&snapshot.identity_key)?;
+        // Restore the group key first so that a mismatched snapshot leaves the states unchanged.
+        self.group_key.write().unwrap().restore(&snapshot.group_key)?;
+        *self.identity_key.write().unwrap() = identity_key;
+        self.db.restore(snapshot.states);
+
+        Ok((snapshot.block_num, snapshot.block_hash))
+    }
+
+    /// Run a view function against the current states on behalf of the user
+    /// represented by the given access right, and return the encoded result.
+    pub fn query(
+        &self,
+        view_id: u32,
+        params: &mut [u8],
+        access_right: &AccessRight,
+    ) -> Result<Vec<u8>> {
+        let caller = UserAddress::from_access_right(access_right)?;
+        let kind = ViewKind::from_view_id(view_id, params)?;
+        let res = Runtime::new(self.clone()).query(kind, caller)?;
+
+        Ok(res)
     }
 
     /// Return Attestation report
     fn report(&self, target_info: &sgx_target_info_t) -> Result<sgx_report_t> {
         let mut report = sgx_report_t::default();
-        let report_data = &self.identity_key.report_data()?;
+        let report_data = &self.identity_key.read().unwrap().report_data()?;
 
         if let Ok(r) = sgx_tse::rsgx_create_report(&target_info, &report_data) {
             report = r;
@@ -1,19 +1,28 @@
 //! This module contains enclave specific cryptographic logics.
 
-use sgx_types::sgx_report_data_t;
+use sgx_types::{
+    sgx_report_data_t, sgx_key_request_t, sgx_key_id_t, sgx_attributes_t,
+    SGX_KEYSELECT_SEAL, SGX_KEYPOLICY_MRSIGNER, TSEAL_DEFAULT_FLAGSMASK, TSEAL_DEFAULT_MISCMASK,
+};
 use std::prelude::v1::Vec;
 use ring::aead::BoundKey;
 use secp256k1::{
-    self, Message, Signature, SecretKey, PublicKey,
-    util::SECRET_KEY_SIZE,
+    self, Message, Signature, SecretKey, PublicKey, RecoveryId,
+    util::{SECRET_KEY_SIZE, SIGNATURE_SIZE},
 };
 use anonify_common::{Keccak256, sgx_rand_assign};
+use anyhow::anyhow;
 use crate::error::Result;
 
 const NONCE_SIZE: usize = 32;
 const ADDRESS_SIZE: usize = 20;
 const FILLED_REPORT_DATA_SIZE: usize = ADDRESS_SIZE + NONCE_SIZE;
 const REPORT_DATA_SIZE: usize = 64;
+/// A signature which the signer's address can be recovered from, consisting of r, s and v like Ethereum's.
+pub const RECOVERABLE_SIGNATURE_SIZE: usize = SIGNATURE_SIZE + 1;
+/// Ethereum's `ecrecover` takes v as 27 or 28, which is the recovery id added by this offset.
+const RECOVERY_ID_OFFSET: u8 = 27;
+const VAULT_KEY_LABEL: &[u8] = b"anonify key vault signing key";
 
 /// Enclave Identity Key
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -43,12 +52,41 @@ impl EnclaveIdentityKey {
         })
     }
 
+    /// Restore a key from a secret sealed in a snapshot, so that the enclave keeps its address across restarts.
+    /// The nonce is generated again, since it is only used for attestation reports.
+    pub fn from_secret(secret: &[u8; SECRET_KEY_SIZE]) -> Result<Self> {
+        let secret = SecretKey::parse(secret)?;
+        let mut nonce = [0u8; NONCE_SIZE];
+        sgx_rand_assign(&mut nonce)?;
+
+        Ok(EnclaveIdentityKey {
+            secret,
+            nonce,
+        })
+    }
+
+    pub fn secret(&self) -> [u8; SECRET_KEY_SIZE] {
+        self.secret.serialize()
+    }
+
     pub fn sign(&self, msg: &[u8]) -> Result<Signature> {
         let msg = Message::parse_slice(msg)?;
         let sig = secp256k1::sign(&msg, &self.secret)?;
         Ok(sig.0)
     }
 
+    /// Sign `msg` so that the address of this key can be recovered from the signature,
+    /// both in the enclave by `recover_address` and on-chain by `ecrecover`.
+    pub fn sign_recoverable(&self, msg: &[u8]) -> Result<[u8; RECOVERABLE_SIGNATURE_SIZE]> {
+        let msg = Message::parse_slice(msg)?;
+        let (sig, recovery_id) = secp256k1::sign(&msg, &self.secret)?;
+
+        let mut res = [0u8; RECOVERABLE_SIGNATURE_SIZE];
+        res[..SIGNATURE_SIZE].copy_from_slice(&sig.serialize()[..]);
+        res[SIGNATURE_SIZE] = recovery_id.serialize() + RECOVERY_ID_OFFSET;
+        Ok(res)
+    }
+
     pub fn public_key(&self) -> PublicKey {
         PublicKey::from_secret_key(&self.secret)
     }
@@ -71,12 +109,99 @@ impl EnclaveIdentityKey {
         })
     }
 
-    fn address(&self) -> [u8; ADDRESS_SIZE] {
-        let pubkey = &self.public_key().serialize();
-        let address = &pubkey.keccak256()[12..];
-        assert_eq!(address.len(), ADDRESS_SIZE);
-        let mut res = [0u8; ADDRESS_SIZE];
-        res.copy_from_slice(address);
-        res
+    pub fn address(&self) -> [u8; ADDRESS_SIZE] {
+        pubkey_to_address(&self.public_key())
+    }
+}
+
+/// Recover the address of the enclave identity key which signed `msg` by `sign_recoverable`.
+pub fn recover_address(msg: &[u8], sig: &[u8]) -> Result<[u8; ADDRESS_SIZE]> {
+    if sig.len() != RECOVERABLE_SIGNATURE_SIZE || sig[SIGNATURE_SIZE] < RECOVERY_ID_OFFSET {
+        return Err(anyhow!("Invalid recoverable signature").into());
+    }
+    let msg = Message::parse_slice(msg)?;
+    let mut raw_sig = [0u8; SIGNATURE_SIZE];
+    raw_sig.copy_from_slice(&sig[..SIGNATURE_SIZE]);
+    let recovery_id = RecoveryId::parse(sig[SIGNATURE_SIZE] - RECOVERY_ID_OFFSET)?;
+
+    let pubkey = secp256k1::recover(&msg, &Signature::parse(&raw_sig), &recovery_id)?;
+    Ok(pubkey_to_address(&pubkey))
+}
+
+/// An address is the last 20 bytes of the keccak256 hash of the uncompressed public key without its prefix,
+/// which is the same as the one `ecrecover` returns on-chain.
+fn pubkey_to_address(pubkey: &PublicKey) -> [u8; ADDRESS_SIZE] {
+    let pubkey = &pubkey.serialize()[1..];
+    let address = &pubkey.keccak256()[12..];
+    assert_eq!(address.len(), ADDRESS_SIZE);
+    let mut res = [0u8; ADDRESS_SIZE];
+    res.copy_from_slice(address);
+    res
+}
+
+/// A key to sign requests to a key vault, which identifies the enclave by its public key.
+/// Unlike `EnclaveIdentityKey`, it is derived from the seal key of the enclave signer,
+/// so it stays the same after the enclave is restarted on the same platform.
+#[derive(Debug, Clone)]
+pub struct VaultSigningKey {
+    secret: SecretKey,
+}
+
+impl VaultSigningKey {
+    pub fn derive() -> Result<Self> {
+        let report = sgx_tse::rsgx_self_report();
+        let mut key_id = sgx_key_id_t::default();
+        key_id.id.copy_from_slice(&VAULT_KEY_LABEL.keccak256());
+
+        let key_request = sgx_key_request_t {
+            key_name: SGX_KEYSELECT_SEAL,
+            key_policy: SGX_KEYPOLICY_MRSIGNER,
+            isv_svn: report.body.isv_svn,
+            cpu_svn: report.body.cpu_svn,
+            config_svn: report.body.config_svn,
+            attribute_mask: sgx_attributes_t {
+                flags: TSEAL_DEFAULT_FLAGSMASK,
+                xfrm: 0,
+            },
+            key_id,
+            misc_mask: TSEAL_DEFAULT_MISCMASK,
+            ..Default::default()
+        };
+        let seal_key = sgx_tse::rsgx_get_key(&key_request)?;
+
+        let mut preimage = VAULT_KEY_LABEL.to_vec();
+        preimage.extend_from_slice(&seal_key);
+        let secret = SecretKey::parse(&preimage.keccak256())?;
+
+        Ok(VaultSigningKey { secret })
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Result<Signature> {
+        let msg = Message::parse_slice(msg)?;
+        let sig = secp256k1::sign(&msg, &self.secret)?;
+        Ok(sig.0)
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&self.secret)
+    }
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+
+    pub fn test_recover_address() {
+        let identity_key = EnclaveIdentityKey::new().unwrap();
+        let msg = b"recover address test".keccak256();
+        let sig = identity_key.sign_recoverable(&msg).unwrap();
+        assert_eq!(recover_address(&msg, &sig).unwrap(), identity_key.address());
+
+        // A signature over another message or by another key doesn't recover the address.
+        let other_msg = b"other message".keccak256();
+        assert_ne!(recover_address(&other_msg, &sig).unwrap(), identity_key.address());
+        let other_sig = EnclaveIdentityKey::new().unwrap().sign_recoverable(&msg).unwrap();
+        assert_ne!(recover_address(&msg, &other_sig).unwrap(), identity_key.address());
+        assert!(recover_address(&msg, &sig[..SIGNATURE_SIZE]).is_err());
     }
 }
@@ -35,6 +35,9 @@ pub enum EnclaveError {
     #[error("Codec error: {0:?}")]
     CodecError(codec::Error),
 
+    #[error("Runtime error: {0}")]
+    RuntimeError(#[from] anonify_runtime::RuntimeError),
+
     #[error("Anyhow error: {0}")]
     AnyhowError(#[from] anyhow::Error),
 }
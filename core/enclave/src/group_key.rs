@@ -1,32 +1,33 @@
 use std::vec::Vec;
 use anonify_treekem::{
     GroupState, AppKeyChain, Handshake,
-    handshake::{PathSecretRequest, HandshakeParams},
+    handshake::{PathSecretRequest, HandshakeParams, HandshakeKind},
 };
 use anonify_app_preluder::Ciphertext;
-use anyhow::Result;
+use anyhow::{Result, anyhow, ensure};
+use codec::{Encode, Decode};
+
+/// The address of an enclave identity key.
+pub type EnclaveAddress = [u8; 20];
 
 #[derive(Clone, Debug)]
 pub struct GroupKey {
     group_state: GroupState,
     keychain: AppKeyChain,
-    max_roster_idx: usize,
+    /// Addresses of the enclaves which have joined the group, indexed by their roster indices.
+    roster: Vec<EnclaveAddress>,
     path_secret_req: PathSecretRequest,
 }
 
 impl GroupKey {
-    pub fn new(
-        my_roster_idx: usize,
-        max_roster_idx: usize,
-        path_secret_req: PathSecretRequest,
-    ) -> Result<Self> {
-        let group_state = GroupState::new(my_roster_idx)?;
+    pub fn new(path_secret_req: PathSecretRequest) -> Result<Self> {
+        let group_state = GroupState::new_unassigned()?;
         let keychain = AppKeyChain::default();
 
         Ok(GroupKey {
             group_state,
             keychain,
-            max_roster_idx,
+            roster: vec![],
             path_secret_req,
         })
     }
@@ -35,17 +36,87 @@ impl GroupKey {
         self.group_state.create_handshake(&self.path_secret_req)
     }
 
+    /// Create an add handshake with the next roster index in the join order.
+    pub fn create_join_handshake(&self) -> Result<HandshakeParams> {
+        self.group_state.create_join_handshake(&self.path_secret_req)
+    }
+
+    pub fn create_remove_handshake(&self, roster_idx: usize) -> Result<HandshakeParams> {
+        ensure!(roster_idx < self.roster.len(), "The roster index to remove hasn't been assigned");
+        self.group_state.create_remove_handshake(&self.path_secret_req, roster_idx as u32)
+    }
+
+    /// Process a handshake of a member who has already joined the group.
+    /// Members join only by `process_join_handshake`, so that the roster follows the join order.
+    /// `signer` is the address recovered from the signature over the handshake,
+    /// which must be the enclave registered at the sender's roster index.
     pub fn process_handshake(
         &mut self,
         handshake: &HandshakeParams,
+        signer: EnclaveAddress,
     ) -> Result<()> {
+        let sender = self.roster
+            .get(handshake.roster_idx as usize)
+            .ok_or_else(|| anyhow!(
+                "The handshake is sent from roster index {}, which hasn't joined the group",
+                handshake.roster_idx
+            ))?;
+        ensure!(
+            *sender == signer,
+            "The handshake isn't signed by the enclave at roster index {}",
+            handshake.roster_idx
+        );
         let keychain = self.group_state
-            .process_handshake(handshake, &self.path_secret_req, self.max_roster_idx as u32)?;
+            .process_handshake(handshake, &self.path_secret_req)?;
+        self.keychain = keychain;
+
+        Ok(())
+    }
+
+    /// Process an add handshake of the enclave at `enclave_address` joining the group,
+    /// which takes the next roster index. The handshake must be signed by the joining enclave.
+    /// If the address is `my_address`, the roster index is assigned to this enclave.
+    pub fn process_join_handshake(
+        &mut self,
+        roster_idx: u32,
+        enclave_address: EnclaveAddress,
+        handshake: &HandshakeParams,
+        signer: EnclaveAddress,
+        my_address: EnclaveAddress,
+    ) -> Result<()> {
+        ensure!(signer == enclave_address, "The join handshake isn't signed by the joining enclave");
+        // A removed enclave cannot rejoin with its registered address.
+        ensure!(!self.roster.contains(&enclave_address), "The enclave has already joined the group");
+        ensure!(
+            roster_idx as usize == self.roster.len(),
+            "Roster index {} is assigned out of the join order, the next is {}",
+            roster_idx,
+            self.roster.len()
+        );
+        ensure!(
+            handshake.roster_idx == roster_idx && handshake.kind == HandshakeKind::Update,
+            "The join handshake isn't an add handshake with roster index {}",
+            roster_idx
+        );
+
+        let mut group_state = self.group_state.clone();
+        if enclave_address == my_address {
+            group_state.set_my_roster_idx(roster_idx);
+        }
+        let keychain = group_state.process_handshake(handshake, &self.path_secret_req)?;
+
+        self.group_state = group_state;
         self.keychain = keychain;
+        self.roster.push(enclave_address);
 
         Ok(())
     }
 
+    /// Enclave addresses indexed by their roster indices.
+    pub fn roster(&self) -> &[EnclaveAddress] {
+        &self.roster
+    }
+
     pub fn encrypt(&self, plaintext: Vec<u8>) -> Result<Ciphertext> {
         self.keychain.encrypt_msg(plaintext, &self.group_state)
     }
@@ -54,8 +125,63 @@ impl GroupKey {
         self.keychain.decrypt_msg(&app_msg, &self.group_state)
     }
 
-    /// Ratchet keychain per a transaction
-    pub fn ratchet(&mut self, roster_idx: usize) -> Result<()> {
-        self.keychain.ratchet(roster_idx)
+    pub fn snapshot(&self) -> GroupKeySnapshot {
+        GroupKeySnapshot {
+            group_state: self.group_state.encode_snapshot(),
+            keychain: self.keychain.encode_snapshot(),
+            roster: self.roster.clone(),
+        }
+    }
+
+    /// Restore the group state, the keychain and the roster from a snapshot.
+    /// If a roster index has been assigned to this enclave, the snapshot must have been taken with the same one.
+    pub fn restore(&mut self, snapshot: &GroupKeySnapshot) -> Result<()> {
+        let group_state = GroupState::decode_snapshot(&snapshot.group_state)?;
+        if let Some(my_roster_idx) = self.group_state.my_roster_idx() {
+            ensure!(
+                group_state.my_roster_idx() == Some(my_roster_idx),
+                "The snapshot is taken by roster index {:?}, but my roster index is {}",
+                group_state.my_roster_idx(),
+                my_roster_idx
+            );
+        }
+        let keychain = AppKeyChain::decode_snapshot(&snapshot.keychain)?;
+
+        self.group_state = group_state;
+        self.keychain = keychain;
+        self.roster = snapshot.roster.clone();
+
+        Ok(())
+    }
+}
+
+/// Encoded `GroupState` and `AppKeyChain` including their secrets, and the roster.
+/// This must be sealed before it leaves the enclave.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct GroupKeySnapshot {
+    group_state: Vec<u8>,
+    keychain: Vec<u8>,
+    roster: Vec<EnclaveAddress>,
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use anonify_treekem::{handshake::PathSecretKVS, init_path_secret_kvs};
+
+    pub fn test_reject_handshake_signed_by_other_enclave() {
+        let mut kvs = PathSecretKVS::new();
+        init_path_secret_kvs(&mut kvs, 3, 3);
+        let mut group_key = GroupKey::new(PathSecretRequest::Local(kvs)).unwrap();
+
+        // A join handshake must be signed by the joining enclave.
+        let handshake = group_key.create_join_handshake().unwrap();
+        assert!(group_key.process_join_handshake(0, [1u8; 20], &handshake, [2u8; 20], [1u8; 20]).is_err());
+        group_key.process_join_handshake(0, [1u8; 20], &handshake, [1u8; 20], [1u8; 20]).unwrap();
+
+        // A handshake must be signed by the enclave registered at the sender's roster index.
+        let handshake = group_key.create_handshake().unwrap();
+        assert!(group_key.process_handshake(&handshake, [2u8; 20]).is_err());
+        group_key.process_handshake(&handshake, [1u8; 20]).unwrap();
     }
 }
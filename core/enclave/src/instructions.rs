@@ -3,6 +3,7 @@ use anonify_app_preluder::{CIPHERTEXT_SIZE, Ciphertext, CallKind, MAX_MEM_SIZE,
 use anonify_common::{UserAddress, AccessRight};
 use anonify_runtime::{UpdatedState, State, StateType};
 use codec::{Encode, Decode};
+use anyhow::anyhow;
 use crate::{
     error::Result,
     group_key::GroupKey,
@@ -30,14 +31,22 @@ impl Instructions {
         // Add padding to fix the ciphertext size of all state types.
         // The padding works for fixing the ciphertext size so that
         // other people cannot distinguish what state is encrypted based on the size.
-        fn append_padding(buf: &mut Vec<u8>) {
-            let padding_size = MAX_MEM_SIZE - buf.len();
+        // An instruction larger than the ciphertext can hold is rejected.
+        fn append_padding(buf: &mut Vec<u8>) -> Result<()> {
+            let padding_size = MAX_MEM_SIZE.checked_sub(buf.len())
+                .ok_or_else(|| anyhow!(
+                    "The encoded instruction of {} bytes exceeds the max size {}",
+                    buf.len(),
+                    MAX_MEM_SIZE
+                ))?;
             let mut padding = vec![0u8; padding_size];
             buf.extend_from_slice(&mut padding);
+
+            Ok(())
         }
 
         let mut buf = self.encode();
-        append_padding(&mut buf);
+        append_padding(&mut buf)?;
         key.encrypt(buf).map_err(Into::into)
     }
 
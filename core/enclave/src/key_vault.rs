@@ -0,0 +1,184 @@
+//! A client of a remote key vault, which backs up path secrets so that they can be recovered after restarts.
+//! Path secrets are sealed before they are sent, so the key vault only sees opaque blobs.
+//! Requests are signed with `VaultSigningKey`, and the key vault keeps the blobs of each signer apart,
+//! so no one else can store a blob which would be returned to the enclave.
+
+use std::{
+    prelude::v1::*,
+    fmt,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+use anonify_common::Keccak256;
+use anonify_treekem::{
+    PathSecret,
+    handshake::{AccessKey, KeyVault},
+};
+use anyhow::anyhow;
+use codec::Encode;
+use serde_json::{json, Value};
+use crate::{
+    crypto::VaultSigningKey,
+    error::Result,
+    sealing::Sealer,
+};
+
+pub const PATH_SECRETS_PATH: &str = "/api/v1/path_secrets";
+const SIGNING_DOMAIN: &[u8] = b"anonify key vault";
+const MAX_RESPONSE_HEADERS: usize = 32;
+/// Requests are sent while the group key is locked, so an unresponsive key vault must not block it for long.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A key vault served over HTTP at `addr`, such as `anonify-key-vault`.
+pub struct RemoteKeyVault<S: Sealer> {
+    addr: String,
+    signing_key: VaultSigningKey,
+    sealer: S,
+}
+
+impl<S: Sealer> fmt::Debug for RemoteKeyVault<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RemoteKeyVault")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+impl<S: Sealer> RemoteKeyVault<S> {
+    pub fn new(addr: String, signing_key: VaultSigningKey, sealer: S) -> Self {
+        RemoteKeyVault { addr, signing_key, sealer }
+    }
+
+    fn request(&self, access_key: AccessKey, path_secret: &PathSecret) -> Result<PathSecret> {
+        let sealed = seal_path_secret(&self.sealer, access_key, path_secret)?;
+        let sig = self.signing_key.sign(&signing_message(access_key, &sealed))?;
+
+        let body = json!({
+            "pubkey": hex::encode(&self.signing_key.public_key().serialize()[..]),
+            "roster_idx": access_key.roster_idx(),
+            "epoch": access_key.epoch(),
+            "sealed": hex::encode(&sealed),
+            "signature": hex::encode(&sig.serialize()[..]),
+        });
+        let res = post_json(&self.addr, PATH_SECRETS_PATH, &body)?;
+        let stored = res["sealed"].as_str()
+            .ok_or(anyhow!("The key vault responded without a sealed path secret"))?;
+
+        unseal_path_secret(&self.sealer, access_key, &hex::decode(stored)?)
+    }
+}
+
+impl<S: Sealer> KeyVault for RemoteKeyVault<S>
+where
+    S: Send + Sync,
+{
+    fn get_or_store(&self, access_key: AccessKey, path_secret: PathSecret) -> anyhow::Result<PathSecret> {
+        self.request(access_key, &path_secret)
+            .map_err(|e| anyhow!("Failed to request a path secret to the key vault at {}: {}", self.addr, e))
+    }
+}
+
+/// The access key is sealed along with the path secret,
+/// so that the key vault can't return a path secret stored with another access key.
+pub fn seal_path_secret<S: Sealer>(sealer: &S, access_key: AccessKey, path_secret: &PathSecret) -> Result<Vec<u8>> {
+    let mut plaintext = access_key.encode();
+    plaintext.extend_from_slice(path_secret.as_bytes());
+    sealer.seal(&plaintext)
+}
+
+pub fn unseal_path_secret<S: Sealer>(sealer: &S, access_key: AccessKey, sealed: &[u8]) -> Result<PathSecret> {
+    let plaintext = sealer.unseal(sealed)?;
+    let encoded_key = access_key.encode();
+    if plaintext.len() <= encoded_key.len() || !plaintext.starts_with(&encoded_key) {
+        return Err(anyhow!("The sealed path secret isn't stored with {:?}", access_key).into());
+    }
+
+    Ok(PathSecret::from(&plaintext[encoded_key.len()..]))
+}
+
+/// The message signed in a request, which the key vault verifies with the public key in the request.
+/// 17bytes: "anonify key vault"
+/// 4bytes: roster_idx in little endian
+/// 4bytes: epoch in little endian
+/// the rest: the sealed path secret
+fn signing_message(access_key: AccessKey, sealed: &[u8]) -> [u8; 32] {
+    let mut preimage = SIGNING_DOMAIN.to_vec();
+    preimage.extend_from_slice(&access_key.encode());
+    preimage.extend_from_slice(sealed);
+    preimage.keccak256()
+}
+
+/// Send a JSON body to `addr` with HTTP/1.1 and parse the JSON body of the response.
+fn post_json(addr: &str, path: &str, body: &Value) -> Result<Value> {
+    let body = serde_json::to_vec(body).map_err(|e| anyhow!("{}", e))?;
+    let mut stream = connect(addr)?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path, addr, body.len(),
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+
+    let mut buf = vec![];
+    stream.read_to_end(&mut buf)?;
+
+    let mut headers = [httparse::EMPTY_HEADER; MAX_RESPONSE_HEADERS];
+    let mut res = httparse::Response::new(&mut headers);
+    let offset = match res.parse(&buf).map_err(|e| anyhow!("Invalid HTTP response: {:?}", e))? {
+        httparse::Status::Complete(offset) => offset,
+        httparse::Status::Partial => return Err(anyhow!("Incomplete HTTP response").into()),
+    };
+    let status = res.code.unwrap_or_default();
+    if status != 200 {
+        return Err(anyhow!(
+            "The key vault responded with status {}: {}", status, String::from_utf8_lossy(&buf[offset..])
+        ).into());
+    }
+
+    serde_json::from_slice(&buf[offset..]).map_err(|e| anyhow!("Invalid JSON response: {}", e).into())
+}
+
+/// Connect to `addr` with `CONNECT_TIMEOUT`, trying each resolved address in turn,
+/// and let reads and writes on the stream fail after `IO_TIMEOUT`.
+fn connect(addr: &str) -> Result<TcpStream> {
+    let mut last_err = None;
+    for socket_addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(match last_err {
+        Some(e) => anyhow!("Failed to connect to the key vault at {}: {}", addr, e).into(),
+        None => anyhow!("The key vault address {} is not resolved", addr).into(),
+    })
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use crate::sealing::{AesSealer, AES_SEALING_KEY_SIZE};
+
+    pub fn test_sealed_path_secret_is_bound_to_access_key() {
+        let sealer = AesSealer::new([1u8; AES_SEALING_KEY_SIZE]);
+        let access_key = AccessKey::new(1, 2);
+        let path_secret = PathSecret::new_from_random_sgx();
+
+        let sealed = seal_path_secret(&sealer, access_key, &path_secret).unwrap();
+        let unsealed = unseal_path_secret(&sealer, access_key, &sealed).unwrap();
+        assert_eq!(unsealed.as_bytes(), path_secret.as_bytes());
+
+        // A key vault can't pass off a path secret of another epoch or member.
+        assert!(unseal_path_secret(&sealer, AccessKey::new(1, 3), &sealed).is_err());
+        assert!(unseal_path_secret(&sealer, AccessKey::new(2, 2), &sealed).is_err());
+        assert_ne!(signing_message(access_key, &sealed), signing_message(AccessKey::new(1, 3), &sealed));
+    }
+}
@@ -3,17 +3,16 @@ use std::{
     collections::HashMap,
     sync::{SgxRwLock, Arc},
 };
-use ed25519_dalek::{PublicKey, Signature};
 use anonify_common::{
     UserAddress,
-    kvs::*,
     Hash256, Sha256,
 };
 use anonify_runtime::{State, MemId, UpdatedState};
-use codec::Encode;
+use anyhow::anyhow;
+use codec::{Encode, Decode};
 use crate::error::Result;
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
 pub struct DBKey((UserAddress, MemId));
 
 // TODO: UserAddress+MemId is not sufficient size for hash digest in terms of collision resistance.
@@ -21,6 +20,14 @@ impl DBKey {
     pub fn new(addr: UserAddress, mem_id: MemId) -> Self {
         DBKey((addr, mem_id))
     }
+
+    pub fn address(&self) -> &UserAddress {
+        &(self.0).0
+    }
+
+    pub fn mem_id(&self) -> MemId {
+        (self.0).1
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -56,41 +63,185 @@ impl<S: State> EnclaveDB<S> {
         let key = DBKey::new(address, mem_id);
         tmp.remove(&key);
     }
+
+    /// Apply all operations in the transaction under a single write lock.
+    /// If any operation fails, the already applied ones are rolled back
+    /// so that the database is left unchanged.
+    /// Returns the state root of the database after the transaction.
+    pub fn write(&self, tx: EnclaveDBTx<S>) -> Result<Sha256> {
+        let mut db = self.0.write().unwrap();
+        // Previous values of the touched keys, to be restored on failure.
+        let mut undo_log: Vec<(DBKey, Option<S>)> = Vec::with_capacity(tx.len());
+
+        for op in tx.0 {
+            let res = match op {
+                EnclaveDBOp::Insert { key, state } => {
+                    let prev = db.insert(key.clone(), state);
+                    undo_log.push((key, prev));
+                    Ok(())
+                }
+                EnclaveDBOp::Delete { key } => match db.remove(&key) {
+                    Some(prev) => {
+                        undo_log.push((key, Some(prev)));
+                        Ok(())
+                    }
+                    None => Err(anyhow!("Failed to delete: state of {:?} does not exist", key)),
+                },
+            };
+
+            if let Err(e) = res {
+                for (key, prev) in undo_log.into_iter().rev() {
+                    match prev {
+                        Some(state) => db.insert(key, state),
+                        None => db.remove(&key),
+                    };
+                }
+                return Err(e.into());
+            }
+        }
+
+        Ok(state_root(&db))
+    }
+
+    /// Digest of all states in the database, used to check consistency across nodes.
+    pub fn state_root(&self) -> Sha256 {
+        state_root(&self.0.read().unwrap())
+    }
+
+    /// All entries sorted by key, e.g. to take a snapshot of the database.
+    pub fn entries(&self) -> Vec<(DBKey, S)> {
+        let mut entries: Vec<(DBKey, S)> = self.0.read().unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Replace all entries with the given ones under a single write lock.
+    pub fn restore(&self, entries: Vec<(DBKey, S)>) {
+        let mut db = self.0.write().unwrap();
+        db.clear();
+        db.extend(entries);
+    }
 }
 
-/// Batches a sequence of put/delete operations for efficiency.
-/// These operations are protected from signature verifications.
-#[derive(Default, Clone, PartialEq)]
-pub struct EnclaveDBTx(DBTx);
+/// Hash all entries in a deterministic order regardless of the insertion order.
+fn state_root<S: State>(db: &HashMap<DBKey, S>) -> Sha256 {
+    let mut entries: Vec<(&DBKey, &S)> = db.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut buf = vec![];
+    for (key, state) in entries {
+        buf.extend_from_slice(key.address().as_bytes());
+        buf.extend_from_slice(&key.mem_id().as_raw().to_le_bytes());
+        buf.extend_from_slice(&state.encode());
+    }
+
+    Sha256::hash(&buf)
+}
+
+/// Database operation on `EnclaveDB`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnclaveDBOp<S: State> {
+    Insert {
+        key: DBKey,
+        state: S,
+    },
+    Delete {
+        key: DBKey,
+    },
+}
 
-impl EnclaveDBTx {
+/// Batches a sequence of put/delete operations, which are applied to `EnclaveDB` atomically.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnclaveDBTx<S: State>(Vec<EnclaveDBOp<S>>);
+
+impl<S: State> Default for EnclaveDBTx<S> {
+    fn default() -> Self {
+        EnclaveDBTx(vec![])
+    }
+}
+
+impl<S: State> EnclaveDBTx<S> {
     pub fn new() -> Self {
-        EnclaveDBTx(DBTx::new())
+        EnclaveDBTx::default()
     }
 
-    /// Put instruction is added to a transaction only if the verification of provided signature returns true.
-    pub fn put(
-        &mut self,
-        user_address: &UserAddress,
-        msg: &[u8],
-    ) {
-        self.0.put(user_address.as_bytes(), msg);
+    /// Build a transaction from all updated states produced by one instruction.
+    pub fn from_updated_states(updated_states: Vec<UpdatedState<S>>) -> Self {
+        let mut tx = EnclaveDBTx::new();
+        for s in updated_states {
+            tx.put(s.address, s.mem_id, s.state);
+        }
+        tx
     }
 
-    /// Delete instruction is added to a transaction only if the verification of provided signature returns true.
-    pub fn delete(
-        &mut self,
-        msg: &[u8],
-        sig: &Signature,
-        pubkey: &PublicKey,
-    ) -> Result<()> {
-        let key = UserAddress::from_sig(&msg, &sig, &pubkey)?;
-        self.0.delete(key.as_bytes());
+    pub fn put(&mut self, address: UserAddress, mem_id: MemId, state: S) {
+        let key = DBKey::new(address, mem_id);
+        self.0.push(EnclaveDBOp::Insert { key, state });
+    }
 
-        Ok(())
+    /// Deleting a state which does not exist makes the whole transaction fail.
+    pub fn delete(&mut self, address: UserAddress, mem_id: MemId) {
+        let key = DBKey::new(address, mem_id);
+        self.0.push(EnclaveDBOp::Delete { key });
     }
 
-    pub(crate) fn into_inner(self) -> DBTx {
-        self.0
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use anonify_runtime::U64;
+
+    pub fn test_write_tx_atomically() {
+        let addr1 = UserAddress::from_array([1; 20]);
+        let addr2 = UserAddress::from_array([2; 20]);
+        let mem_id = MemId::from_raw(0);
+
+        let db1 = EnclaveDB::<U64>::new();
+        let mut tx = EnclaveDBTx::new();
+        tx.put(addr1, mem_id, U64::from_raw(10));
+        tx.put(addr2, mem_id, U64::from_raw(20));
+        let root1 = db1.write(tx).unwrap();
+        assert_eq!(db1.get(addr1, mem_id), U64::from_raw(10));
+        assert_eq!(db1.get(addr2, mem_id), U64::from_raw(20));
+        assert_eq!(root1, db1.state_root());
+
+        // The state root does not depend on the order of operations.
+        let db2 = EnclaveDB::<U64>::new();
+        let mut tx = EnclaveDBTx::new();
+        tx.put(addr2, mem_id, U64::from_raw(20));
+        tx.put(addr1, mem_id, U64::from_raw(10));
+        assert_eq!(db2.write(tx).unwrap(), root1);
+    }
+
+    pub fn test_rollback_failed_tx() {
+        let addr1 = UserAddress::from_array([1; 20]);
+        let addr2 = UserAddress::from_array([2; 20]);
+        let mem_id = MemId::from_raw(0);
+
+        let db = EnclaveDB::<U64>::new();
+        db.insert(addr1, mem_id, U64::from_raw(10));
+        let root = db.state_root();
+
+        let mut tx = EnclaveDBTx::new();
+        tx.put(addr1, mem_id, U64::from_raw(5));
+        tx.put(addr2, mem_id, U64::from_raw(5));
+        // addr2 does not have any other state, so the transaction fails.
+        tx.delete(addr2, MemId::from_raw(1));
+        assert!(db.write(tx).is_err());
+
+        assert_eq!(db.get(addr1, mem_id), U64::from_raw(10));
+        assert_eq!(db.get(addr2, mem_id), U64::default());
+        assert_eq!(db.state_root(), root);
     }
 }
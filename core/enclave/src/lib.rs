@@ -22,5 +22,8 @@ mod config;
 mod group_key;
 mod notify;
 mod instructions;
+mod sealing;
+mod snapshot;
+mod key_vault;
 
 use bridges::ocalls;
\ No newline at end of file
@@ -5,7 +5,8 @@ use std::{
 };
 use anonify_common::UserAddress;
 use anonify_runtime::{UpdatedState, StateType};
-use anonify_types::RawUpdatedState;
+use anonify_types::RawUpdatedStates;
+use codec::Encode;
 use crate::{
     error::Result,
     bridges::ocalls::save_to_host_memory,
@@ -34,12 +35,12 @@ impl Notifier {
     }
 }
 
-pub fn updated_state_into_raw(updated_state: UpdatedState<StateType>) -> Result<RawUpdatedState> {
-    let state = save_to_host_memory(updated_state.state.as_bytes())? as *const u8;
+pub fn updated_states_into_raw(updated_states: Vec<UpdatedState<StateType>>) -> Result<RawUpdatedStates> {
+    if updated_states.is_empty() {
+        return Ok(RawUpdatedStates::default());
+    }
+
+    let states = save_to_host_memory(&updated_states.encode())? as *const u8;
 
-    Ok(RawUpdatedState {
-        address: updated_state.address.into_array(),
-        mem_id: updated_state.mem_id.as_raw(),
-        state,
-    })
+    Ok(RawUpdatedStates { states })
 }
@@ -0,0 +1,141 @@
+//! Sealing backends to encrypt data persisted outside of the enclave.
+
+use std::prelude::v1::*;
+use sgx_types::sgx_sealed_data_t;
+use sgx_tseal::SgxSealedData;
+use ring::aead::{LessSafeKey, UnboundKey, Nonce, Aad, AES_256_GCM, NONCE_LEN};
+use anonify_common::sgx_rand_assign;
+use anyhow::anyhow;
+use crate::error::Result;
+
+const SEALING_AAD: &[u8] = b"anonify sealed data";
+pub const AES_SEALING_KEY_SIZE: usize = 32;
+
+/// Encrypts data so that only the enclave can decrypt it after restarts.
+pub trait Sealer {
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    fn unseal(&self, sealed: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Seal data with a key derived by SGX from the enclave signer's identity,
+/// so it can be unsealed after the enclave is restarted on the same platform.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SgxSealer;
+
+impl Sealer for SgxSealer {
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let sealed_data = SgxSealedData::<[u8]>::seal_data(SEALING_AAD, plaintext)?;
+        let sealed_len = SgxSealedData::<[u8]>::calc_raw_sealed_data_size(
+            SEALING_AAD.len() as u32,
+            plaintext.len() as u32,
+        );
+        if sealed_len == u32::max_value() {
+            return Err(anyhow!("Too large data to seal: {} bytes", plaintext.len()).into());
+        }
+
+        let mut buf = vec![0u8; sealed_len as usize];
+        unsafe {
+            sealed_data.to_raw_sealed_data_t(buf.as_mut_ptr() as *mut sgx_sealed_data_t, sealed_len)
+        }
+        .ok_or(anyhow!("Failed to convert into raw sealed data"))?;
+
+        Ok(buf)
+    }
+
+    fn unseal(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        // The raw sealed data is read in place, so copy it to keep the input untouched.
+        let mut buf = sealed.to_vec();
+        let sealed_data = unsafe {
+            SgxSealedData::<[u8]>::from_raw_sealed_data_t(
+                buf.as_mut_ptr() as *mut sgx_sealed_data_t,
+                buf.len() as u32,
+            )
+        }
+        .ok_or(anyhow!("Invalid sealed data"))?;
+
+        let unsealed_data = sealed_data.unseal_data()?;
+        Ok(unsealed_data.get_decrypt_txt().to_vec())
+    }
+}
+
+/// Seal data with AES-256-GCM under a given key, without relying on SGX sealing keys.
+/// This is meant for simulation and tests; a sealed blob is `nonce || ciphertext || tag`.
+#[derive(Debug, Clone)]
+pub struct AesSealer {
+    key: [u8; AES_SEALING_KEY_SIZE],
+}
+
+impl AesSealer {
+    pub fn new(key: [u8; AES_SEALING_KEY_SIZE]) -> Self {
+        AesSealer { key }
+    }
+
+    fn sealing_key(&self) -> Result<LessSafeKey> {
+        let ub_key = UnboundKey::new(&AES_256_GCM, &self.key)?;
+        Ok(LessSafeKey::new(ub_key))
+    }
+}
+
+impl Sealer for AesSealer {
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        sgx_rand_assign(&mut nonce)?;
+
+        let mut in_out = plaintext.to_vec();
+        self.sealing_key()?.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(SEALING_AAD),
+            &mut in_out,
+        )?;
+
+        let mut buf = Vec::with_capacity(NONCE_LEN + in_out.len());
+        buf.extend_from_slice(&nonce);
+        buf.extend_from_slice(&in_out);
+        Ok(buf)
+    }
+
+    fn unseal(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN + AES_256_GCM.tag_len() {
+            return Err(anyhow!("Sealed data is too short: {} bytes", sealed.len()).into());
+        }
+
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce.copy_from_slice(&sealed[..NONCE_LEN]);
+        let mut in_out = sealed[NONCE_LEN..].to_vec();
+        let plaintext = self.sealing_key()?.open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(SEALING_AAD),
+            &mut in_out,
+        )?;
+
+        Ok(plaintext.to_vec())
+    }
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+
+    pub fn test_aes_sealer_roundtrip() {
+        let sealer = AesSealer::new([1u8; AES_SEALING_KEY_SIZE]);
+        let plaintext = b"aes sealer roundtrip test";
+
+        let sealed = sealer.seal(plaintext).unwrap();
+        assert_ne!(&sealed[NONCE_LEN..NONCE_LEN + plaintext.len()], &plaintext[..]);
+        assert_eq!(sealer.unseal(&sealed).unwrap(), plaintext.to_vec());
+
+        let other_sealer = AesSealer::new([2u8; AES_SEALING_KEY_SIZE]);
+        assert!(other_sealer.unseal(&sealed).is_err());
+    }
+
+    pub fn test_aes_sealer_detects_tampering() {
+        let sealer = AesSealer::new([1u8; AES_SEALING_KEY_SIZE]);
+        let mut sealed = sealer.seal(b"aes sealer tampering test").unwrap();
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(sealer.unseal(&sealed).is_err());
+        assert!(sealer.unseal(&sealed[..NONCE_LEN]).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::prelude::v1::*;
+use anonify_runtime::State;
+use codec::{Encode, Decode};
+use secp256k1::util::SECRET_KEY_SIZE;
+use crate::{
+    kvs::DBKey,
+    group_key::GroupKeySnapshot,
+    sealing::Sealer,
+    error::Result,
+};
+
+/// Enclave's whole states to be persisted across restarts.
+/// It is sealed before it is written to the host.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct Snapshot<S: State> {
+    /// The latest block number whose logs have been inserted into the enclave.
+    pub block_num: u64,
+    /// Hash of the block, used to find the snapshot to roll back to on a chain reorganization.
+    pub block_hash: [u8; 32],
+    pub states: Vec<(DBKey, S)>,
+    pub group_key: GroupKeySnapshot,
+    /// Secret of the enclave identity key, whose address is registered in the roster and the contract.
+    pub identity_key: [u8; SECRET_KEY_SIZE],
+}
+
+impl<S: State> Snapshot<S> {
+    pub fn seal<T: Sealer>(&self, sealer: &T) -> Result<Vec<u8>> {
+        sealer.seal(&self.encode())
+    }
+
+    pub fn unseal<T: Sealer>(sealer: &T, sealed: &[u8]) -> Result<Self> {
+        let plaintext = sealer.unseal(sealed)?;
+        Snapshot::decode(&mut &plaintext[..]).map_err(Into::into)
+    }
+}
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use anonify_common::{UserAddress, Sha256, Hash256};
+    use anonify_runtime::{U64, MemId};
+    use anonify_treekem::{
+        handshake::{PathSecretRequest, PathSecretKVS},
+        init_path_secret_kvs,
+    };
+    use crate::{
+        kvs::{EnclaveDB, EnclaveDBTx},
+        group_key::GroupKey,
+        sealing::{AesSealer, AES_SEALING_KEY_SIZE},
+        context::EnclaveContext,
+        crypto::recover_address,
+        config::TEST_SPID,
+    };
+
+    fn new_group_key() -> GroupKey {
+        let mut kvs = PathSecretKVS::new();
+        init_path_secret_kvs(&mut kvs, 3, 3);
+        GroupKey::new(PathSecretRequest::Local(kvs)).unwrap()
+    }
+
+    pub fn test_restore_sealed_snapshot() {
+        let mem_id = MemId::from_raw(0);
+        let db = EnclaveDB::<U64>::new();
+        let mut tx = EnclaveDBTx::new();
+        tx.put(UserAddress::from_array([1; 20]), mem_id, U64::from_raw(10));
+        tx.put(UserAddress::from_array([2; 20]), mem_id, U64::from_raw(20));
+        db.write(tx).unwrap();
+
+        let mut group_key = new_group_key();
+        let handshake = group_key.create_join_handshake().unwrap();
+        group_key.process_join_handshake(0, [1u8; 20], &handshake, [1u8; 20], [1u8; 20]).unwrap();
+
+        let snapshot = Snapshot {
+            block_num: 42,
+            block_hash: [42u8; 32],
+            states: db.entries(),
+            group_key: group_key.snapshot(),
+            identity_key: [1u8; SECRET_KEY_SIZE],
+        };
+        let sealer = AesSealer::new([1u8; AES_SEALING_KEY_SIZE]);
+        let sealed = snapshot.seal(&sealer).unwrap();
+
+        // Restore into a freshly started enclave.
+        let restored = Snapshot::<U64>::unseal(&sealer, &sealed).unwrap();
+        let new_db = EnclaveDB::<U64>::new();
+        new_db.restore(restored.states);
+        let mut restored_group_key = new_group_key();
+        restored_group_key.restore(&restored.group_key).unwrap();
+
+        assert_eq!(restored.block_num, 42);
+        assert_eq!(restored.block_hash, [42u8; 32]);
+        assert_eq!(new_db.entries(), db.entries());
+        assert_eq!(new_db.state_root(), db.state_root());
+        assert_eq!(restored_group_key.roster(), &[[1u8; 20]][..]);
+        assert_eq!(restored.identity_key, [1u8; SECRET_KEY_SIZE]);
+
+        // The restored group key shares the application secrets with the original one.
+        let ciphertext = group_key.encrypt(b"snapshot test".to_vec()).unwrap();
+        assert_eq!(restored_group_key.decrypt(&ciphertext).unwrap(), Some(b"snapshot test".to_vec()));
+
+        // A snapshot taken by another member cannot be restored.
+        let mut other_group_key = new_group_key();
+        other_group_key.process_join_handshake(0, [1u8; 20], &handshake, [1u8; 20], [2u8; 20]).unwrap();
+        let other_handshake = other_group_key.create_join_handshake().unwrap();
+        other_group_key.process_join_handshake(1, [2u8; 20], &other_handshake, [2u8; 20], [2u8; 20]).unwrap();
+        assert!(other_group_key.restore(&restored.group_key).is_err());
+        // A snapshot sealed with another key cannot be unsealed.
+        let other_sealer = AesSealer::new([2u8; AES_SEALING_KEY_SIZE]);
+        assert!(Snapshot::<U64>::unseal(&other_sealer, &sealed).is_err());
+    }
+
+    pub fn test_keep_enclave_address_after_restore() {
+        let ctx = EnclaveContext::new(TEST_SPID).unwrap();
+        let sealer = AesSealer::new([1u8; AES_SEALING_KEY_SIZE]);
+        let sealed = ctx.seal_snapshot(&sealer, 42, [42u8; 32]).unwrap();
+
+        // A restarted enclave has a new identity key until it restores the snapshot.
+        let restarted = EnclaveContext::new(TEST_SPID).unwrap();
+        assert_ne!(restarted.enclave_address(), ctx.enclave_address());
+        assert_eq!(restarted.restore_snapshot(&sealer, &sealed).unwrap(), (42, [42u8; 32]));
+        assert_eq!(restarted.enclave_address(), ctx.enclave_address());
+
+        // Handshakes signed after the restart are recovered to the registered address.
+        let handshake = b"handshake after restart";
+        let sig = restarted.sign_handshake(handshake).unwrap();
+        assert_eq!(recover_address(Sha256::hash(handshake).as_bytes(), &sig).unwrap(), ctx.enclave_address());
+    }
+}
@@ -28,6 +28,8 @@ pub struct JoinGroupTx {
     report: AttestationReport,
     report_sig: ReportSig,
     handshake: HandshakeParams,
+    enclave_sig: Vec<u8>,
+    enclave_address: [u8; 20],
 }
 
 impl EnclaveTx for JoinGroupTx {
@@ -37,21 +39,33 @@ impl EnclaveTx for JoinGroupTx {
         let report = save_to_host_memory(&self.report.as_bytes())? as *const u8;
         let report_sig = save_to_host_memory(&self.report_sig.as_bytes())? as *const u8;
         let handshake = save_to_host_memory(&self.handshake.encode())? as *const u8;
+        let enclave_sig = save_to_host_memory(&self.enclave_sig)? as *const u8;
 
         Ok(RawJoinGroupTx {
             report,
             report_sig,
             handshake,
+            enclave_sig,
+            roster_idx: self.handshake.roster_idx,
+            enclave_address: self.enclave_address,
         })
     }
 }
 
 impl JoinGroupTx {
-    pub fn new(report: AttestationReport, report_sig: ReportSig, handshake: HandshakeParams) -> Self {
+    pub fn new(
+        report: AttestationReport,
+        report_sig: ReportSig,
+        handshake: HandshakeParams,
+        enclave_sig: Vec<u8>,
+        enclave_address: [u8; 20],
+    ) -> Self {
         JoinGroupTx {
             report,
             report_sig,
             handshake,
+            enclave_sig,
+            enclave_address,
         }
     }
 
@@ -63,12 +77,15 @@ impl JoinGroupTx {
         let quote = ctx.quote()?;
         let (report, report_sig) = RAService::remote_attestation(ias_url, ias_api_key, &quote)?;
         let group_key = ctx.group_key.read().unwrap();
-        let handshake = group_key.create_handshake()?;
+        let handshake = group_key.create_join_handshake()?;
+        let enclave_sig = ctx.sign_handshake(&handshake.encode())?.to_vec();
 
         Ok(JoinGroupTx {
             report,
             report_sig,
             handshake,
+            enclave_sig,
+            enclave_address: ctx.enclave_address(),
         })
     }
 }
@@ -127,6 +144,7 @@ impl InstructionTx {
 #[derive(Debug, Clone)]
 pub struct HandshakeTx {
     handshake: HandshakeParams,
+    enclave_sig: Vec<u8>,
 }
 
 impl EnclaveTx for HandshakeTx {
@@ -134,14 +152,15 @@ impl EnclaveTx for HandshakeTx {
 
     fn into_raw(self) -> Result<Self::R> {
         let handshake = save_to_host_memory(&self.handshake.encode())? as *const u8;
+        let enclave_sig = save_to_host_memory(&self.enclave_sig)? as *const u8;
 
-        Ok(RawHandshakeTx { handshake })
+        Ok(RawHandshakeTx { handshake, enclave_sig })
     }
 }
 
 impl HandshakeTx {
-    pub fn new(handshake: HandshakeParams) -> Self {
-        HandshakeTx { handshake }
+    pub fn new(handshake: HandshakeParams, enclave_sig: Vec<u8>) -> Self {
+        HandshakeTx { handshake, enclave_sig }
     }
 
     pub fn construct(
@@ -149,7 +168,20 @@ impl HandshakeTx {
     ) -> Result<Self> {
         let group_key = ctx.group_key.read().unwrap();
         let handshake = group_key.create_handshake()?;
+        let enclave_sig = ctx.sign_handshake(&handshake.encode())?.to_vec();
+
+        Ok(HandshakeTx { handshake, enclave_sig })
+    }
+
+    /// Construct a handshake removing the member at `roster_idx` from the group.
+    pub fn construct_remove(
+        ctx: &EnclaveContext<StateType>,
+        roster_idx: usize,
+    ) -> Result<Self> {
+        let group_key = ctx.group_key.read().unwrap();
+        let handshake = group_key.create_remove_handshake(roster_idx)?;
+        let enclave_sig = ctx.sign_handshake(&handshake.encode())?.to_vec();
 
-        Ok(HandshakeTx { handshake })
+        Ok(HandshakeTx { handshake, enclave_sig })
     }
 }
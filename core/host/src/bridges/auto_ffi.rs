@@ -5,31 +5,50 @@ use anonify_types::*;
 use sgx_types::*;
 
 extern "C" {
-    pub fn ecall_insert_ciphertext(
+    pub fn ecall_insert_events(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
-        ciphertexts: *mut u8,
-        ciphertexts_len: usize,
-        enclave_kv: *mut RawUpdatedState,
+        events: *mut u8,
+        events_len: usize,
+        updated_states: *mut RawUpdatedStates,
     ) -> sgx_status_t;
 }
 extern "C" {
-    pub fn ecall_insert_handshake(
+    pub fn ecall_get_state(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
-        handshake: *mut u8,
-        handshake_len: usize,
+        sig: *mut [u8; 64usize],
+        pubkey: *mut [u8; 32usize],
+        challenge: *mut [u8; 32usize],
+        mem_id: u32,
+        state: *mut EnclaveState,
     ) -> sgx_status_t;
 }
 extern "C" {
-    pub fn ecall_get_state(
+    pub fn ecall_query(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
         sig: *mut [u8; 64usize],
         pubkey: *mut [u8; 32usize],
         challenge: *mut [u8; 32usize],
-        mem_id: u32,
-        state: *mut EnclaveState,
+        params: *mut u8,
+        params_len: usize,
+        view_id: u32,
+        result: *mut EnclaveState,
+    ) -> sgx_status_t;
+}
+extern "C" {
+    pub fn ecall_get_state_root(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        state_root: *mut [u8; 32usize],
+    ) -> sgx_status_t;
+}
+extern "C" {
+    pub fn ecall_get_roster(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        roster: *mut EnclaveState,
     ) -> sgx_status_t;
 }
 extern "C" {
@@ -46,6 +65,32 @@ extern "C" {
         result: *mut RawInstructionTx,
     ) -> sgx_status_t;
 }
+extern "C" {
+    pub fn ecall_seal_snapshot(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        block_num: u64,
+        block_hash: *const [u8; 32usize],
+        sealed: *mut EnclaveState,
+    ) -> sgx_status_t;
+}
+extern "C" {
+    pub fn ecall_restore_snapshot(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        sealed: *mut u8,
+        sealed_len: usize,
+        block_num: *mut u64,
+        block_hash: *mut [u8; 32usize],
+    ) -> sgx_status_t;
+}
+extern "C" {
+    pub fn ecall_get_mr_enclave(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        mr_enclave: *mut [u8; 32usize],
+    ) -> sgx_status_t;
+}
 extern "C" {
     pub fn ecall_join_group(
         eid: sgx_enclave_id_t,
@@ -60,6 +105,14 @@ extern "C" {
         result: *mut RawHandshakeTx,
     ) -> sgx_status_t;
 }
+extern "C" {
+    pub fn ecall_remove_member(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        roster_idx: u32,
+        result: *mut RawHandshakeTx,
+    ) -> sgx_status_t;
+}
 extern "C" {
     pub fn ecall_register_notification(
         eid: sgx_enclave_id_t,
@@ -1,101 +1,72 @@
 use std::boxed::Box;
 use sgx_types::*;
-use anonify_types::{traits::SliceCPtr, EnclaveState, RawJoinGroupTx, RawInstructionTx, RawHandshakeTx, RawUpdatedState};
-use anonify_common::{AccessRight, IntoVec};
-use anonify_app_preluder::{mem_name_to_id, CIPHERTEXT_SIZE};
-use anonify_runtime::{traits::State, UpdatedState};
+use anonify_types::{traits::SliceCPtr, EnclaveState, RawJoinGroupTx, RawInstructionTx, RawHandshakeTx, RawUpdatedStates};
+use anonify_common::{AccessRight, EnclaveEvent, IntoVec};
+use anonify_app_preluder::{mem_name_to_id, view_name_to_id};
+use anonify_runtime::{traits::State, UpdatedState, updated_states_from_raw};
 use anonify_bc_connector::{
     eventdb::InnerEnclaveLog,
     utils::StateInfo,
     error::{HostError, Result},
 };
 use ed25519_dalek::{Signature, PublicKey};
+use codec::{Encode, Decode};
+use anyhow::anyhow;
 use log::debug;
-use crate::auto_ffi::*;
+use crate::{auto_ffi::*, init_enclave::SnapshotDir};
 
+/// Insert logs into the enclave, and save a snapshot taken after inserting them to `snapshot_dir`.
 pub(crate) fn insert_logs<S: State>(
     eid: sgx_enclave_id_t,
     enclave_log: &InnerEnclaveLog,
+    snapshot_dir: &SnapshotDir,
 ) -> Result<Option<Vec<UpdatedState<S>>>> {
-    if enclave_log.ciphertexts.len() != 0 && enclave_log.handshakes.len() == 0 {
-        insert_ciphertexts(eid, &enclave_log)
-    } else if enclave_log.ciphertexts.len() == 0 && enclave_log.handshakes.len() != 0 {
-        // The size of handshake cannot be calculated in this host directory,
-        // so the ecall_insert_handshake function is repeatedly called over the number of fetched handshakes.
-        for handshake in &enclave_log.handshakes {
-            insert_handshake(eid, handshake)?;
-        }
-
-        Ok(None)
-    } else {
+    if enclave_log.events.is_empty() {
         debug!("No logs to insert into the enclave.");
-        Ok(None)
+        return Ok(None);
     }
 
-}
-/// Insert event logs from blockchain nodes into enclave memory database.
-fn insert_ciphertexts<S: State>(
-    eid: sgx_enclave_id_t,
-    enclave_log: &InnerEnclaveLog,
-) -> Result<Option<Vec<UpdatedState<S>>>> {
-    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
-    let mut acc = vec![];
-
-    for ciphertext in &enclave_log.ciphertexts {
-        let mut raw_updated_state = RawUpdatedState::default();
-        let status = unsafe {
-            ecall_insert_ciphertext(
-                eid,
-                &mut rt,
-                ciphertext.into_vec().as_c_ptr() as *mut u8,
-                CIPHERTEXT_SIZE,
-                &mut raw_updated_state,
-            )
-        };
-
-        if status != sgx_status_t::SGX_SUCCESS {
-            return Err(HostError::Sgx{ status, function: "ecall_insert_ciphertext" }.into());
-        }
-        if rt != sgx_status_t::SGX_SUCCESS {
-            return Err(HostError::Sgx{ status: rt, function: "ecall_insert_ciphertext" }.into());
-        }
-
-        if raw_updated_state != Default::default() {
-            let updated_state = UpdatedState::from(raw_updated_state);
-            acc.push(updated_state)
-        }
-    }
-
-    if acc.is_empty() {
-        return Ok(None)
-    } else {
-        return Ok(Some(acc))
-    }
+    let updated_states = insert_events(eid, &enclave_log.events)?;
+    let block_hash = enclave_log.latest_blc_hash.unwrap_or_default().to_fixed_bytes();
+    let sealed = seal_snapshot(eid, enclave_log.latest_blc_num, block_hash)?;
+    snapshot_dir.save_sealed_snapshot(enclave_log.latest_blc_num, block_hash, &sealed)?;
+
+    Ok(updated_states)
 }
 
-fn insert_handshake(
+/// Insert events from blockchain nodes into enclave memory database.
+/// All events are passed by one ecall so that the enclave processes them in chain order.
+fn insert_events<S: State>(
     eid: sgx_enclave_id_t,
-    handshake: &[u8],
-) -> Result<()> {
+    events: &[EnclaveEvent],
+) -> Result<Option<Vec<UpdatedState<S>>>> {
     let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut raw_updated_states = RawUpdatedStates::default();
+    let encoded_events = events.encode();
 
     let status = unsafe {
-        ecall_insert_handshake(
+        ecall_insert_events(
             eid,
             &mut rt,
-            handshake.as_c_ptr() as *mut u8,
-            handshake.len(),
+            encoded_events.as_c_ptr() as *mut u8,
+            encoded_events.len(),
+            &mut raw_updated_states,
         )
     };
 
     if status != sgx_status_t::SGX_SUCCESS {
-		return Err(HostError::Sgx{ status, function: "ecall_insert_handshake" }.into());
+        return Err(HostError::Sgx{ status, function: "ecall_insert_events" }.into());
     }
     if rt != sgx_status_t::SGX_SUCCESS {
-		return Err(HostError::Sgx{ status: rt, function: "ecall_insert_handshake" }.into());
+        return Err(HostError::Sgx{ status: rt, function: "ecall_insert_events" }.into());
     }
 
-    Ok(())
+    let updated_states = updated_states_from_raw(raw_updated_states)?;
+    if updated_states.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(updated_states))
+    }
 }
 
 /// Get state only if the signature verification returns true.
@@ -107,7 +78,7 @@ pub(crate) fn get_state_from_enclave(
 {
     let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
     let mut state = EnclaveState::default();
-    let mem_id = mem_name_to_id(mem_name).as_raw();
+    let mem_id = mem_name_to_id(mem_name)?.as_raw();
 
     let status = unsafe {
         ecall_get_state(
@@ -131,6 +102,165 @@ pub(crate) fn get_state_from_enclave(
     Ok(state.into_vec())
 }
 
+/// Run a view function in the enclave only if the signature verification returns true.
+pub(crate) fn query_state(
+    eid: sgx_enclave_id_t,
+    access_right: &AccessRight,
+    params: &[u8],
+    view_name: &str,
+) -> Result<Vec<u8>>
+{
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut result = EnclaveState::default();
+    let view_id = view_name_to_id(view_name)?;
+
+    let status = unsafe {
+        ecall_query(
+            eid,
+            &mut rt,
+            access_right.sig().to_bytes().as_ptr() as _,
+            access_right.pubkey().to_bytes().as_ptr() as _,
+            access_right.challenge().as_ptr() as _,
+            params.as_c_ptr() as *mut u8,
+            params.len(),
+            view_id,
+            &mut result,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_query" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_query" }.into());
+    }
+
+    Ok(result.into_vec())
+}
+
+/// Get the digest of all states in the enclave.
+pub(crate) fn get_state_root(eid: sgx_enclave_id_t) -> Result<[u8; 32]> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut state_root = [0u8; 32];
+
+    let status = unsafe {
+        ecall_get_state_root(
+            eid,
+            &mut rt,
+            &mut state_root,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_get_state_root" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_get_state_root" }.into());
+    }
+
+    Ok(state_root)
+}
+
+/// Get the addresses of the enclaves which have joined the group, indexed by their roster indices.
+pub(crate) fn get_roster(eid: sgx_enclave_id_t) -> Result<Vec<[u8; 20]>> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut roster = EnclaveState::default();
+
+    let status = unsafe {
+        ecall_get_roster(
+            eid,
+            &mut rt,
+            &mut roster,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_get_roster" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_get_roster" }.into());
+    }
+
+    Vec::<[u8; 20]>::decode(&mut &roster.into_vec()[..])
+        .map_err(|e| anyhow!("Failed to decode the roster: {:?}", e).into())
+}
+
+/// Let the enclave seal its states, group key and identity key at the latest block of the inserted logs.
+fn seal_snapshot(eid: sgx_enclave_id_t, block_num: u64, block_hash: [u8; 32]) -> Result<Vec<u8>> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut sealed = EnclaveState::default();
+
+    let status = unsafe {
+        ecall_seal_snapshot(
+            eid,
+            &mut rt,
+            block_num,
+            &block_hash,
+            &mut sealed,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_seal_snapshot" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_seal_snapshot" }.into());
+    }
+
+    Ok(sealed.into_vec())
+}
+
+/// Restore the enclave's states, group key and identity key from a sealed snapshot.
+/// Returns the latest block number and hash whose logs had been inserted when the snapshot was taken.
+pub(crate) fn restore_snapshot(eid: sgx_enclave_id_t, sealed: &[u8]) -> Result<(u64, [u8; 32])> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut block_num = 0u64;
+    let mut block_hash = [0u8; 32];
+
+    let status = unsafe {
+        ecall_restore_snapshot(
+            eid,
+            &mut rt,
+            sealed.as_c_ptr() as *mut u8,
+            sealed.len(),
+            &mut block_num,
+            &mut block_hash,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_restore_snapshot" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_restore_snapshot" }.into());
+    }
+
+    Ok((block_num, block_hash))
+}
+
+/// MRENCLAVE of the enclave, which identifies the enclave whose snapshots can be restored.
+pub(crate) fn get_mr_enclave(eid: sgx_enclave_id_t) -> Result<[u8; 32]> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut mr_enclave = [0u8; 32];
+
+    let status = unsafe {
+        ecall_get_mr_enclave(
+            eid,
+            &mut rt,
+            &mut mr_enclave,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status, function: "ecall_get_mr_enclave" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+		return Err(HostError::Sgx{ status: rt, function: "ecall_get_mr_enclave" }.into());
+    }
+
+    Ok(mr_enclave)
+}
+
 pub(crate) fn join_group(eid: sgx_enclave_id_t) -> Result<RawJoinGroupTx> {
     let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
     let mut raw_reg_tx = RawJoinGroupTx::default();
@@ -161,7 +291,7 @@ pub(crate) fn encrypt_instruction<S: State>(
     let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
     let mut raw_instruction_tx = RawInstructionTx::default();
     let state = state_info.state_as_bytes();
-    let call_id = state_info.call_name_to_id();
+    let call_id = state_info.call_name_to_id()?;
 
     let status = unsafe {
         ecall_instruction(
@@ -213,6 +343,33 @@ pub(crate) fn handshake(
     Ok(raw_handshake_tx)
 }
 
+/// Handshake to remove the member at `roster_idx` from the group
+pub(crate) fn remove_member(
+    eid: sgx_enclave_id_t,
+    roster_idx: u32,
+) -> Result<RawHandshakeTx> {
+    let mut rt = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    let mut raw_handshake_tx = RawHandshakeTx::default();
+
+    let status = unsafe {
+        ecall_remove_member(
+            eid,
+            &mut rt,
+            roster_idx,
+            &mut raw_handshake_tx,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(HostError::Sgx{ status, function: "ecall_remove_member" }.into());
+    }
+    if rt != sgx_status_t::SGX_SUCCESS {
+        return Err(HostError::Sgx{ status: rt, function: "ecall_remove_member" }.into());
+    }
+
+    Ok(raw_handshake_tx)
+}
+
 pub(crate) fn register_notification(
     eid: sgx_enclave_id_t,
     access_right: AccessRight,
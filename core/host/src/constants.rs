@@ -1,5 +1,9 @@
 pub const ENCLAVE_DIR: &'static str = ".anonify";
 pub const ENCLAVE_TOKEN: &'static str = "enclave.token";
+pub const SNAPSHOT_DIR: &str = "snapshots";
+pub const SNAPSHOT_EXTENSION: &str = "snapshot";
+/// The number of the newest snapshots kept to roll back the enclave on a chain reorganization.
+pub const SNAPSHOT_HISTORY: usize = 16;
 pub const ENCLAVE_FILE: &str = "../bin/enclave.signed.so";
 pub const DEBUG: i32 = 1;
 
@@ -1,6 +1,6 @@
 use std::{
-    path::Path,
     sync::Arc,
+    time::{Duration, Instant},
     convert::{TryInto, TryFrom},
     fmt::Debug,
 };
@@ -9,31 +9,54 @@ use crate::bridges::ecalls::{
     join_group as join_fn,
     encrypt_instruction as enc_ins_fn,
     handshake as handshake_fn,
+    remove_member as remove_member_fn,
     insert_logs as insert_fn,
     register_notification as reg_notify_fn,
     get_state_from_enclave,
+    query_state,
+    get_state_root,
+    get_roster,
+    restore_snapshot as restore_snapshot_fn,
+    get_mr_enclave,
 };
+use crate::init_enclave::{EnclaveDir, SnapshotDir};
 use anonify_bc_connector::{
     traits::*,
     utils::*,
-    eventdb::BlockNumDB,
+    eth::GasConfig,
+    eventdb::{BlockNumDB, EventDBTx},
+    sync::{Rollback, SyncConfig},
     error::{Result, HostError},
 };
+use anyhow::anyhow;
 use anonify_common::AccessRight;
-use anonify_runtime::{traits::State, UpdatedState};
+use anonify_runtime::{traits::State, UpdatedState, StateType};
 use parking_lot::RwLock;
+use futures::executor::block_on;
+use futures_timer::Delay;
+
+/// Interval to poll the receipt of a transaction in `wait_for_receipt`.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 /// This dispatcher communicates with a blockchain node.
+/// Calls to the node have non-blocking counterparts, which don't hold the lock of the dispatcher while awaiting.
+/// The deployer, the sender and the watcher handle the same kind of contract artifact.
 #[derive(Debug)]
-pub struct Dispatcher<D: Deployer, S: Sender, W: Watcher<WatcherDB=DB>, DB: BlockNumDB> {
+pub struct Dispatcher<D, S, W, DB>
+    where
+        D: Deployer,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
+        DB: BlockNumDB,
+{
     inner: RwLock<SgxDispatcher<D, S, W, DB>>,
 }
 
 impl<D, S, W, DB> Dispatcher<D, S, W, DB>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
     pub fn new(
@@ -48,12 +71,33 @@ impl<D, S, W, DB> Dispatcher<D, S, W, DB>
         })
     }
 
-    pub fn set_contract_addr<P>(&self, contract_addr: &str, abi_path: P) -> Result<()>
-        where
-            P: AsRef<Path> + Copy,
-    {
+    /// Set the number of blocks built on top of a block before its logs are inserted into the enclave.
+    /// It takes effect on the next `set_contract_addr` or `join_group`.
+    pub fn set_confirmations(&self, confirmations: u64) {
+        self.inner.write().sync_config.confirmations = confirmations;
+    }
+
+    /// Set the maximum number of blocks whose logs are fetched by one request.
+    /// It takes effect on the next `set_contract_addr` or `join_group`.
+    pub fn set_page_size(&self, page_size: u64) {
+        self.inner.write().sync_config.page_size = page_size;
+    }
+
+    /// Set the number of blocks built on top of a block before its transactions are reported
+    /// as mined or reverted by `get_receipt` and `wait_for_receipt`.
+    pub fn set_receipt_confirmations(&self, confirmations: u64) {
+        self.inner.write().receipt_confirmations = confirmations;
+    }
+
+    /// Set how gas limits and fees of transactions are decided, and when stuck transactions are resubmitted.
+    /// It takes effect on the next `deploy`, `set_contract_addr` or `join_group`.
+    pub fn set_gas_config(&self, gas_config: GasConfig) {
+        self.inner.write().gas_config = gas_config;
+    }
+
+    pub fn set_contract_addr(&self, contract_addr: &str, artifact: &D::Artifact) -> Result<()> {
         let mut inner = self.inner.write();
-        let contract_info = ContractInfo::new(abi_path, contract_addr);
+        let contract_info = ContractInfo::new(artifact, contract_addr);
         inner.set_contract_addr(contract_info)?;
 
         Ok(())
@@ -62,97 +106,260 @@ impl<D, S, W, DB> Dispatcher<D, S, W, DB>
     pub fn deploy(
         &self,
         deploy_user: &SignerAddress,
+        artifact: &D::Artifact,
     ) -> Result<String> {
         let mut inner = self.inner.write();
-        inner.deploy(deploy_user)
+        inner.deploy(deploy_user, artifact)
     }
 
-    pub fn join_group<P: AsRef<Path> + Copy>(
+    /// Join the group with the next roster index in the join order.
+    /// Events of the contract are fetched beforehand, so that the enclave knows the current roster.
+    pub fn join_group(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         contract_addr: &str,
-        abi_path: P,
+        artifact: &D::Artifact,
     ) -> Result<String> {
-        let mut inner = self.inner.write();
-        let contract_info = ContractInfo::new(abi_path, contract_addr);
-        inner.join_group(signer, gas, contract_info)
+        block_on(self.join_group_async(signer, gas, contract_addr, artifact))
     }
 
-    pub fn send_instruction<ST, P>(
+    /// A non-blocking counterpart of `join_group`.
+    pub async fn join_group_async(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        contract_addr: &str,
+        artifact: &D::Artifact,
+    ) -> Result<String> {
+        self.set_contract_addr(contract_addr, artifact)?;
+        // No ciphertexts can be decrypted before joining, so there are no updated states to return.
+        self.fetch_events::<StateType>(contract_addr).await?;
+
+        let sender = self.inner.read().sender()?;
+        AsyncSender::join_group(&*sender, signer, gas, join_fn).await
+    }
+
+    pub fn send_instruction<ST>(
         &self,
         access_right: AccessRight,
         state: ST,
         state_id: u64,
         call_name: &str,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         contract_addr: &str,
-        abi_path: P,
     ) -> Result<String>
         where
-            ST: State,
-            P: AsRef<Path> + Copy,
+            ST: State + Send,
+    {
+        block_on(self.send_instruction_async(
+            access_right,
+            state,
+            state_id,
+            call_name,
+            signer,
+            gas,
+            contract_addr,
+        ))
+    }
+
+    /// A non-blocking counterpart of `send_instruction`.
+    pub async fn send_instruction_async<ST>(
+        &self,
+        access_right: AccessRight,
+        state: ST,
+        state_id: u64,
+        call_name: &str,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        _contract_addr: &str,
+    ) -> Result<String>
+        where
+            ST: State + Send,
     {
-        let inner = self.inner.read();
-        let contract_info = ContractInfo::new(abi_path, contract_addr);
+        let sender = self.inner.read().sender()?;
         let state_info = StateInfo::new(state, state_id, call_name);
 
-        inner.send_instruction(access_right, signer, state_info, contract_info, gas)
+        AsyncSender::send_instruction(&*sender, access_right, signer, state_info, gas, enc_ins_fn).await
     }
 
-    pub fn handshake<P>(
+    pub fn handshake(
         &self,
         signer: SignerAddress,
-        gas: u64,
+        gas: Option<u64>,
         contract_addr: &str,
-        abi_path: P,
-    ) -> Result<String>
+    ) -> Result<String> {
+        block_on(self.handshake_async(signer, gas, contract_addr))
+    }
+
+    /// A non-blocking counterpart of `handshake`.
+    pub async fn handshake_async(
+        &self,
+        signer: SignerAddress,
+        gas: Option<u64>,
+        _contract_addr: &str,
+    ) -> Result<String> {
+        let sender = self.inner.read().sender()?;
+        AsyncSender::handshake(&*sender, signer, gas, handshake_fn).await
+    }
+
+    /// Send a handshake removing the member at `roster_idx` from the group.
+    /// After it is processed, the removed member can't decrypt ciphertexts any more.
+    pub fn remove_member(
+        &self,
+        signer: SignerAddress,
+        roster_idx: u32,
+        gas: Option<u64>,
+        contract_addr: &str,
+    ) -> Result<String> {
+        block_on(self.remove_member_async(signer, roster_idx, gas, contract_addr))
+    }
+
+    /// A non-blocking counterpart of `remove_member`.
+    pub async fn remove_member_async(
+        &self,
+        signer: SignerAddress,
+        roster_idx: u32,
+        gas: Option<u64>,
+        _contract_addr: &str,
+    ) -> Result<String> {
+        let sender = self.inner.read().sender()?;
+        AsyncSender::handshake(&*sender, signer, gas, |eid| remove_member_fn(eid, roster_idx)).await
+    }
+
+    pub fn block_on_event<St>(
+        &self,
+        contract_addr: &str,
+    ) -> Result<Option<Vec<UpdatedState<St>>>>
         where
-            P: AsRef<Path> + Copy,
+            St: State + Send,
     {
-        let inner = self.inner.read();
-        let contract_info = ContractInfo::new(abi_path, contract_addr);
-
-        inner.handshake(signer, contract_info, gas)
+        block_on(self.fetch_events(contract_addr))
     }
 
-    pub fn block_on_event<P, St>(
+    /// A non-blocking counterpart of `block_on_event`.
+    pub async fn fetch_events<St>(
         &self,
         contract_addr: &str,
-        abi_path: P,
     ) -> Result<Option<Vec<UpdatedState<St>>>>
         where
-            P: AsRef<Path> + Copy,
-            St: State,
+            St: State + Send,
     {
-        let inner = self.inner.read();
-        let contract_info = ContractInfo::new(abi_path, contract_addr);
-        inner.block_on_event(contract_info).into()
+        let (eid, watcher, snapshot_dir) = {
+            let inner = self.inner.read();
+            (inner.deployer.get_enclave_id(), inner.watcher()?, inner.snapshot_dir(contract_addr)?)
+        };
+
+        watcher.fetch_events(
+            eid,
+            |eid, log| insert_fn(eid, log, &snapshot_dir),
+            &SnapshotRollback(snapshot_dir.clone()),
+        ).await
     }
 
     pub fn get_account(&self, index: usize) -> Result<SignerAddress> {
         self.inner.read().get_account(index)
     }
 
+    /// Get the current status of a transaction sent by this dispatcher.
+    pub fn get_receipt(&self, tx_hash: &str) -> Result<TxReceipt> {
+        block_on(self.get_receipt_async(tx_hash))
+    }
+
+    /// A non-blocking counterpart of `get_receipt`.
+    pub async fn get_receipt_async(&self, tx_hash: &str) -> Result<TxReceipt> {
+        let (sender, confirmations) = {
+            let inner = self.inner.read();
+            (inner.sender()?, inner.receipt_confirmations)
+        };
+
+        AsyncSender::get_receipt(&*sender, tx_hash, confirmations).await
+    }
+
+    /// Poll the receipt until the transaction is no longer pending or `timeout` elapses.
+    /// A pending receipt is returned on timeout.
+    pub fn wait_for_receipt(&self, tx_hash: &str, timeout: Duration) -> Result<TxReceipt> {
+        block_on(self.wait_for_receipt_async(tx_hash, timeout))
+    }
+
+    /// A non-blocking counterpart of `wait_for_receipt`.
+    pub async fn wait_for_receipt_async(&self, tx_hash: &str, timeout: Duration) -> Result<TxReceipt> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let receipt = self.get_receipt_async(tx_hash).await?;
+            if !receipt.is_pending() || Instant::now() >= deadline {
+                return Ok(receipt);
+            }
+
+            Delay::new(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
     pub fn register_notification(&self, access_right: AccessRight) -> Result<()> {
         self.inner.read().register_notification(access_right)
     }
+
+    /// Digest of all states in the enclave, which is the same among nodes
+    /// which have processed the same instructions.
+    pub fn state_root(&self) -> Result<[u8; 32]> {
+        let eid = self.inner.read().deployer.get_enclave_id();
+        get_state_root(eid)
+    }
+
+    /// Addresses of the enclaves which have joined the group, indexed by their roster indices.
+    /// The roster is derived from the `JoinGroup` events inserted into the enclave.
+    pub fn roster(&self) -> Result<Vec<[u8; 20]>> {
+        let eid = self.inner.read().deployer.get_enclave_id();
+        get_roster(eid)
+    }
+
+    /// Run a view function in the enclave and decode its result.
+    /// Only the user authorized by the view function can get the result.
+    pub fn query<P, R>(
+        &self,
+        access_right: &AccessRight,
+        params: P,
+        view_name: &str,
+    ) -> Result<R>
+        where
+            P: State,
+            R: State,
+    {
+        self.inner.read().query(access_right, params, view_name)
+    }
+
+    /// Restore the enclave from the newest snapshot it sealed while syncing with the contract if it exists,
+    /// and resume fetching events of the contract from the next block of the snapshot.
+    /// Returns the block number the snapshot was taken at.
+    pub fn restore_snapshot(&self, contract_addr: &str) -> Result<Option<u64>> {
+        self.inner.read().restore_snapshot(contract_addr)
+    }
 }
 
 #[derive(Debug)]
-struct SgxDispatcher<D: Deployer, S: Sender, W: Watcher<WatcherDB=DB>, DB: BlockNumDB> {
+struct SgxDispatcher<D, S, W, DB>
+    where
+        D: Deployer,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
+        DB: BlockNumDB,
+{
     deployer: D,
-    sender: Option<S>,
-    watcher: Option<W>,
+    // Shared with the calls awaiting them, so that the dispatcher is not locked meanwhile.
+    sender: Option<Arc<S>>,
+    watcher: Option<Arc<W>>,
     event_db: Arc<DB>,
+    sync_config: SyncConfig,
+    receipt_confirmations: u64,
+    gas_config: GasConfig,
 }
 
 impl<D, S, W, DB> SgxDispatcher<D, S, W, DB>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
     fn new_with_deployer(
@@ -167,23 +374,23 @@ impl<D, S, W, DB> SgxDispatcher<D, S, W, DB>
             event_db,
             sender: None,
             watcher: None,
+            sync_config: SyncConfig::default(),
+            receipt_confirmations: 0,
+            gas_config: GasConfig::default(),
         })
     }
 
-    fn set_contract_addr<P>(
+    fn set_contract_addr(
         &mut self,
-        contract_info: ContractInfo<'_, P>,
-    ) -> Result<()>
-        where
-            P: AsRef<Path> + Copy
-    {
+        contract_info: ContractInfo<'_, D::Artifact>,
+    ) -> Result<()> {
         let enclave_id = self.deployer.get_enclave_id();
         let node_url = self.deployer.get_node_url();
-        let sender = S::new(enclave_id, node_url, contract_info)?;
-        let watcher = W::new(node_url, contract_info, self.event_db.clone())?;
+        let sender = <S as AsyncSender>::new(enclave_id, node_url, contract_info, self.gas_config)?;
+        let watcher = <W as AsyncWatcher>::new(node_url, contract_info, self.event_db.clone(), self.sync_config)?;
 
-        self.sender = Some(sender);
-        self.watcher = Some(watcher);
+        self.sender = Some(Arc::new(sender));
+        self.watcher = Some(Arc::new(watcher));
 
         Ok(())
     }
@@ -191,9 +398,10 @@ impl<D, S, W, DB> SgxDispatcher<D, S, W, DB>
     fn deploy(
         &mut self,
         deploy_user: &SignerAddress,
+        artifact: &D::Artifact,
     ) -> Result<String> {
         self.deployer
-            .deploy(deploy_user, join_fn)
+            .deploy(deploy_user, artifact, self.gas_config, join_fn)
     }
 
     fn get_account(&self, index: usize) -> Result<SignerAddress> {
@@ -201,78 +409,85 @@ impl<D, S, W, DB> SgxDispatcher<D, S, W, DB>
             .get_account(index)
     }
 
-    fn block_on_event<P, St>(
-        &self,
-        contract_info: ContractInfo<'_, P>,
-    ) -> Result<Option<Vec<UpdatedState<St>>>>
-        where
-            P: AsRef<Path> + Copy,
-            St: State,
-    {
-        if self.watcher.is_none() {
-            return Err(HostError::EventWatcherNotSet);
-        }
+    fn sender(&self) -> Result<Arc<S>> {
+        self.sender.clone().ok_or(HostError::AddressNotSet)
+    }
 
-        let eid = self.deployer.get_enclave_id();
-        self.watcher.as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .block_on_event(eid, insert_fn)
+    fn watcher(&self) -> Result<Arc<W>> {
+        self.watcher.clone().ok_or(HostError::EventWatcherNotSet)
     }
 
-    fn join_group<P: AsRef<Path> + Copy>(
-        &mut self,
-        signer: SignerAddress,
-        gas: u64,
-        contract_info: ContractInfo<'_, P>,
-    ) -> Result<String> {
-        self.set_contract_addr(contract_info)?;
+    /// The directory of snapshots taken by this enclave while syncing with the contract.
+    fn snapshot_dir(&self, contract_addr: &str) -> Result<SnapshotDir> {
+        let mr_enclave = get_mr_enclave(self.deployer.get_enclave_id())?;
+        let contract_addr = contract_address(contract_addr)?.to_fixed_bytes();
+
+        Ok(EnclaveDir::new().snapshot_dir(contract_addr, mr_enclave))
+    }
 
-        self.sender.as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .join_group(signer, gas, join_fn)
+    fn register_notification(&self, access_right: AccessRight) -> Result<()> {
+        self.deployer.register_notification(access_right, reg_notify_fn)
     }
 
-    fn send_instruction<ST, P>(
+    fn query<P, R>(
         &self,
-        access_right: AccessRight,
-        signer: SignerAddress,
-        state_info: StateInfo<'_, ST>,
-        contract_info: ContractInfo<'_, P>,
-        gas: u64,
-    ) -> Result<String>
+        access_right: &AccessRight,
+        params: P,
+        view_name: &str,
+    ) -> Result<R>
         where
-            ST: State,
-            P: AsRef<Path> + Copy,
+            P: State,
+            R: State,
     {
-        if self.sender.is_none() {
-            return Err(HostError::AddressNotSet);
-        }
+        let eid = self.deployer.get_enclave_id();
+        let mut res = query_state(eid, access_right, &params.as_bytes(), view_name)?;
 
-        self.sender.as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .send_instruction(access_right, signer, state_info, gas, enc_ins_fn)
+        R::from_bytes(&mut res).map_err(Into::into)
     }
 
-    fn handshake<P>(
-        &self,
-        signer: SignerAddress,
-        contract_info: ContractInfo<'_, P>,
-        gas: u64,
-    ) -> Result<String>
-        where
-            P: AsRef<Path> + Copy,
-    {
-        if self.sender.is_none() {
-            return Err(HostError::AddressNotSet);
-        }
+    fn restore_snapshot(&self, contract_addr: &str) -> Result<Option<u64>> {
+        let snapshot = match self.snapshot_dir(contract_addr)?.load_sealed_snapshots()?.into_iter().next() {
+            Some(snapshot) => snapshot,
+            None => return Ok(None),
+        };
+
+        let eid = self.deployer.get_enclave_id();
+        let (block_num, block_hash) = restore_snapshot_fn(eid, &snapshot.sealed)?;
+
+        // If the block has been reorganized away while stopped, the enclave is rolled back on fetching events.
+        let mut dbtx = EventDBTx::new();
+        dbtx.put(contract_address(contract_addr)?, block_num + 1, Some(block_hash.into()));
+        self.event_db.set_next_block_num(dbtx)?;
 
-        self.sender.as_ref()
-            .ok_or(HostError::AddressNotSet)?
-            .handshake(signer, gas, handshake_fn)
+        Ok(Some(block_num))
     }
+}
 
-    fn register_notification(&self, access_right: AccessRight) -> Result<()> {
-        self.deployer.register_notification(access_right, reg_notify_fn)
+/// Rolls the enclave back to the sealed snapshots in the snapshot directory of the contract.
+struct SnapshotRollback(SnapshotDir);
+
+impl Rollback for SnapshotRollback {
+    fn snapshot_blocks(&self) -> Result<Vec<(u64, [u8; 32])>> {
+        let snapshots = self.0.load_sealed_snapshots()?;
+        Ok(snapshots.into_iter().map(|s| (s.block_num, s.block_hash)).collect())
+    }
+
+    /// Restore the snapshot taken at the block, and remove the snapshots taken after it.
+    fn rollback(&self, eid: sgx_enclave_id_t, block_num: u64) -> Result<(u64, [u8; 32])> {
+        let snapshot = self.0.load_sealed_snapshots()?
+            .into_iter()
+            .find(|s| s.block_num == block_num)
+            .ok_or_else(|| anyhow!("No snapshot is taken at block {}", block_num))?;
+        let (block_num, block_hash) = restore_snapshot_fn(eid, &snapshot.sealed)?;
+        self.0.remove_snapshots_after(block_num)?;
+
+        Ok((block_num, block_hash))
+    }
+
+    /// Logs change the enclave only after a `JoinGroup` event is inserted,
+    /// since ciphertexts and handshakes are rejected before any member joins.
+    fn is_initial(&self, eid: sgx_enclave_id_t) -> Result<bool> {
+        Ok(get_roster(eid)?.is_empty())
     }
 }
 
@@ -1,12 +1,23 @@
 use std::{
     fs, path::{Path, PathBuf},
-    io::{Read, Write, BufReader, BufWriter},
+    io::{self, Read, Write, BufReader, BufWriter},
 };
 use sgx_types::*;
 use sgx_urts::SgxEnclave;
 use anonify_bc_connector::error::Result;
+use log::warn;
 use crate::constants::*;
 
+/// A snapshot sealed by the enclave, with the block it was taken at.
+/// The block number and hash are only hints to choose a snapshot;
+/// the enclave returns the authentic ones on restoring it.
+#[derive(Debug, Clone)]
+pub struct SealedSnapshot {
+    pub block_num: u64,
+    pub block_hash: [u8; 32],
+    pub sealed: Vec<u8>,
+}
+
 pub struct EnclaveDir(PathBuf);
 
 impl EnclaveDir {
@@ -46,6 +57,17 @@ impl EnclaveDir {
         self.0.join(ENCLAVE_TOKEN)
     }
 
+    /// The directory of snapshots taken by the enclave with `mr_enclave` while syncing with the contract,
+    /// so that snapshots of other contracts or enclaves are never restored.
+    pub fn snapshot_dir(&self, contract_addr: [u8; 20], mr_enclave: [u8; 32]) -> SnapshotDir {
+        let path = self.0
+            .join(SNAPSHOT_DIR)
+            .join(to_hex(&contract_addr))
+            .join(to_hex(&mr_enclave));
+
+        SnapshotDir(path)
+    }
+
     fn get_launch_token<P: AsRef<Path>>(path: P) -> Result<sgx_launch_token_t> {
         let mut buf = vec![];
         let mut res = [0u8; 1024];
@@ -104,3 +126,95 @@ impl EnclaveDir {
     }
 }
 
+/// A directory of sealed snapshots of one enclave syncing with one contract.
+#[derive(Debug, Clone)]
+pub struct SnapshotDir(PathBuf);
+
+impl SnapshotDir {
+    /// Persist a snapshot sealed by the enclave at the given block.
+    /// It is written to a temporary file first so that a crash never leaves a partially written snapshot.
+    /// Only the newest `SNAPSHOT_HISTORY` snapshots are kept.
+    pub fn save_sealed_snapshot(&self, block_num: u64, block_hash: [u8; 32], sealed: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.0)?;
+        let path = self.0.join(format!("{:020}.{}", block_num, SNAPSHOT_EXTENSION));
+        let tmp_path = path.with_extension("tmp");
+
+        let f = fs::File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(f);
+        writer.write_all(&block_hash)?;
+        writer.write_all(sealed)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(tmp_path, path)?;
+
+        for (_, path) in self.snapshot_paths()?.into_iter().skip(SNAPSHOT_HISTORY) {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load all sealed snapshots, newest first.
+    pub fn load_sealed_snapshots(&self) -> Result<Vec<SealedSnapshot>> {
+        let mut snapshots = vec![];
+        for (block_num, path) in self.snapshot_paths()? {
+            let mut buf = vec![];
+            BufReader::new(fs::File::open(&path)?).read_to_end(&mut buf)?;
+            if buf.len() < 32 {
+                warn!("Skipped a broken snapshot file: {}", path.display());
+                continue;
+            }
+
+            let mut block_hash = [0u8; 32];
+            block_hash.copy_from_slice(&buf[..32]);
+            snapshots.push(SealedSnapshot {
+                block_num,
+                block_hash,
+                sealed: buf[32..].to_vec(),
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Remove snapshots taken after the given block, which have been dropped by a chain reorganization.
+    pub fn remove_snapshots_after(&self, block_num: u64) -> Result<()> {
+        for (num, path) in self.snapshot_paths()? {
+            if num > block_num {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Paths of snapshot files with their block numbers, newest first.
+    fn snapshot_paths(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let entries = match fs::read_dir(&self.0) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            if let Some(block_num) = path.file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                paths.push((block_num, path));
+            }
+        }
+        paths.sort_by(|a, b| b.0.cmp(&a.0));
+
+        Ok(paths)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -1,15 +1,17 @@
 use std::{
     sync::Arc,
-    env,
     collections::BTreeMap,
+    time::Duration,
 };
 use sgx_types::*;
 use anonify_common::{AccessRight, UserAddress, COMMON_ACCESS_RIGHT};
-use anonify_runtime::{State, U64, Approved};
-use anonify_app_preluder::{transfer, construct, approve, transfer_from, mint, burn};
+use anonify_runtime::{State, U256, Approved};
+use anonify_app_preluder::{transfer, construct, approve, transfer_from, mint, burn, allowance};
 use anonify_bc_connector::{
-    eventdb::{EventDB, BlockNumDB},
+    eventdb::EventDB,
     eth::*,
+    mock::{MockChain, MockDeployer, MockSender, MockWatcher},
+    utils::TxStatus,
 };
 use crate::auto_ffi::ecall_run_tests;
 use crate::init_enclave::EnclaveDir;
@@ -17,6 +19,13 @@ use crate::dispatcher::*;
 
 const ETH_URL: &'static str = "http://172.18.0.2:8545";
 const ANONYMOUS_ASSET_ABI_PATH: &str = "../../build/Anonify.abi";
+const ANONYMOUS_ASSET_BIN_PATH: &str = "../../build/Anonify.bin";
+
+type MockDispatcher = Dispatcher<MockDeployer, MockSender, MockWatcher<EventDB>, EventDB>;
+
+fn anonify_artifact() -> ContractArtifact {
+    ContractArtifact::load(ANONYMOUS_ASSET_ABI_PATH, ANONYMOUS_ASSET_BIN_PATH).unwrap()
+}
 
 #[test]
 fn test_in_enclave() {
@@ -29,29 +38,28 @@ fn test_in_enclave() {
 
 #[test]
 fn test_integration_eth_construct() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -61,29 +69,235 @@ fn test_integration_eth_construct() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Get state from enclave
     let owner_address = get_state::<UserAddress>(&*COMMON_ACCESS_RIGHT, eid, "Owner").unwrap();
-    let my_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let actual_total_supply = get_state::<U64>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
+    let my_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let actual_total_supply = get_state::<U256>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
     assert_eq!(owner_address, my_access_right.user_address());
     assert_eq!(my_balance, total_supply);
     assert_eq!(actual_total_supply, total_supply);
 }
 
+#[test]
+fn test_mock_multi_node_handshake() {
+    let node_url = "mock://test_mock_multi_node_handshake";
+    let my_access_right = AccessRight::new_from_rng().unwrap();
+    let state_id = 0;
+    let gas = Some(3_000_000);
+
+    // Roster indices are assigned in the join order, starting with the deployer.
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+    let deployer_addr = dispatcher.get_account(0).unwrap();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &()).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &()).unwrap();
+    let mut nodes = vec![(enclave, dispatcher)];
+
+    for roster_idx in 1..=2 {
+        let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+        let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+        let signer = dispatcher.get_account(roster_idx).unwrap();
+        dispatcher.join_group(signer, gas, &contract_addr, &()).unwrap();
+        nodes.push((enclave, dispatcher));
+    }
+
+    // All members process the handshakes of the deployer and the joined members,
+    // and derive the same roster from them.
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+    let roster = nodes[0].1.roster().unwrap();
+    assert_eq!(roster.len(), 3);
+    for (_, dispatcher) in &nodes {
+        assert_eq!(dispatcher.roster().unwrap(), roster);
+    }
+
+    // Rotate the group key.
+    let (_, dispatcher) = &nodes[0];
+    dispatcher.handshake(deployer_addr.clone(), gas, &contract_addr).unwrap();
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+
+    // Init state
+    let total_supply = U256::from(100u64);
+    let init_state = construct{ total_supply };
+    let (_, dispatcher) = &nodes[0];
+    dispatcher.send_instruction(
+        my_access_right.clone(),
+        init_state,
+        state_id,
+        "construct",
+        deployer_addr.clone(),
+        gas,
+        &contract_addr,
+    ).unwrap();
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+
+    // Every member decrypted the same instruction with the rotated key.
+    let state_root = nodes[0].1.state_root().unwrap();
+    for (enclave, dispatcher) in &nodes {
+        let my_balance = get_state::<U256>(&my_access_right, enclave.geteid(), "Balance").unwrap();
+        assert_eq!(my_balance, total_supply);
+        assert_eq!(dispatcher.state_root().unwrap(), state_root);
+    }
+}
+
+#[test]
+fn test_mock_remove_member() {
+    let node_url = "mock://test_mock_remove_member";
+    let my_access_right = AccessRight::new_from_rng().unwrap();
+    let state_id = 0;
+    let gas = Some(3_000_000);
+
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+    let deployer_addr = dispatcher.get_account(0).unwrap();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &()).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &()).unwrap();
+    let mut nodes = vec![(enclave, dispatcher)];
+
+    for roster_idx in 1..=2 {
+        let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+        let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+        let signer = dispatcher.get_account(roster_idx).unwrap();
+        dispatcher.join_group(signer, gas, &contract_addr, &()).unwrap();
+        nodes.push((enclave, dispatcher));
+    }
+
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+
+    // The deployer removes the last member.
+    let (_, dispatcher) = &nodes[0];
+    assert!(dispatcher.remove_member(deployer_addr.clone(), 0, gas, &contract_addr).is_err());
+    dispatcher.remove_member(deployer_addr.clone(), 2, gas, &contract_addr).unwrap();
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+
+    // Init state
+    let total_supply = U256::from(100u64);
+    let init_state = construct{ total_supply };
+    let (_, dispatcher) = &nodes[0];
+    dispatcher.send_instruction(
+        my_access_right.clone(),
+        init_state,
+        state_id,
+        "construct",
+        deployer_addr.clone(),
+        gas,
+        &contract_addr,
+    ).unwrap();
+    for (_, dispatcher) in &nodes {
+        dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    }
+
+    // Only the remaining members decrypted the instruction.
+    for (enclave, _) in &nodes[..2] {
+        let my_balance = get_state::<U256>(&my_access_right, enclave.geteid(), "Balance").unwrap();
+        assert_eq!(my_balance, total_supply);
+    }
+    let (removed_enclave, removed_dispatcher) = &nodes[2];
+    let removed_balance = get_state::<U256>(&my_access_right, removed_enclave.geteid(), "Balance").unwrap();
+    assert_eq!(removed_balance, U256::default());
+    assert_ne!(removed_dispatcher.state_root().unwrap(), nodes[0].1.state_root().unwrap());
+}
+
+#[test]
+fn test_mock_handshakes_and_ciphertexts_in_one_batch() {
+    let node_url = "mock://test_mock_handshakes_and_ciphertexts_in_one_batch";
+    let my_access_right = AccessRight::new_from_rng().unwrap();
+    let state_id = 0;
+    let gas = Some(3_000_000);
+
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+    let deployer_addr = dispatcher.get_account(0).unwrap();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &()).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &()).unwrap();
+
+    let other_enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let other_dispatcher = MockDispatcher::new(other_enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+    let signer = other_dispatcher.get_account(1).unwrap();
+    other_dispatcher.join_group(signer, gas, &contract_addr, &()).unwrap();
+
+    // Init state with the key derived from the handshakes of both members.
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    let total_supply = U256::from(100u64);
+    let init_state = construct{ total_supply };
+    dispatcher.send_instruction(
+        my_access_right.clone(),
+        init_state,
+        state_id,
+        "construct",
+        deployer_addr,
+        gas,
+        &contract_addr,
+    ).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+
+    // The other member fetches its own handshake and the ciphertext at once,
+    // and decrypts the ciphertext after processing the handshake.
+    other_dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    let my_balance = get_state::<U256>(&my_access_right, other_enclave.geteid(), "Balance").unwrap();
+    assert_eq!(my_balance, total_supply);
+    assert_eq!(other_dispatcher.state_root().unwrap(), dispatcher.state_root().unwrap());
+}
+
+#[test]
+fn test_mock_receipt_confirmations() {
+    let node_url = "mock://test_mock_receipt_confirmations";
+    let my_access_right = AccessRight::new_from_rng().unwrap();
+    let gas = Some(3_000_000);
+
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let dispatcher = MockDispatcher::new(enclave.geteid(), node_url, Arc::new(EventDB::new())).unwrap();
+    let deployer_addr = dispatcher.get_account(0).unwrap();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &()).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &()).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
+    dispatcher.set_receipt_confirmations(1);
+
+    let init_state = construct{ total_supply: U256::from(100u64) };
+    let tx_hash = dispatcher.send_instruction(
+        my_access_right,
+        init_state,
+        0,
+        "construct",
+        deployer_addr,
+        gas,
+        &contract_addr,
+    ).unwrap();
+
+    // The transaction is pending until a block is built on top of it.
+    let receipt = dispatcher.wait_for_receipt(&tx_hash, Duration::from_secs(0)).unwrap();
+    assert_eq!(receipt.status, TxStatus::Pending);
+
+    let chain = MockChain::connect(node_url);
+    let block_num = chain.latest_block_num();
+    chain.mine();
+    let receipt = dispatcher.wait_for_receipt(&tx_hash, Duration::from_secs(1)).unwrap();
+    assert_eq!(receipt.status, TxStatus::Mined(block_num));
+
+    let unknown_tx_hash = "ff".repeat(32);
+    assert_eq!(dispatcher.get_receipt(&unknown_tx_hash).unwrap().status, TxStatus::Dropped);
+}
+
 #[test]
 fn test_auto_notification() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
@@ -91,22 +305,23 @@ fn test_auto_notification() {
     let third_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -116,14 +331,13 @@ fn test_auto_notification() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
     // Get logs from contract and update state inside enclave.
     let updated_state = dispatcher
-        .block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap().unwrap();
+        .block_on_event::<U256>(&contract_addr).unwrap().unwrap();
 
     assert_eq!(updated_state.len(), 1);
     assert_eq!(updated_state[0].address, my_access_right.user_address());
@@ -131,7 +345,7 @@ fn test_auto_notification() {
     assert_eq!(updated_state[0].state, total_supply);
 
     // Send a transaction to contract
-    let amount = U64::from_raw(30);
+    let amount = U256::from(30u64);
     let recipient = other_access_right.user_address();
     let transfer_state = transfer{ amount, recipient };
     let receipt = dispatcher.send_instruction(
@@ -142,23 +356,20 @@ fn test_auto_notification() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
     // Update state inside enclave
-    let updated_state = dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap().unwrap();
+    let updated_state = dispatcher.block_on_event::<U256>(&contract_addr).unwrap().unwrap();
 
     assert_eq!(updated_state.len(), 1);
     assert_eq!(updated_state[0].address, my_access_right.user_address());
     assert_eq!(updated_state[0].mem_id.as_raw(), 0);
-    assert_eq!(updated_state[0].state, U64::from_raw(70));
+    assert_eq!(updated_state[0].state, U256::from(70u64));
 }
 
 #[test]
 fn test_integration_eth_transfer() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
@@ -166,22 +377,23 @@ fn test_integration_eth_transfer() {
     let third_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -191,27 +403,26 @@ fn test_integration_eth_transfer() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Get state from enclave
-    let my_state = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_state = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_state = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
+    let my_state = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_state = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_state = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
     assert_eq!(my_state, total_supply);
-    assert_eq!(other_state, U64::zero());
-    assert_eq!(third_state, U64::zero());
+    assert_eq!(other_state, U256::zero());
+    assert_eq!(third_state, U256::zero());
 
 
     // Send a transaction to contract
-    let amount = U64::from_raw(30);
+    let amount = U256::from(30u64);
     let recipient = other_access_right.user_address();
     let transfer_state = transfer{ amount, recipient };
     let receipt = dispatcher.send_instruction(
@@ -222,28 +433,25 @@ fn test_integration_eth_transfer() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Check the updated states
-    let my_updated_state = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_updated_state = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_updated_state = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
+    let my_updated_state = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_updated_state = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_updated_state = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
 
-    assert_eq!(my_updated_state, U64::from_raw(70));
+    assert_eq!(my_updated_state, U256::from(70u64));
     assert_eq!(other_updated_state, amount);
-    assert_eq!(third_updated_state, U64::zero());
+    assert_eq!(third_updated_state, U256::zero());
 }
 
 #[test]
 fn test_key_rotation() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
@@ -251,29 +459,30 @@ fn test_key_rotation() {
     let third_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Send handshake
-    let receipt = dispatcher.handshake(deployer_addr.clone(), gas, &contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let receipt = dispatcher.handshake(deployer_addr.clone(), gas, &contract_addr).unwrap();
     println!("handshake receipt: {}", receipt);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -283,48 +492,46 @@ fn test_key_rotation() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("init state receipt: {}", receipt);
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Get state from enclave
-    let my_state = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_state = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_state = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
+    let my_state = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_state = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_state = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
     assert_eq!(my_state, total_supply);
-    assert_eq!(other_state, U64::zero());
-    assert_eq!(third_state, U64::zero());
+    assert_eq!(other_state, U256::zero());
+    assert_eq!(third_state, U256::zero());
 }
 
 #[test]
 fn test_integration_eth_approve() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
     let other_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct { total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -334,14 +541,13 @@ fn test_integration_eth_approve() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Get state from enclave
     let my_state = get_state::<Approved>(&my_access_right, eid, "Approved").unwrap();
@@ -350,7 +556,7 @@ fn test_integration_eth_approve() {
     assert_eq!(other_state, Approved::default());
 
     // Send a transaction to contract
-    let amount = U64::from_raw(30);
+    let amount = U256::from(30u64);
     let spender = other_access_right.user_address();
     let approve_state = approve { amount, spender };
     let receipt = dispatcher.send_instruction(
@@ -361,13 +567,12 @@ fn test_integration_eth_approve() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Check the updated states
@@ -380,12 +585,21 @@ fn test_integration_eth_approve() {
     });
     assert_eq!(my_state, want_my_state);
     assert_eq!(other_state, Approved::default());
+
+    // Both the owner and the spender can query the allowance
+    let owner = my_access_right.user_address();
+    let my_allowance = dispatcher
+        .query::<_, U256>(&my_access_right, allowance { owner, spender }, "allowance")
+        .unwrap();
+    let other_allowance = dispatcher
+        .query::<_, U256>(&other_access_right, allowance { owner, spender }, "allowance")
+        .unwrap();
+    assert_eq!(my_allowance, amount);
+    assert_eq!(other_allowance, amount);
 }
 
 #[test]
 fn test_integration_eth_transfer_from() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
@@ -393,22 +607,23 @@ fn test_integration_eth_transfer_from() {
     let third_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct { total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -418,22 +633,21 @@ fn test_integration_eth_transfer_from() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Get initial state from enclave
-    let my_state_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_state_balance = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_state_balance = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
-    assert_eq!(my_state_balance, U64::from_raw(100));
-    assert_eq!(other_state_balance, U64::zero());
-    assert_eq!(third_state_balance, U64::zero());
+    let my_state_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_state_balance = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_state_balance = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
+    assert_eq!(my_state_balance, U256::from(100u64));
+    assert_eq!(other_state_balance, U256::zero());
+    assert_eq!(third_state_balance, U256::zero());
 
     let my_state_approved = get_state::<Approved>(&my_access_right, eid, "Approved").unwrap();
     let other_state_approved = get_state::<Approved>(&other_access_right, eid, "Approved").unwrap();
@@ -443,7 +657,7 @@ fn test_integration_eth_transfer_from() {
     assert_eq!(third_state_approved, Approved::default());
 
     // Send a transaction to contract
-    let amount = U64::from_raw(30);
+    let amount = U256::from(30u64);
     let spender = other_access_right.user_address();
     let approve_state = approve { amount, spender };
     let receipt = dispatcher.send_instruction(
@@ -454,21 +668,20 @@ fn test_integration_eth_transfer_from() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Check the updated states
-    let my_state_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_state_balance = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_state_balance = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
-    assert_eq!(my_state_balance, U64::from_raw(100));
-    assert_eq!(other_state_balance, U64::zero());
-    assert_eq!(third_state_balance, U64::zero());
+    let my_state_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_state_balance = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_state_balance = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
+    assert_eq!(my_state_balance, U256::from(100u64));
+    assert_eq!(other_state_balance, U256::zero());
+    assert_eq!(third_state_balance, U256::zero());
 
     let my_state_approved = get_state::<Approved>(&my_access_right, eid, "Approved").unwrap();
     let other_state_approved = get_state::<Approved>(&other_access_right, eid, "Approved").unwrap();
@@ -483,7 +696,7 @@ fn test_integration_eth_transfer_from() {
     assert_eq!(third_state_approved, Approved::default());
 
     // Send a transaction to contract
-    let amount = U64::from_raw(20);
+    let amount = U256::from(20u64);
     let owner = my_access_right.user_address();
     let recipient = third_access_right.user_address();
     let transferred_from_state = transfer_from { owner, recipient, amount };
@@ -495,28 +708,27 @@ fn test_integration_eth_transfer_from() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Check the final states
-    let my_state_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_state_balance = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    let third_state_balance = get_state::<U64>(&third_access_right, eid, "Balance").unwrap();
-    assert_eq!(my_state_balance, U64::from_raw(80));
-    assert_eq!(other_state_balance, U64::zero());
-    assert_eq!(third_state_balance, U64::from_raw(20));
+    let my_state_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_state_balance = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    let third_state_balance = get_state::<U256>(&third_access_right, eid, "Balance").unwrap();
+    assert_eq!(my_state_balance, U256::from(80u64));
+    assert_eq!(other_state_balance, U256::zero());
+    assert_eq!(third_state_balance, U256::from(20u64));
 
     let my_state_approved = get_state::<Approved>(&my_access_right, eid, "Approved").unwrap();
     let other_state_approved = get_state::<Approved>(&other_access_right, eid, "Approved").unwrap();
     let third_state_approved = get_state::<Approved>(&third_access_right, eid, "Approved").unwrap();
     let want_my_state = Approved::new({
         let mut bt = BTreeMap::new();
-        bt.insert(spender, U64::from_raw(10));
+        bt.insert(spender, U256::from(10u64));
         bt
     });
     assert_eq!(my_state_approved, want_my_state);
@@ -526,30 +738,29 @@ fn test_integration_eth_transfer_from() {
 
 #[test]
 fn test_integration_eth_mint() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
     let other_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -559,18 +770,17 @@ fn test_integration_eth_mint() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // transit state
-    let amount = U64::from_raw(50);
+    let amount = U256::from(50u64);
     let recipient = other_access_right.user_address();
     let minting_state = mint{ amount, recipient };
     let receipt = dispatcher.send_instruction(
@@ -581,51 +791,49 @@ fn test_integration_eth_mint() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("minted state receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Check the final states
-    let actual_total_supply = get_state::<U64>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
-    let owner_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_balance = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    assert_eq!(actual_total_supply, U64::from_raw(150));
-    assert_eq!(owner_balance, U64::from_raw(100));
+    let actual_total_supply = get_state::<U256>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
+    let owner_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_balance = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    assert_eq!(actual_total_supply, U256::from(150u64));
+    assert_eq!(owner_balance, U256::from(100u64));
     assert_eq!(other_balance, amount);
 }
 
 #[test]
 fn test_integration_eth_burn() {
-    env::set_var("MY_ROSTER_IDX", "0");
-    env::set_var("MAX_ROSTER_IDX", "2");
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
     let my_access_right = AccessRight::new_from_rng().unwrap();
     let other_access_right = AccessRight::new_from_rng().unwrap();
 
     let state_id = 0;
-    let gas = 3_000_000;
+    let gas = Some(3_000_000);
     let event_db = Arc::new(EventDB::new());
     let dispatcher = Dispatcher::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, ETH_URL, event_db).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher.get_account(0).unwrap();
-    let contract_addr = dispatcher.deploy(&deployer_addr).unwrap();
-    dispatcher.set_contract_addr(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    let artifact = anonify_artifact();
+    let contract_addr = dispatcher.deploy(&deployer_addr, &artifact).unwrap();
+    dispatcher.set_contract_addr(&contract_addr, &artifact).unwrap();
     println!("Deployer address: {:?}", deployer_addr);
     println!("deployed contract address: {}", contract_addr);
 
     // Get handshake from contract
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
     // Init state
-    let total_supply = U64::from_raw(100);
+    let total_supply = U256::from(100u64);
     let init_state = construct{ total_supply };
     let receipt = dispatcher.send_instruction(
         my_access_right.clone(),
@@ -635,18 +843,17 @@ fn test_integration_eth_burn() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
 
     println!("init state receipt: {}", receipt);
 
 
     // Get logs from contract and update state inside enclave.
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Send a transaction to contract
-    let amount = U64::from_raw(30);
+    let amount = U256::from(30u64);
     let recipient = other_access_right.user_address();
     let transfer_state = transfer{ amount, recipient };
     let receipt = dispatcher.send_instruction(
@@ -657,17 +864,16 @@ fn test_integration_eth_burn() {
         deployer_addr.clone(),
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Send a transaction to contract
-    let amount = U64::from_raw(20);
+    let amount = U256::from(20u64);
     let burn_state = burn{ amount };
     let receipt = dispatcher.send_instruction(
         other_access_right.clone(),
@@ -677,20 +883,19 @@ fn test_integration_eth_burn() {
         deployer_addr,
         gas,
         &contract_addr,
-        ANONYMOUS_ASSET_ABI_PATH,
     ).unwrap();
     println!("receipt: {}", receipt);
 
 
     // Update state inside enclave
-    dispatcher.block_on_event::<_, U64>(&contract_addr, ANONYMOUS_ASSET_ABI_PATH).unwrap();
+    dispatcher.block_on_event::<U256>(&contract_addr).unwrap();
 
 
     // Check the final states
-    let actual_total_supply = get_state::<U64>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
-    let owner_balance = get_state::<U64>(&my_access_right, eid, "Balance").unwrap();
-    let other_balance = get_state::<U64>(&other_access_right, eid, "Balance").unwrap();
-    assert_eq!(actual_total_supply, U64::from_raw(80)); // 100 - 20(burn)
-    assert_eq!(owner_balance, U64::from_raw(70)); // 100 - 30(transfer)
-    assert_eq!(other_balance, U64::from_raw(10)); // 30 - 20(burn)
+    let actual_total_supply = get_state::<U256>(&*COMMON_ACCESS_RIGHT, eid, "TotalSupply").unwrap();
+    let owner_balance = get_state::<U256>(&my_access_right, eid, "Balance").unwrap();
+    let other_balance = get_state::<U256>(&other_access_right, eid, "Balance").unwrap();
+    assert_eq!(actual_total_supply, U256::from(80u64)); // 100 - 20(burn)
+    assert_eq!(owner_balance, U256::from(70u64)); // 100 - 30(transfer)
+    assert_eq!(other_balance, U256::from(10u64)); // 30 - 20(burn)
 }
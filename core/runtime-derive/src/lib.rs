@@ -0,0 +1,70 @@
+//! Procedural macros for defining anonify runtimes.
+
+extern crate proc_macro;
+
+mod memory;
+mod runtime;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{parse_macro_input, spanned::Spanned, Error, ItemImpl};
+use crate::memory::MemDecls;
+
+/// Generate `CallKind`, `ViewKind`, `Runtime<G>`, `call_name_to_id`, `view_name_to_id`,
+/// `CALL_SCHEMA` and `VIEW_SCHEMA` from the functions defined in an `impl Runtime` block.
+///
+/// Functions marked `#[view]` are read-only queries. They declare the type of the query result
+/// and return it wrapped in `Ok`, never producing updated states.
+///
+/// ```ignore
+/// #[anonify_runtime::runtime]
+/// impl Runtime {
+///     /// Mint the initial supply to the sender.
+///     #[fn_id = 0]
+///     pub fn construct(self, sender: UserAddress, total_supply: U64) {
+///         let sender_balance = update!(sender, "Balance", total_supply);
+///         insert![sender_balance]
+///     }
+///
+///     #[view]
+///     #[fn_id = 0]
+///     pub fn balance_of(self, caller: UserAddress) -> U64 {
+///         self.get_map::<U64>(caller, "Balance")
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn runtime(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(
+            TokenStream2::from(attr).span(),
+            "`#[anonify_runtime::runtime]` does not take any arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let item = parse_macro_input!(item as ItemImpl);
+    runtime::parse_runtime(item)
+        .map(|fns| runtime::expand_runtime(&fns))
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Generate `mem_name_to_id`, `ensure_mem_size`, `MEM_SCHEMA` and `MAX_MEM_SIZE`
+/// from `(id, name, type, max encoded size)` entries.
+/// Duplicate ids or names are rejected at compile time.
+///
+/// ```ignore
+/// impl_memory! {
+///     (0, "Balance", U64, 8),
+///     (1, "Owner", UserAddress, 20)
+/// }
+/// ```
+#[proc_macro]
+pub fn impl_memory(input: TokenStream) -> TokenStream {
+    let decls = parse_macro_input!(input as MemDecls);
+    memory::expand_memory(&decls)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
@@ -0,0 +1,128 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use std::collections::HashMap;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Error, LitInt, LitStr, Result, Token, Type,
+};
+
+/// A memory entry declared as `(id, name, type, max encoded size)`.
+pub struct MemDecl {
+    id: LitInt,
+    name: LitStr,
+    ty: Type,
+    max_size: LitInt,
+}
+
+impl Parse for MemDecl {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let content;
+        parenthesized!(content in input);
+        let id = content.parse()?;
+        content.parse::<Token![,]>()?;
+        let name = content.parse()?;
+        content.parse::<Token![,]>()?;
+        let ty = content.parse()?;
+        content.parse::<Token![,]>()?;
+        let max_size = content.parse()?;
+        content.parse::<Option<Token![,]>>()?;
+
+        Ok(MemDecl { id, name, ty, max_size })
+    }
+}
+
+pub struct MemDecls(Punctuated<MemDecl, Token![,]>);
+
+impl Parse for MemDecls {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Punctuated::parse_terminated(input).map(MemDecls)
+    }
+}
+
+pub fn expand_memory(decls: &MemDecls) -> Result<TokenStream2> {
+    if decls.0.is_empty() {
+        return Err(Error::new(proc_macro2::Span::call_site(), "at least one memory must be declared"));
+    }
+
+    let mut seen_ids: HashMap<u32, String> = HashMap::new();
+    let mut seen_names: HashMap<String, u32> = HashMap::new();
+    let mut ids = vec![];
+    let mut max_sizes = vec![];
+    for decl in &decls.0 {
+        let id = decl.id.base10_parse::<u32>()?;
+        let name = decl.name.value();
+        let max_size = decl.max_size.base10_parse::<usize>()?;
+
+        if let Some(prev) = seen_ids.get(&id) {
+            return Err(Error::new(
+                decl.id.span(),
+                format!("mem id {} is already used by \"{}\"", id, prev),
+            ));
+        }
+        if let Some(prev) = seen_names.get(&name) {
+            return Err(Error::new(
+                decl.name.span(),
+                format!("mem name \"{}\" is already declared with id {}", name, prev),
+            ));
+        }
+        if max_size == 0 {
+            return Err(Error::new(decl.max_size.span(), "max size must be greater than zero"));
+        }
+
+        seen_ids.insert(id, name.clone());
+        seen_names.insert(name, id);
+        ids.push(id);
+        max_sizes.push(max_size);
+    }
+
+    let max_mem_size = *max_sizes.iter().max().expect("declarations are not empty");
+    let names = decls.0.iter().map(|d| &d.name).collect::<Vec<_>>();
+    let tys = decls.0.iter()
+        .map(|d| {
+            let ty = &d.ty;
+            quote!(#ty).to_string().replace(' ', "")
+        })
+        .collect::<Vec<_>>();
+
+    Ok(quote! {
+        pub fn mem_name_to_id(name: &str) -> Result<MemId, ::anonify_runtime::RuntimeError> {
+            match name {
+                #( #names => Ok(MemId::from_raw(#ids)), )*
+                _ => Err(::anonify_runtime::RuntimeError::UnknownMemName(name.into())),
+            }
+        }
+
+        /// Check that the encoded size of a state does not exceed the declared max size.
+        /// Not applied by `update!`, since the declared sizes only bound `MAX_MEM_SIZE`.
+        pub fn ensure_mem_size(name: &str, size: usize) -> Result<(), ::anonify_runtime::RuntimeError> {
+            let max_size = match name {
+                #( #names => #max_sizes, )*
+                _ => return Err(::anonify_runtime::RuntimeError::UnknownMemName(name.into())),
+            };
+            if size > max_size {
+                return Err(::anonify_runtime::RuntimeError::ExceedsMaxSize {
+                    mem_name: name.into(),
+                    size,
+                    max_size,
+                });
+            }
+            Ok(())
+        }
+
+        /// Schema of all declared memories.
+        pub const MEM_SCHEMA: &[::anonify_runtime::MemSchema] = &[
+            #( ::anonify_runtime::MemSchema {
+                id: #ids,
+                name: #names,
+                ty: #tys,
+                max_size: #max_sizes,
+            }, )*
+        ];
+
+        /// Maximum encoded size among all declared memories.
+        pub const MAX_MEM_SIZE: usize = #max_mem_size;
+    })
+}
+
@@ -0,0 +1,363 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use std::collections::HashMap;
+use syn::{
+    spanned::Spanned, Attribute, Block, Error, FnArg, Ident, ImplItem, ItemImpl, Lit, Meta, Pat,
+    Receiver, Result, ReturnType, Type, Visibility,
+};
+
+/// A state transition or view function parsed from the annotated `impl` block.
+pub struct RuntimeFn {
+    id: u32,
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    receiver: Receiver,
+    sender: Ident,
+    sender_ty: Box<Type>,
+    params: Vec<(Ident, Box<Type>)>,
+    /// The type of the query result, only for view functions.
+    output: Option<Box<Type>>,
+    block: Block,
+}
+
+impl RuntimeFn {
+    fn is_view(&self) -> bool {
+        self.output.is_some()
+    }
+}
+
+pub fn parse_runtime(item: ItemImpl) -> Result<Vec<RuntimeFn>> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new(path.span(), "runtime must be an inherent `impl Runtime` block"));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(item.generics.span(), "runtime must not have generic parameters"));
+    }
+    match &*item.self_ty {
+        Type::Path(p) if p.qself.is_none() && p.path.is_ident("Runtime") => {}
+        ty => return Err(Error::new(ty.span(), "expected `impl Runtime`")),
+    }
+
+    let mut fns = vec![];
+    // State transition functions and view functions have separate id spaces.
+    let mut call_ids: HashMap<u32, Ident> = HashMap::new();
+    let mut view_ids: HashMap<u32, Ident> = HashMap::new();
+    for impl_item in item.items {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "only state transition and view functions are allowed in runtime",
+                ))
+            }
+        };
+        let sig = method.sig;
+
+        if !sig.generics.params.is_empty() {
+            return Err(Error::new(sig.generics.span(), "runtime functions must not be generic"));
+        }
+        if let Some(asyncness) = sig.asyncness {
+            return Err(Error::new(asyncness.span(), "runtime functions must not be async"));
+        }
+
+        let mut id = None;
+        let mut is_view = false;
+        let mut attrs = vec![];
+        for attr in method.attrs {
+            if attr.path.is_ident("view") {
+                if !attr.tokens.is_empty() {
+                    return Err(Error::new(attr.tokens.span(), "`#[view]` does not take any arguments"));
+                }
+                is_view = true;
+                continue;
+            }
+            if !attr.path.is_ident("fn_id") {
+                attrs.push(attr);
+                continue;
+            }
+            if id.is_some() {
+                return Err(Error::new(attr.span(), "duplicate `#[fn_id]` attribute"));
+            }
+            let lit = match attr.parse_meta()? {
+                Meta::NameValue(nv) => match nv.lit {
+                    Lit::Int(lit) => lit,
+                    lit => return Err(Error::new(lit.span(), "`fn_id` must be an integer")),
+                },
+                meta => return Err(Error::new(meta.span(), "expected `#[fn_id = N]`")),
+            };
+            id = Some((lit.base10_parse::<u32>()?, lit));
+        }
+        let (id, lit) = id.ok_or_else(|| {
+            Error::new(sig.ident.span(), "missing `#[fn_id = N]` attribute")
+        })?;
+
+        let ids = if is_view { &mut view_ids } else { &mut call_ids };
+        if let Some(prev) = ids.get(&id) {
+            return Err(Error::new(
+                lit.span(),
+                format!("fn_id {} is already used by `{}`", id, prev),
+            ));
+        }
+        ids.insert(id, sig.ident.clone());
+
+        let output = match (is_view, sig.output) {
+            (false, ReturnType::Default) => None,
+            (false, ReturnType::Type(_, ty)) => {
+                return Err(Error::new(
+                    ty.span(),
+                    "the return type of state transition functions is generated by `#[anonify_runtime::runtime]`",
+                ))
+            }
+            (true, ReturnType::Type(_, ty)) => Some(ty),
+            (true, ReturnType::Default) => {
+                return Err(Error::new(
+                    sig.paren_token.span,
+                    "view functions must declare the type of the query result, e.g. `-> U64`",
+                ))
+            }
+        };
+
+        let mut inputs = sig.inputs.into_iter();
+        let receiver = match inputs.next() {
+            Some(FnArg::Receiver(r)) if r.reference.is_none() && r.mutability.is_none() => r,
+            Some(arg) => return Err(Error::new(arg.span(), "the first parameter must be `self`")),
+            None => return Err(Error::new(sig.ident.span(), "the first parameter must be `self`")),
+        };
+        let (sender, sender_ty) = match inputs.next() {
+            Some(arg) => parse_param(arg)?,
+            None => {
+                return Err(Error::new(
+                    sig.paren_token.span,
+                    "the second parameter must be the sender, e.g. `sender: UserAddress`",
+                ))
+            }
+        };
+        let params = inputs.map(parse_param).collect::<Result<Vec<_>>>()?;
+
+        fns.push(RuntimeFn {
+            id,
+            attrs,
+            vis: method.vis,
+            name: sig.ident,
+            receiver,
+            sender,
+            sender_ty,
+            params,
+            output,
+            block: method.block,
+        });
+    }
+
+    Ok(fns)
+}
+
+fn parse_param(arg: FnArg) -> Result<(Ident, Box<Type>)> {
+    match arg {
+        FnArg::Typed(pat_ty) => match *pat_ty.pat {
+            Pat::Ident(ref pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {
+                Ok((pat.ident.clone(), pat_ty.ty))
+            }
+            ref pat => Err(Error::new(pat.span(), "parameters must be plain identifiers")),
+        },
+        FnArg::Receiver(r) => Err(Error::new(r.span(), "unexpected `self` parameter")),
+    }
+}
+
+fn expand_schema<'a>(fns: impl Iterator<Item=&'a RuntimeFn>) -> Vec<TokenStream2> {
+    fns.map(|f| {
+        let name = f.name.to_string();
+        let id = f.id;
+        let params = f.params.iter().map(|(p, ty)| {
+            let p = p.to_string();
+            let ty = quote!(#ty).to_string().replace(' ', "");
+            quote! { ::anonify_runtime::ParamSchema { name: #p, ty: #ty } }
+        });
+        quote! {
+            ::anonify_runtime::CallSchema {
+                name: #name,
+                id: #id,
+                params: &[ #( #params ),* ],
+            }
+        }
+    })
+    .collect()
+}
+
+fn expand_dispatches<'a>(enum_name: &Ident, fns: impl Iterator<Item=&'a RuntimeFn>) -> Vec<TokenStream2> {
+    fns.map(|f| {
+        let name = &f.name;
+        let fields = f.params.iter().map(|(p, _)| p);
+        let call = quote! {
+            self.#name(
+                caller,
+                #( params.#fields, )*
+            )
+        };
+        if f.is_view() {
+            quote! { #enum_name::#name(params) => #call.map(|r| State::as_bytes(&r)), }
+        } else {
+            quote! { #enum_name::#name(params) => #call, }
+        }
+    })
+    .collect()
+}
+
+pub fn expand_runtime(fns: &[RuntimeFn]) -> TokenStream2 {
+    let params_structs = fns.iter().map(|f| {
+        let name = &f.name;
+        let docs = f.attrs.iter().filter(|a| a.path.is_ident("doc"));
+        let fields = f.params.iter().map(|(p, ty)| quote! { pub #p: #ty, });
+        quote! {
+            #( #docs )*
+            #[allow(non_camel_case_types)]
+            #[derive(Encode, Decode, Debug, Clone, Default)]
+            pub struct #name {
+                #( #fields )*
+            }
+        }
+    });
+
+    let calls = fns.iter().filter(|f| !f.is_view()).collect::<Vec<_>>();
+    let call_names = calls.iter().map(|f| &f.name).collect::<Vec<_>>();
+    let call_ids = calls.iter().map(|f| f.id).collect::<Vec<_>>();
+    let call_name_strs = calls.iter().map(|f| f.name.to_string()).collect::<Vec<_>>();
+    let call_schemas = expand_schema(calls.iter().cloned());
+    let call_dispatches = expand_dispatches(&Ident::new("CallKind", proc_macro2::Span::call_site()), calls.iter().cloned());
+
+    let views = fns.iter().filter(|f| f.is_view()).collect::<Vec<_>>();
+    let view_names = views.iter().map(|f| &f.name).collect::<Vec<_>>();
+    let view_ids = views.iter().map(|f| f.id).collect::<Vec<_>>();
+    let view_name_strs = views.iter().map(|f| f.name.to_string()).collect::<Vec<_>>();
+    let view_schemas = expand_schema(views.iter().cloned());
+    let view_dispatches = expand_dispatches(&Ident::new("ViewKind", proc_macro2::Span::call_site()), views.iter().cloned());
+
+    let methods = fns.iter().map(|f| {
+        let RuntimeFn { attrs, vis, name, receiver, sender, sender_ty, block, .. } = f;
+        let params = f.params.iter().map(|(p, ty)| quote! { #p: #ty });
+        let output = match &f.output {
+            Some(ty) => quote! { Result<#ty> },
+            None => quote! { Result<Vec<UpdatedState<StateType>>> },
+        };
+        quote! {
+            #( #attrs )*
+            #vis fn #name(
+                #receiver,
+                #sender: #sender_ty
+                #(, #params )*
+            ) -> #output #block
+        }
+    });
+
+    quote! {
+        #( #params_structs )*
+
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Encode, Decode)]
+        pub enum CallKind {
+            #( #call_names(#call_names), )*
+        }
+
+        impl CallKind {
+            pub fn from_call_id(id: u32, state: &mut [u8]) -> Result<Self, ::anonify_runtime::RuntimeError> {
+                match id {
+                    #( #call_ids => {
+                        let params = #call_names::from_bytes(state)
+                            .map_err(|_| ::anonify_runtime::RuntimeError::DecodeParams(id))?;
+                        Ok(CallKind::#call_names(params))
+                    }, )*
+                    _ => Err(::anonify_runtime::RuntimeError::InvalidCallId(id)),
+                }
+            }
+        }
+
+        pub fn call_name_to_id(name: &str) -> Result<u32, ::anonify_runtime::RuntimeError> {
+            match name {
+                #( #call_name_strs => Ok(#call_ids), )*
+                _ => Err(::anonify_runtime::RuntimeError::UnknownCallName(name.into())),
+            }
+        }
+
+        /// Read-only queries, which are never broadcasted to other members.
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone)]
+        pub enum ViewKind {
+            #( #view_names(#view_names), )*
+        }
+
+        impl ViewKind {
+            pub fn from_view_id(id: u32, params: &mut [u8]) -> Result<Self, ::anonify_runtime::RuntimeError> {
+                match id {
+                    #( #view_ids => {
+                        let params = #view_names::from_bytes(params)
+                            .map_err(|_| ::anonify_runtime::RuntimeError::DecodeParams(id))?;
+                        Ok(ViewKind::#view_names(params))
+                    }, )*
+                    _ => Err(::anonify_runtime::RuntimeError::InvalidViewId(id)),
+                }
+            }
+        }
+
+        pub fn view_name_to_id(name: &str) -> Result<u32, ::anonify_runtime::RuntimeError> {
+            match name {
+                #( #view_name_strs => Ok(#view_ids), )*
+                _ => Err(::anonify_runtime::RuntimeError::UnknownViewName(name.into())),
+            }
+        }
+
+        /// Schema of all state transition functions, for clients building call parameters.
+        pub const CALL_SCHEMA: &[::anonify_runtime::CallSchema] = &[ #( #call_schemas ),* ];
+
+        /// Schema of all view functions, for clients building query parameters.
+        pub const VIEW_SCHEMA: &[::anonify_runtime::CallSchema] = &[ #( #view_schemas ),* ];
+
+        pub struct Runtime<G: StateGetter> {
+            db: G,
+        }
+
+        impl<G: StateGetter> Runtime<G> {
+            pub fn new(db: G) -> Self {
+                Runtime {
+                    db,
+                }
+            }
+
+            pub fn get_map<S: State>(
+                &self,
+                key: UserAddress,
+                name: &str
+            ) -> Result<S> {
+                self.db.get(key, name)
+            }
+
+            pub fn get<S: State>(&self, name: &str) -> Result<S> {
+                self.db.get(name, name)
+            }
+
+            pub fn call(
+                self,
+                kind: CallKind,
+                caller: UserAddress,
+            ) -> Result<Vec<UpdatedState<StateType>>> {
+                match kind {
+                    #( #call_dispatches )*
+                }
+            }
+
+            /// Run a view function and return the encoded result.
+            #[allow(unused_variables)]
+            pub fn query(
+                self,
+                kind: ViewKind,
+                caller: UserAddress,
+            ) -> Result<Vec<u8>> {
+                match kind {
+                    #( #view_dispatches )*
+                }
+            }
+
+            #( #methods )*
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::localstd::{
+    fmt,
+    string::String,
+};
+
+/// Errors raised by the functions generated from `impl_memory!` and `#[anonify_runtime::runtime]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// The memory name is not declared in `impl_memory!`.
+    UnknownMemName(String),
+    /// The call name is not declared in `#[anonify_runtime::runtime]`.
+    UnknownCallName(String),
+    /// The call id is not declared in `#[anonify_runtime::runtime]`.
+    InvalidCallId(u32),
+    /// The view name is not declared in `#[anonify_runtime::runtime]`.
+    UnknownViewName(String),
+    /// The view id is not declared in `#[anonify_runtime::runtime]`.
+    InvalidViewId(u32),
+    /// Failed to decode the parameters of the call with the given id.
+    DecodeParams(u32),
+    /// A condition in a state transition function was not satisfied.
+    Ensure(String),
+    /// The encoded state exceeds the max size declared in `impl_memory!`.
+    ExceedsMaxSize {
+        mem_name: String,
+        size: usize,
+        max_size: usize,
+    },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::UnknownMemName(name) => write!(f, "Unknown memory name: {}", name),
+            RuntimeError::UnknownCallName(name) => write!(f, "Unknown call name: {}", name),
+            RuntimeError::InvalidCallId(id) => write!(f, "Invalid call id: {}", id),
+            RuntimeError::UnknownViewName(name) => write!(f, "Unknown view name: {}", name),
+            RuntimeError::InvalidViewId(id) => write!(f, "Invalid view id: {}", id),
+            RuntimeError::DecodeParams(id) => write!(f, "Failed to decode parameters of call id: {}", id),
+            RuntimeError::Ensure(msg) => write!(f, "{}", msg),
+            RuntimeError::ExceedsMaxSize { mem_name, size, max_size } => write!(
+                f, "Encoded size of {} is {} bytes, exceeding max size {} bytes", mem_name, size, max_size
+            ),
+        }
+    }
+}
+
+#[cfg(any(feature = "std", feature = "sgx"))]
+impl crate::localstd::error::Error for RuntimeError {}
@@ -1,163 +1,151 @@
 #[macro_export]
-macro_rules! impl_memory {
-    ( $( $t:tt )* ) => {
-        $crate::__impl_inner_memory!(@normalize $( $t )* );
-    };
+macro_rules! update {
+    ($addr:expr, $mem_name:expr, $value:expr) => {{
+        UpdatedState::new($addr, mem_name_to_id($mem_name)?, $value)
+    }};
+
+    ($mem_name:expr, $value:expr) => {{
+        UpdatedState::new($mem_name, mem_name_to_id($mem_name)?, $value)
+    }};
 }
 
+/// Return early with `RuntimeError::Ensure` if the condition is not satisfied.
 #[macro_export]
-macro_rules! __impl_inner_memory {
-    (@normalize
-        $( ($id:expr, $name:expr, Address => $value:ty) ),*
-    ) => {
-        $crate::__impl_inner_memory!(@normalize $( ($id, $name, $value) ),* );
-    };
-
-    (@normalize
-        $( ($id:expr, $name:expr, $value:ty) ),*
-    ) => {
-        $crate::__impl_inner_memory!(@imp $( ($id, $name, $value) ),* );
-    };
-
-    (@imp
-        $( ($id:expr, $name:expr, $value:ty) ),*
-    ) => {
-        pub fn mem_name_to_id(name: &str) -> MemId {
-            match name {
-                $( $name => MemId::from_raw($id), )*
-                _ => panic!("invalid mem name"),
-            }
-        }
-
-        /// Return maximum size of mem values
-        fn max_size() -> usize {
-            *[ $( <$value>::default().size(), )* ]
-                .into_iter()
-                .max()
-                .expect("Iterator should not be empty.")
+macro_rules! ensure {
+    ($cond:expr, $msg:expr) => {
+        if !($cond) {
+            return Err($crate::RuntimeError::Ensure($msg.into()).into());
         }
     };
 }
 
 #[macro_export]
-macro_rules! impl_runtime {
-    (
-        $( $t:tt )*
-    ) => {
-        $crate::__impl_inner_runtime!(@imp
-            $($t)*
-        );
+macro_rules! insert {
+    ( $($update:expr),* ) => {
+        Ok(vec![$( $update),* ])
     };
 }
 
-#[macro_export]
-macro_rules! __impl_inner_runtime {
-    (@imp
-        $(
-            #[fn_id=$fn_id:expr]
-            pub fn $fn_name:ident(
-                $runtime:ident,
-                $sender:ident : $address:ty
-                $(, $param_name:ident : $param:ty )*
-            ) {
-                $( $impl:tt )*
-            }
-        )*
-    ) => {
-        $(
-            #[derive(Encode, Decode, Debug, Clone, Default)]
-            pub struct $fn_name {
-                $( pub $param_name: $param, )*
-            }
-        )*
-
-        #[derive(Debug, Clone, Encode, Decode)]
-        pub enum CallKind {
-            $( $fn_name($fn_name), )*
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::schema::{ParamSchema, MemSchema, schema_to_json};
+    use crate::state_type::{U64, StateType};
+    use anonify_common::UserAddress;
+    use codec::{Encode, Decode};
+
+    impl_memory! {
+        (0, "Balance", U64, 8),
+        (1, "Owner", UserAddress, 20)
+    }
+
+    #[anonify_runtime::runtime]
+    impl Runtime {
+        /// Mint the total supply to the sender.
+        #[fn_id=0]
+        pub fn construct(
+            self,
+            sender: UserAddress,
+            total_supply: U64
+        ) {
+            ensure!(total_supply > U64::zero(), "total supply must be positive.");
+            let sender_balance = update!(sender, "Balance", total_supply);
+            insert![sender_balance]
         }
 
-        impl CallKind {
-            pub fn from_call_id(id: u32, state: &mut [u8]) -> Result<Self> {
-                match id {
-                    $( $fn_id => Ok(CallKind::$fn_name($fn_name::from_bytes(state)?)), )*
-                    _ => return Err(anyhow!("Invalid Call ID")),
-                }
-            }
+        #[view]
+        #[fn_id=0]
+        pub fn balance_of(
+            self,
+            caller: UserAddress,
+            owner: UserAddress
+        ) -> U64 {
+            ensure!(caller == owner, "only the owner can query the balance.");
+            self.get_map::<U64>(owner, "Balance")
         }
+    }
 
-        pub fn call_name_to_id(name: &str) -> u32 {
-            match name {
-                $( stringify!($fn_name) => $fn_id, )*
-                _ => panic!("invalid call name"),
-            }
-        }
+    struct TestDB;
 
-        pub struct Runtime<G: StateGetter> {
-            db: G,
+    impl StateGetter for TestDB {
+        fn get<S: State>(&self, _key: impl Into<UserAddress>, _name: &str) -> Result<S> {
+            S::from_state(&U64::from_raw(10))
         }
 
-        impl<G: StateGetter> Runtime<G> {
-            pub fn new(db: G) -> Self {
-                Runtime {
-                    db,
-                }
-            }
-
-            pub fn get_map<S: State>(
-                &self,
-                key: UserAddress,
-                name: &str
-            ) -> Result<S> {
-                self.db.get(key, name)
-            }
-
-            pub fn get<S: State>(&self, name: &str) -> Result<S> {
-                self.db.get(name, name)
-            }
-
-            pub fn call(
-                self,
-                kind: CallKind,
-                my_addr: UserAddress,
-            ) -> Result<Vec<UpdatedState<StateType>>> {
-                match kind {
-                    $( CallKind::$fn_name($fn_name) => {
-                        self.$fn_name(
-                            my_addr,
-                            $( $fn_name.$param_name, )*
-                        )
-                    }, )*
-                    _ => unimplemented!()
-                }
-            }
-
-            $(
-                pub fn $fn_name (
-                    $runtime,
-                    $sender: $address
-                    $(, $param_name : $param )*
-                ) -> Result<Vec<UpdatedState<StateType>>> {
-                    $( $impl )*
-                }
-            )*
+        fn get_by_id(&self, _key: UserAddress, _mem_id: MemId) -> StateType {
+            U64::from_raw(10).into()
         }
-    };
-}
-
-#[macro_export]
-macro_rules! update {
-    ($addr:expr, $mem_name:expr, $value:expr) => {
-        UpdatedState::new($addr, mem_name_to_id($mem_name), $value)
-    };
-
-    ($mem_name:expr, $value:expr) => {
-        UpdatedState::new($mem_name, mem_name_to_id($mem_name), $value)
-    };
-}
-
-#[macro_export]
-macro_rules! insert {
-    ( $($update:expr),* ) => {
-        Ok(vec![$( $update),* ])
-    };
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        assert_eq!(mem_name_to_id("Balance"), Ok(MemId::from_raw(0)));
+        assert_eq!(
+            mem_name_to_id("Balanse"),
+            Err(RuntimeError::UnknownMemName("Balanse".into()))
+        );
+        assert_eq!(call_name_to_id("construct"), Ok(0));
+        assert_eq!(
+            call_name_to_id("constract"),
+            Err(RuntimeError::UnknownCallName("constract".into()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_call_id() {
+        let mut params = construct { total_supply: U64::from_raw(100) }.encode();
+        assert!(CallKind::from_call_id(0, &mut params).is_ok());
+        assert_eq!(
+            CallKind::from_call_id(1, &mut params).unwrap_err(),
+            RuntimeError::InvalidCallId(1)
+        );
+        assert_eq!(
+            CallKind::from_call_id(0, &mut []).unwrap_err(),
+            RuntimeError::DecodeParams(0)
+        );
+    }
+
+    #[test]
+    fn test_mem_schema() {
+        assert_eq!(MAX_MEM_SIZE, 20);
+        assert_eq!(MEM_SCHEMA[0], MemSchema { id: 0, name: "Balance", ty: "U64", max_size: 8 });
+        assert_eq!(MEM_SCHEMA[1], MemSchema { id: 1, name: "Owner", ty: "UserAddress", max_size: 20 });
+
+        assert_eq!(ensure_mem_size("Balance", 8), Ok(()));
+        assert_eq!(
+            ensure_mem_size("Balance", 9),
+            Err(RuntimeError::ExceedsMaxSize { mem_name: "Balance".into(), size: 9, max_size: 8 })
+        );
+    }
+
+    #[test]
+    fn test_query() {
+        let owner = UserAddress::from_array([1; 20]);
+        let other = UserAddress::from_array([2; 20]);
+        let mut params = balance_of { owner }.encode();
+
+        let kind = ViewKind::from_view_id(0, &mut params).unwrap();
+        let mut res = Runtime::new(TestDB).query(kind.clone(), owner).unwrap();
+        assert_eq!(U64::from_bytes(&mut res).unwrap(), U64::from_raw(10));
+        assert!(Runtime::new(TestDB).query(kind, other).is_err());
+
+        assert_eq!(view_name_to_id("balance_of"), Ok(0));
+        assert_eq!(
+            ViewKind::from_view_id(1, &mut params).unwrap_err(),
+            RuntimeError::InvalidViewId(1)
+        );
+        assert_eq!(VIEW_SCHEMA[0].params, &[ParamSchema { name: "owner", ty: "UserAddress" }]);
+    }
+
+    #[test]
+    fn test_call_schema() {
+        assert_eq!(CALL_SCHEMA.len(), 1);
+        assert_eq!(CALL_SCHEMA[0].name, "construct");
+        assert_eq!(CALL_SCHEMA[0].id, 0);
+        assert_eq!(CALL_SCHEMA[0].params, &[ParamSchema { name: "total_supply", ty: "U64" }]);
+        assert_eq!(
+            schema_to_json(CALL_SCHEMA),
+            r#"[{"name":"construct","id":0,"params":[{"name":"total_supply","type":"U64"}]}]"#
+        );
+    }
 }
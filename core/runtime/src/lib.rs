@@ -12,8 +12,14 @@ use sgx_anyhow as local_anyhow;
 #[cfg(all(not(feature = "std"), not(feature = "sgx")))]
 extern crate core as localstd;
 
+// The code generated by `#[anonify_runtime::runtime]` and `impl_memory!` refers to `::anonify_runtime`,
+// which has to resolve in this crate's own tests too.
+extern crate self as anonify_runtime;
+
 pub mod state_type;
 pub mod impls;
+pub mod error;
+pub mod schema;
 pub mod utils;
 pub mod prelude;
 pub mod traits;
@@ -21,3 +27,6 @@ pub mod traits;
 pub use crate::state_type::*;
 pub use crate::traits::*;
 pub use crate::utils::*;
+pub use crate::error::RuntimeError;
+pub use crate::schema::{CallSchema, ParamSchema, MemSchema, schema_to_json};
+pub use anonify_runtime_derive::{runtime, impl_memory};
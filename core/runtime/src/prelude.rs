@@ -1,4 +1,5 @@
-pub use crate::{impl_memory, __impl_inner_memory, impl_runtime, __impl_inner_runtime, update, insert};
+pub use crate::{impl_memory, update, insert, ensure};
 pub use crate::utils::{MemId, UpdatedState};
 pub use crate::traits::{State, StateGetter};
-pub use crate::local_anyhow::{ensure, Result, anyhow};
+pub use crate::error::RuntimeError;
+pub use crate::local_anyhow::{Result, anyhow};
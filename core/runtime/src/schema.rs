@@ -0,0 +1,51 @@
+use crate::localstd::{
+    string::String,
+    fmt::Write,
+};
+
+/// Description of a state transition function generated by `#[anonify_runtime::runtime]`.
+/// Clients use it to build call parameters without hard-coding each application's calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSchema {
+    pub name: &'static str,
+    pub id: u32,
+    pub params: &'static [ParamSchema],
+}
+
+/// A parameter of a state transition function, except for the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamSchema {
+    pub name: &'static str,
+    pub ty: &'static str,
+}
+
+/// Description of a memory declared in `impl_memory!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemSchema {
+    pub id: u32,
+    pub name: &'static str,
+    pub ty: &'static str,
+    /// Maximum encoded size of the state in bytes.
+    pub max_size: usize,
+}
+
+/// Serialize call schemas into a JSON array like
+/// `[{"name":"transfer","id":1,"params":[{"name":"amount","type":"U64"}]}]`.
+pub fn schema_to_json(calls: &[CallSchema]) -> String {
+    let mut json = String::from("[");
+    for (i, call) in calls.iter().enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+        let _ = write!(json, r#"{{"name":"{}","id":{},"params":["#, call.name, call.id);
+        for (j, param) in call.params.iter().enumerate() {
+            if j != 0 {
+                json.push(',');
+            }
+            let _ = write!(json, r#"{{"name":"{}","type":"{}"}}"#, param.name, param.ty);
+        }
+        json.push_str("]}");
+    }
+    json.push(']');
+    json
+}
@@ -2,19 +2,16 @@ use crate::traits::State;
 use crate::localstd::{
     vec::Vec,
     collections::BTreeMap,
-    ops::{Add, Sub, Mul, Div},
     convert::TryFrom,
+    cmp::Ordering,
     mem::size_of,
 };
 use crate::local_anyhow::{Result, Error, anyhow};
 use anonify_common::UserAddress;
 use codec::{Encode, Decode};
 
-macro_rules! impl_uint {
-    ($name:ident, $raw:ident) => {
-        #[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
-        pub struct $name($raw);
-
+macro_rules! impl_state_conversions {
+    ($name:ident) => {
         impl TryFrom<Vec<u8>> for $name {
             type Error = Error;
 
@@ -55,54 +52,71 @@ macro_rules! impl_uint {
                 $name::from_bytes(&mut buf)
             }
         }
+    };
+}
 
-        impl Add for $name {
-            type Output = $name;
+macro_rules! impl_uint {
+    ($name:ident, $raw:ident) => {
+        #[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+        pub struct $name($raw);
+
+        impl_state_conversions!($name);
 
-            fn add(self, other: Self) -> Self {
-                let r = self.0 + other.0;
-                $name(r)
+        impl $name {
+            pub fn as_raw(&self) -> $raw {
+                self.0
             }
-        }
 
-        impl Sub for $name {
-            type Output = $name;
+            pub fn from_raw(u: $raw) -> Self {
+                $name(u)
+            }
 
-            fn sub(self, other: Self) -> Self {
-                let r = self.0 - other.0;
-                $name(r)
+            pub fn zero() -> Self {
+                $name(0)
             }
-        }
 
-        impl Mul<$name> for $name {
-            type Output = $name;
+            pub fn max_value() -> Self {
+                $name($raw::max_value())
+            }
 
-            fn mul(self, rhs: Self) -> Self {
-                let r = self.0 * rhs.0;
-                $name(r)
+            /// Addition which returns an error instead of overflowing.
+            pub fn checked_add(self, other: Self) -> Result<Self> {
+                self.0.checked_add(other.0)
+                    .map($name)
+                    .ok_or_else(|| anyhow!("{} overflow: {:?} + {:?}", stringify!($name), self, other))
             }
-        }
 
-        impl Div<$name> for $name {
-            type Output = $name;
+            /// Subtraction which returns an error instead of underflowing.
+            pub fn checked_sub(self, other: Self) -> Result<Self> {
+                self.0.checked_sub(other.0)
+                    .map($name)
+                    .ok_or_else(|| anyhow!("{} underflow: {:?} - {:?}", stringify!($name), self, other))
+            }
 
-            fn div(self, rhs: Self) -> Self {
-                let r = self.0 / rhs.0;
-                $name(r)
+            /// Multiplication which returns an error instead of overflowing.
+            pub fn checked_mul(self, other: Self) -> Result<Self> {
+                self.0.checked_mul(other.0)
+                    .map($name)
+                    .ok_or_else(|| anyhow!("{} overflow: {:?} * {:?}", stringify!($name), self, other))
             }
-        }
 
-        impl $name {
-            pub fn as_raw(&self) -> $raw {
-                self.0
+            /// Division which returns an error if the divisor is zero.
+            pub fn checked_div(self, other: Self) -> Result<Self> {
+                self.0.checked_div(other.0)
+                    .map($name)
+                    .ok_or_else(|| anyhow!("{} division by zero: {:?} / {:?}", stringify!($name), self, other))
             }
 
-            pub fn from_raw(u: $raw) -> Self {
-                $name(u)
+            pub fn saturating_add(self, other: Self) -> Self {
+                $name(self.0.saturating_add(other.0))
             }
 
-            pub fn zero() -> Self {
-                $name(0)
+            pub fn saturating_sub(self, other: Self) -> Self {
+                $name(self.0.saturating_sub(other.0))
+            }
+
+            pub fn saturating_mul(self, other: Self) -> Self {
+                $name(self.0.saturating_mul(other.0))
             }
         }
     };
@@ -111,6 +125,206 @@ macro_rules! impl_uint {
 impl_uint!(U16, u16);
 impl_uint!(U32, u32);
 impl_uint!(U64, u64);
+impl_uint!(U128, u128);
+
+const U256_LIMBS: usize = 4;
+
+/// 256-bit unsigned integer, e.g. for mirroring on-chain ERC20 balances.
+/// Stored as four `u64` limbs, least significant first.
+#[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; U256_LIMBS]);
+
+impl_state_conversions!(U256);
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(u: u64) -> Self {
+        U256([u, 0, 0, 0])
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(u: u128) -> Self {
+        U256([u as u64, (u >> 64) as u64, 0, 0])
+    }
+}
+
+impl U256 {
+    pub fn from_limbs(limbs: [u64; U256_LIMBS]) -> Self {
+        U256(limbs)
+    }
+
+    pub fn as_limbs(&self) -> [u64; U256_LIMBS] {
+        self.0
+    }
+
+    /// Build from 32 big-endian bytes, as used by Ethereum's `uint256`.
+    pub fn from_big_endian(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; U256_LIMBS];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let mut buf = [0u8; 8];
+            let start = 32 - (i + 1) * 8;
+            buf.copy_from_slice(&bytes[start..start + 8]);
+            *limb = u64::from_be_bytes(buf);
+        }
+        U256(limbs)
+    }
+
+    pub fn to_big_endian(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().enumerate() {
+            let start = 32 - (i + 1) * 8;
+            bytes[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Returns the value as `u128`, or an error if it doesn't fit.
+    pub fn try_as_u128(&self) -> Result<u128> {
+        if self.0[2] != 0 || self.0[3] != 0 {
+            return Err(anyhow!("{:?} exceeds u128 range", self));
+        }
+        Ok(((self.0[1] as u128) << 64) | self.0[0] as u128)
+    }
+
+    pub fn zero() -> Self {
+        U256([0; U256_LIMBS])
+    }
+
+    pub fn max_value() -> Self {
+        U256([u64::max_value(); U256_LIMBS])
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&limb| limb == 0)
+    }
+
+    fn overflowing_add(self, other: Self) -> (Self, bool) {
+        let mut res = [0u64; U256_LIMBS];
+        let mut carry = false;
+        for i in 0..U256_LIMBS {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            res[i] = sum;
+            carry = c1 || c2;
+        }
+        (U256(res), carry)
+    }
+
+    fn overflowing_sub(self, other: Self) -> (Self, bool) {
+        let mut res = [0u64; U256_LIMBS];
+        let mut borrow = false;
+        for i in 0..U256_LIMBS {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            res[i] = diff;
+            borrow = b1 || b2;
+        }
+        (U256(res), borrow)
+    }
+
+    fn overflowing_mul(self, other: Self) -> (Self, bool) {
+        let mut res = [0u64; U256_LIMBS * 2];
+        for i in 0..U256_LIMBS {
+            let mut carry = 0u128;
+            for j in 0..U256_LIMBS {
+                let cur = res[i + j] as u128
+                    + (self.0[i] as u128) * (other.0[j] as u128)
+                    + carry;
+                res[i + j] = cur as u64;
+                carry = cur >> 64;
+            }
+            res[i + U256_LIMBS] = carry as u64;
+        }
+
+        let mut low = [0u64; U256_LIMBS];
+        low.copy_from_slice(&res[..U256_LIMBS]);
+        let overflow = res[U256_LIMBS..].iter().any(|&limb| limb != 0);
+        (U256(low), overflow)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        (self.0[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    fn shl1(self) -> Self {
+        let mut res = [0u64; U256_LIMBS];
+        let mut carry = 0u64;
+        for i in 0..U256_LIMBS {
+            res[i] = (self.0[i] << 1) | carry;
+            carry = self.0[i] >> 63;
+        }
+        U256(res)
+    }
+
+    /// Addition which returns an error instead of overflowing.
+    pub fn checked_add(self, other: Self) -> Result<Self> {
+        match self.overflowing_add(other) {
+            (r, false) => Ok(r),
+            (_, true) => Err(anyhow!("U256 overflow: {:?} + {:?}", self, other)),
+        }
+    }
+
+    /// Subtraction which returns an error instead of underflowing.
+    pub fn checked_sub(self, other: Self) -> Result<Self> {
+        match self.overflowing_sub(other) {
+            (r, false) => Ok(r),
+            (_, true) => Err(anyhow!("U256 underflow: {:?} - {:?}", self, other)),
+        }
+    }
+
+    /// Multiplication which returns an error instead of overflowing.
+    pub fn checked_mul(self, other: Self) -> Result<Self> {
+        match self.overflowing_mul(other) {
+            (r, false) => Ok(r),
+            (_, true) => Err(anyhow!("U256 overflow: {:?} * {:?}", self, other)),
+        }
+    }
+
+    /// Division which returns an error if the divisor is zero.
+    pub fn checked_div(self, other: Self) -> Result<Self> {
+        if other.is_zero() {
+            return Err(anyhow!("U256 division by zero: {:?} / {:?}", self, other));
+        }
+
+        // Binary long division from the most significant bit.
+        let mut quotient = U256::zero();
+        let mut remainder = U256::zero();
+        for i in (0..U256_LIMBS * 64).rev() {
+            remainder = remainder.shl1();
+            remainder.0[0] |= self.bit(i) as u64;
+            if remainder >= other {
+                remainder = remainder.overflowing_sub(other).0;
+                quotient.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        Ok(quotient)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        self.checked_add(other).unwrap_or_else(|_| U256::max_value())
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        self.checked_sub(other).unwrap_or_else(|_| U256::zero())
+    }
+
+    pub fn saturating_mul(self, other: Self) -> Self {
+        self.checked_mul(other).unwrap_or_else(|_| U256::max_value())
+    }
+}
 
 #[derive(Encode, Decode, Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Bytes(Vec<u8>);
@@ -187,35 +401,80 @@ mod tests {
         assert_eq!(U16(0).size(), 2);
         assert_eq!(U32(0).size(), 4);
         assert_eq!(U64(0).size(), 8);
+        assert_eq!(U128(0).size(), 16);
+        assert_eq!(U256::zero().size(), 32);
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(U64(3).checked_add(U64(4)).unwrap(), U64(7));
+        assert!(U64::max_value().checked_add(U64(1)).is_err());
+        assert!(U64(3).checked_sub(U64(4)).is_err());
+        assert!(U16::max_value().checked_mul(U16(2)).is_err());
+        assert!(U32(1).checked_div(U32(0)).is_err());
+
+        assert_eq!(U64::max_value().saturating_add(U64(1)), U64::max_value());
+        assert_eq!(U64(3).saturating_sub(U64(4)), U64(0));
+        assert_eq!(U128::max_value().saturating_mul(U128(2)), U128::max_value());
+    }
+
+    #[test]
+    fn test_u256_arithmetic() {
+        let a = U256::from(u128::max_value());
+        let one = U256::from(1u64);
+        let sum = a.checked_add(one).unwrap();
+        assert_eq!(sum.as_limbs(), [0, 0, 1, 0]);
+        assert_eq!(sum.checked_sub(one).unwrap(), a);
+        assert!(sum > a);
+
+        let prod = a.checked_mul(a).unwrap();
+        assert_eq!(prod.checked_div(a).unwrap(), a);
+        assert_eq!(U256::from(100u64).checked_div(U256::from(7u64)).unwrap(), U256::from(14u64));
+
+        assert!(U256::max_value().checked_add(one).is_err());
+        assert!(U256::zero().checked_sub(one).is_err());
+        assert!(U256::max_value().checked_mul(U256::from(2u64)).is_err());
+        assert!(one.checked_div(U256::zero()).is_err());
+        assert!(sum.try_as_u128().is_err());
+    }
+
+    #[test]
+    fn test_u256_conversions() {
+        let u = U256::from_limbs([1, 2, 3, 4]);
+        assert_eq!(U256::from_big_endian(&u.to_big_endian()), u);
+        assert_eq!(U256::from_state(&u).unwrap(), u);
+
+        let state: StateType = u.into();
+        assert_eq!(U256::try_from(state).unwrap(), u);
     }
 }
 
 #[derive(Encode, Decode, Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
-pub struct Approved(BTreeMap<UserAddress, U64>);
+pub struct Approved(BTreeMap<UserAddress, U256>);
 
 impl Approved {
-    pub fn new(inner: BTreeMap<UserAddress, U64>) -> Self {
+    pub fn new(inner: BTreeMap<UserAddress, U256>) -> Self {
         Approved(inner)
     }
 
-    pub fn total(&self) -> U64 {
-        let sum = self.0.iter()
-            .fold(U64(0), |acc, (_, &amount)| acc + amount);
-        sum
+    pub fn total(&self) -> Result<U256> {
+        self.0.iter()
+            .try_fold(U256::zero(), |acc, (_, &amount)| acc.checked_add(amount))
     }
 
-    pub fn approve(&mut self, user_address: UserAddress, amount: U64) {
+    pub fn approve(&mut self, user_address: UserAddress, amount: U256) -> Result<()> {
         match self.allowance(&user_address) {
             Some(&existing_amount) => {
-                self.0.insert(user_address, existing_amount + amount);
+                self.0.insert(user_address, existing_amount.checked_add(amount)?);
             }
             None => {
                 self.0.insert(user_address, amount);
             }
         }
+        Ok(())
     }
 
-    pub fn consume(&mut self, user_address: UserAddress, amount: U64) -> Result<(), Error> {
+    pub fn consume(&mut self, user_address: UserAddress, amount: U256) -> Result<(), Error> {
         match self.allowance(&user_address) {
             Some(&existing_amount) => {
                 if existing_amount < amount {
@@ -225,19 +484,19 @@ impl Approved {
                      amount,
                      ).into());
                 }
-                self.0.insert(user_address, existing_amount - amount);
+                self.0.insert(user_address, existing_amount.checked_sub(amount)?);
                 Ok(())
             }
             None => return Err(anyhow!("{:?} doesn't have any balance.", user_address).into())
         }
     }
 
-    pub fn allowance(&self, user_address: &UserAddress) -> Option<&U64> {
+    pub fn allowance(&self, user_address: &UserAddress) -> Option<&U256> {
         self.0.get(user_address)
     }
 
     pub fn size(&self) -> usize {
-        self.0.len() * (UserAddress::default().size() + U64::default().size())
+        self.0.len() * (UserAddress::default().size() + U256::default().size())
     }
 }
 
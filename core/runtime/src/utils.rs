@@ -1,11 +1,12 @@
 use crate::traits::State;
-use crate::local_anyhow::Result;
-use crate::localstd::boxed::Box;
+use crate::local_anyhow::{Result, anyhow};
+use crate::localstd::vec::Vec;
+use crate::state_type::StateType;
 use anonify_common::UserAddress;
-use anonify_types::RawUpdatedState;
+use anonify_types::RawUpdatedStates;
 use codec::{Encode, Decode};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Encode, Decode)]
 pub struct UpdatedState<S: State> {
     pub address: UserAddress,
     pub mem_id: MemId,
@@ -26,19 +27,26 @@ impl<S: State> UpdatedState<S> {
     }
 }
 
-impl<S: State> From<RawUpdatedState> for UpdatedState<S> {
-    fn from(raw: RawUpdatedState) -> Self {
-        let box_state = raw.state as *mut Box<[u8]>;
-        let mut state = unsafe { Box::from_raw(box_state) };
-        let state = S::from_bytes(&mut state)
-            .expect("Failed to read raw pointer of state in RawUpdatedState");
-
-        UpdatedState {
-            address: UserAddress::from_array(raw.address),
-            mem_id: MemId::from_raw(raw.mem_id),
-            state,
-        }
+/// Read the list of updated states which the enclave saved in host memory.
+pub fn updated_states_from_raw<S: State>(raw: RawUpdatedStates) -> Result<Vec<UpdatedState<S>>> {
+    if raw.is_empty() {
+        return Ok(vec![]);
     }
+
+    let buf = raw.into_vec();
+    let states = Vec::<UpdatedState<StateType>>::decode(&mut &buf[..])
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+    states.into_iter()
+        .map(|s| {
+            let state = S::from_bytes(&mut s.state.into_bytes())?;
+            Ok(UpdatedState {
+                address: s.address,
+                mem_id: s.mem_id,
+                state,
+            })
+        })
+        .collect()
 }
 
 pub fn into_trait<S: State>(s: UpdatedState<impl State>) -> Result<UpdatedState<S>> {
@@ -63,3 +71,28 @@ impl MemId {
         MemId(u)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_type::U64;
+    use crate::localstd::boxed::Box;
+
+    #[test]
+    fn test_updated_states_from_raw() {
+        assert!(updated_states_from_raw::<U64>(RawUpdatedStates::default()).unwrap().is_empty());
+
+        let states: Vec<UpdatedState<StateType>> = vec![
+            UpdatedState::new(UserAddress::from_array([1; 20]), MemId::from_raw(0), U64::from_raw(10)),
+            UpdatedState::new(UserAddress::from_array([2; 20]), MemId::from_raw(0), U64::from_raw(20)),
+        ];
+        // Same layout as the bytes saved in host memory by the enclave.
+        let boxed = Box::new(states.encode().into_boxed_slice());
+        let raw = RawUpdatedStates { states: Box::into_raw(boxed) as *const u8 };
+
+        let res = updated_states_from_raw::<U64>(raw).unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].address, UserAddress::from_array([1; 20]));
+        assert_eq!(res[1].state, U64::from_raw(20));
+    }
+}
@@ -1,5 +1,7 @@
 use std::vec::Vec;
+use std::fmt;
 use std::convert::TryFrom;
+use std::collections::BTreeMap;
 use crate::group_state::GroupState;
 use crate::crypto::{
     hmac::HmacKey,
@@ -9,7 +11,7 @@ use crate::crypto::{
 };
 use crate::ratchet_tree::RatchetTreeNode;
 use anyhow::{Result, anyhow, ensure};
-use codec::Encode;
+use codec::{Encode, Decode};
 use log::info;
 use ring::aead::{
     OpeningKey, SealingKey, Nonce, UnboundKey, BoundKey,
@@ -17,10 +19,50 @@ use ring::aead::{
 };
 use anonify_app_preluder::Ciphertext;
 
+/// How many generations a message can be ahead of its sender's current generation,
+/// and how many generations behind it the secrets skipped by ratcheting are kept.
+/// Messages out of the window can't be decrypted.
+pub const MAX_SKIPPED_GENERATIONS: u32 = 32;
+
+/// An application message which can't be decrypted because its generation is out of the skipped-message window
+/// or its secret has already been used. Such a message is never decryptable later, so it can be skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkippedMessageError {
+    /// The generation is behind the window, or the message has already been decrypted.
+    Replayed { roster_idx: u32, generation: u32 },
+    /// The generation is more than `MAX_SKIPPED_GENERATIONS` ahead of the sender's current one.
+    AheadOfWindow { roster_idx: u32, generation: u32, current: u32 },
+}
+
+impl fmt::Display for SkippedMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkippedMessageError::Replayed { roster_idx, generation } => write!(
+                f,
+                "application message's generation {} from roster index {} is behind the skipped-message window or already used",
+                generation,
+                roster_idx
+            ),
+            SkippedMessageError::AheadOfWindow { roster_idx, generation, current } => write!(
+                f,
+                "application message's generation {} from roster index {} is ahead of the skipped-message window from {}",
+                generation,
+                roster_idx,
+                current
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SkippedMessageError {}
+
 /// Application Keychain manages each member's `AppMemberSecret' and generation.
 #[derive(Debug, Clone, Default)]
 pub struct AppKeyChain {
     member_secrets_and_gens: Vec<(AppMemberSecret, u32)>,
+    /// Secrets of the generations which were skipped by ratcheting past them, keyed by roster index and generation,
+    /// so that messages delivered out of order can still be decrypted once.
+    skipped_secrets: BTreeMap<(u32, u32), AppMemberSecret>,
     epoch: u32,
 }
 
@@ -31,7 +73,8 @@ impl AppKeyChain {
         mut plaintext: Vec<u8>,
         group_state: &GroupState
     ) -> Result<Ciphertext> {
-        let my_roster_idx = group_state.my_roster_idx();
+        let my_roster_idx = group_state.my_roster_idx()
+            .ok_or(anyhow!("Cannot encrypt messages before joining the group"))?;
 
         let (ub_key, nonce_seq, generation) = self.key_nonce_gen(my_roster_idx as usize)?;
         let mut sealing_key = SealingKey::new(ub_key, nonce_seq);
@@ -41,29 +84,34 @@ impl AppKeyChain {
         Ok(Ciphertext::new(generation, group_state.epoch(), my_roster_idx, ciphertext))
     }
 
-    /// Decrypt messag with current member's application secret.
+    /// Decrypt messag with the sender's application secret of the message's generation.
+    /// The sender's keychain is ratcheted past the generation, and a secret is never used for decryption twice.
     pub fn decrypt_msg(
         &mut self,
         app_msg: &Ciphertext,
         group_state: &GroupState,
     ) -> Result<Option<Vec<u8>>> {
-        match group_state.my_node()? {
+        match group_state.my_node() {
             // If current my node contains a DhKeypair, cannot decrypt message because you haven't join the group.
-            RatchetTreeNode::Blank => {
+            None | Some(RatchetTreeNode::Blank) => {
                 info!("The received message is ignored because your enclave hasn't join the group yet");
                 Ok(None)
             },
-            _ => {
+            Some(_) => {
                 ensure!(app_msg.epoch() == self.epoch, "application messages's epoch differs from the app key chain's");
 
-                let (ub_key, nonce_seq, generation) = self.key_nonce_gen(app_msg.roster_idx() as usize)?;
-                ensure!(app_msg.generation() == generation, "application messages's generation differs from the AppMemberSecret's");
+                let roster_idx = app_msg.roster_idx() as usize;
+                let generation = app_msg.generation();
+                let member_secret = self.member_secret_at(roster_idx, generation)?;
+                let (ub_key, nonce_seq) = Self::key_nonce(&member_secret)?;
 
                 let mut ciphertext = app_msg.encrypted_state_ref().to_vec();
                 let mut opening_key = OpeningKey::new(ub_key, nonce_seq);
-                let plaintext = opening_key.open_in_place(Aad::empty(), &mut ciphertext)?;
+                let plaintext = opening_key.open_in_place(Aad::empty(), &mut ciphertext)?.to_vec();
+                // The keychain is changed only after the message is authenticated.
+                self.consume_generation(roster_idx, generation)?;
 
-                Ok(Some(plaintext.to_vec()))
+                Ok(Some(plaintext))
             }
         }
     }
@@ -94,6 +142,7 @@ impl AppKeyChain {
 
         AppKeyChain {
             member_secrets_and_gens,
+            skipped_secrets: BTreeMap::new(),
             epoch: group_state.epoch(),
         }
     }
@@ -103,27 +152,132 @@ impl AppKeyChain {
         let (member_secret, gen) = self.member_secrets_and_gens
             .get_mut(roster_idx)
             .ok_or(anyhow!("ratchet: Roster index is out of range of application key chain"))?;
-        let current_secret = member_secret.clone();
 
-        let roster_idx = u32::try_from(roster_idx)?;
+        *member_secret = Self::next_member_secret(member_secret, u32::try_from(roster_idx)?)?;
+        *gen = gen.checked_add(1).ok_or(anyhow!("generation is over u32::MAX"))?;
+
+        Ok(())
+    }
+
+    fn next_member_secret(member_secret: &AppMemberSecret, roster_idx: u32) -> Result<AppMemberSecret> {
+        let mut next_secret = member_secret.clone();
         hkdf::expand_label(
-            &current_secret.into(),
+            &member_secret.into(),
             b"app sender",
             &roster_idx.encode(),
-            member_secret.as_mut_bytes(),
+            next_secret.as_mut_bytes(),
         )?;
 
-        *gen = gen.checked_add(1).ok_or(anyhow!("generation is over u32::MAX"))?;
+        Ok(next_secret)
+    }
+
+    /// Get the sender's secret of `generation` without changing the keychain.
+    /// A past generation's secret is found only if it was skipped and hasn't been used yet,
+    /// otherwise `SkippedMessageError` is returned.
+    fn member_secret_at(&self, roster_idx: usize, generation: u32) -> Result<AppMemberSecret> {
+        let (member_secret, gen) = self.member_secrets_and_gens
+            .get(roster_idx)
+            .ok_or(anyhow!("member_secret_at: Roster index is out of range of application key chain"))?;
+
+        let roster_idx_u32 = u32::try_from(roster_idx)?;
+        if generation < *gen {
+            return self.skipped_secrets
+                .get(&(roster_idx_u32, generation))
+                .cloned()
+                .ok_or_else(|| SkippedMessageError::Replayed { roster_idx: roster_idx_u32, generation }.into());
+        }
+        if generation - gen > MAX_SKIPPED_GENERATIONS {
+            return Err(SkippedMessageError::AheadOfWindow { roster_idx: roster_idx_u32, generation, current: *gen }.into());
+        }
+
+        let mut member_secret = member_secret.clone();
+        for _ in *gen..generation {
+            member_secret = Self::next_member_secret(&member_secret, roster_idx_u32)?;
+        }
+
+        Ok(member_secret)
+    }
+
+    /// Drop the secret of the decrypted generation. If it is ahead of the sender's current generation,
+    /// ratchet past it and keep the secrets of the skipped generations within the window.
+    fn consume_generation(&mut self, roster_idx: usize, generation: u32) -> Result<()> {
+        let roster_idx_u32 = u32::try_from(roster_idx)?;
+        if self.skipped_secrets.remove(&(roster_idx_u32, generation)).is_some() {
+            return Ok(());
+        }
+
+        loop {
+            let (member_secret, gen) = self.member_secrets_and_gens
+                .get(roster_idx)
+                .ok_or(anyhow!("consume_generation: Roster index is out of range of application key chain"))?;
+            if *gen > generation {
+                break;
+            }
+            if *gen < generation {
+                self.skipped_secrets.insert((roster_idx_u32, *gen), member_secret.clone());
+            }
+            self.ratchet(roster_idx)?;
+        }
+
+        let current_gen = self.member_secrets_and_gens[roster_idx].1;
+        let expired: Vec<(u32, u32)> = self.skipped_secrets
+            .keys()
+            .filter(|(idx, gen)| *idx == roster_idx_u32 && current_gen - gen > MAX_SKIPPED_GENERATIONS)
+            .cloned()
+            .collect();
+        for key in expired {
+            self.skipped_secrets.remove(&key);
+        }
 
         Ok(())
     }
 
+    /// Encode all members' secrets and generations so that the keychain can be sealed
+    /// and restored after the enclave restarts.
+    pub fn encode_snapshot(&self) -> Vec<u8> {
+        AppKeyChainSnapshot {
+            member_secrets_and_gens: self.member_secrets_and_gens
+                .iter()
+                .map(|(secret, gen)| (HmacKey::from(secret).into_bytes(), *gen))
+                .collect(),
+            skipped_secrets: self.skipped_secrets
+                .iter()
+                .map(|(key, secret)| (*key, HmacKey::from(secret).into_bytes()))
+                .collect(),
+            epoch: self.epoch,
+        }
+        .encode()
+    }
+
+    pub fn decode_snapshot(bytes: &[u8]) -> Result<Self> {
+        let snapshot = AppKeyChainSnapshot::decode(&mut &bytes[..])
+            .map_err(|e| anyhow!("Failed to decode app keychain snapshot: {:?}", e))?;
+
+        Ok(AppKeyChain {
+            member_secrets_and_gens: snapshot.member_secrets_and_gens
+                .into_iter()
+                .map(|(secret, gen)| (AppMemberSecret::from(secret), gen))
+                .collect(),
+            skipped_secrets: snapshot.skipped_secrets
+                .into_iter()
+                .map(|(key, secret)| (key, AppMemberSecret::from(secret)))
+                .collect(),
+            epoch: snapshot.epoch,
+        })
+    }
+
     /// Compute UnboundKey, Nonce, and member's generation.
     fn key_nonce_gen(&self, roster_idx: usize) -> Result<(UnboundKey, OneNonceSequence, u32)> {
         let (member_secret, gen) = self.member_secrets_and_gens
             .get(roster_idx)
             .ok_or(anyhow!("key_nonce_gen: Roster index is out of range of application key chain"))?;
+        let (ub_key, nonce_seq) = Self::key_nonce(member_secret)?;
+
+        Ok((ub_key, nonce_seq, *gen))
+    }
 
+    /// Compute UnboundKey and Nonce from a member's secret.
+    fn key_nonce(member_secret: &AppMemberSecret) -> Result<(UnboundKey, OneNonceSequence)> {
         let prk = HmacKey::from(member_secret);
         let mut key_buf = [0u8; AES_256_GCM_KEY_SIZE];
         let nonce_buf = [0u8; AES_256_GCM_NONCE_SIZE];
@@ -134,16 +288,23 @@ impl AppKeyChain {
         let nonce = Nonce::assume_unique_for_key(nonce_buf);
         let nonce_seq = OneNonceSequence::new(nonce);
 
-        Ok((ub_key, nonce_seq, *gen))
+        Ok((ub_key, nonce_seq))
     }
 }
 
+#[derive(Encode, Decode)]
+struct AppKeyChainSnapshot {
+    member_secrets_and_gens: Vec<(Vec<u8>, u32)>,
+    skipped_secrets: Vec<((u32, u32), Vec<u8>)>,
+    epoch: u32,
+}
+
 #[cfg(debug_assertions)]
 pub mod tests {
     use super::*;
     use crate::test_utils;
     use rand::{self, SeedableRng};
-    use crate::handshake::{PathSecretKVS, PathSecretRequest};
+    use crate::handshake::{Handshake, HandshakeKind, HandshakeParams, PathSecretKVS, PathSecretRequest};
 
     pub fn app_msg_correctness() {
         let mut rng = rand::rngs::StdRng::seed_from_u64(1);
@@ -352,4 +513,307 @@ pub mod tests {
             &mut key_chain2_epoch6,
         );
     }
+
+    pub fn snapshot_correctness() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let msg = b"snapshot correctness test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new(0).unwrap();
+        let mut group_state2 = GroupState::new(1).unwrap();
+        let mut group_state3 = GroupState::new(2).unwrap();
+
+        let (_key_chain1, _key_chain2, _key_chain3) = test_utils::do_handshake_three_party(
+            &mut group_state1,
+            &mut group_state2,
+            &mut group_state3,
+            &req,
+            &mut rng
+        );
+        let (key_chain1, mut key_chain2, mut key_chain3) = test_utils::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &req,
+            &mut rng
+        );
+
+        // Member1 restarts and restores its group state and keychain from the snapshot.
+        let mut restored_group_state1 = GroupState::decode_snapshot(&group_state1.encode_snapshot()).unwrap();
+        let mut restored_key_chain1 = AppKeyChain::decode_snapshot(&key_chain1.encode_snapshot()).unwrap();
+        assert_eq!(restored_group_state1.epoch(), group_state1.epoch());
+        assert_eq!(restored_group_state1.my_roster_idx(), group_state1.my_roster_idx());
+        assert_eq!(restored_group_state1.encode(), group_state1.encode());
+
+        // 1 --> 2
+        test_utils::encrypt_decrypt_helper(
+            msg,
+            &restored_group_state1,
+            &mut restored_key_chain1,
+            &group_state2,
+            &mut key_chain2,
+            &group_state3,
+            &mut key_chain3,
+        );
+
+        // The restored group state can still process subsequent handshakes.
+        let (mut key_chain2, mut restored_key_chain1, mut key_chain3) = test_utils::do_handshake_three_party(
+            &mut group_state2,
+            &mut restored_group_state1,
+            &mut group_state3,
+            &req,
+            &mut rng
+        );
+
+        // 2 --> 1
+        test_utils::encrypt_decrypt_helper(
+            msg,
+            &group_state2,
+            &mut key_chain2,
+            &restored_group_state1,
+            &mut restored_key_chain1,
+            &group_state3,
+            &mut key_chain3,
+        );
+    }
+
+    pub fn forward_secrecy_against_removed_member() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let msg = b"forward secrecy against removed member test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new(0).unwrap();
+        let mut group_state2 = GroupState::new(1).unwrap();
+        let mut group_state3 = GroupState::new(2).unwrap();
+
+        // All three members join the group.
+        test_utils::do_handshake_three_party(&mut group_state1, &mut group_state2, &mut group_state3, &req, &mut rng);
+        test_utils::do_handshake_three_party(&mut group_state2, &mut group_state1, &mut group_state3, &req, &mut rng);
+        let (key_chain3, _, _) = test_utils::do_handshake_three_party(
+            &mut group_state3,
+            &mut group_state1,
+            &mut group_state2,
+            &req,
+            &mut rng
+        );
+
+        // Only a member who has joined can remove another joined member.
+        assert!(group_state1.create_remove_handshake(&req, 0).is_err());
+        assert!(group_state1.create_remove_handshake(&req, 3).is_err());
+        assert!(GroupState::new(3).unwrap().create_remove_handshake(&req, 2).is_err());
+
+        // Member1 removes member3, who keeps its state before the removal.
+        let removed_group_state = group_state3.clone();
+        let mut removed_key_chain = key_chain3;
+        let handshake = group_state1.create_remove_handshake(&req, 2).unwrap();
+        assert_eq!(handshake.kind, HandshakeKind::Remove(2));
+
+        let mut key_chain1 = group_state1.process_handshake(&handshake, &req).unwrap();
+        let mut key_chain2 = group_state2.process_handshake(&handshake, &req).unwrap();
+        let mut key_chain3 = group_state3.process_handshake(&handshake, &req).unwrap();
+
+        // The remaining members share the new keys.
+        let app_msg = key_chain1.encrypt_msg(msg.to_vec(), &group_state1).unwrap();
+        assert_eq!(key_chain1.decrypt_msg(&app_msg, &group_state1).unwrap().unwrap(), msg.to_vec());
+        assert_eq!(key_chain2.decrypt_msg(&app_msg, &group_state2).unwrap().unwrap(), msg.to_vec());
+
+        // The removed member ignores the messages like a member who hasn't joined,
+        // and its keys before the removal are of the previous epoch.
+        assert!(key_chain3.decrypt_msg(&app_msg, &group_state3).unwrap().is_none());
+        assert!(removed_key_chain.decrypt_msg(&app_msg, &removed_group_state).is_err());
+
+        // Even if the removed member processes the handshake as an update with its previous keys,
+        // the new path secrets aren't encrypted to them.
+        let mut as_update = handshake.clone();
+        as_update.kind = HandshakeKind::Update;
+        assert!(removed_group_state.clone().process_handshake(&as_update, &req).is_err());
+
+        // The removed member stays out of the group after the remaining members update their keys.
+        let (mut key_chain2, mut key_chain1, mut key_chain3) = test_utils::do_handshake_three_party(
+            &mut group_state2,
+            &mut group_state1,
+            &mut group_state3,
+            &req,
+            &mut rng
+        );
+        let app_msg = key_chain2.encrypt_msg(msg.to_vec(), &group_state2).unwrap();
+        assert_eq!(key_chain1.decrypt_msg(&app_msg, &group_state1).unwrap().unwrap(), msg.to_vec());
+        assert!(key_chain3.decrypt_msg(&app_msg, &group_state3).unwrap().is_none());
+    }
+
+    pub fn reject_handshake_from_removed_member() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new(0).unwrap();
+        let mut group_state2 = GroupState::new(1).unwrap();
+        let mut group_state3 = GroupState::new(2).unwrap();
+        test_utils::do_handshake_three_party(&mut group_state1, &mut group_state2, &mut group_state3, &req, &mut rng);
+        test_utils::do_handshake_three_party(&mut group_state2, &mut group_state1, &mut group_state3, &req, &mut rng);
+        test_utils::do_handshake_three_party(&mut group_state3, &mut group_state1, &mut group_state2, &req, &mut rng);
+
+        // Member1 removes member3.
+        let handshake = group_state1.create_remove_handshake(&req, 2).unwrap();
+        group_state1.process_handshake(&handshake, &req).unwrap();
+        group_state2.process_handshake(&handshake, &req).unwrap();
+        group_state3.process_handshake(&handshake, &req).unwrap();
+        assert!(group_state1.is_removed(2));
+
+        // The removed member can neither update its keys nor be removed again.
+        assert!(group_state3.create_handshake(&req).is_err());
+        assert!(group_state1.create_remove_handshake(&req, 2).is_err());
+
+        // Even if the removed member drops the record of its removal, an update from its blanked leaf,
+        // which encrypts new path secrets to the current keys of the others, is rejected.
+        let mut reentry_group_state = group_state3.clone();
+        reentry_group_state.removed_roster_idxs.clear();
+        let reentry = reentry_group_state.create_handshake(&req).unwrap();
+        assert!(group_state1.clone().process_handshake(&reentry, &req).is_err());
+        assert!(group_state2.clone().process_handshake(&reentry, &req).is_err());
+
+        // The removed member's roster index survives a snapshot.
+        let restored_group_state1 = GroupState::decode_snapshot(&group_state1.encode_snapshot()).unwrap();
+        assert!(restored_group_state1.is_removed(2));
+        assert!(restored_group_state1.clone().process_handshake(&reentry, &req).is_err());
+
+        // The remaining members still update their keys.
+        let handshake = group_state2.create_handshake(&req).unwrap();
+        group_state1.process_handshake(&handshake, &req).unwrap();
+        group_state2.process_handshake(&handshake, &req).unwrap();
+    }
+
+    pub fn join_in_roster_order() {
+        let msg = b"join in roster order test";
+
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new_unassigned().unwrap();
+        let mut group_state2 = GroupState::new_unassigned().unwrap();
+        let mut group_state3 = GroupState::new_unassigned().unwrap();
+        assert!(group_state1.create_handshake(&req).is_err());
+
+        // Member1 joins first and takes roster index 0.
+        let handshake = group_state1.create_join_handshake(&req).unwrap();
+        assert_eq!(handshake.roster_idx, 0);
+        group_state1.set_my_roster_idx(handshake.roster_idx);
+        process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2, &mut group_state3]);
+
+        // Member2 and member3 join at the same time with roster index 1,
+        // but only member2's handshake is accepted, and member3 retries with the next roster index.
+        let handshake = group_state2.create_join_handshake(&req).unwrap();
+        assert_eq!(group_state3.create_join_handshake(&req).unwrap().roster_idx, 1);
+        group_state2.set_my_roster_idx(handshake.roster_idx);
+        process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2, &mut group_state3]);
+
+        let handshake = group_state3.create_join_handshake(&req).unwrap();
+        assert_eq!(handshake.roster_idx, 2);
+        group_state3.set_my_roster_idx(handshake.roster_idx);
+        let mut key_chains = process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2, &mut group_state3]);
+
+        // The tree has grown by a leaf per joined member.
+        assert_eq!(group_state1.next_roster_idx().unwrap(), 3);
+        assert_eq!(group_state1.encode(), group_state3.encode());
+        assert!(group_state1.create_join_handshake(&req).is_err());
+
+        let app_msg = key_chains[2].encrypt_msg(msg.to_vec(), &group_state3).unwrap();
+        assert_eq!(key_chains[0].decrypt_msg(&app_msg, &group_state1).unwrap().unwrap(), msg.to_vec());
+        assert_eq!(key_chains[1].decrypt_msg(&app_msg, &group_state2).unwrap().unwrap(), msg.to_vec());
+    }
+
+    pub fn reject_handshake_on_forked_transcript() {
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new_unassigned().unwrap();
+        let mut group_state2 = GroupState::new_unassigned().unwrap();
+        let handshake = group_state1.create_join_handshake(&req).unwrap();
+        group_state1.set_my_roster_idx(handshake.roster_idx);
+        process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2]);
+        let handshake = group_state2.create_join_handshake(&req).unwrap();
+        group_state2.set_my_roster_idx(handshake.roster_idx);
+        process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2]);
+        assert_eq!(group_state1.transcript_hash(), group_state2.transcript_hash());
+
+        // Both members update their keys in the same epoch, and the forked state applies the other handshake.
+        let handshake1 = group_state1.create_handshake(&req).unwrap();
+        let handshake2 = group_state2.create_handshake(&req).unwrap();
+        let mut forked_group_state = group_state1.clone();
+        forked_group_state.process_handshake(&handshake2, &req).unwrap();
+        process_all(&handshake1, &req, vec![&mut group_state1, &mut group_state2]);
+        assert_eq!(forked_group_state.epoch(), group_state2.epoch());
+        assert_ne!(forked_group_state.transcript_hash(), group_state2.transcript_hash());
+
+        // The following handshake has the current epoch of the forked state, but its history differs.
+        let handshake = group_state1.create_handshake(&req).unwrap();
+        assert!(forked_group_state.process_handshake(&handshake, &req).is_err());
+        group_state2.process_handshake(&handshake, &req).unwrap();
+    }
+
+    pub fn out_of_order_app_msgs() {
+        let mut kvs = PathSecretKVS::new();
+        test_utils::init_path_secret_kvs(&mut kvs, 10, 10);
+        let req = PathSecretRequest::Local(kvs);
+
+        let mut group_state1 = GroupState::new_unassigned().unwrap();
+        let mut group_state2 = GroupState::new_unassigned().unwrap();
+        let handshake = group_state1.create_join_handshake(&req).unwrap();
+        group_state1.set_my_roster_idx(handshake.roster_idx);
+        process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2]);
+        let handshake = group_state2.create_join_handshake(&req).unwrap();
+        group_state2.set_my_roster_idx(handshake.roster_idx);
+        let mut key_chains = process_all(&handshake, &req, vec![&mut group_state1, &mut group_state2]);
+
+        // Member1 sends three messages, and its keychain is ratcheted as each of them is received in order.
+        let msgs: Vec<Vec<u8>> = (0u8..3).map(|i| vec![i; 8]).collect();
+        let mut app_msgs = vec![];
+        for msg in &msgs {
+            let app_msg = key_chains[0].encrypt_msg(msg.clone(), &group_state1).unwrap();
+            assert_eq!(key_chains[0].decrypt_msg(&app_msg, &group_state1).unwrap().unwrap(), *msg);
+            app_msgs.push(app_msg);
+        }
+
+        // Member2 receives them in reverse order, since the skipped generations' secrets are kept.
+        for (app_msg, msg) in app_msgs.iter().zip(msgs.iter()).rev() {
+            assert_eq!(key_chains[1].decrypt_msg(app_msg, &group_state2).unwrap().unwrap(), *msg);
+        }
+        // A secret is used only once, so a replayed message can't be decrypted.
+        let err = key_chains[1].decrypt_msg(&app_msgs[0], &group_state2).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SkippedMessageError>(),
+            Some(&SkippedMessageError::Replayed { roster_idx: 0, generation: 0 })
+        );
+
+        // A message too far ahead of the window is rejected without changing the keychain.
+        for _ in 0..=MAX_SKIPPED_GENERATIONS {
+            key_chains[0].ratchet(0).unwrap();
+        }
+        let far_app_msg = key_chains[0].encrypt_msg(b"far".to_vec(), &group_state1).unwrap();
+        let key_chain_snapshot = key_chains[1].encode_snapshot();
+        let err = key_chains[1].decrypt_msg(&far_app_msg, &group_state2).unwrap_err();
+        assert!(err.downcast_ref::<SkippedMessageError>().is_some());
+        assert_eq!(key_chains[1].encode_snapshot(), key_chain_snapshot);
+    }
+
+    fn process_all(
+        handshake: &HandshakeParams,
+        req: &PathSecretRequest,
+        group_states: Vec<&mut GroupState>,
+    ) -> Vec<AppKeyChain> {
+        group_states
+            .into_iter()
+            .map(|group_state| group_state.process_handshake(handshake, req).unwrap())
+            .collect()
+    }
 }
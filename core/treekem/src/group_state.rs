@@ -1,25 +1,33 @@
+use std::vec::Vec;
+use std::convert::TryFrom;
 use crate::crypto::{
+    SHA256_OUTPUT_LEN,
+    hash::hash_encodable,
     hkdf,
     secrets::*,
     hmac::HmacKey,
 };
 use crate::application::AppKeyChain;
-use crate::handshake::{Handshake, HandshakeParams, PathSecretRequest, AccessKey};
-use crate::ratchet_tree::{RatchetTree, RatchetTreeNode};
-use crate::tree_math;
+use crate::handshake::{Handshake, HandshakeParams, HandshakeKind, PathSecretRequest, AccessKey};
+use crate::ratchet_tree::{RatchetTree, RatchetTreeNode, RatchetTreeNodeSnapshot};
 use anyhow::{Result, anyhow, ensure};
-use codec::Encode;
+use codec::{Encode, Decode};
 
 #[derive(Clone, Debug, Encode)]
 pub struct GroupState {
     /// The current version of the group key
     epoch: u32,
-    /// Only if a member has a leaf node contained DhPrivKey, this indicates the roster index.
-    /// Otherwise, this field is None.
+    /// The roster index assigned by the order the member joined the group.
+    /// This field is None until the member's own add handshake is processed.
     #[codec(skip)]
-    pub my_roster_idx: u32,
+    pub my_roster_idx: Option<u32>,
     /// RatchetTree contains blank nodes or filled nodes which consist of DhPubkey and DhPrivKey.
     pub tree: RatchetTree,
+    /// A hash chained over all handshakes processed so far, which is zeros at the very first epoch.
+    /// It is a part of the context of key derivations, so the keys are bound to the group's history.
+    transcript_hash: [u8; SHA256_OUTPUT_LEN],
+    /// Roster indices of the members removed from the group, which are never assigned again.
+    pub(crate) removed_roster_idxs: Vec<u32>,
     /// The initial secret used to derive app_secret.
     /// It works as a salt of HKDF.
     #[codec(skip)]
@@ -28,73 +36,82 @@ pub struct GroupState {
 
 impl Handshake for GroupState {
     fn create_handshake(&self, req: &PathSecretRequest) -> Result<HandshakeParams> {
-        let my_roster_idx = self.my_roster_idx;
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(my_roster_idx)?;
-
-        let path_secret = Self::request_new_path_secret(req, my_roster_idx, self.epoch)?;
-        let mut new_group_state = self.clone();
+        self.create_handshake_with_kind(req, HandshakeKind::Update)
+    }
 
-        if my_tree_idx == self.tree.size() {
-            new_group_state.tree.add_leaf_node(RatchetTreeNode::Blank);
-            new_group_state.tree.propagate_blank(my_roster_idx as usize);
+    fn create_remove_handshake(&self, req: &PathSecretRequest, roster_idx: u32) -> Result<HandshakeParams> {
+        ensure!(Some(roster_idx) != self.my_roster_idx, "Cannot remove own member from the group.");
+        match self.my_node() {
+            Some(RatchetTreeNode::Filled { .. }) => {},
+            _ => return Err(anyhow!("Cannot remove a member before joining the group.")),
+        }
+        let tree_idx = RatchetTree::roster_idx_to_tree_idx(roster_idx)?;
+        match self.tree.get(tree_idx) {
+            Some(RatchetTreeNode::Filled { .. }) => {},
+            _ => return Err(anyhow!("The member to remove hasn't joined the group.")),
         }
 
-        let update_secret = new_group_state.set_new_path_secret(path_secret.clone(), my_tree_idx)?;
-        let direct_path_msg = new_group_state.tree.encrypt_direct_path_secret(my_tree_idx, path_secret.clone())?;
-
-        let handshake = HandshakeParams {
-            prior_epoch: self.epoch,
-            roster_idx: my_roster_idx,
-            path: direct_path_msg
-        };
-
-        Ok(handshake)
+        self.create_handshake_with_kind(req, HandshakeKind::Remove(roster_idx))
     }
 
     fn process_handshake(
         &mut self,
         handshake: &HandshakeParams,
         req: &PathSecretRequest,
-        max_roster_idx: u32,
     ) -> Result<AppKeyChain> {
         ensure!(handshake.prior_epoch == self.epoch, "Handshake's prior epoch isn't the current epoch.");
+        ensure!(
+            handshake.prior_transcript_hash == self.transcript_hash,
+            "Handshake's prior transcript hash isn't the current one."
+        );
         let sender_tree_idx = RatchetTree::roster_idx_to_tree_idx(handshake.roster_idx)?;
-        ensure!(sender_tree_idx <= self.tree.size(), "Invalid tree index");
+        let next_roster_idx = self.next_roster_idx()?;
+        ensure!(handshake.roster_idx <= next_roster_idx, "Invalid tree index");
+        // A removed member cannot get back into the group by updating its blanked leaf.
+        if handshake.roster_idx < next_roster_idx {
+            ensure!(!self.is_removed(handshake.roster_idx), "The handshake is sent from a removed member.");
+            match self.tree.get(sender_tree_idx) {
+                Some(RatchetTreeNode::Filled { .. }) => {},
+                _ => return Err(anyhow!("The handshake is sent from a blank leaf.")),
+            }
+        }
 
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
-        let max_tree_size = RatchetTree::roster_idx_to_tree_idx(max_roster_idx)?;
+        if let HandshakeKind::Remove(removed_roster_idx) = handshake.kind {
+            ensure!(handshake.roster_idx < next_roster_idx, "A member cannot remove others before joining the group.");
+            ensure!(removed_roster_idx != handshake.roster_idx, "A member cannot remove itself.");
+            ensure!(!self.is_removed(removed_roster_idx), "The member has already been removed.");
+            // If this member is the removed one, its own keys are dropped here. So it cannot decrypt
+            // the new path secrets, and ignores the following messages as if it hasn't joined yet.
+            self.blank_member(removed_roster_idx)?;
+            self.removed_roster_idxs.push(removed_roster_idx);
+        }
 
-        // If sender_tree_size equals to the current tree size, the handshake contains an add operation.
-        if sender_tree_idx == self.tree.size() {
-            // This is just special operation for the very first epoch.
-            if self.tree.nodes.is_empty() {
-                self.tree.add_leaf_node(RatchetTreeNode::Blank);
-            }
-            for _ in 0..=(max_tree_size - self.tree.size()) / 2 {
-                self.tree.add_leaf_node(RatchetTreeNode::Blank);
-                self.tree.propagate_blank(sender_tree_idx);
-            }
+        // If the sender takes the next roster index, the handshake contains an add operation,
+        // so the tree grows by the sender's leaf.
+        if handshake.roster_idx == next_roster_idx {
+            self.tree.add_leaf_node(RatchetTreeNode::Blank);
+            self.tree.propagate_blank(sender_tree_idx);
         }
 
         let mut my_path_secret: Option<PathSecret> = None;
-        if let Some(my_leaf) = self.tree.get_mut(my_tree_idx) {
-            // Only if the received handshake sent from my own,
-            // request path secret to external key vault and then update the leaf node.
-            if sender_tree_idx == my_tree_idx {
-                let path_secret = Self::request_new_path_secret(req, self.my_roster_idx, self.epoch)?;
-                let (node_pubkey, node_privkey, _, _) = path_secret.clone().derive_node_values()?;
+        // Only if the received handshake sent from my own,
+        // request path secret to external key vault and then update the leaf node.
+        if self.my_tree_idx()? == Some(sender_tree_idx) {
+            let path_secret = Self::request_new_path_secret(req, handshake.roster_idx, self.epoch)?;
+            let (node_pubkey, node_privkey, _, _) = path_secret.clone().derive_node_values()?;
 
-                my_leaf.update_pub_key(node_pubkey);
-                my_leaf.update_priv_key(node_privkey);
+            let my_leaf = self.tree.get_mut(sender_tree_idx).ok_or(anyhow!("Not found my node"))?;
+            my_leaf.update_pub_key(node_pubkey);
+            my_leaf.update_priv_key(node_privkey);
 
-                my_path_secret = Some(path_secret);
-            }
+            my_path_secret = Some(path_secret);
         }
 
         let (update_secret, common_ancestor) = self.apply_handshake(handshake, sender_tree_idx, my_path_secret)?;
         let direct_path_pub_keys = handshake.path.node_msgs.iter().map(|m| &m.public_key);
         self.tree.set_public_keys(sender_tree_idx, common_ancestor, direct_path_pub_keys.clone())?;
         self.increment_epoch()?;
+        self.update_transcript_hash(handshake);
 
         let app_secret = self.update_epoch_secret(&update_secret)?;
         let app_key_chain = AppKeyChain::from_app_secret(&self, app_secret);
@@ -105,18 +122,82 @@ impl Handshake for GroupState {
 
 impl GroupState {
     pub fn new(my_roster_idx: usize) -> Result<Self> {
+        let mut group_state = Self::new_unassigned()?;
+        group_state.my_roster_idx = Some(my_roster_idx as u32);
+
+        Ok(group_state)
+    }
+
+    /// Create a group state of a member whose roster index is assigned when it joins the group.
+    pub fn new_unassigned() -> Result<Self> {
         let epoch = 0;
         let tree = RatchetTree::new_empty();
         let init_secret = HmacKey::default();
 
         Ok(GroupState {
             epoch,
-            my_roster_idx: my_roster_idx as u32,
+            my_roster_idx: None,
             tree,
+            transcript_hash: [0u8; SHA256_OUTPUT_LEN],
+            removed_roster_idxs: vec![],
             init_secret,
         })
     }
 
+    /// Create a handshake adding this member to the group with the next roster index.
+    /// The roster index has to be assigned by `set_my_roster_idx` before the handshake is processed,
+    /// since a handshake of another member joining at the same time may take it first.
+    pub fn create_join_handshake(&self, req: &PathSecretRequest) -> Result<HandshakeParams> {
+        ensure!(self.my_roster_idx.is_none(), "This member has already joined the group.");
+        let mut joining_group_state = self.clone();
+        joining_group_state.my_roster_idx = Some(self.next_roster_idx()?);
+
+        joining_group_state.create_handshake_with_kind(req, HandshakeKind::Update)
+    }
+
+    fn create_handshake_with_kind(&self, req: &PathSecretRequest, kind: HandshakeKind) -> Result<HandshakeParams> {
+        let my_roster_idx = self.my_roster_idx
+            .ok_or(anyhow!("Cannot create a handshake before the roster index is assigned."))?;
+        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(my_roster_idx)?;
+        let next_roster_idx = self.next_roster_idx()?;
+        ensure!(my_roster_idx <= next_roster_idx, "Members have to join the group in the order of roster indices.");
+        ensure!(!self.is_removed(my_roster_idx), "This member has been removed from the group.");
+
+        let path_secret = Self::request_new_path_secret(req, my_roster_idx, self.epoch)?;
+        let mut new_group_state = self.clone();
+
+        if my_roster_idx == next_roster_idx {
+            new_group_state.tree.add_leaf_node(RatchetTreeNode::Blank);
+            new_group_state.tree.propagate_blank(my_tree_idx);
+        }
+        // Blank the removed member before encrypting path secrets, so they aren't encrypted to its keys.
+        if let HandshakeKind::Remove(removed_roster_idx) = kind {
+            new_group_state.blank_member(removed_roster_idx)?;
+        }
+
+        let update_secret = new_group_state.set_new_path_secret(path_secret.clone(), my_tree_idx)?;
+        let direct_path_msg = new_group_state.tree.encrypt_direct_path_secret(my_tree_idx, path_secret.clone())?;
+
+        let handshake = HandshakeParams {
+            prior_epoch: self.epoch,
+            prior_transcript_hash: self.transcript_hash,
+            roster_idx: my_roster_idx,
+            kind,
+            path: direct_path_msg
+        };
+
+        Ok(handshake)
+    }
+
+    /// Blank the leaf and the direct path of a member, which drops all keys the member knows from the tree.
+    fn blank_member(&mut self, roster_idx: u32) -> Result<()> {
+        let tree_idx = RatchetTree::roster_idx_to_tree_idx(roster_idx)?;
+        ensure!(tree_idx < self.tree.size(), "The removed member is out of the roster.");
+        self.tree.propagate_blank(tree_idx);
+
+        Ok(())
+    }
+
     /// Request own new path secret to external key vault
     pub fn request_new_path_secret(req: &PathSecretRequest, roster_idx: u32, epoch: u32) -> Result<PathSecret> {
         match req {
@@ -131,7 +212,10 @@ impl GroupState {
                 *current = next.clone();
                 Ok(next)
             },
-            PathSecretRequest::Remote(url) => unimplemented!(),
+            PathSecretRequest::Remote(key_vault) => {
+                let access_key = AccessKey::new(roster_idx, epoch);
+                key_vault.get_or_store(access_key, PathSecret::new_from_random_sgx())
+            },
         }
     }
 
@@ -141,26 +225,22 @@ impl GroupState {
         sender_tree_idx: usize,
         path_secret: Option<PathSecret>
     ) -> Result<(UpdateSecret, usize)> {
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
+        let my_tree_idx = self.my_tree_idx()?;
 
         // If the received handshake sent from my own, set the requested path secret.
-        if sender_tree_idx == my_tree_idx {
-                let update_secret = self.set_new_path_secret(path_secret.unwrap(), my_tree_idx)?;
+        if my_tree_idx == Some(sender_tree_idx) {
+                let update_secret = self.set_new_path_secret(path_secret.unwrap(), sender_tree_idx)?;
 
-                Ok((update_secret, my_tree_idx))
+                Ok((update_secret, sender_tree_idx))
         } else {
-            // What the node is still blank means the member hasn't join the group yet.
-            // More precisely, the member hasn't send an add handshake yet.
-            // Otherwise, the handshake is an update operation,
-            // so decrypt direct path message using based on current group state.
-            match self.tree.get(my_tree_idx).unwrap() {
-                RatchetTreeNode::Blank => {
-                    let num_leaves = tree_math::num_leaves_in_tree(self.tree.size());
-                    let common_ancestor = tree_math::common_ancestor(sender_tree_idx, my_tree_idx, num_leaves);
-
-                    Ok((UpdateSecret::default(), common_ancestor))
-                },
-                _ => {
+            // If the handshake is an update operation and my node is filled,
+            // decrypt direct path message using based on current group state.
+            // Otherwise, the member hasn't join the group yet or has been removed.
+            // More precisely, the member hasn't send an add handshake yet,
+            // so it just sets all public keys on the sender's direct path.
+            // No node is at the tree size, so it is used as the stop index.
+            match my_tree_idx.and_then(|idx| self.tree.get(idx).map(|node| (idx, node))) {
+                Some((my_tree_idx, RatchetTreeNode::Filled { .. })) => {
                     let (path_secret, common_ancestor) = self.tree.decrypt_direct_path_msg(
                         &handshake.path,
                         sender_tree_idx,
@@ -169,7 +249,8 @@ impl GroupState {
                     let update_secret = self.set_new_path_secret(path_secret, common_ancestor)?;
 
                     Ok((update_secret, common_ancestor))
-                }
+                },
+                _ => Ok((UpdateSecret::default(), self.tree.size())),
             }
         }
     }
@@ -196,6 +277,12 @@ impl GroupState {
         Ok(())
     }
 
+    /// Chain the processed handshake to the transcript hash.
+    fn update_transcript_hash(&mut self, handshake: &HandshakeParams) {
+        let digest = hash_encodable(&(self.transcript_hash, handshake));
+        self.transcript_hash.copy_from_slice(digest.as_ref());
+    }
+
     /// Set the next generation of Group Epoch Secret.
     fn update_epoch_secret(
         &mut self,
@@ -208,11 +295,18 @@ impl GroupState {
         Ok(app_secret.into())
     }
 
-    pub(crate) fn my_node(&self) -> Result<&RatchetTreeNode> {
-        let my_tree_idx = RatchetTree::roster_idx_to_tree_idx(self.my_roster_idx)?;
-        self.tree
-            .get(my_tree_idx)
-            .ok_or(anyhow!("Not found my node"))
+    fn my_tree_idx(&self) -> Result<Option<usize>> {
+        self.my_roster_idx
+            .map(RatchetTree::roster_idx_to_tree_idx)
+            .transpose()
+    }
+
+    /// Return None if my roster index isn't assigned or my leaf isn't added to the tree yet.
+    pub(crate) fn my_node(&self) -> Option<&RatchetTreeNode> {
+        match self.my_tree_idx() {
+            Ok(Some(my_tree_idx)) => self.tree.get(my_tree_idx),
+            _ => None,
+        }
     }
 
     pub(crate) fn roster_len(&self) -> Result<usize> {
@@ -226,7 +320,66 @@ impl GroupState {
         self.epoch
     }
 
-    pub fn my_roster_idx(&self) -> u32 {
+    pub fn transcript_hash(&self) -> [u8; SHA256_OUTPUT_LEN] {
+        self.transcript_hash
+    }
+
+    pub fn my_roster_idx(&self) -> Option<u32> {
         self.my_roster_idx
     }
+
+    pub fn is_removed(&self, roster_idx: u32) -> bool {
+        self.removed_roster_idxs.contains(&roster_idx)
+    }
+
+    /// Assign my roster index when my own add handshake is found in the join order.
+    pub fn set_my_roster_idx(&mut self, roster_idx: u32) {
+        self.my_roster_idx = Some(roster_idx);
+    }
+
+    /// The roster index the next joining member takes, which is the number of leaves in the tree.
+    pub fn next_roster_idx(&self) -> Result<u32> {
+        u32::try_from(self.tree.num_leaves())
+            .map_err(|_| anyhow!("roster length exceeds u32::MAX"))
+    }
+
+    /// Encode the whole group state including private keys and the init secret
+    /// so that it can be sealed and restored after the enclave restarts.
+    pub fn encode_snapshot(&self) -> Vec<u8> {
+        GroupStateSnapshot {
+            epoch: self.epoch,
+            my_roster_idx: self.my_roster_idx,
+            nodes: self.tree.nodes.iter().map(Into::into).collect(),
+            transcript_hash: self.transcript_hash,
+            removed_roster_idxs: self.removed_roster_idxs.clone(),
+            init_secret: self.init_secret.as_bytes().to_vec(),
+        }
+        .encode()
+    }
+
+    pub fn decode_snapshot(bytes: &[u8]) -> Result<Self> {
+        let snapshot = GroupStateSnapshot::decode(&mut &bytes[..])
+            .map_err(|e| anyhow!("Failed to decode group state snapshot: {:?}", e))?;
+
+        Ok(GroupState {
+            epoch: snapshot.epoch,
+            my_roster_idx: snapshot.my_roster_idx,
+            tree: RatchetTree::new(snapshot.nodes.into_iter().map(Into::into).collect()),
+            transcript_hash: snapshot.transcript_hash,
+            removed_roster_idxs: snapshot.removed_roster_idxs,
+            init_secret: snapshot.init_secret.into(),
+        })
+    }
+}
+
+/// `GroupState`'s own encoding skips secrets because it is used as a context of key derivations,
+/// so snapshots are encoded through this type instead.
+#[derive(Encode, Decode)]
+struct GroupStateSnapshot {
+    epoch: u32,
+    my_roster_idx: Option<u32>,
+    nodes: Vec<RatchetTreeNodeSnapshot>,
+    transcript_hash: [u8; SHA256_OUTPUT_LEN],
+    removed_roster_idxs: Vec<u32>,
+    init_secret: Vec<u8>,
 }
@@ -1,10 +1,11 @@
 use std::vec::Vec;
 use std::collections::HashMap;
-use std::string::String;
+use std::fmt::Debug;
 use std::sync::{SgxRwLock, Arc};
 use crate::application::AppKeyChain;
 use crate::crypto::{
     CryptoRng,
+    SHA256_OUTPUT_LEN,
     dh::DhPubKey,
     ecies::EciesCiphertext,
     secrets::PathSecret,
@@ -17,26 +18,42 @@ pub trait Handshake: Sized {
     /// Create a handshake to broadcast other members.
     fn create_handshake(&self, req: &PathSecretRequest) -> Result<HandshakeParams>;
 
+    /// Create a handshake removing the member at `roster_idx` from the group,
+    /// which re-keys the group so that the removed member can't derive the following keys.
+    fn create_remove_handshake(&self, req: &PathSecretRequest, roster_idx: u32) -> Result<HandshakeParams>;
+
     /// Process a received handshake from other members.
     fn process_handshake(
         &mut self,
         handshake: &HandshakeParams,
         req: &PathSecretRequest,
-        max_roster_idx: u32
     ) -> Result<AppKeyChain>;
 }
 
-// TODO: Does need signature over the group's history?
 /// This `Handshake` is sent to global ledger.
 #[derive(Clone, Debug, Encode, Decode)]
 pub struct HandshakeParams {
     /// This is equal to the epoch of the current groupstate
     /// at the time of receicing and applying the handshake.
     pub prior_epoch: u32,
+    /// The transcript hash of the group state the handshake is created on.
+    /// Members with a different history of the group reject the handshake even if the epoch is the same.
+    pub prior_transcript_hash: [u8; SHA256_OUTPUT_LEN],
     pub roster_idx: u32,
+    pub kind: HandshakeKind,
     pub path: DirectPathMsg,
 }
 
+/// What a handshake does to the group besides updating the sender's direct path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum HandshakeKind {
+    /// Add the sender to the group if it hasn't joined yet, otherwise just update its keys.
+    Update,
+    /// Remove the member with the roster index. Its leaf and direct path are blanked
+    /// before the sender's path secrets are encrypted, so they are never encrypted to its keys.
+    Remove(u32),
+}
+
 /// Encrypted direct path
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct DirectPathMsg {
@@ -65,7 +82,7 @@ impl DirectPathNodeMsg {
 #[derive(Debug, Clone)]
 pub enum PathSecretRequest {
     Local(PathSecretKVS),
-    Remote(String),
+    Remote(Arc<dyn KeyVault>),
     /// just for test use to derive new path secret depending on current path secret.
     LocalTest(CurrentPathSecret),
 }
@@ -83,6 +100,22 @@ impl AccessKey {
     pub fn new(roster_idx: u32, epoch: u32) -> Self {
         AccessKey { roster_idx, epoch }
     }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+}
+
+/// A key vault outside of the enclave, which backs up path secrets
+/// so that they can be recovered after the enclave is restarted.
+pub trait KeyVault: Debug + Send + Sync {
+    /// Store `path_secret` with `access_key` unless a path secret is already stored with it,
+    /// and return the stored one. So a path secret is the same however many times it is requested.
+    fn get_or_store(&self, access_key: AccessKey, path_secret: PathSecret) -> Result<PathSecret>;
 }
 
 impl PathSecretKVS {
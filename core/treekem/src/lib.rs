@@ -12,7 +12,7 @@ mod crypto;
 #[cfg(debug_assertions)]
 mod test_utils;
 
-pub use crate::application::AppKeyChain;
+pub use crate::application::{AppKeyChain, SkippedMessageError};
 pub use crate::group_state::GroupState;
 pub use crate::handshake::Handshake;
 pub use crate::crypto::secrets::PathSecret;
@@ -9,7 +9,7 @@ use crate::{
     handshake::{DirectPathMsg, DirectPathNodeMsg},
 };
 use anyhow::{Result, anyhow, ensure};
-use codec::Encode;
+use codec::{Encode, Decode};
 
 #[derive(Clone, Debug, Encode)]
 pub struct RatchetTree {
@@ -245,6 +245,13 @@ impl RatchetTree {
         self.nodes.len()
     }
 
+    pub fn num_leaves(&self) -> usize {
+        match self.nodes.is_empty() {
+            true => 0,
+            false => tree_math::num_leaves_in_tree(self.size()),
+        }
+    }
+
     pub fn get(&self, idx: usize) -> Option<&RatchetTreeNode> {
         self.nodes.get(idx)
     }
@@ -347,3 +354,37 @@ impl RatchetTreeNode {
         }
     }
 }
+
+/// `RatchetTreeNode` including its private key, used to persist the tree in a sealed snapshot.
+#[derive(Debug, Clone, Encode, Decode)]
+pub(crate) enum RatchetTreeNodeSnapshot {
+    Blank,
+    Filled {
+        public_key: DhPubKey,
+        private_key: Option<DhPrivateKey>,
+    },
+}
+
+impl From<&RatchetTreeNode> for RatchetTreeNodeSnapshot {
+    fn from(node: &RatchetTreeNode) -> Self {
+        match node {
+            RatchetTreeNode::Blank => RatchetTreeNodeSnapshot::Blank,
+            RatchetTreeNode::Filled { public_key, private_key } => RatchetTreeNodeSnapshot::Filled {
+                public_key: public_key.clone(),
+                private_key: private_key.clone(),
+            },
+        }
+    }
+}
+
+impl From<RatchetTreeNodeSnapshot> for RatchetTreeNode {
+    fn from(node: RatchetTreeNodeSnapshot) -> Self {
+        match node {
+            RatchetTreeNodeSnapshot::Blank => RatchetTreeNode::Blank,
+            RatchetTreeNodeSnapshot::Filled { public_key, private_key } => RatchetTreeNode::Filled {
+                public_key,
+                private_key,
+            },
+        }
+    }
+}
@@ -23,7 +23,7 @@ pub fn change_group_state_idx(
     assert!(new_idx as usize <= group_state.roster_len().unwrap());
 
     let mut new_group_state = group_state.clone();
-    new_group_state.my_roster_idx = new_idx;
+    new_group_state.my_roster_idx = Some(new_idx);
 
     new_group_state
 }
@@ -35,13 +35,12 @@ pub fn do_handshake_three_party<R: CryptoRng>(
     req: &PathSecretRequest,
     csprng: &mut R,
 ) -> (AppKeyChain, AppKeyChain, AppKeyChain) {
-    let max_roster_idx = 2;
     let new_path_secret = PathSecret::new_from_random(csprng);
     let handshake = my_group.create_handshake(req).unwrap();
 
-    let my_keychain = my_group.process_handshake(&handshake, req, max_roster_idx).unwrap();
-    let others_keychain1 = others_group1.process_handshake(&handshake, req, max_roster_idx).unwrap();
-    let others_keychain2 = others_group2.process_handshake(&handshake, req, max_roster_idx).unwrap();
+    let my_keychain = my_group.process_handshake(&handshake, req).unwrap();
+    let others_keychain1 = others_group1.process_handshake(&handshake, req).unwrap();
+    let others_keychain2 = others_group2.process_handshake(&handshake, req).unwrap();
 
     (my_keychain, others_keychain1, others_keychain2)
 }
@@ -52,6 +52,12 @@ pub struct RawJoinGroupTx {
     pub report: *const u8,
     pub report_sig: *const u8,
     pub handshake: *const u8,
+    /// A signature over the handshake by the enclave identity key.
+    pub enclave_sig: *const u8,
+    /// The roster index the enclave joins the group with, which is the next one in the join order.
+    pub roster_idx: u32,
+    /// The address of the enclave identity key, which is also included in the report.
+    pub enclave_address: [u8; 20],
 }
 
 impl RawEnclaveTx for RawJoinGroupTx { }
@@ -62,6 +68,9 @@ impl Default for RawJoinGroupTx {
             report: ptr::null(),
             report_sig: ptr::null(),
             handshake: ptr::null(),
+            enclave_sig: ptr::null(),
+            roster_idx: 0,
+            enclave_address: [0u8; 20],
         }
     }
 }
@@ -72,6 +81,9 @@ impl fmt::Debug for RawJoinGroupTx {
         debug_trait_builder.field("report", &(self.report));
         debug_trait_builder.field("report_sig", &(self.report_sig));
         debug_trait_builder.field("handshake", &(self.handshake));
+        debug_trait_builder.field("enclave_sig", &(self.enclave_sig));
+        debug_trait_builder.field("roster_idx", &(self.roster_idx));
+        debug_trait_builder.field("enclave_address", &(self.enclave_address));
         debug_trait_builder.finish()
     }
 }
@@ -115,6 +127,8 @@ impl fmt::Debug for RawInstructionTx {
 #[derive(Clone, Copy)]
 pub struct RawHandshakeTx {
     pub handshake: *const u8,
+    /// A signature over the handshake by the enclave identity key.
+    pub enclave_sig: *const u8,
 }
 
 impl RawEnclaveTx for RawHandshakeTx { }
@@ -123,6 +137,7 @@ impl Default for RawHandshakeTx {
     fn default() -> Self {
         RawHandshakeTx {
             handshake: ptr::null(),
+            enclave_sig: ptr::null(),
         }
     }
 }
@@ -131,6 +146,7 @@ impl fmt::Debug for RawHandshakeTx {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut debug_trait_builder = f.debug_struct("RawHandshakeTx");
         debug_trait_builder.field("handshake", &(self.handshake));
+        debug_trait_builder.field("enclave_sig", &(self.enclave_sig));
         debug_trait_builder.finish()
     }
 }
@@ -194,30 +210,40 @@ impl fmt::Debug for EnclaveState {
     }
 }
 
-/// Key Value data stored in an Enclave
+/// Updated states of all addresses registered for notification, returned from inserting a ciphertext.
+/// `states` points to the encoded list of updated states saved in host memory,
+/// or is null if no registered address is updated.
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq)]
-pub struct RawUpdatedState{
-    pub address: Address,
-    pub mem_id: u32,
-    pub state: *const u8,
+pub struct RawUpdatedStates {
+    pub states: *const u8,
+}
+
+impl RawUpdatedStates {
+    pub fn is_empty(&self) -> bool {
+        self.states.is_null()
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        let raw_states = self.states as *mut Box<[u8]>;
+        let box_states = unsafe { Box::from_raw(raw_states) };
+
+        box_states.into_vec()
+    }
 }
 
-impl Default for RawUpdatedState {
+impl Default for RawUpdatedStates {
     fn default() -> Self {
-        RawUpdatedState {
-            state: ptr::null(),
-            .. unsafe { mem::zeroed() }
+        RawUpdatedStates {
+            states: ptr::null(),
         }
     }
 }
 
-impl fmt::Debug for RawUpdatedState {
+impl fmt::Debug for RawUpdatedStates {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut debug_trait_builder = f.debug_struct("RawUpdatedState");
-        debug_trait_builder.field("address", &(self.address));
-        debug_trait_builder.field("mem_id", &(self.mem_id));
-        debug_trait_builder.field("state", &(self.state));
+        let mut debug_trait_builder = f.debug_struct("RawUpdatedStates");
+        debug_trait_builder.field("states", &(self.states));
         debug_trait_builder.finish()
     }
 }
@@ -87,7 +87,7 @@ pub mod init_state {
             pub sig: [u8; SIGNATURE_LENGTH],
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
-            pub total_supply: u64,
+            pub total_supply: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -95,7 +95,7 @@ pub mod init_state {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                total_supply: u64,
+                total_supply: u128,
                 state_id: u64,
                 contract_addr: String,
                 rng: &mut R
@@ -149,7 +149,7 @@ pub mod transfer {
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
             pub target: UserAddress,
-            pub amount: u64,
+            pub amount: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -157,7 +157,7 @@ pub mod transfer {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                amount: u64,
+                amount: u128,
                 state_id: u64,
                 target: UserAddress,
                 contract_addr: String,
@@ -213,7 +213,7 @@ pub mod approve {
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
             pub target: UserAddress,
-            pub amount: u64,
+            pub amount: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -221,7 +221,7 @@ pub mod approve {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                amount: u64,
+                amount: u128,
                 state_id: u64,
                 target: UserAddress,
                 contract_addr: String,
@@ -278,7 +278,7 @@ pub mod transfer_from {
             pub challenge: [u8; 32],
             pub owner: UserAddress,
             pub target: UserAddress,
-            pub amount: u64,
+            pub amount: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -286,7 +286,7 @@ pub mod transfer_from {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                amount: u64,
+                amount: u128,
                 state_id: u64,
                 owner: UserAddress,
                 target: UserAddress,
@@ -344,7 +344,7 @@ pub mod mint {
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
             pub target: UserAddress,
-            pub amount: u64,
+            pub amount: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -352,7 +352,7 @@ pub mod mint {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                amount: u64,
+                amount: u128,
                 state_id: u64,
                 target: UserAddress,
                 contract_addr: String,
@@ -407,7 +407,7 @@ pub mod burn {
             pub sig: [u8; SIGNATURE_LENGTH],
             pub pubkey: [u8; PUBLIC_KEY_LENGTH],
             pub challenge: [u8; 32],
-            pub amount: u64,
+            pub amount: u128,
             pub state_id: u64,
             pub contract_addr: String,
         }
@@ -415,7 +415,7 @@ pub mod burn {
         impl Request {
             pub fn new<R: Rng>(
                 keypair: &Keypair,
-                amount: u64,
+                amount: u128,
                 state_id: u64,
                 contract_addr: String,
                 rng: &mut R,
@@ -471,6 +471,21 @@ pub mod key_rotation {
     }
 }
 
+pub mod remove_member {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Clone, Deserialize, Serialize, Debug)]
+        pub struct Request {
+            pub roster_idx: u32,
+            pub contract_addr: String,
+        }
+
+        #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Deserialize, Serialize)]
+        pub struct Response(pub String);
+    }
+}
+
 pub mod allowance {
     pub mod get {
         use super::super::*;
@@ -527,6 +542,8 @@ pub mod allowance {
             }
         }
 
+        /// The approved amount, which is stored as `U256` but returned as `u128` like amounts in requests.
+        /// Querying an amount larger than `u128::MAX` fails.
         #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, Deserialize, Serialize)]
         pub struct Response<S: State>(pub S);
     }
@@ -585,6 +602,8 @@ pub mod state {
             }
         }
 
+        /// The balance, which is stored as `U256` but returned as `u128` like amounts in requests.
+        /// Querying a balance larger than `u128::MAX` fails.
         #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, Deserialize, Serialize)]
         pub struct Response<S: State>(pub S);
     }
@@ -660,3 +679,33 @@ pub mod register_notification {
         }
     }
 }
+
+pub mod tx {
+    pub mod get {
+        use super::super::*;
+
+        /// Query of `/api/v1/tx/{hash}`. The server waits at most `timeout_secs` seconds
+        /// while the transaction is pending, and returns immediately if it is not given.
+        #[derive(Clone, Deserialize, Serialize, Debug, Default)]
+        pub struct Request {
+            pub timeout_secs: Option<u64>,
+        }
+
+        #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+        #[serde(tag = "status", rename_all = "snake_case")]
+        pub enum Status {
+            Pending,
+            Mined { block_number: u64 },
+            Reverted { reason: Option<String> },
+            Dropped,
+        }
+
+        #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+        pub struct Response {
+            pub tx_hash: String,
+            #[serde(flatten)]
+            pub status: Status,
+            pub gas_used: Option<u64>,
+        }
+    }
+}
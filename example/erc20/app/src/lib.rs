@@ -19,22 +19,24 @@ use crate::localstd::{
 use anonify_common::{UserAddress, OWNER_ADDRESS};
 use codec::{Encode, Decode};
 
-pub const MAX_MEM_SIZE: usize = 100;
 pub const CIPHERTEXT_SIZE: usize = MAX_MEM_SIZE + 30;
 
 impl_memory! {
-    (0, "Balance", U64),
-    (1, "Approved", Approved),
-    (2, "TotalSupply", U64),
-    (3, "Owner", UserAddress)
+    (0, "Balance", U256, 32),
+    // Sized for 4 approvals: 1 byte length prefix and 52 bytes per (UserAddress, U256) pair.
+    // Approvals for more spenders are still stored, as the declared size only bounds `MAX_MEM_SIZE`.
+    (1, "Approved", Approved, 209),
+    (2, "TotalSupply", U256, 32),
+    (3, "Owner", UserAddress, 20)
 }
 
-impl_runtime! {
+#[anonify_runtime::runtime]
+impl Runtime {
     #[fn_id=0]
     pub fn construct(
         self,
         sender: UserAddress,
-        total_supply: U64
+        total_supply: U256
     ) {
         let owner_address = update!(*OWNER_ADDRESS, "Owner", sender);
         let sender_balance = update!(sender, "Balance", total_supply);
@@ -48,15 +50,15 @@ impl_runtime! {
         self,
         sender: UserAddress,
         recipient: UserAddress,
-        amount: U64
+        amount: U256
     ) {
-        let sender_balance = self.get_map::<U64>(sender, "Balance")?;
-        let recipient_balance = self.get_map::<U64>(recipient, "Balance")?;
+        let sender_balance = self.get_map::<U256>(sender, "Balance")?;
+        let recipient_balance = self.get_map::<U256>(recipient, "Balance")?;
 
         ensure!(sender_balance > amount, "transfer amount exceeds balance.");
 
-        let sender_update = update!(sender, "Balance", sender_balance - amount);
-        let recipient_update = update!(recipient, "Balance", recipient_balance + amount);
+        let sender_update = update!(sender, "Balance", sender_balance.checked_sub(amount)?);
+        let recipient_update = update!(recipient, "Balance", recipient_balance.checked_add(amount)?);
 
         insert![sender_update, recipient_update]
     }
@@ -66,17 +68,17 @@ impl_runtime! {
         self,
         owner: UserAddress,
         spender: UserAddress,
-        amount: U64
+        amount: U256
     ) {
-        let owner_balance = self.get_map::<U64>(owner, "Balance")?;
+        let owner_balance = self.get_map::<U256>(owner, "Balance")?;
         let mut owner_approved = self.get_map::<Approved>(owner, "Approved")?;
 
         ensure!(
-            owner_approved.total() + amount <= owner_balance,
+            owner_approved.total()?.checked_add(amount)? <= owner_balance,
             "approving amount exceeds balance and already approved."
         );
 
-        owner_approved.approve(spender, amount);
+        owner_approved.approve(spender, amount)?;
         let owner_approved_update = update!(owner, "Approved", owner_approved);
         insert![owner_approved_update]
     }
@@ -87,9 +89,9 @@ impl_runtime! {
         sender: UserAddress,
         owner: UserAddress,
         recipient: UserAddress,
-        amount: U64
+        amount: U256
     ) {
-        let owner_balance = self.get_map::<U64>(owner, "Balance")?;
+        let owner_balance = self.get_map::<U256>(owner, "Balance")?;
         ensure!(
             amount <= owner_balance,
             "transferring amount exceeds owner's balance."
@@ -106,10 +108,10 @@ impl_runtime! {
         owner_approved.consume(sender, amount)?;
         let owner_approved_update = update!(owner, "Approved", owner_approved);
 
-        let recipient_balance = self.get_map::<U64>(recipient, "Balance")?;
+        let recipient_balance = self.get_map::<U256>(recipient, "Balance")?;
 
-        let owner_balance_update = update!(owner, "Balance", owner_balance - amount);
-        let recipient_balance_update = update!(recipient, "Balance", recipient_balance + amount);
+        let owner_balance_update = update!(owner, "Balance", owner_balance.checked_sub(amount)?);
+        let recipient_balance_update = update!(recipient, "Balance", recipient_balance.checked_add(amount)?);
 
         insert![owner_approved_update, owner_balance_update, recipient_balance_update]
     }
@@ -119,16 +121,16 @@ impl_runtime! {
         self,
         executer: UserAddress,
         recipient: UserAddress,
-        amount: U64
+        amount: U256
     ) {
         let owner_address = self.get_map::<UserAddress>(*OWNER_ADDRESS, "Owner")?;
         ensure!(executer == owner_address, "only owner can mint");
 
-        let recipient_balance = self.get_map::<U64>(recipient, "Balance")?;
-        let recipient_balance_update = update!(recipient, "Balance", recipient_balance + amount);
+        let recipient_balance = self.get_map::<U256>(recipient, "Balance")?;
+        let recipient_balance_update = update!(recipient, "Balance", recipient_balance.checked_add(amount)?);
 
-        let total_supply = self.get_map::<U64>(*OWNER_ADDRESS, "TotalSupply")?;
-        let total_supply_update = update!(*OWNER_ADDRESS, "TotalSupply", total_supply + amount);
+        let total_supply = self.get_map::<U256>(*OWNER_ADDRESS, "TotalSupply")?;
+        let total_supply_update = update!(*OWNER_ADDRESS, "TotalSupply", total_supply.checked_add(amount)?);
 
         insert![recipient_balance_update, total_supply_update]
     }
@@ -137,15 +139,32 @@ impl_runtime! {
     pub fn burn(
         self,
         sender: UserAddress,
-        amount: U64
+        amount: U256
     ) {
-        let balance = self.get_map::<U64>(sender, "Balance")?;
+        let balance = self.get_map::<U256>(sender, "Balance")?;
         ensure!(balance >= amount, "not enough balance to burn");
-        let balance_update = update!(sender, "Balance", balance - amount);
+        let balance_update = update!(sender, "Balance", balance.checked_sub(amount)?);
 
-        let total_supply = self.get_map::<U64>(*OWNER_ADDRESS, "TotalSupply")?;
-        let total_supply_update = update!(*OWNER_ADDRESS, "TotalSupply", total_supply - amount);
+        let total_supply = self.get_map::<U256>(*OWNER_ADDRESS, "TotalSupply")?;
+        let total_supply_update = update!(*OWNER_ADDRESS, "TotalSupply", total_supply.checked_sub(amount)?);
 
         insert![balance_update, total_supply_update]
     }
+
+    #[view]
+    #[fn_id=0]
+    pub fn allowance(
+        self,
+        caller: UserAddress,
+        owner: UserAddress,
+        spender: UserAddress
+    ) -> U256 {
+        ensure!(
+            caller == owner || caller == spender,
+            "only the owner or the spender can query the allowance."
+        );
+
+        let owner_approved = self.get_map::<Approved>(owner, "Approved")?;
+        Ok(owner_approved.allowance(&spender).copied().unwrap_or_default())
+    }
 }
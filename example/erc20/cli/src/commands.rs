@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 use rand::Rng;
-use anonify_wallet::{WalletDirectory, KeystoreDirectory, KeyFile, DirOperations};
+use anonify_wallet::{WalletDirectory, KeystoreDirectory, EthKeystoreDirectory, KeyFile, EthKeyFile, DirOperations};
 use anonify_common::UserAddress;
 use bip39::{Mnemonic, Language, MnemonicType, Seed};
 use reqwest::Client;
@@ -53,7 +53,7 @@ pub(crate) fn init_state<R: Rng>(
     root_dir: PathBuf,
     anonify_url: String,
     index: usize,
-    total_supply: u64,
+    total_supply: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -79,7 +79,7 @@ pub(crate) fn transfer<R: Rng>(
     anonify_url: String,
     index: usize,
     target: UserAddress,
-    amount: u64,
+    amount: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -104,7 +104,7 @@ pub(crate) fn approve<R: Rng>(
     anonify_url: String,
     index: usize,
     target: UserAddress,
-    amount: u64,
+    amount: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -130,7 +130,7 @@ pub(crate) fn transfer_from<R: Rng>(
     index: usize,
     owner: UserAddress,
     target: UserAddress,
-    amount: u64,
+    amount: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -155,7 +155,7 @@ pub(crate) fn mint<R: Rng>(
     anonify_url: String,
     index: usize,
     target: UserAddress,
-    amount: u64,
+    amount: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -179,7 +179,7 @@ pub(crate) fn burn<R: Rng>(
     root_dir: PathBuf,
     anonify_url: String,
     index: usize,
-    amount: u64,
+    amount: u128,
     state_id: u64,
     contract_addr: String,
     rng: &mut R
@@ -214,6 +214,23 @@ pub(crate) fn key_rotation(
     Ok(())
 }
 
+pub(crate) fn remove_member(
+    anonify_url: String,
+    roster_idx: u32,
+    contract_addr: String,
+) -> Result<()> {
+    let req = api::remove_member::post::Request{ roster_idx, contract_addr };
+    let res = Client::new()
+        .post(&format!("{}/api/v1/remove_member", &anonify_url))
+        .json(&req)
+        .send()?
+        .text()?;
+
+    println!("Transaction Receipt: {}", res);
+
+    Ok(())
+}
+
 pub(crate) fn allowance<R: Rng>(
     term: &mut Term,
     root_dir: PathBuf,
@@ -377,6 +394,41 @@ pub(crate) fn add_account<R: Rng>(term: &mut Term, root_dir: PathBuf, rng: &mut
     Ok(())
 }
 
+/// Add a new secp256k1 key which the server signs Ethereum transactions with.
+pub(crate) fn add_eth_key<R: Rng>(term: &mut Term, root_dir: PathBuf, rng: &mut R) -> Result<()> {
+    // 1. configure wallet directory
+    let wallet_dir = WalletDirectory::create(&root_dir)?;
+    let eth_keystore_dir = EthKeystoreDirectory::create(wallet_dir.get_default_eth_keystore_dir())?;
+
+    // 2. configure user-defined password
+    term.info("Set a password of the Ethereum key. The server needs it to sign transactions.\n")?;
+    let password = term.new_password("key password", "confirm key password", "password mismatch")?;
+
+    // 3. enter new account name
+    term.info("Enter a new account name.\n")?;
+    let account_name = term.account_name("new account name")?;
+
+    // 4. create and store a keyfile
+    let mut keyfile = EthKeyFile::new_random(
+        account_name.as_str(),
+        VERSION,
+        &password,
+        ITERS,
+        rng
+    )?;
+    eth_keystore_dir.insert(&mut keyfile, rng)?;
+
+    term.success(&format!(
+        "a new Ethereum key successfully created.\n
+        {}: 0x{}\n
+        keyfile: {}\n\n",
+        keyfile.account_name,
+        keyfile.address,
+        eth_keystore_dir.0.join(keyfile.file_name.as_ref().expect("The keyfile has been stored.")).display(),
+    ))?;
+
+    Ok(())
+}
 
 pub(crate) fn show_list(
     term: &mut Term,
@@ -99,7 +99,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let total_supply: u64 = matches.value_of("total_supply")
+            let total_supply: u128 = matches.value_of("total_supply")
                 .expect("Not found total_supply.")
                 .parse()
                 .expect("Failed to parse total_supply");
@@ -129,7 +129,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let amount: u64 = matches.value_of("amount")
+            let amount: u128 = matches.value_of("amount")
                 .expect("Not found amount.")
                 .parse()
                 .expect("Failed to parse amount");
@@ -164,7 +164,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let amount: u64 = matches.value_of("amount")
+            let amount: u128 = matches.value_of("amount")
                 .expect("Not found amount.")
                 .parse()
                 .expect("Failed to parse amount");
@@ -199,7 +199,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let amount: u64 = matches.value_of("amount")
+            let amount: u128 = matches.value_of("amount")
                 .expect("Not found amount.")
                 .parse()
                 .expect("Failed to parse amount");
@@ -238,7 +238,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let amount: u64 = matches.value_of("amount")
+            let amount: u128 = matches.value_of("amount")
                 .expect("Not found amount.")
                 .parse()
                 .expect("Failed to parse amount");
@@ -273,7 +273,7 @@ fn subcommand_anonify<R: Rng>(
                 .expect("Not found keyfile-index.")
                 .parse()
                 .expect("Failed to parse keyfile-index");
-            let amount: u64 = matches.value_of("amount")
+            let amount: u128 = matches.value_of("amount")
                 .expect("Not found amount.")
                 .parse()
                 .expect("Failed to parse amount");
@@ -311,6 +311,23 @@ fn subcommand_anonify<R: Rng>(
             )
             .expect("Failed to key_rotation command");
         },
+        ("remove_member", Some(matches)) => {
+            let roster_idx: u32 = matches.value_of("roster-idx")
+                .expect("Not found roster-idx.")
+                .parse()
+                .expect("Failed to parse roster-idx");
+            let contract_addr = match matches.value_of("contract-addr") {
+                Some(addr) => addr.to_string(),
+                None => default_contract_addr,
+            };
+
+            commands::remove_member(
+                anonify_url,
+                roster_idx,
+                contract_addr,
+            )
+            .expect("Failed to remove_member command");
+        },
         ("allowance", Some(matches)) => {
             let keyfile_index: usize = matches.value_of("keyfile-index")
                 .expect("Not found keyfile-index.")
@@ -601,6 +618,18 @@ fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
             )
         )
+        .subcommand(SubCommand::with_name("remove_member")
+            .about("handshake with other group members to remove a member from the group")
+            .arg(Arg::with_name("roster-idx")
+                .short("r")
+                .takes_value(true)
+                .required(true)
+            )
+            .arg(Arg::with_name("contract-addr")
+                .short("c")
+                .takes_value(true)
+            )
+        )
         .subcommand(SubCommand::with_name("allowance")
             .about("Get approved balance of the spender address from anonify services.")
             .arg(Arg::with_name("keyfile-index")
@@ -679,6 +708,10 @@ fn subcommand_wallet<R: Rng>(mut term: term::Term, root_dir: PathBuf, matches: &
             commands::add_account(&mut term, root_dir, rng)
                 .expect("Invalid operations of Adding a new account.");
         },
+        ("add-eth-key", Some(_)) => {
+            commands::add_eth_key(&mut term, root_dir, rng)
+                .expect("Invalid operations of Adding a new Ethereum key.");
+        },
         ("list", Some(_)) => {
             commands::show_list(&mut term, root_dir)
                 .expect("Invalid operations of showing accounts list.");
@@ -699,6 +732,9 @@ fn wallet_commands_definition<'a, 'b>() -> App<'a, 'b> {
         .subcommand(SubCommand::with_name("add-account")
             .about("Add a new account into your wallet.")
         )
+        .subcommand(SubCommand::with_name("add-eth-key")
+            .about("Add a new key to sign Ethereum transactions without node-managed accounts.")
+        )
         .subcommand(SubCommand::with_name("list")
             .about("Show list your accounts.")
         )
@@ -1,13 +1,19 @@
-use std::{sync::Arc, thread, time};
+use std::{sync::Arc, thread, time::Duration, future::Future};
 use failure::Error;
 use log::debug;
+use futures::{
+    compat::Compat,
+    future::{FutureExt, LocalBoxFuture, TryFutureExt},
+};
 use anonify_host::dispatcher::get_state;
 use anonify_bc_connector::{
     BlockNumDB,
+    error::HostError,
     traits::*,
+    utils::TxStatus,
 };
-use anonify_runtime::{U64, Approved};
-use erc20_app::{approve, transfer, construct, transfer_from, mint, burn};
+use anonify_runtime::U256;
+use erc20_app::{approve, transfer, construct, transfer_from, mint, burn, allowance};
 use actix_web::{
     web,
     HttpResponse,
@@ -15,7 +21,15 @@ use actix_web::{
 use anyhow::anyhow;
 use crate::Server;
 
-const DEFAULT_SEND_GAS: u64 = 3_000_000;
+/// actix-web polls futures 0.1, so handlers awaiting the dispatcher return their futures converted into them.
+pub type HandlerFuture = Compat<LocalBoxFuture<'static, Result<HttpResponse, Error>>>;
+
+fn handler_future<F>(future: F) -> HandlerFuture
+where
+    F: Future<Output = Result<HttpResponse, Error>> + 'static,
+{
+    future.boxed_local().compat()
+}
 
 pub fn handle_deploy<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
@@ -23,18 +37,18 @@ pub fn handle_deploy<D, S, W, DB>(
 ) -> Result<HttpResponse, Error>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
     debug!("Starting deploy a contract...");
 
-    let deployer_addr = server.dispatcher.get_account(0)?;
+    let deployer_addr = server.signer()?;
     let contract_addr = server.dispatcher
-        .deploy(&deployer_addr)?;
+        .deploy(&deployer_addr, &server.artifact)?;
 
     debug!("Contract address: {:?}", &contract_addr);
-    server.dispatcher.set_contract_addr(&contract_addr, &server.abi_path)?;
+    server.dispatcher.set_contract_addr(&contract_addr, &server.artifact)?;
 
     Ok(HttpResponse::Ok().json(api::deploy::post::Response(contract_addr)))
 }
@@ -42,262 +56,304 @@ pub fn handle_deploy<D, S, W, DB>(
 pub fn handle_join_group<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::join_group::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let signer = server.dispatcher.get_account(0)?;
-    let receipt = server.dispatcher.join_group(
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::join_group::post::Response(receipt)))
+    handler_future(async move {
+        let signer = server.signer()?;
+        let receipt = server.dispatcher.join_group_async(
+            signer,
+            None,
+            &req.contract_addr,
+            &server.artifact,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::join_group::post::Response(receipt)))
+    })
 }
 
 pub fn handle_init_state<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::init_state::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let total_supply = U64::from_raw(req.total_supply);
-    let init_state = construct{ total_supply };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        init_state,
-        req.state_id,
-        "construct",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::init_state::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let total_supply = U256::from(req.total_supply);
+        let init_state = construct{ total_supply };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            init_state,
+            req.state_id,
+            "construct",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::init_state::post::Response(receipt)))
+    })
 }
 
 pub fn handle_transfer<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::transfer::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let amount = U64::from_raw(req.amount);
-    let recipient = req.target;
-    let transfer_state = transfer{ amount, recipient };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        transfer_state,
-        req.state_id,
-        "transfer",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::transfer::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let amount = U256::from(req.amount);
+        let recipient = req.target;
+        let transfer_state = transfer{ amount, recipient };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            transfer_state,
+            req.state_id,
+            "transfer",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::transfer::post::Response(receipt)))
+    })
 }
 
 pub fn handle_approve<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::approve::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let amount = U64::from_raw(req.amount);
-    let spender = req.target;
-    let approve_state = approve { amount, spender };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        approve_state,
-        req.state_id,
-        "approve",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::approve::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let amount = U256::from(req.amount);
+        let spender = req.target;
+        let approve_state = approve { amount, spender };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            approve_state,
+            req.state_id,
+            "approve",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::approve::post::Response(receipt)))
+    })
 }
 
 pub fn handle_mint<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::mint::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let amount = U64::from_raw(req.amount);
-    let recipient = req.target;
-    let minting_state = mint{ amount, recipient };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        minting_state,
-        req.state_id,
-        "mint",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::mint::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let amount = U256::from(req.amount);
+        let recipient = req.target;
+        let minting_state = mint{ amount, recipient };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            minting_state,
+            req.state_id,
+            "mint",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::mint::post::Response(receipt)))
+    })
 }
 
 pub fn handle_burn<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::burn::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let amount = U64::from_raw(req.amount);
-    let burn_state = burn{ amount };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        burn_state,
-        req.state_id,
-        "burn",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::burn::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let amount = U256::from(req.amount);
+        let burn_state = burn{ amount };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            burn_state,
+            req.state_id,
+            "burn",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::burn::post::Response(receipt)))
+    })
 }
 
 pub fn handle_transfer_from<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::transfer_from::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let amount = U64::from_raw(req.amount);
-    let owner = req.owner;
-    let recipient = req.target;
-    let transferred_from_state = transfer_from { owner, recipient, amount };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        transferred_from_state,
-        req.state_id,
-        "transfer_from",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::transfer_from::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.signer()?;
+        let amount = U256::from(req.amount);
+        let owner = req.owner;
+        let recipient = req.target;
+        let transferred_from_state = transfer_from { owner, recipient, amount };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            transferred_from_state,
+            req.state_id,
+            "transfer_from",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::transfer_from::post::Response(receipt)))
+    })
 }
 
 pub fn handle_key_rotation<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::key_rotation::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
+{
+    handler_future(async move {
+        let signer = server.signer()?;
+        let receipt = server.dispatcher.handshake_async(
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::key_rotation::post::Response(receipt)))
+    })
+}
+
+pub fn handle_remove_member<D, S, W, DB>(
+    server: web::Data<Arc<Server<D, S, W, DB>>>,
+    req: web::Json<api::remove_member::post::Request>,
+) -> HandlerFuture
+    where
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let signer = server.dispatcher.get_account(0)?;
-    let receipt = server.dispatcher.handshake(
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(api::key_rotation::post::Response(receipt)))
+    handler_future(async move {
+        let signer = server.signer()?;
+        let receipt = server.dispatcher.remove_member_async(
+            signer,
+            req.roster_idx,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(api::remove_member::post::Response(receipt)))
+    })
 }
 
-/// Fetch events from blockchain nodes manually, and then get the balance of the address approved by the owner from enclave.
+/// Fetch events from blockchain nodes manually, and then query the balance of the address approved by the owner from enclave.
 pub fn handle_allowance<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::allowance::get::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    server.dispatcher.block_on_event::<_, U64>(&req.contract_addr, &server.abi_path)?;
-
-    let access_right = req.into_access_right()?;
-    let owner_approved = get_state::<Approved>(&access_right, server.eid, "Approved")?;
-    let approved_amount = owner_approved.allowance(&req.spender).unwrap();
-    // TODO: stop using unwrap when switching from failure to anyhow.
-
-    Ok(HttpResponse::Ok().json(api::allowance::get::Response((*approved_amount).as_raw())))
+    handler_future(async move {
+        server.dispatcher.fetch_events::<U256>(&req.contract_addr).await?;
+
+        let access_right = req.into_access_right()?;
+        let owner = access_right.user_address();
+        let spender = req.spender;
+        let approved_amount = server.dispatcher.query::<_, U256>(
+            &access_right,
+            allowance { owner, spender },
+            "allowance",
+        )?;
+        let approved_amount = approved_amount.try_as_u128().map_err(HostError::from)?;
+
+        Ok(HttpResponse::Ok().json(api::allowance::get::Response(approved_amount)))
+    })
 }
 
 /// Fetch events from blockchain nodes manually, and then get balance of the address from enclave.
 pub fn handle_balance_of<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<api::state::get::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    server.dispatcher.block_on_event::<_, U64>(&req.contract_addr, &server.abi_path)?;
+    handler_future(async move {
+        server.dispatcher.fetch_events::<U256>(&req.contract_addr).await?;
 
-    let access_right = req.into_access_right()?;
-    let state = get_state::<U64>(&access_right, server.eid, "Balance")?;
+        let access_right = req.into_access_right()?;
+        let state = get_state::<U256>(&access_right, server.eid, "Balance")?;
+        let balance = state.try_as_u128().map_err(HostError::from)?;
 
-    Ok(HttpResponse::Ok().json(api::state::get::Response(state.as_raw())))
+        Ok(HttpResponse::Ok().json(api::state::get::Response(balance)))
+    })
 }
 
 pub fn handle_start_sync_bc<D, S, W, DB>(
@@ -306,15 +362,16 @@ pub fn handle_start_sync_bc<D, S, W, DB>(
 ) -> Result<HttpResponse, Error>
     where
         D: Deployer + Send + Sync + 'static,
-        S: Sender + Send + Sync + 'static,
-        W: Watcher<WatcherDB=DB> + Send + Sync + 'static,
-        DB: BlockNumDB + Send + Sync + 'static,
+        D::Artifact: Send + Sync,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
+    // The watcher waits until logs are pushed, so events are fetched again right away.
     let _ = thread::spawn(move || {
         loop {
-            server.dispatcher.block_on_event::<_, U64>(&req.contract_addr, &server.abi_path).unwrap();
+            server.dispatcher.block_on_event::<U256>(&req.contract_addr).unwrap();
             debug!("event fetched...");
-            thread::sleep(time::Duration::from_secs(3));
         }
     });
 
@@ -327,14 +384,19 @@ pub fn handle_set_contract_addr<D, S, W, DB>(
 ) -> Result<HttpResponse, Error>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
     debug!("Starting set a contract address...");
 
     debug!("Contract address: {:?}", &req.contract_addr);
-    server.dispatcher.set_contract_addr(&req.contract_addr, &server.abi_path)?;
+    server.dispatcher.set_contract_addr(&req.contract_addr, &server.artifact)?;
+
+    // Resume from the sealed snapshot if the enclave has processed events of the contract before.
+    if let Some(block_num) = server.dispatcher.restore_snapshot(&req.contract_addr)? {
+        debug!("Restored the enclave snapshot at block number: {}", block_num);
+    }
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -345,8 +407,8 @@ pub fn handle_register_notification<D, S, W, DB>(
 ) -> Result<HttpResponse, Error>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
     let access_right = req.into_access_right()?;
@@ -354,3 +416,34 @@ pub fn handle_register_notification<D, S, W, DB>(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+pub fn handle_tx<D, S, W, DB>(
+    server: web::Data<Arc<Server<D, S, W, DB>>>,
+    tx_hash: web::Path<String>,
+    req: web::Query<api::tx::get::Request>,
+) -> HandlerFuture
+    where
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
+{
+    handler_future(async move {
+        let timeout = Duration::from_secs(req.timeout_secs.unwrap_or(0));
+        let receipt = server.dispatcher.wait_for_receipt_async(&tx_hash, timeout).await?;
+        debug!("Receipt of {}: {:?}", &tx_hash, &receipt);
+
+        let status = match receipt.status {
+            TxStatus::Pending => api::tx::get::Status::Pending,
+            TxStatus::Mined(block_number) => api::tx::get::Status::Mined { block_number },
+            TxStatus::Reverted(reason) => api::tx::get::Status::Reverted { reason },
+            TxStatus::Dropped => api::tx::get::Status::Dropped,
+        };
+
+        Ok(HttpResponse::Ok().json(api::tx::get::Response {
+            tx_hash: tx_hash.into_inner(),
+            status,
+            gas_used: receipt.gas_used,
+        }))
+    })
+}
@@ -1,45 +1,114 @@
-use std::{sync::Arc, io, env};
+use std::{sync::Arc, io, env, time::Duration};
 use sgx_types::sgx_enclave_id_t;
 use anonify_host::{
     EnclaveDir,
     Dispatcher,
 };
 use anonify_bc_connector::{
-    EventDB, BlockNumDB,
+    FileEventDB, BlockNumDB,
     traits::*,
     eth::*,
+    ledger::*,
+    utils::SignerAddress,
+    error::Result,
 };
+use anonify_wallet::EthKeystoreDirectory;
 use handlers::*;
 use actix_web::{web, App, HttpServer};
 
 mod handlers;
 
 #[derive(Debug)]
-pub struct Server<D: Deployer, S: Sender, W: Watcher<WatcherDB=DB>, DB: BlockNumDB> {
+pub struct Server<D, S, W, DB>
+where
+    D: Deployer,
+    S: AsyncSender<Artifact=D::Artifact>,
+    W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
+    DB: BlockNumDB,
+{
     pub eid: sgx_enclave_id_t,
-    pub eth_url: String,
-    pub abi_path: String,
+    /// The url of an Ethereum node or a ledger server
+    pub node_url: String,
+    /// The contract to deploy and to send transactions to
+    pub artifact: D::Artifact,
     pub dispatcher: Dispatcher<D, S, W, DB>,
+    /// A local key to sign transactions. If it is not set, the first account of the node is used.
+    pub eth_signer: Option<EthSigner>,
+}
+
+/// Gas configurations read from environment variables.
+/// EIP-1559 fees are paid if `MAX_PRIORITY_FEE` (in wei) is set, and legacy gas prices otherwise.
+fn gas_config_from_env() -> GasConfig {
+    let mut gas_config = GasConfig::default();
+    if let Ok(percent) = env::var("GAS_MULTIPLIER_PERCENT") {
+        gas_config.multiplier_percent = percent.parse().expect("GAS_MULTIPLIER_PERCENT is not a number.");
+    }
+    if let Ok(fee) = env::var("MAX_PRIORITY_FEE") {
+        let max_priority_fee: u64 = fee.parse().expect("MAX_PRIORITY_FEE is not a number.");
+        gas_config.fee_policy = FeePolicy::Eip1559 { max_priority_fee: max_priority_fee.into() };
+    }
+    if let Ok(secs) = env::var("RESUBMIT_AFTER_SECS") {
+        gas_config.resubmit_after = Duration::from_secs(secs.parse().expect("RESUBMIT_AFTER_SECS is not a number."));
+    }
+    if let Ok(max) = env::var("MAX_RESUBMISSIONS") {
+        gas_config.max_resubmissions = max.parse().expect("MAX_RESUBMISSIONS is not a number.");
+    }
+    if let Ok(secs) = env::var("TRACK_TX_FOR_SECS") {
+        gas_config.track_for = Duration::from_secs(secs.parse().expect("TRACK_TX_FOR_SECS is not a number."));
+    }
+
+    gas_config
 }
 
 impl<D, S, W, DB> Server<D, S, W, DB>
 where
     D: Deployer,
-    S: Sender,
-    W: Watcher<WatcherDB=DB>,
+    S: AsyncSender<Artifact=D::Artifact>,
+    W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
     DB: BlockNumDB,
 {
-    pub fn new(eid: sgx_enclave_id_t) -> Self {
-        let eth_url = env::var("ETH_URL").expect("ETH_URL is not set.");
-        let abi_path = env::var("ANONYMOUS_ASSET_ABI_PATH").expect("ANONYMOUS_ASSET_ABI_PATH is not set.");
-        let event_db = Arc::new(DB::new());
-        let dispatcher = Dispatcher::<D,S,W,DB>::new(eid, &eth_url, event_db).unwrap();
+    pub fn new(eid: sgx_enclave_id_t, node_url: String, artifact: D::Artifact, event_db: Arc<DB>) -> Self {
+        let dispatcher = Dispatcher::<D,S,W,DB>::new(eid, &node_url, event_db).unwrap();
+        if let Ok(confirmations) = env::var("CONFIRMATIONS") {
+            dispatcher.set_confirmations(confirmations.parse().expect("CONFIRMATIONS is not a number."));
+        }
+        if let Ok(page_size) = env::var("LOG_PAGE_SIZE") {
+            dispatcher.set_page_size(page_size.parse().expect("LOG_PAGE_SIZE is not a number."));
+        }
+        if let Ok(confirmations) = env::var("RECEIPT_CONFIRMATIONS") {
+            dispatcher.set_receipt_confirmations(confirmations.parse().expect("RECEIPT_CONFIRMATIONS is not a number."));
+        }
+        dispatcher.set_gas_config(gas_config_from_env());
+
+        let eth_signer = env::var("ETH_KEYFILE").ok().map(|keyfile_name| {
+            let keystore_dir = env::var("ETH_KEYSTORE_DIR").expect("ETH_KEYSTORE_DIR is not set.");
+            let password = env::var("ETH_KEY_PASSWORD").expect("ETH_KEY_PASSWORD is not set.");
+            let secret = EthKeystoreDirectory::create(keystore_dir)
+                .and_then(|dir| dir.load(&keyfile_name))
+                .and_then(|keyfile| keyfile.get_secret_key(password.as_bytes()))
+                .expect("Failed to load the Ethereum keyfile.");
+            let signer = EthSigner::from_secret(&secret[..]).expect("Invalid Ethereum secret key.");
+
+            match env::var("CHAIN_ID") {
+                Ok(chain_id) => signer.with_chain_id(chain_id.parse().expect("CHAIN_ID is not a number.")),
+                Err(_) => signer,
+            }
+        });
 
         Server {
             eid,
-            eth_url,
-            abi_path,
-            dispatcher
+            node_url,
+            artifact,
+            dispatcher,
+            eth_signer,
+        }
+    }
+
+    /// The address which signs transactions sent by this server.
+    pub fn signer(&self) -> Result<SignerAddress> {
+        match &self.eth_signer {
+            Some(signer) => Ok(SignerAddress::EthSigner(signer.clone())),
+            None => self.dispatcher.get_account(0),
         }
     }
 }
@@ -47,33 +116,58 @@ where
 fn main() -> io::Result<()> {
     env_logger::init();
     let anonify_url = env::var("ANONIFY_URL").expect("ANONIFY_URL is not set.");
+    let event_db_path = env::var("EVENT_DB_PATH").expect("EVENT_DB_PATH is not set.");
 
     // Enclave must be initialized in main function.
     let enclave = EnclaveDir::new()
             .init_enclave(true)
             .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(
-        Server::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid)
-    );
+    // Block numbers of processed events are persisted to resume syncing after restarts.
+    let event_db = Arc::new(FileEventDB::open(event_db_path).expect("Failed to open event db."));
+    // Without an Ethereum node, transactions are appended to a ledger server.
+    match env::var("LEDGER_URL") {
+        Ok(ledger_url) => {
+            let server = Server::<LedgerDeployer, LedgerSender, LedgerWatcher<FileEventDB>, FileEventDB>::new(eid, ledger_url, (), event_db);
+            run_server(Arc::new(server), anonify_url)
+        }
+        Err(_) => {
+            let eth_url = env::var("ETH_URL").expect("ETH_URL is not set.");
+            let abi_path = env::var("ANONYMOUS_ASSET_ABI_PATH").expect("ANONYMOUS_ASSET_ABI_PATH is not set.");
+            let bin_path = env::var("ANONYMOUS_ASSET_BIN_PATH").expect("ANONYMOUS_ASSET_BIN_PATH is not set.");
+            let artifact = ContractArtifact::load(abi_path, bin_path).expect("Failed to load the contract artifact.");
+            let server = Server::<EthDeployer, EthSender, SubscriptionWatcher<FileEventDB>, FileEventDB>::new(eid, eth_url, artifact, event_db);
+            run_server(Arc::new(server), anonify_url)
+        }
+    }
+}
 
+fn run_server<D, S, W>(server: Arc<Server<D, S, W, FileEventDB>>, anonify_url: String) -> io::Result<()>
+where
+    D: Deployer + Send + Sync + 'static,
+    D::Artifact: Send + Sync,
+    S: AsyncSender<Artifact=D::Artifact> + 'static,
+    W: AsyncWatcher<WatcherDB=FileEventDB, Artifact=D::Artifact> + 'static,
+{
     HttpServer::new(move || {
         App::new()
             .data(server.clone())
-            .route("/api/v1/deploy", web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/join_group", web::post().to(handle_join_group::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/init_state", web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/transfer", web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/key_rotation", web::post().to(handle_key_rotation::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/approve", web::post().to(handle_approve::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/transfer_from", web::post().to(handle_transfer_from::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/mint", web::post().to(handle_mint::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/burn", web::post().to(handle_burn::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/allowance", web::get().to(handle_allowance::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/balance_of", web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/start_sync_bc", web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/set_contract_addr", web::get().to(handle_set_contract_addr::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
-            .route("/api/v1/register_notification", web::post().to(handle_register_notification::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
+            .route("/api/v1/deploy", web::post().to(handle_deploy::<D, S, W, FileEventDB>))
+            .route("/api/v1/join_group", web::post().to_async(handle_join_group::<D, S, W, FileEventDB>))
+            .route("/api/v1/init_state", web::post().to_async(handle_init_state::<D, S, W, FileEventDB>))
+            .route("/api/v1/transfer", web::post().to_async(handle_transfer::<D, S, W, FileEventDB>))
+            .route("/api/v1/key_rotation", web::post().to_async(handle_key_rotation::<D, S, W, FileEventDB>))
+            .route("/api/v1/remove_member", web::post().to_async(handle_remove_member::<D, S, W, FileEventDB>))
+            .route("/api/v1/approve", web::post().to_async(handle_approve::<D, S, W, FileEventDB>))
+            .route("/api/v1/transfer_from", web::post().to_async(handle_transfer_from::<D, S, W, FileEventDB>))
+            .route("/api/v1/mint", web::post().to_async(handle_mint::<D, S, W, FileEventDB>))
+            .route("/api/v1/burn", web::post().to_async(handle_burn::<D, S, W, FileEventDB>))
+            .route("/api/v1/allowance", web::get().to_async(handle_allowance::<D, S, W, FileEventDB>))
+            .route("/api/v1/balance_of", web::get().to_async(handle_balance_of::<D, S, W, FileEventDB>))
+            .route("/api/v1/start_sync_bc", web::get().to(handle_start_sync_bc::<D, S, W, FileEventDB>))
+            .route("/api/v1/set_contract_addr", web::get().to(handle_set_contract_addr::<D, S, W, FileEventDB>))
+            .route("/api/v1/register_notification", web::post().to(handle_register_notification::<D, S, W, FileEventDB>))
+            .route("/api/v1/tx/{hash}", web::get().to_async(handle_tx::<D, S, W, FileEventDB>))
     })
     .bind(anonify_url)?
     .run()
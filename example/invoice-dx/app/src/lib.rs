@@ -21,14 +21,14 @@ use crate::localstd::{
 use anonify_common::UserAddress;
 use codec::{Encode, Decode};
 
-pub const MAX_MEM_SIZE: usize = 500;
 pub const CIPHERTEXT_SIZE: usize = MAX_MEM_SIZE + 30;
 
 impl_memory! {
-    (0, "Invoice", Bytes)
+    (0, "Invoice", Bytes, 500)
 }
 
-impl_runtime! {
+#[anonify_runtime::runtime]
+impl Runtime {
     #[fn_id=0]
     pub fn send_invoice(
         self,
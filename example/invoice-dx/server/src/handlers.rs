@@ -1,6 +1,10 @@
-use std::{sync::{Arc, mpsc}, env, thread, time};
+use std::{sync::{Arc, mpsc}, env, thread, time, future::Future};
 use failure::Error;
 use log::debug;
+use futures::{
+    compat::Compat,
+    future::{FutureExt, LocalBoxFuture, TryFutureExt},
+};
 // use anonify_host::dispatcher::get_state;
 use anonify_bc_connector::{
     // EventDB,
@@ -20,68 +24,79 @@ use sgx_types::sgx_enclave_id_t;
 use crate::moneyforward::MFClient;
 use crate::sunabar::SunabarClient;
 
+/// actix-web polls futures 0.1, so handlers awaiting the dispatcher return their futures converted into them.
+pub type HandlerFuture = Compat<LocalBoxFuture<'static, Result<HttpResponse, Error>>>;
+
+fn handler_future<F>(future: F) -> HandlerFuture
+where
+    F: Future<Output = Result<HttpResponse, Error>> + 'static,
+{
+    future.boxed_local().compat()
+}
+
 #[derive(Debug)]
-pub struct Server<D: Deployer, S: Sender, W: Watcher<WatcherDB=DB>, DB: BlockNumDB> {
+pub struct Server<D, S, W, DB>
+    where
+        D: Deployer,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
+        DB: BlockNumDB,
+{
     pub eid: sgx_enclave_id_t,
     pub eth_url: String,
-    pub abi_path: String,
     pub dispatcher: Dispatcher<D, S, W, DB>,
 }
 
 impl<D, S, W, DB> Server<D, S, W, DB>
     where
         D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
+        S: AsyncSender<Artifact=D::Artifact>,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact>,
         DB: BlockNumDB,
 {
-    pub fn new(eid: sgx_enclave_id_t) -> Self {
+    pub fn new(eid: sgx_enclave_id_t, event_db: Arc<DB>) -> Self {
         let eth_url = env::var("ETH_URL").expect("ETH_URL is not set.");
-        let abi_path = env::var("ANONYMOUS_ASSET_ABI_PATH").expect("ANONYMOUS_ASSET_ABI_PATH is not set.");
-        let event_db = Arc::new(DB::new());
         let dispatcher = Dispatcher::<D, S, W, DB>::new(eid, &eth_url, event_db).unwrap();
 
         Server {
             eid,
             eth_url,
-            abi_path,
             dispatcher,
         }
     }
 }
 
-const DEFAULT_SEND_GAS: u64 = 3_000_000;
-
 pub fn handle_send_invoice<D, S, W, DB>(
     server: web::Data<Arc<Server<D, S, W, DB>>>,
     req: web::Json<dx_api::send_invoice::post::Request>,
-) -> Result<HttpResponse, Error>
+) -> HandlerFuture
     where
-        D: Deployer,
-        S: Sender,
-        W: Watcher<WatcherDB=DB>,
-        DB: BlockNumDB,
+        D: Deployer + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
-    let access_right = req.into_access_right()?;
-    let signer = server.dispatcher.get_account(0)?;
-    let recipient = req.recipient;
-    let invoice = Bytes::new(req.invoice.clone().into());
-    let invoice = Bytes::from(invoice);
-
-    let send_invoice_state = send_invoice{ recipient, invoice };
-
-    let receipt = server.dispatcher.send_instruction(
-        access_right,
-        send_invoice_state,
-        req.state_id,
-        "send_invoice",
-        signer,
-        DEFAULT_SEND_GAS,
-        &req.contract_addr,
-        &server.abi_path,
-    )?;
-
-    Ok(HttpResponse::Ok().json(dx_api::send_invoice::post::Response(receipt)))
+    handler_future(async move {
+        let access_right = req.into_access_right()?;
+        let signer = server.dispatcher.get_account(0)?;
+        let recipient = req.recipient;
+        let invoice = Bytes::new(req.invoice.clone().into());
+        let invoice = Bytes::from(invoice);
+
+        let send_invoice_state = send_invoice{ recipient, invoice };
+
+        let receipt = server.dispatcher.send_instruction_async(
+            access_right,
+            send_invoice_state,
+            req.state_id,
+            "send_invoice",
+            signer,
+            None,
+            &req.contract_addr,
+        ).await?;
+
+        Ok(HttpResponse::Ok().json(dx_api::send_invoice::post::Response(receipt)))
+    })
 }
 
 pub fn handle_start_polling_moneyforward(
@@ -123,9 +138,9 @@ pub fn handle_start_sync_bc<D, S, W, DB>(
 ) -> Result<HttpResponse, Error>
     where
         D: Deployer + Send + Sync + 'static,
-        S: Sender + Send + Sync + 'static,
-        W: Watcher<WatcherDB=DB> + Send + Sync + 'static,
-        DB: BlockNumDB + Send + Sync + 'static,
+        S: AsyncSender<Artifact=D::Artifact> + 'static,
+        W: AsyncWatcher<WatcherDB=DB, Artifact=D::Artifact> + 'static,
+        DB: BlockNumDB + 'static,
 {
     let client = SunabarClient::new();
     let (tx, rx) = mpsc::channel();
@@ -135,7 +150,7 @@ pub fn handle_start_sync_bc<D, S, W, DB>(
             debug!("event fetched...");
             let shared_invoices = server
                 .dispatcher
-                .block_on_event::<_, Bytes>(&req.contract_addr, &server.abi_path).unwrap();
+                .block_on_event::<Bytes>(&req.contract_addr).unwrap();
 
             if let Some(invoices) = shared_invoices {
                 for invoice in invoices {
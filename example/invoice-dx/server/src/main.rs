@@ -17,14 +17,15 @@ fn main() -> io::Result<()> {
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
+    let event_db = Arc::new(EventDB::new());
     let server = Arc::new(
-        Server::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid)
+        Server::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>::new(eid, event_db)
     );
 
     HttpServer::new(move || {
         App::new()
             .data(server.clone())
-            .route("/api/v1/send_invoice", web::post().to(handle_send_invoice::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
+            .route("/api/v1/send_invoice", web::post().to_async(handle_send_invoice::<EthDeployer, EthSender, EventWatcher<EventDB>, EventDB>))
             .route("/api/v1/start_polling_moneyforward", web::post().to(handle_start_polling_moneyforward))
             .route("/api/v1/handle_start_sync_bc", web::post().to(handle_start_sync_bc))
     })
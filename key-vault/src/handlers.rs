@@ -0,0 +1,44 @@
+use std::sync::{Arc, Mutex};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use actix_web::{
+    web,
+    error::{self, Error},
+    HttpResponse,
+};
+use crate::vault::{Vault, SignedRequest};
+
+/// A sealed path secret to store with the roster index and the epoch, signed by `pubkey`.
+/// Byte strings are hex-encoded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub pubkey: String,
+    pub roster_idx: u32,
+    pub epoch: u32,
+    pub sealed: String,
+    pub signature: String,
+}
+
+/// The sealed path secret stored with the roster index and the epoch,
+/// which is the one in the request unless one is already stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    pub sealed: String,
+}
+
+pub fn handle_path_secret(
+    vault: web::Data<Arc<Mutex<Vault>>>,
+    req: web::Json<Request>,
+) -> Result<HttpResponse, Error> {
+    let req = req.into_inner();
+    let signed = SignedRequest::parse(&req.pubkey, req.roster_idx, req.epoch, &req.sealed, &req.signature)
+        .map_err(error::ErrorBadRequest)?;
+    signed.verify().map_err(error::ErrorUnauthorized)?;
+
+    let sealed = vault.lock().unwrap()
+        .get_or_store(signed)
+        .map_err(error::ErrorInternalServerError)?;
+    debug!("Served the path secret of roster index {} at epoch {}", req.roster_idx, req.epoch);
+
+    Ok(HttpResponse::Ok().json(Response { sealed: hex::encode(sealed) }))
+}
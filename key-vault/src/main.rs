@@ -0,0 +1,29 @@
+//! A key vault server, which backs up path secrets sealed by enclaves in a file and serves them over HTTP.
+//! Requests are signed by enclaves, and the path secrets stored by a signer are only returned to it.
+use std::{
+    env, io,
+    sync::{Arc, Mutex},
+};
+use handlers::*;
+use vault::Vault;
+use actix_web::{web, App, HttpServer};
+
+mod handlers;
+mod vault;
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let key_vault_addr = env::var("KEY_VAULT_ADDR").expect("KEY_VAULT_ADDR is not set.");
+    let key_vault_path = env::var("KEY_VAULT_PATH").expect("KEY_VAULT_PATH is not set.");
+
+    let vault = Vault::open(key_vault_path).expect("Failed to open the key vault.");
+    let vault = Arc::new(Mutex::new(vault));
+
+    HttpServer::new(move || {
+        App::new()
+            .data(vault.clone())
+            .route("/api/v1/path_secrets", web::post().to(handle_path_secret))
+    })
+    .bind(key_vault_addr)?
+    .run()
+}
@@ -0,0 +1,235 @@
+//! Sealed path secrets persisted as a file of JSON lines, one line per stored path secret.
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+use serde::{Deserialize, Serialize};
+use secp256k1::{Message, PublicKey, Signature};
+use tiny_keccak::keccak256;
+use anyhow::{anyhow, ensure, Result};
+
+const SIGNING_DOMAIN: &[u8] = b"anonify key vault";
+const PUBKEY_SIZE: usize = 65;
+const SIGNATURE_SIZE: usize = 64;
+
+/// A request to store a sealed path secret, signed by the enclave which sealed it.
+#[derive(Debug, Clone)]
+pub struct SignedRequest {
+    pubkey: Vec<u8>,
+    roster_idx: u32,
+    epoch: u32,
+    sealed: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedRequest {
+    /// Parse hex-encoded byte strings.
+    pub fn parse(pubkey: &str, roster_idx: u32, epoch: u32, sealed: &str, signature: &str) -> Result<Self> {
+        Ok(SignedRequest {
+            pubkey: decode_hex(pubkey, "public key", Some(PUBKEY_SIZE))?,
+            roster_idx,
+            epoch,
+            sealed: decode_hex(sealed, "sealed path secret", None)?,
+            signature: decode_hex(signature, "signature", Some(SIGNATURE_SIZE))?,
+        })
+    }
+
+    /// Verify the signature over the keccak256 hash of `"anonify key vault" || roster_idx || epoch || sealed`,
+    /// where the roster index and the epoch are encoded in little endian.
+    pub fn verify(&self) -> Result<()> {
+        let mut pubkey = [0u8; PUBKEY_SIZE];
+        pubkey.copy_from_slice(&self.pubkey);
+        let pubkey = PublicKey::parse(&pubkey).map_err(|e| anyhow!("Invalid public key: {:?}", e))?;
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        signature.copy_from_slice(&self.signature);
+        let signature = Signature::parse(&signature);
+        let msg = Message::parse(&self.signing_message());
+        ensure!(secp256k1::verify(&msg, &signature, &pubkey), "Invalid signature");
+
+        Ok(())
+    }
+
+    fn signing_message(&self) -> [u8; 32] {
+        let mut preimage = SIGNING_DOMAIN.to_vec();
+        preimage.extend_from_slice(&self.roster_idx.to_le_bytes());
+        preimage.extend_from_slice(&self.epoch.to_le_bytes());
+        preimage.extend_from_slice(&self.sealed);
+        keccak256(&preimage)
+    }
+}
+
+fn decode_hex(s: &str, name: &str, len: Option<usize>) -> Result<Vec<u8>> {
+    let bytes = hex::decode(s).map_err(|e| anyhow!("Invalid {}: {}", name, e))?;
+    if let Some(len) = len {
+        ensure!(bytes.len() == len, "Invalid {} length: {}", name, bytes.len());
+    }
+    Ok(bytes)
+}
+
+/// Byte strings are hex-encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct Entry {
+    pubkey: String,
+    roster_idx: u32,
+    epoch: u32,
+    sealed: String,
+}
+
+/// Path secrets are kept apart per public key, so a path secret is only returned to its signer.
+type EntryKey = (String, u32, u32);
+
+#[derive(Debug)]
+pub struct Vault {
+    file: File,
+    entries: HashMap<EntryKey, Vec<u8>>,
+}
+
+impl Vault {
+    /// Open the key vault at `path`, which is created empty if it doesn't exist.
+    /// A last line truncated by a crash while appending is discarded.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).read(true).append(true).open(path.as_ref())?;
+
+        let mut entries = HashMap::new();
+        let mut valid_len = 0;
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? != 0 {
+            let entry = match serde_json::from_str::<Entry>(&line) {
+                Ok(entry) if line.ends_with('\n') => entry,
+                _ => break,
+            };
+            let sealed = hex::decode(&entry.sealed)?;
+            entries.insert((entry.pubkey, entry.roster_idx, entry.epoch), sealed);
+            valid_len += line.len() as u64;
+            line.clear();
+        }
+        file.set_len(valid_len)?;
+
+        Ok(Vault { file, entries })
+    }
+
+    /// Store the sealed path secret unless one is already stored by the signer
+    /// with the same roster index and epoch, and return the stored one.
+    /// The request must be verified beforehand.
+    pub fn get_or_store(&mut self, req: SignedRequest) -> Result<Vec<u8>> {
+        let key = (hex::encode(&req.pubkey), req.roster_idx, req.epoch);
+        if let Some(sealed) = self.entries.get(&key) {
+            return Ok(sealed.clone());
+        }
+
+        let entry = Entry {
+            pubkey: key.0.clone(),
+            roster_idx: key.1,
+            epoch: key.2,
+            sealed: hex::encode(&req.sealed),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+
+        self.entries.insert(key, req.sealed.clone());
+        Ok(req.sealed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf, process};
+    use secp256k1::SecretKey;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("anonify-key-vault-{}-{}", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn signed_request(secret: u8, roster_idx: u32, epoch: u32, sealed: &[u8]) -> SignedRequest {
+        let secret = SecretKey::parse(&[secret; 32]).unwrap();
+        let mut req = SignedRequest {
+            pubkey: PublicKey::from_secret_key(&secret).serialize().to_vec(),
+            roster_idx,
+            epoch,
+            sealed: sealed.to_vec(),
+            signature: vec![],
+        };
+        let (signature, _) = secp256k1::sign(&Message::parse(&req.signing_message()), &secret).unwrap();
+        req.signature = signature.serialize().to_vec();
+        req
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let req = signed_request(1, 0, 3, b"sealed");
+        req.verify().unwrap();
+
+        let parsed = SignedRequest::parse(
+            &hex::encode(&req.pubkey),
+            0,
+            3,
+            &hex::encode(&req.sealed),
+            &hex::encode(&req.signature),
+        ).unwrap();
+        parsed.verify().unwrap();
+        assert!(SignedRequest::parse("04", 0, 3, "", &hex::encode(&req.signature)).is_err());
+
+        // The signature covers the roster index, the epoch and the sealed path secret.
+        let mut other = req.clone();
+        other.epoch = 4;
+        assert!(other.verify().is_err());
+        let mut other = req.clone();
+        other.sealed = b"tampered".to_vec();
+        assert!(other.verify().is_err());
+        let mut other = req.clone();
+        other.pubkey = signed_request(2, 0, 3, b"sealed").pubkey;
+        assert!(other.verify().is_err());
+    }
+
+    #[test]
+    fn test_store_once_and_reopen() {
+        let path = temp_path("reopen");
+        let mut vault = Vault::open(&path).unwrap();
+
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 0, b"first")).unwrap(), b"first".to_vec());
+        // Requesting again returns the stored path secret.
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 0, b"second")).unwrap(), b"first".to_vec());
+        // Path secrets of other signers, roster indices or epochs are stored apart.
+        assert_eq!(vault.get_or_store(signed_request(2, 0, 0, b"other")).unwrap(), b"other".to_vec());
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 1, b"next")).unwrap(), b"next".to_vec());
+        drop(vault);
+
+        let mut vault = Vault::open(&path).unwrap();
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 0, b"third")).unwrap(), b"first".to_vec());
+        assert_eq!(vault.get_or_store(signed_request(2, 0, 0, b"third")).unwrap(), b"other".to_vec());
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 1, b"third")).unwrap(), b"next".to_vec());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_discard_truncated_entry() {
+        let path = temp_path("truncated");
+        let mut vault = Vault::open(&path).unwrap();
+        vault.get_or_store(signed_request(1, 0, 0, b"first")).unwrap();
+        drop(vault);
+
+        // A crash while appending leaves a partial line.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"pubkey":"04"#).unwrap();
+        drop(file);
+
+        let mut vault = Vault::open(&path).unwrap();
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 1, b"next")).unwrap(), b"next".to_vec());
+        drop(vault);
+
+        let mut vault = Vault::open(&path).unwrap();
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 0, b"again")).unwrap(), b"first".to_vec());
+        assert_eq!(vault.get_or_store(signed_request(1, 0, 1, b"again")).unwrap(), b"next".to_vec());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Request and response types of the append-only ledger server.
+//!
+//! The ledger is a chain of blocks, each of which includes at most one transaction.
+//! A transaction deploys a contract or appends an event to a deployed contract,
+//! and its hash is the hash of the block including it.
+use serde::{Deserialize, Serialize};
+use web3::types::{Address, Bytes, H256};
+
+/// An event appended to a contract, which is passed to the enclave as it is.
+/// Signatures of handshakes are verified by enclaves, not by the ledger.
+/// `JoinGroup` is appended only by deploying a contract or by `/api/v1/contracts/{contract}/members`
+/// with an attestation report of the joining enclave.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "payload")]
+pub enum Event {
+    Ciphertext(Bytes),
+    Handshake {
+        handshake: Bytes,
+        enclave_sig: Bytes,
+    },
+    /// An enclave joins the group with the next roster index, which starts from 0 at the deployer.
+    JoinGroup {
+        roster_idx: u32,
+        enclave_address: Address,
+        handshake: Bytes,
+        enclave_sig: Bytes,
+    },
+}
+
+// ----------------------
+//  GET and POST types
+// ----------------------
+
+/// `/api/v1/contracts`
+pub mod deploy {
+    pub mod post {
+        use super::super::*;
+
+        /// Deploy a contract whose first event is the deployer's enclave joining the group with roster index 0.
+        /// The enclave address and MRENCLAVE of the contract are taken from the attestation report.
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Request {
+            pub sender: String,
+            pub report: Bytes,
+            pub report_sig: Bytes,
+            pub handshake: Bytes,
+            pub enclave_sig: Bytes,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub contract: Address,
+            pub tx_hash: H256,
+        }
+    }
+}
+
+/// `/api/v1/contracts/{contract}`
+pub mod contract {
+    pub mod get {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub contract: Address,
+            /// The number of the block which deployed the contract
+            pub deployed_at: u64,
+        }
+    }
+}
+
+/// `/api/v1/contracts/{contract}/members`
+pub mod join {
+    pub mod post {
+        use super::super::*;
+
+        /// An enclave joins the group with the next roster index.
+        /// The attestation report must have the same MRENCLAVE as the deployer's.
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Request {
+            pub sender: String,
+            pub roster_idx: u32,
+            pub report: Bytes,
+            pub report_sig: Bytes,
+            pub handshake: Bytes,
+            pub enclave_sig: Bytes,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub tx_hash: H256,
+        }
+    }
+}
+
+/// `/api/v1/contracts/{contract}/events`
+pub mod events {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Request {
+            pub sender: String,
+            pub event: Event,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub tx_hash: H256,
+        }
+    }
+
+    pub mod get {
+        use super::super::*;
+
+        /// Blocks from `from` to `to`, both inclusive.
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Request {
+            pub from: u64,
+            pub to: u64,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            /// Events of the contract in the order they were appended
+            pub events: Vec<Event>,
+            /// The hash of block `to`, or `None` if it is not built yet
+            pub to_hash: Option<H256>,
+        }
+    }
+}
+
+/// `/api/v1/blocks/latest` and `/api/v1/blocks/{number}`
+pub mod block {
+    pub mod get {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub number: u64,
+            pub hash: H256,
+        }
+    }
+}
+
+/// `/api/v1/txs/{hash}`
+pub mod tx {
+    pub mod get {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+        pub struct Response {
+            pub tx_hash: H256,
+            pub block_number: u64,
+        }
+    }
+}
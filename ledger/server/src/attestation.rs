@@ -0,0 +1,62 @@
+//! Verification of attestation reports from Intel Attestation Service, in the same way as `ReportHandle.sol`.
+use serde::{Deserialize, Serialize};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use web3::types::{Address, H256};
+use anyhow::{anyhow, Result};
+
+/// The modulus of intel's report signing certificate, which is the same as `RSA_MOD` of the contract.
+const RSA_MOD: &str = "A97A2DE0E66EA6147C9EE745AC0162686C7192099AFC4B3F040FAD6DE093511D74E802F510D716038157DCAF84F4104BD3FED7E6B8F99C8817FD1FF5B9B864296C3D81FA8F1B729E02D21D72FFEE4CED725EFE74BEA68FBC4D4244286FCDD4BF64406A439A15BCB4CF67754489C423972B4A80DF5C2E7C5BC2DBAF2D42BB7B244F7C95BF92C75D3B33FC5410678A89589D1083DA3ACC459F2704CD99598C275E7C1878E00757E5BDB4E840226C11C0A17FF79C80B15C1DDB5AF21CC2417061FBD2A2DA819ED3B72B7EFAA3BFEBE2805C9B8AC19AA346512D484CFC81941E15F55881CC127E8F7AA12300CD5AFB5742FA1D20CB467A5BEB1C666CF76A368978B5";
+const RSA_EXP: [u8; 3] = [0x01, 0x00, 0x01];
+
+// See https://api.trustedservices.intel.com/documents/sgx-attestation-api-spec.pdf, P.23.
+const MR_ENCLAVE_OFFSET: usize = 112;
+const ENCLAVE_ADDRESS_OFFSET: usize = 368;
+const REPORT_NONCE_OFFSET: usize = 388;
+const REPORT_NONCE_END: usize = 420;
+
+/// The fields of a verified attestation report which the ledger checks joins against.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Attestation {
+    pub mr_enclave: H256,
+    pub enclave_address: Address,
+    pub report_nonce: H256,
+}
+
+#[derive(Deserialize)]
+struct Report {
+    #[serde(rename = "isvEnclaveQuoteBody")]
+    isv_enclave_quote_body: String,
+}
+
+/// Verify that the report is signed by intel, and extract MRENCLAVE and the report data from its quote.
+pub fn verify_report(report: &[u8], report_sig: &[u8]) -> Result<Attestation> {
+    let modulus = hex::decode(RSA_MOD)?;
+    RsaPublicKeyComponents { n: &modulus[..], e: &RSA_EXP[..] }
+        .verify(&RSA_PKCS1_2048_8192_SHA256, report, report_sig)
+        .map_err(|_| anyhow!("Invalid report's signature"))?;
+
+    let report: Report = serde_json::from_slice(report)?;
+    let quote = base64::decode(&report.isv_enclave_quote_body)?;
+    if quote.len() < REPORT_NONCE_END {
+        return Err(anyhow!("The quote is too short: {} bytes", quote.len()));
+    }
+
+    Ok(Attestation {
+        mr_enclave: H256::from_slice(&quote[MR_ENCLAVE_OFFSET..MR_ENCLAVE_OFFSET + 32]),
+        enclave_address: Address::from_slice(&quote[ENCLAVE_ADDRESS_OFFSET..REPORT_NONCE_OFFSET]),
+        report_nonce: H256::from_slice(&quote[REPORT_NONCE_OFFSET..REPORT_NONCE_END]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reject_report_not_signed_by_intel() {
+        let quote = base64::encode(&[0u8; 432][..]);
+        let report = format!(r#"{{"isvEnclaveQuoteBody":"{}"}}"#, quote);
+        assert!(verify_report(report.as_bytes(), &[0u8; 256]).is_err());
+        assert!(verify_report(report.as_bytes(), &[]).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use log::debug;
+use web3::types::{Address, H256};
+use anonify_ledger_api as api;
+use actix_web::{
+    web,
+    error::{self, Error},
+    HttpResponse,
+};
+use crate::{
+    attestation::verify_report,
+    ledger::Ledger,
+};
+
+pub fn handle_deploy(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    req: web::Json<api::deploy::post::Request>,
+) -> Result<HttpResponse, Error> {
+    let req = req.into_inner();
+    let attestation = verify_report(&req.report.0, &req.report_sig.0)
+        .map_err(error::ErrorBadRequest)?;
+    let event = api::Event::JoinGroup {
+        roster_idx: 0,
+        enclave_address: attestation.enclave_address,
+        handshake: req.handshake,
+        enclave_sig: req.enclave_sig,
+    };
+    let (contract, tx_hash) = ledger.lock().unwrap()
+        .deploy(req.sender, attestation, event)
+        .map_err(error::ErrorInternalServerError)?;
+    debug!("Deployed contract {:?} in {:?}", contract, tx_hash);
+
+    Ok(HttpResponse::Ok().json(api::deploy::post::Response { contract, tx_hash }))
+}
+
+pub fn handle_contract(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    contract: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let contract = parse_address(&contract)?;
+    let deployed_at = deployed_at(&ledger.lock().unwrap(), contract)?;
+
+    Ok(HttpResponse::Ok().json(api::contract::get::Response { contract, deployed_at }))
+}
+
+pub fn handle_join(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    contract: web::Path<String>,
+    req: web::Json<api::join::post::Request>,
+) -> Result<HttpResponse, Error> {
+    let contract = parse_address(&contract)?;
+    let req = req.into_inner();
+    let attestation = verify_report(&req.report.0, &req.report_sig.0)
+        .map_err(error::ErrorBadRequest)?;
+    let event = api::Event::JoinGroup {
+        roster_idx: req.roster_idx,
+        enclave_address: attestation.enclave_address,
+        handshake: req.handshake,
+        enclave_sig: req.enclave_sig,
+    };
+    let mut ledger = ledger.lock().unwrap();
+    deployed_at(&ledger, contract)?;
+    let tx_hash = ledger.join(req.sender, contract, attestation, event)
+        .map_err(error::ErrorInternalServerError)?;
+    debug!("An enclave joined contract {:?} with roster index {} in {:?}", contract, req.roster_idx, tx_hash);
+
+    Ok(HttpResponse::Ok().json(api::join::post::Response { tx_hash }))
+}
+
+pub fn handle_send_event(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    contract: web::Path<String>,
+    req: web::Json<api::events::post::Request>,
+) -> Result<HttpResponse, Error> {
+    let contract = parse_address(&contract)?;
+    let req = req.into_inner();
+    let mut ledger = ledger.lock().unwrap();
+    deployed_at(&ledger, contract)?;
+    let tx_hash = ledger.send(req.sender, contract, req.event)
+        .map_err(error::ErrorInternalServerError)?;
+    debug!("Appended an event to contract {:?} in {:?}", contract, tx_hash);
+
+    Ok(HttpResponse::Ok().json(api::events::post::Response { tx_hash }))
+}
+
+pub fn handle_events(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    contract: web::Path<String>,
+    req: web::Query<api::events::get::Request>,
+) -> Result<HttpResponse, Error> {
+    let contract = parse_address(&contract)?;
+    let ledger = ledger.lock().unwrap();
+    deployed_at(&ledger, contract)?;
+
+    Ok(HttpResponse::Ok().json(api::events::get::Response {
+        events: ledger.events(contract, req.from, req.to),
+        to_hash: ledger.block(req.to).map(|b| b.hash),
+    }))
+}
+
+pub fn handle_latest_block(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+) -> Result<HttpResponse, Error> {
+    let ledger = ledger.lock().unwrap();
+    let block = ledger.latest();
+
+    Ok(HttpResponse::Ok().json(api::block::get::Response { number: block.number, hash: block.hash }))
+}
+
+pub fn handle_block(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    number: web::Path<u64>,
+) -> Result<HttpResponse, Error> {
+    let number = number.into_inner();
+    let ledger = ledger.lock().unwrap();
+    let block = ledger.block(number)
+        .ok_or_else(|| error::ErrorNotFound(format!("Block {} is not built yet", number)))?;
+
+    Ok(HttpResponse::Ok().json(api::block::get::Response { number: block.number, hash: block.hash }))
+}
+
+pub fn handle_tx(
+    ledger: web::Data<Arc<Mutex<Ledger>>>,
+    tx_hash: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let tx_hash = H256::from_str(tx_hash.trim_start_matches("0x"))
+        .map_err(|e| error::ErrorBadRequest(format!("Invalid transaction hash {}: {:?}", *tx_hash, e)))?;
+    let ledger = ledger.lock().unwrap();
+    let block = ledger.tx_block(tx_hash)
+        .ok_or_else(|| error::ErrorNotFound(format!("Transaction {:?} is not found", tx_hash)))?;
+
+    Ok(HttpResponse::Ok().json(api::tx::get::Response { tx_hash, block_number: block.number }))
+}
+
+fn parse_address(address: &str) -> Result<Address, Error> {
+    Address::from_str(address.trim_start_matches("0x"))
+        .map_err(|e| error::ErrorBadRequest(format!("Invalid contract address {}: {:?}", address, e)))
+}
+
+fn deployed_at(ledger: &Ledger, contract: Address) -> Result<u64, Error> {
+    ledger.deployed_at(contract)
+        .ok_or_else(|| error::ErrorNotFound(format!("Contract {:?} is not deployed", contract)))
+}
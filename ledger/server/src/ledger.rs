@@ -0,0 +1,389 @@
+//! An append-only ledger persisted as a file of JSON lines, one line per block.
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+use serde::{Deserialize, Serialize};
+use tiny_keccak::keccak256;
+use web3::types::{Address, H256};
+use anonify_ledger_api::Event;
+use anyhow::{anyhow, Result};
+use crate::attestation::Attestation;
+
+/// A transaction, which deploys `contract` if `deploy` is true and appends `event` to it.
+/// `attestation` is the verified report of the enclave which joins the group by the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Transaction {
+    pub sender: String,
+    pub contract: Address,
+    pub deploy: bool,
+    pub event: Event,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Block {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    /// `None` only for the genesis block
+    pub tx: Option<Transaction>,
+}
+
+impl Block {
+    fn new(number: u64, parent_hash: H256, tx: Option<Transaction>) -> Result<Self> {
+        let hash = Self::compute_hash(number, parent_hash, &tx)?;
+        Ok(Block { number, hash, parent_hash, tx })
+    }
+
+    fn compute_hash(number: u64, parent_hash: H256, tx: &Option<Transaction>) -> Result<H256> {
+        let preimage = serde_json::to_vec(&(number, parent_hash, tx))?;
+        Ok(H256::from(keccak256(&preimage)))
+    }
+
+    fn is_valid_child_of(&self, parent: &Block) -> bool {
+        self.number == parent.number + 1
+            && self.parent_hash == parent.hash
+            && Self::compute_hash(self.number, self.parent_hash, &self.tx).ok() == Some(self.hash)
+    }
+}
+
+#[derive(Debug)]
+pub struct Ledger {
+    file: File,
+    blocks: Vec<Block>,
+}
+
+impl Ledger {
+    /// Open the ledger at `path`, which is created with only a genesis block if it doesn't exist.
+    /// A last line truncated by a crash while appending is discarded.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new().create(true).read(true).append(true).open(path.as_ref())?;
+
+        let mut blocks: Vec<Block> = vec![];
+        let mut valid_len = 0;
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? != 0 {
+            let block = match serde_json::from_str::<Block>(&line) {
+                Ok(block) if line.ends_with('\n') => block,
+                _ => break,
+            };
+            if let Some(parent) = blocks.last() {
+                if !block.is_valid_child_of(parent) {
+                    return Err(anyhow!("Block {} doesn't follow block {} in the ledger", block.number, parent.number));
+                }
+            }
+            valid_len += line.len() as u64;
+            blocks.push(block);
+            line.clear();
+        }
+        file.set_len(valid_len)?;
+
+        let mut ledger = Ledger { file, blocks };
+        if ledger.blocks.is_empty() {
+            ledger.append(None)?;
+        }
+
+        Ok(ledger)
+    }
+
+    /// Deploy a contract whose address is derived from the latest block hash and the sender.
+    /// The deployer's enclave joins the group with roster index 0, and its MRENCLAVE is the contract's.
+    pub fn deploy(&mut self, sender: String, attestation: Attestation, event: Event) -> Result<(Address, H256)> {
+        let mut preimage = self.latest().hash.as_bytes().to_vec();
+        preimage.extend_from_slice(sender.as_bytes());
+        let contract = Address::from_slice(&keccak256(&preimage)[12..]);
+        self.check_join(contract, &attestation, &event)?;
+
+        let block = self.append(Some(Transaction {
+            sender,
+            contract,
+            deploy: true,
+            event,
+            attestation: Some(attestation),
+        }))?;
+        Ok((contract, block.hash))
+    }
+
+    /// An enclave joins the group of a deployed contract with a verified attestation report,
+    /// like `joinGroup` of the anonify contract.
+    pub fn join(&mut self, sender: String, contract: Address, attestation: Attestation, event: Event) -> Result<H256> {
+        if self.deployed_at(contract).is_none() {
+            return Err(anyhow!("Contract {:?} is not deployed", contract));
+        }
+        self.check_join(contract, &attestation, &event)?;
+
+        let block = self.append(Some(Transaction {
+            sender,
+            contract,
+            deploy: false,
+            event,
+            attestation: Some(attestation),
+        }))?;
+        Ok(block.hash)
+    }
+
+    pub fn send(&mut self, sender: String, contract: Address, event: Event) -> Result<H256> {
+        if self.deployed_at(contract).is_none() {
+            return Err(anyhow!("Contract {:?} is not deployed", contract));
+        }
+        if let Event::JoinGroup { .. } = event {
+            return Err(anyhow!("Enclaves join the group only with an attestation report"));
+        }
+
+        let block = self.append(Some(Transaction { sender, contract, deploy: false, event, attestation: None }))?;
+        Ok(block.hash)
+    }
+
+    pub fn latest(&self) -> &Block {
+        self.blocks.last().expect("The ledger has a genesis block at least.")
+    }
+
+    pub fn block(&self, number: u64) -> Option<&Block> {
+        self.blocks.get(number as usize)
+    }
+
+    /// The block including the transaction. Block hashes are used as transaction hashes.
+    pub fn tx_block(&self, tx_hash: H256) -> Option<&Block> {
+        self.blocks.iter().find(|b| b.hash == tx_hash && b.tx.is_some())
+    }
+
+    pub fn deployed_at(&self, contract: Address) -> Option<u64> {
+        self.blocks.iter()
+            .find(|b| b.tx.as_ref().map_or(false, |tx| tx.deploy && tx.contract == contract))
+            .map(|b| b.number)
+    }
+
+    /// Events of `contract` in blocks from `from` to `to`, both inclusive.
+    pub fn events(&self, contract: Address, from: u64, to: u64) -> Vec<Event> {
+        self.blocks.iter()
+            .take(to.saturating_add(1) as usize)
+            .skip(from as usize)
+            .filter_map(|b| b.tx.as_ref())
+            .filter(|tx| tx.contract == contract)
+            .map(|tx| tx.event.clone())
+            .collect()
+    }
+
+    /// Enclaves join the group of a contract in the order of roster indices, like the anonify contract.
+    /// An enclave is registered only once with the MRENCLAVE of the deployer's, and a report nonce is used only once.
+    fn check_join(&self, contract: Address, attestation: &Attestation, event: &Event) -> Result<()> {
+        let (roster_idx, enclave_address) = match event {
+            Event::JoinGroup { roster_idx, enclave_address, .. } => (*roster_idx, *enclave_address),
+            _ => return Err(anyhow!("Only a JoinGroup event can be appended with an attestation report")),
+        };
+        if enclave_address != attestation.enclave_address {
+            return Err(anyhow!("The enclave address {:?} isn't the one in the report", enclave_address));
+        }
+
+        let joined: Vec<&Attestation> = self.blocks.iter()
+            .filter_map(|b| b.tx.as_ref())
+            .filter(|tx| tx.contract == contract)
+            .filter_map(|tx| tx.attestation.as_ref())
+            .collect();
+        if roster_idx != joined.len() as u32 {
+            return Err(anyhow!("Roster index {} is not the next one, {}", roster_idx, joined.len()));
+        }
+        if let Some(deployer) = joined.first() {
+            if deployer.mr_enclave != attestation.mr_enclave {
+                return Err(anyhow!("MRENCLAVE {:?} included in the report is not correct", attestation.mr_enclave));
+            }
+        }
+        if joined.iter().any(|a| a.enclave_address == enclave_address) {
+            return Err(anyhow!("The enclave {:?} has already been registered", enclave_address));
+        }
+        if joined.iter().any(|a| a.report_nonce == attestation.report_nonce) {
+            return Err(anyhow!("The report nonce has already been used"));
+        }
+
+        Ok(())
+    }
+
+    fn append(&mut self, tx: Option<Transaction>) -> Result<&Block> {
+        let block = match self.blocks.last() {
+            Some(parent) => Block::new(parent.number + 1, parent.hash, tx)?,
+            None => Block::new(0, H256::zero(), tx)?,
+        };
+
+        let mut line = serde_json::to_vec(&block)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+
+        self.blocks.push(block);
+        Ok(self.latest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf, process};
+    use web3::types::Bytes;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("anonify-ledger-{}-{}", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn handshake(bytes: &[u8]) -> Event {
+        Event::Handshake { handshake: Bytes(bytes.to_vec()), enclave_sig: Bytes(vec![]) }
+    }
+
+    fn ciphertext(bytes: &[u8]) -> Event {
+        Event::Ciphertext(Bytes(bytes.to_vec()))
+    }
+
+    fn join_group(roster_idx: u32, bytes: &[u8]) -> Event {
+        Event::JoinGroup {
+            roster_idx,
+            enclave_address: Address::repeat_byte(roster_idx as u8),
+            handshake: Bytes(bytes.to_vec()),
+            enclave_sig: Bytes(vec![]),
+        }
+    }
+
+    /// The attestation of the enclave which joins with `roster_idx` by `join_group`.
+    fn attestation(roster_idx: u32) -> Attestation {
+        Attestation {
+            mr_enclave: H256::repeat_byte(0xaa),
+            enclave_address: Address::repeat_byte(roster_idx as u8),
+            report_nonce: H256::repeat_byte(roster_idx as u8),
+        }
+    }
+
+    fn deploy(ledger: &mut Ledger, bytes: &[u8]) -> Address {
+        ledger.deploy("alice".to_string(), attestation(0), join_group(0, bytes)).unwrap().0
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let path = temp_path("reopen");
+        let mut ledger = Ledger::open(&path).unwrap();
+        assert_eq!(ledger.latest().number, 0);
+
+        let (contract, deploy_hash) = ledger.deploy("alice".to_string(), attestation(0), join_group(0, b"h0")).unwrap();
+        let other = deploy(&mut ledger, b"other");
+        assert_ne!(contract, other);
+        let tx_hash = ledger.send("bob".to_string(), contract, ciphertext(b"c0")).unwrap();
+        assert!(ledger.send("bob".to_string(), Address::repeat_byte(0xff), ciphertext(b"c1")).is_err());
+        drop(ledger);
+
+        let ledger = Ledger::open(&path).unwrap();
+        assert_eq!(ledger.latest().number, 3);
+        assert_eq!(ledger.deployed_at(contract), Some(1));
+        assert_eq!(ledger.deployed_at(Address::repeat_byte(0xff)), None);
+        assert_eq!(ledger.tx_block(deploy_hash).unwrap().number, 1);
+        assert_eq!(ledger.tx_block(tx_hash).unwrap().number, 3);
+        assert!(ledger.tx_block(ledger.block(0).unwrap().hash).is_none());
+
+        // Only the events of the contract are returned, in the order they were appended.
+        assert_eq!(ledger.events(contract, 0, 3), vec![join_group(0, b"h0"), ciphertext(b"c0")]);
+        assert_eq!(ledger.events(contract, 2, 2), vec![]);
+        assert_eq!(ledger.events(other, 0, u64::max_value()), vec![join_group(0, b"other")]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_join_in_roster_order() {
+        let path = temp_path("roster");
+        let mut ledger = Ledger::open(&path).unwrap();
+        assert!(ledger.deploy("alice".to_string(), attestation(1), join_group(1, b"h0")).is_err());
+        let contract = deploy(&mut ledger, b"h0");
+
+        // A join with an index which is already assigned or skips the next one is rejected.
+        assert!(ledger.join("bob".to_string(), contract, attestation(0), join_group(0, b"h1")).is_err());
+        assert!(ledger.join("bob".to_string(), contract, attestation(2), join_group(2, b"h1")).is_err());
+        ledger.join("bob".to_string(), contract, attestation(1), join_group(1, b"h1")).unwrap();
+        ledger.send("bob".to_string(), contract, handshake(b"h2")).unwrap();
+        ledger.join("carol".to_string(), contract, attestation(2), join_group(2, b"h3")).unwrap();
+
+        assert_eq!(
+            ledger.events(contract, 0, u64::max_value()),
+            vec![join_group(0, b"h0"), join_group(1, b"h1"), handshake(b"h2"), join_group(2, b"h3")]
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_join_only_with_attestation() {
+        let path = temp_path("attestation");
+        let mut ledger = Ledger::open(&path).unwrap();
+        let contract = deploy(&mut ledger, b"h0");
+
+        // A JoinGroup event cannot be appended without an attestation report.
+        assert!(ledger.send("eve".to_string(), contract, join_group(1, b"h1")).is_err());
+        // The enclave address must be the one in the report.
+        assert!(ledger.join("eve".to_string(), contract, attestation(2), join_group(1, b"h1")).is_err());
+        // The report must have the deployer's MRENCLAVE.
+        let mut other_enclave = attestation(1);
+        other_enclave.mr_enclave = H256::repeat_byte(0xbb);
+        assert!(ledger.join("eve".to_string(), contract, other_enclave, join_group(1, b"h1")).is_err());
+        // A report nonce cannot be replayed.
+        let mut replayed = attestation(1);
+        replayed.report_nonce = attestation(0).report_nonce;
+        assert!(ledger.join("eve".to_string(), contract, replayed, join_group(1, b"h1")).is_err());
+
+        ledger.join("bob".to_string(), contract, attestation(1), join_group(1, b"h1")).unwrap();
+        drop(ledger);
+
+        // Attestations are restored from the ledger file, so a registered enclave cannot join again.
+        let mut ledger = Ledger::open(&path).unwrap();
+        let mut registered = attestation(2);
+        registered.enclave_address = attestation(1).enclave_address;
+        let rejoin = Event::JoinGroup {
+            roster_idx: 2,
+            enclave_address: registered.enclave_address,
+            handshake: Bytes(b"h2".to_vec()),
+            enclave_sig: Bytes(vec![]),
+        };
+        assert!(ledger.join("bob".to_string(), contract, registered, rejoin).is_err());
+        ledger.join("carol".to_string(), contract, attestation(2), join_group(2, b"h2")).unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_discard_truncated_block() {
+        let path = temp_path("truncated");
+        let mut ledger = Ledger::open(&path).unwrap();
+        let contract = deploy(&mut ledger, b"h0");
+        drop(ledger);
+
+        // A crash while appending leaves a partial line.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"number":2,"hash":"0x"#).unwrap();
+        drop(file);
+
+        let mut ledger = Ledger::open(&path).unwrap();
+        assert_eq!(ledger.latest().number, 1);
+        ledger.send("bob".to_string(), contract, ciphertext(b"c0")).unwrap();
+        drop(ledger);
+
+        let ledger = Ledger::open(&path).unwrap();
+        assert_eq!(ledger.latest().number, 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reject_tampered_block() {
+        let path = temp_path("tampered");
+        let mut ledger = Ledger::open(&path).unwrap();
+        let contract = deploy(&mut ledger, b"h0");
+        ledger.send("bob".to_string(), contract, ciphertext(b"c0")).unwrap();
+        drop(ledger);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replace("\"bob\"", "\"eve\"")).unwrap();
+        assert!(Ledger::open(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+//! A ledger server, which serves an append-only ledger persisted in a file over HTTP.
+//! It lets Anonify run without an Ethereum node, using the ledger backend of `anonify-bc-connector`.
+//! It is for development only: senders are not authenticated and blocks are not replicated.
+//! Enclaves join the group only with attestation reports signed by Intel Attestation Service.
+use std::{
+    env, io,
+    sync::{Arc, Mutex},
+};
+use handlers::*;
+use ledger::Ledger;
+use actix_web::{web, App, HttpServer};
+
+mod attestation;
+mod handlers;
+mod ledger;
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let ledger_addr = env::var("LEDGER_ADDR").expect("LEDGER_ADDR is not set.");
+    let ledger_path = env::var("LEDGER_PATH").expect("LEDGER_PATH is not set.");
+
+    let ledger = Ledger::open(ledger_path).expect("Failed to open the ledger.");
+    let ledger = Arc::new(Mutex::new(ledger));
+
+    HttpServer::new(move || {
+        App::new()
+            .data(ledger.clone())
+            .route("/api/v1/contracts", web::post().to(handle_deploy))
+            .route("/api/v1/contracts/{contract}", web::get().to(handle_contract))
+            .route("/api/v1/contracts/{contract}/members", web::post().to(handle_join))
+            .route("/api/v1/contracts/{contract}/events", web::post().to(handle_send_event))
+            .route("/api/v1/contracts/{contract}/events", web::get().to(handle_events))
+            .route("/api/v1/blocks/latest", web::get().to(handle_latest_block))
+            .route("/api/v1/blocks/{number}", web::get().to(handle_block))
+            .route("/api/v1/txs/{hash}", web::get().to(handle_tx))
+    })
+    .bind(ledger_addr)?
+    .run()
+}
@@ -9,6 +9,8 @@ export RUSTFLAGS=-Ctarget-feature=+aes,+sse2,+sse4.1,+ssse3
 export ANONIFY_URL=172.18.0.3:8080
 export ETH_URL=http://172.18.0.2:8545
 export ANONYMOUS_ASSET_ABI_PATH="../../build/Anonify.abi"
+export ANONYMOUS_ASSET_BIN_PATH="../../build/Anonify.bin"
+export EVENT_DB_PATH="$HOME/.anonify/event.db"
 
 dirpath=$(cd $(dirname $0) && pwd)
 cd "${dirpath}/../core"
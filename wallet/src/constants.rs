@@ -1,4 +1,5 @@
 pub const KEYSTORE_DIR: &'static str = "keystore";
+pub const ETH_KEYSTORE_DIR: &'static str = "eth_keystore";
 pub const MASTER_KEYFILE: &'static str = "master.json";
 pub const MASTER_ACCOUNTNAME: &'static str = "master";
 pub const INDEXFILE: &'static str = "index.json";
@@ -14,6 +14,7 @@ use chrono::Utc;
 use crate::{
     error::{Result, WalletError},
     keyfile::{KeyFile, IndexFile},
+    eth_keyfile::EthKeyFile,
     constants::*,
     DirOperations,
 };
@@ -75,6 +76,11 @@ impl WalletDirectory {
         self.0.as_path().join(KEYSTORE_DIR)
     }
 
+    /// Get the path to directory where keyfiles of Ethereum transaction signers are stored.
+    pub fn get_default_eth_keystore_dir(&self) -> PathBuf {
+        self.0.as_path().join(ETH_KEYSTORE_DIR)
+    }
+
     /// Get the index file path.
     pub fn get_default_indexfile_path(&self) -> PathBuf {
         self.0.as_path().join(INDEXFILE)
@@ -161,6 +167,50 @@ impl KeystoreDirectory {
     }
 }
 
+/// Directory's path of keystore which is included bunch of Ethereum keyfiles.
+/// It is separated from `KeystoreDirectory` because the keyfiles have a different format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthKeystoreDirectory(pub PathBuf);
+
+impl EthKeystoreDirectory {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        fs::create_dir_all(path.as_ref())?;
+        Ok(EthKeystoreDirectory(path.as_ref().to_path_buf()))
+    }
+
+    pub fn insert<R: Rng>(&self, keyfile: &mut EthKeyFile, rng: &mut R) -> Result<()> {
+        let filename = get_unique_filename(&self.0, rng)?;
+        let keyfile_path = self.0.join(filename.as_str());
+        keyfile.file_name = Some(filename);
+
+        let mut file = create_new_file(&keyfile_path)?;
+        serde_json::to_writer(&mut file, keyfile)?;
+
+        file.flush()?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    pub fn load(&self, keyfile_name: &str) -> Result<EthKeyFile> {
+        let file = fs::File::open(self.0.join(keyfile_name))?;
+        let reader = BufReader::new(file);
+        let keyfile = serde_json::from_reader(reader)?;
+
+        Ok(keyfile)
+    }
+
+    pub fn load_all(&self) -> Result<Vec<EthKeyFile>> {
+        let mut keyfiles = vec![];
+        for entry in fs::read_dir(&self.0)? {
+            let file = fs::File::open(entry?.path())?;
+            keyfiles.push(serde_json::from_reader(BufReader::new(file))?);
+        }
+
+        Ok(keyfiles)
+    }
+}
+
 fn save_keyfile(filename: String, keyfile_path: &PathBuf, keyfile: &mut KeyFile) -> Result<()> {
     keyfile.file_name = Some(filename);
 
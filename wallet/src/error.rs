@@ -15,6 +15,7 @@ pub enum WalletError {
     CryptoError(crypto::Error),
     SerdeError(serde_json::Error),
     Ed25519Error(ed25519_dalek::SignatureError),
+    Secp256k1Error(secp256k1::Error),
 }
 
 impl From<io::Error> for WalletError {
@@ -41,6 +42,12 @@ impl From<ed25519_dalek::SignatureError> for WalletError {
     }
 }
 
+impl From<secp256k1::Error> for WalletError {
+    fn from(err: secp256k1::Error) -> Self {
+        WalletError::Secp256k1Error(err)
+    }
+}
+
 impl fmt::Display for WalletError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -52,6 +59,7 @@ impl fmt::Display for WalletError {
             WalletError::CryptoError(ref err) => write!(f, "crypto error: {}", err),
             WalletError::SerdeError(ref err) => write!(f, "serde error: {}", err),
             WalletError::Ed25519Error(ref err) => write!(f, "Ed25519 error: {}", err),
+            WalletError::Secp256k1Error(ref err) => write!(f, "Secp256k1 error: {}", err),
         }
     }
 }
@@ -67,6 +75,7 @@ impl Error for WalletError {
             WalletError::CryptoError(ref err) => err.description(),
             WalletError::SerdeError(ref err) => err.description(),
             WalletError::Ed25519Error(ref err) => "Ed25519 error:",
+            WalletError::Secp256k1Error(ref err) => "Secp256k1 error:",
         }
     }
 }
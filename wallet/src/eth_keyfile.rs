@@ -0,0 +1,113 @@
+//! Keyfiles of secp256k1 keys which sign Ethereum transactions.
+use secp256k1::{Secp256k1, SecretKey, PublicKey};
+use parity_crypto::Keccak256;
+use serde::{Serialize, Deserialize};
+use rand::Rng;
+use crate::{
+    error::Result,
+    keyfile::KeyCiphertext,
+};
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EthKeyFile {
+    /// Unique Keyfile name which is used for filename.
+    /// If this keyfile is not stored yet, no name exits.
+    pub file_name: Option<String>,
+    /// User defined account name
+    pub account_name: String,
+    /// hex-encoded Ethereum address without `0x` prefix
+    pub address: String,
+    /// Keyfile version
+    pub version: u32,
+    /// Encrypted secret key
+    pub encrypted_key: KeyCiphertext,
+}
+
+impl EthKeyFile {
+    pub fn new<R: Rng>(
+        account_name: &str,
+        version: u32,
+        password: &[u8],
+        iters: u32,
+        secret: &SecretKey,
+        rng: &mut R,
+    ) -> Result<Self> {
+        let encrypted_key = KeyCiphertext::encrypt_bytes(&secret[..], password, iters, rng)?;
+        let address = hex::encode(Self::secret_to_address(secret));
+
+        Ok(EthKeyFile {
+            file_name: None,
+            account_name: account_name.to_string(),
+            address,
+            version,
+            encrypted_key,
+        })
+    }
+
+    /// Generate a new secret key and encrypt it.
+    pub fn new_random<R: Rng>(
+        account_name: &str,
+        version: u32,
+        password: &[u8],
+        iters: u32,
+        rng: &mut R,
+    ) -> Result<Self> {
+        // The probability that random bytes are out of the curve order is negligible.
+        let secret = loop {
+            let bytes: [u8; 32] = rng.gen();
+            if let Ok(secret) = SecretKey::from_slice(&bytes) {
+                break secret;
+            }
+        };
+
+        Self::new(account_name, version, password, iters, &secret, rng)
+    }
+
+    pub fn get_secret_key(&self, password: &[u8]) -> Result<SecretKey> {
+        let plain = self.encrypted_key.decrypt_bytes(password)?;
+        let secret = SecretKey::from_slice(&plain[..])?;
+
+        Ok(secret)
+    }
+
+    /// An Ethereum address is the last 20 bytes of the keccak256 hash of the uncompressed public key.
+    fn secret_to_address(secret: &SecretKey) -> [u8; 20] {
+        let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), secret);
+        let hash = (&public.serialize_uncompressed()[1..]).keccak256();
+
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_eth_keyfile_roundtrip() {
+        let rng = &mut StdRng::seed_from_u64(1);
+        let password = b"eth-password";
+        let keyfile = EthKeyFile::new_random("signer", 1, password, 10, rng).unwrap();
+
+        let secret = keyfile.get_secret_key(password).unwrap();
+        assert_eq!(hex::encode(EthKeyFile::secret_to_address(&secret)), keyfile.address);
+        assert!(keyfile.get_secret_key(b"wrong-password").is_err());
+    }
+
+    #[test]
+    fn test_known_address() {
+        // The first account of ganache's deterministic mnemonic.
+        let secret = SecretKey::from_slice(
+            &hex::decode("4f3edf983ac636a65a842ce7c78d9aa706d3b113bce9c46f30d7d21715b23b1d").unwrap()
+        ).unwrap();
+
+        assert_eq!(
+            hex::encode(EthKeyFile::secret_to_address(&secret)),
+            "90f8bf6a479f320ead074411a4b0e7944ea8c9c1"
+        );
+    }
+}
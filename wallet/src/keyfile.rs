@@ -117,24 +117,41 @@ pub struct KeyCiphertext {
 }
 
 impl KeyCiphertext {
-    /// Encrypt plain bytes data
-    /// Currently using `parity-crypto`.
+    /// Encrypt an ed25519 key pair
     pub fn encrypt<R: Rng>(
         key_pair: &Keypair,
         password: &[u8],
         iters: u32,
         rng: &mut R,
     ) -> Result<Self>
+    {
+        Self::encrypt_bytes(&key_pair.to_bytes()[..], password, iters, rng)
+    }
+
+    pub fn decrypt(&self, password: &[u8]) -> Result<Keypair> {
+        let plain = self.decrypt_bytes(password)?;
+        let key_pair = Keypair::from_bytes(&plain[..])?;
+
+        Ok(key_pair)
+    }
+
+    /// Encrypt plain bytes data
+    /// Currently using `parity-crypto`.
+    pub fn encrypt_bytes<R: Rng>(
+        plain: &[u8],
+        password: &[u8],
+        iters: u32,
+        rng: &mut R,
+    ) -> Result<Self>
     {
         assert!(iters != 0);
         let salt: [u8; 32] = rng.gen();
         let iv: [u8; 16] = rng.gen();
 
         let (derived_left, derived_right) = crypto::derive_key_iterations(password, &salt, iters);
-        let key_pair_bytes = key_pair.to_bytes();
-        let mut ciphertext: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; key_pair_bytes.len()]);
+        let mut ciphertext: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; plain.len()]);
 
-        crypto::aes::encrypt_128_ctr(&derived_left, &iv, &key_pair_bytes[..], &mut *ciphertext)
+        crypto::aes::encrypt_128_ctr(&derived_left, &iv, plain, &mut *ciphertext)
             .map_err(crypto::Error::from)?;
         let mac = crypto::derive_mac(&derived_right, &*ciphertext).keccak256();
 
@@ -147,7 +164,7 @@ impl KeyCiphertext {
         })
     }
 
-    pub fn decrypt(&self, password: &[u8]) -> Result<Keypair> {
+    pub fn decrypt_bytes(&self, password: &[u8]) -> Result<Vec<u8>> {
         let (derived_left, derived_right) = crypto::derive_key_iterations(password, &self.salt.0[..], self.iters);
         let mac = crypto::derive_mac(&derived_right, &self.ciphertext.0).keccak256();
 
@@ -159,9 +176,7 @@ impl KeyCiphertext {
         crypto::aes::decrypt_128_ctr(&derived_left, &self.iv.0, &self.ciphertext.0, &mut plain)
             .map_err(crypto::Error::from)?;
 
-        let key_pair = Keypair::from_bytes(&plain.to_vec()[..])?;
-
-        Ok(key_pair)
+        Ok(plain.into_vec())
     }
 }
 
@@ -7,11 +7,13 @@ mod constants;
 mod derive;
 mod disk;
 mod error;
+mod eth_keyfile;
 mod keyfile;
 
 pub use error::WalletError as Error;
-pub use disk::{WalletDirectory, KeystoreDirectory};
+pub use disk::{WalletDirectory, KeystoreDirectory, EthKeystoreDirectory};
 pub use keyfile::KeyFile;
+pub use eth_keyfile::EthKeyFile;
 
 /// Operations in a wallet directory
 pub trait DirOperations {